@@ -0,0 +1,227 @@
+// RustQL playground: a dependency-free query editor served from the binary,
+// so it works without network access beyond the gateway itself.
+(function () {
+    'use strict';
+
+    const options = JSON.parse(document.getElementById('playground-options').textContent);
+    const endpoint = new URL(options.endpoint, window.location.origin).toString();
+    const storage = window.localStorage;
+    const stored = (key, fallback) => storage.getItem('rustql:' + key) ?? fallback;
+
+    const query = document.getElementById('query');
+    const extra = document.getElementById('query-extra');
+    const result = document.getElementById('result');
+    const explorer = document.getElementById('explorer');
+    const tabs = document.querySelectorAll('.tabs button');
+
+    const editors = {
+        query: stored('query', '{\n  health\n}\n'),
+        variables: stored('variables', '{}'),
+        headers: stored('headers', JSON.stringify(options.headers, null, 2)),
+    };
+    let tab = 'variables';
+    query.value = editors.query;
+    extra.value = editors[tab];
+
+    function save(name, value) {
+        editors[name] = value;
+        storage.setItem('rustql:' + name, value);
+    }
+
+    query.addEventListener('input', () => save('query', query.value));
+    extra.addEventListener('input', () => save(tab, extra.value));
+    tabs.forEach((button) => {
+        button.addEventListener('click', () => {
+            tab = button.dataset.tab;
+            tabs.forEach((other) => other.classList.toggle('active', other === button));
+            extra.value = editors[tab];
+        });
+    });
+
+    // Tab inserts two spaces instead of leaving the editor
+    [query, extra].forEach((editor) => {
+        editor.addEventListener('keydown', (event) => {
+            if (event.key === 'Tab' && !event.shiftKey) {
+                event.preventDefault();
+                editor.setRangeText('  ', editor.selectionStart, editor.selectionEnd, 'end');
+                editor.dispatchEvent(new Event('input'));
+            }
+        });
+    });
+
+    function parseJson(name) {
+        const text = editors[name].trim();
+        if (text === '') {
+            return {};
+        }
+        try {
+            return JSON.parse(text);
+        } catch (error) {
+            throw new Error(name + ' are not valid JSON: ' + error.message);
+        }
+    }
+
+    async function execute(body) {
+        const headers = Object.assign(
+            { 'content-type': 'application/json', accept: 'application/json' },
+            parseJson('headers')
+        );
+        const response = await fetch(endpoint, {
+            method: 'POST',
+            headers,
+            body: JSON.stringify(body),
+            credentials: 'omit',
+        });
+        return response.json();
+    }
+
+    function show(value, failed) {
+        result.textContent = typeof value === 'string' ? value : JSON.stringify(value, null, 2);
+        result.classList.toggle('error', failed);
+    }
+
+    async function run() {
+        show('Running...', false);
+        try {
+            const response = await execute({
+                query: editors.query,
+                variables: parseJson('variables'),
+            });
+            show(response, Array.isArray(response.errors) && response.errors.length > 0);
+        } catch (error) {
+            show(error.message, true);
+        }
+    }
+
+    document.getElementById('run').addEventListener('click', run);
+    document.addEventListener('keydown', (event) => {
+        if (event.key === 'Enter' && (event.ctrlKey || event.metaKey)) {
+            event.preventDefault();
+            run();
+        }
+    });
+
+    // Schema explorer, loaded by introspection when first opened
+    const INTROSPECTION = `query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types {
+      kind name description
+      fields(includeDeprecated: true) {
+        name description
+        args { name description type { ...TypeRef } defaultValue }
+        type { ...TypeRef }
+      }
+      inputFields { name description type { ...TypeRef } defaultValue }
+      enumValues(includeDeprecated: true) { name description }
+    }
+  }
+}
+fragment TypeRef on __Type {
+  kind name
+  ofType { kind name ofType { kind name ofType { kind name ofType { kind name } } } }
+}`;
+    let schema = null;
+
+    function element(tag, text) {
+        const node = document.createElement(tag);
+        if (text !== undefined) {
+            node.textContent = text;
+        }
+        return node;
+    }
+
+    function typeLink(ref) {
+        if (ref.kind === 'NON_NULL') {
+            const node = typeLink(ref.ofType);
+            node.append('!');
+            return node;
+        }
+        const span = element('span');
+        if (ref.kind === 'LIST') {
+            span.append('[', typeLink(ref.ofType), ']');
+            return span;
+        }
+        const link = element('a', ref.name);
+        link.addEventListener('click', () => showType(ref.name));
+        span.append(link);
+        return span;
+    }
+
+    function showRoots() {
+        explorer.replaceChildren(element('h2', 'Schema'));
+        const list = element('ul');
+        const roots = [
+            ['query', schema.queryType],
+            ['mutation', schema.mutationType],
+            ['subscription', schema.subscriptionType],
+        ];
+        roots
+            .filter(([, type]) => type)
+            .forEach(([label, type]) => {
+                const item = element('li', label + ': ');
+                item.append(typeLink({ kind: 'OBJECT', name: type.name }));
+                list.append(item);
+            });
+        explorer.append(list, element('h2', 'Types'));
+        const types = element('ul');
+        schema.types
+            .filter((type) => !type.name.startsWith('__'))
+            .forEach((type) => {
+                const item = element('li');
+                item.append(typeLink({ kind: type.kind, name: type.name }));
+                types.append(item);
+            });
+        explorer.append(types);
+    }
+
+    function showType(name) {
+        const type = schema.types.find((candidate) => candidate.name === name);
+        if (!type) {
+            return;
+        }
+        const back = element('a', '< Schema');
+        back.addEventListener('click', showRoots);
+        explorer.replaceChildren(back, element('h2', type.name));
+        explorer.append(element('p', type.description || type.kind));
+
+        const list = element('ul');
+        (type.fields || type.inputFields || []).forEach((field) => {
+            const item = element('li', field.name);
+            if (field.args && field.args.length > 0) {
+                item.append('(');
+                field.args.forEach((arg, i) => {
+                    item.append(i > 0 ? ', ' : '', arg.name + ': ', typeLink(arg.type));
+                });
+                item.append(')');
+            }
+            item.append(': ', typeLink(field.type));
+            if (field.description) {
+                item.title = field.description;
+            }
+            list.append(item);
+        });
+        (type.enumValues || []).forEach((value) => list.append(element('li', value.name)));
+        explorer.append(list);
+    }
+
+    document.getElementById('docs').addEventListener('click', async () => {
+        explorer.hidden = !explorer.hidden;
+        if (explorer.hidden || schema) {
+            return;
+        }
+        explorer.replaceChildren(element('p', 'Loading schema...'));
+        try {
+            const response = await execute({ query: INTROSPECTION });
+            if (!response.data) {
+                throw new Error(JSON.stringify(response.errors, null, 2));
+            }
+            schema = response.data.__schema;
+            showRoots();
+        } catch (error) {
+            explorer.replaceChildren(element('p', 'Schema unavailable: ' + error.message));
+        }
+    });
+})();
@@ -8,7 +8,7 @@ fn benchmark_schema_creation(c: &mut Criterion) {
 
     c.bench_function("schema_creation", |b| {
         b.iter(|| {
-            let schema = create_schema(black_box(settings.clone()));
+            let schema = create_schema(black_box(settings.clone())).unwrap();
             black_box(schema);
         })
     });
@@ -16,7 +16,7 @@ fn benchmark_schema_creation(c: &mut Criterion) {
 
 fn benchmark_simple_query(c: &mut Criterion) {
     let settings = Arc::new(Settings::default());
-    let schema = create_schema(settings);
+    let schema = create_schema(settings).unwrap();
 
     c.bench_function("simple_query", |b| {
         b.iter(|| {
@@ -0,0 +1,162 @@
+use crate::auth::{AuthError, Claims};
+use crate::config::settings::{ApiKeyConfig, ApiKeysConfig, KeyRateLimit};
+use crate::utils::{Result, RustQLError, sha256_hex};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+use warp::http::HeaderMap;
+
+/// Layout of the `keys_path` file.
+#[derive(Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Default)]
+struct Keys {
+    /// Keys by lowercase hex digest
+    by_hash: HashMap<String, ApiKeyConfig>,
+    /// Per-key quota overrides by key name
+    rate_limits: HashMap<String, KeyRateLimit>,
+}
+
+/// The configured API keys, swapped as a whole when `keys_path` changes so a
+/// request never sees a partially loaded file.
+#[derive(Clone)]
+pub struct ApiKeyStore {
+    config: ApiKeysConfig,
+    keys: Arc<RwLock<Arc<Keys>>>,
+}
+
+impl ApiKeyStore {
+    pub fn from_config(config: &ApiKeysConfig) -> Result<Self> {
+        let keys = load_keys(config)?;
+        Ok(Self {
+            config: config.clone(),
+            keys: Arc::new(RwLock::new(Arc::new(keys))),
+        })
+    }
+
+    pub fn required(&self) -> bool {
+        self.config.required
+    }
+
+    /// The key sent in the configured header, or else the query parameter.
+    pub fn presented<'a>(
+        &self,
+        headers: &'a HeaderMap,
+        query: &'a HashMap<String, String>,
+    ) -> Option<&'a str> {
+        let from_header = headers
+            .get(self.config.header.as_str())
+            .and_then(|value| value.to_str().ok());
+        let from_query = || {
+            let param = self.config.query_param.as_ref()?;
+            query.get(param).map(String::as_str)
+        };
+        from_header
+            .or_else(from_query)
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+    }
+
+    /// Claims of the key: its name as `sub` and `api_key`, and its scopes.
+    pub fn verify(&self, key: &str) -> std::result::Result<Claims, AuthError> {
+        let keys = self.current();
+        let entry = keys
+            .by_hash
+            .get(&sha256_hex(key))
+            .ok_or_else(|| AuthError::InvalidApiKey("unknown key".to_string()))?;
+
+        let mut claims = Map::new();
+        claims.insert("sub".to_string(), Value::from(entry.name.clone()));
+        claims.insert("api_key".to_string(), Value::from(entry.name.clone()));
+        claims.insert("scope".to_string(), Value::from(entry.scopes.join(" ")));
+        if let Some(expires_at) = entry.expires_at {
+            if expires_at <= chrono::Utc::now() {
+                return Err(AuthError::InvalidApiKey("key has expired".to_string()));
+            }
+            claims.insert("exp".to_string(), Value::from(expires_at.timestamp()));
+        }
+        Ok(Claims(claims))
+    }
+
+    pub fn rate_limit(&self, name: &str) -> Option<KeyRateLimit> {
+        self.current().rate_limits.get(name).copied()
+    }
+
+    pub fn reload(&self) -> Result<()> {
+        let keys = load_keys(&self.config)?;
+        *self.keys.write().expect("API key lock poisoned") = Arc::new(keys);
+        Ok(())
+    }
+
+    /// Polls `keys_path` every `reload_interval` seconds and reloads the keys
+    /// when its modification time changes.
+    pub fn spawn_reload_task(&self) {
+        let Some(path) = self.config.keys_path.clone() else {
+            return;
+        };
+
+        let store = self.clone();
+        let watched = path.clone();
+        let modified = move || std::fs::metadata(&watched).and_then(|m| m.modified()).ok();
+        // Taken before spawning so changes made right after are not missed
+        let mut last_modified: Option<SystemTime> = modified();
+        tokio::spawn(async move {
+            let mut ticker =
+                tokio::time::interval(Duration::from_secs(store.config.reload_interval));
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let current = modified();
+                if current == last_modified {
+                    continue;
+                }
+
+                match store.reload() {
+                    Ok(()) => {
+                        info!(keys_path = %path, "Reloaded API keys");
+                        last_modified = current;
+                    }
+                    // Keep the previous keys; the file may be mid-write and
+                    // will be retried on the next tick.
+                    Err(e) => error!(error = %e, "Failed to reload API keys"),
+                }
+            }
+        });
+    }
+
+    fn current(&self) -> Arc<Keys> {
+        self.keys.read().expect("API key lock poisoned").clone()
+    }
+}
+
+fn load_keys(config: &ApiKeysConfig) -> Result<Keys> {
+    let mut entries = config.keys.clone();
+    if let Some(path) = &config.keys_path {
+        let document = std::fs::read_to_string(path).map_err(|e| {
+            RustQLError::Config(format!("Failed to read API keys '{}': {}", path, e))
+        })?;
+        let file: KeysFile = toml::from_str(&document)
+            .map_err(|e| RustQLError::Config(format!("Invalid API keys '{}': {}", path, e)))?;
+        entries.extend(file.keys);
+    }
+
+    let mut keys = Keys::default();
+    for entry in entries {
+        entry.validate().map_err(RustQLError::Config)?;
+        if let Some(limit) = entry.rate_limit {
+            keys.rate_limits.insert(entry.name.clone(), limit);
+        }
+        keys.by_hash
+            .insert(entry.key_hash.to_ascii_lowercase(), entry);
+    }
+    Ok(keys)
+}
@@ -0,0 +1,158 @@
+use crate::auth::AuthError;
+use crate::auth::jwt::Key;
+use crate::config::settings::{JwtAlgorithm, JwtConfig};
+use crate::utils::{Result, RustQLError};
+use arc_swap::ArcSwapOption;
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, PublicKeyUse};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Unknown `kid`s trigger a reload at most this often.
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<serde_json::Value>,
+}
+
+/// The verifying key of a JWK, when it is an RSA or P-256 signature key.
+fn jwk_key(jwk: Jwk) -> Option<Key> {
+    if jwk
+        .common
+        .public_key_use
+        .as_ref()
+        .is_some_and(|usage| *usage != PublicKeyUse::Signature)
+    {
+        return None;
+    }
+    let algorithm = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => JwtAlgorithm::Rs256,
+        AlgorithmParameters::EllipticCurve(ec) if ec.curve == EllipticCurve::P256 => {
+            JwtAlgorithm::Es256
+        }
+        _ => return None,
+    };
+    Some(Key {
+        key: DecodingKey::from_jwk(&jwk).ok()?,
+        kid: jwk.common.key_id,
+        algorithm,
+    })
+}
+
+/// Parses a JWKS document, skipping keys that are not RSA or P-256
+/// signature keys.
+pub fn parse_jwks(document: &str) -> Result<Vec<Key>> {
+    let set: JwkSet = serde_json::from_str(document)
+        .map_err(|e| RustQLError::Config(format!("Invalid JWKS: {}", e)))?;
+    Ok(set
+        .keys
+        .into_iter()
+        .filter_map(|jwk| serde_json::from_value(jwk).ok())
+        .filter_map(jwk_key)
+        .collect())
+}
+
+enum Location {
+    Path(String),
+    Url(String),
+}
+
+struct Loaded {
+    keys: Arc<Vec<Key>>,
+    loaded_at: Instant,
+}
+
+/// A JWKS document read from a file or URL, cached for the configured
+/// refresh interval. One caller at a time reloads it while the others keep
+/// verifying against the previous keys, and a failed reload keeps serving
+/// them too.
+pub struct JwksSource {
+    location: Location,
+    refresh: Duration,
+    http: reqwest::Client,
+    loaded: ArcSwapOption<Loaded>,
+    reloading: Mutex<()>,
+}
+
+impl JwksSource {
+    pub fn from_config(config: &JwtConfig) -> Result<Option<Self>> {
+        let location = match (&config.jwks_url, &config.jwks_path) {
+            (Some(url), _) => Location::Url(url.clone()),
+            (None, Some(path)) => Location::Path(path.clone()),
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some(Self {
+            location,
+            refresh: Duration::from_secs(config.jwks_refresh_interval),
+            http: reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?,
+            loaded: ArcSwapOption::empty(),
+            reloading: Mutex::new(()),
+        }))
+    }
+
+    pub async fn keys(&self) -> std::result::Result<Arc<Vec<Key>>, AuthError> {
+        self.load(self.refresh).await
+    }
+
+    /// Reloads the document unless it was loaded very recently.
+    pub async fn reload(&self) -> std::result::Result<Arc<Vec<Key>>, AuthError> {
+        self.load(MIN_RELOAD_INTERVAL).await
+    }
+
+    async fn load(&self, max_age: Duration) -> std::result::Result<Arc<Vec<Key>>, AuthError> {
+        let current = self.loaded.load_full();
+        if let Some(loaded) = current.as_ref().filter(|l| l.loaded_at.elapsed() < max_age) {
+            return Ok(loaded.keys.clone());
+        }
+
+        let _reloading = match (self.reloading.try_lock(), &current) {
+            (Ok(guard), _) => guard,
+            (Err(_), Some(loaded)) => return Ok(loaded.keys.clone()),
+            // Nothing to serve until the first load completes
+            (Err(_), None) => self.reloading.lock().await,
+        };
+        let current = self.loaded.load_full();
+        if let Some(loaded) = current.as_ref().filter(|l| l.loaded_at.elapsed() < max_age) {
+            return Ok(loaded.keys.clone());
+        }
+
+        let keys = match (self.fetch().await, current) {
+            (Ok(keys), _) => {
+                info!(keys = keys.len(), "Loaded JWKS");
+                Arc::new(keys)
+            }
+            (Err(e), Some(loaded)) => {
+                warn!(error = %e, "Failed to reload JWKS, keeping previous keys");
+                loaded.keys.clone()
+            }
+            (Err(e), None) => return Err(AuthError::KeysUnavailable(e.to_string())),
+        };
+        self.loaded.store(Some(Arc::new(Loaded {
+            keys: keys.clone(),
+            loaded_at: Instant::now(),
+        })));
+        Ok(keys)
+    }
+
+    async fn fetch(&self) -> Result<Vec<Key>> {
+        let document = match &self.location {
+            Location::Path(path) => tokio::fs::read_to_string(path).await?,
+            Location::Url(url) => {
+                self.http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?
+            }
+        };
+        parse_jwks(&document)
+    }
+}
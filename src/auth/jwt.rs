@@ -0,0 +1,183 @@
+use crate::auth::jwks::JwksSource;
+use crate::auth::{AuthError, Claims};
+use crate::config::settings::{JwtAlgorithm, JwtConfig};
+use crate::rest::expand_env;
+use crate::utils::{Result, RustQLError};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use tracing::debug;
+
+/// A verifying key, the algorithm it verifies and the `kid` it is
+/// published under.
+#[derive(Clone)]
+pub struct Key {
+    pub kid: Option<String>,
+    pub algorithm: JwtAlgorithm,
+    pub key: DecodingKey,
+}
+
+/// Reads the RSA and P-256 `PUBLIC KEY` (SPKI) entries of a PEM document.
+pub fn keys_from_pem(pem: &str) -> Result<Vec<Key>> {
+    let entries =
+        pem::parse_many(pem).map_err(|e| RustQLError::Config(format!("Invalid PEM: {}", e)))?;
+    let mut keys = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.tag() == "PUBLIC KEY") {
+        let pem = pem::encode(entry);
+        let (algorithm, key) = match DecodingKey::from_rsa_pem(pem.as_bytes()) {
+            Ok(key) => (JwtAlgorithm::Rs256, key),
+            Err(_) => DecodingKey::from_ec_pem(pem.as_bytes())
+                .map(|key| (JwtAlgorithm::Es256, key))
+                .map_err(|_| {
+                    RustQLError::Config("Public key is neither RSA nor P-256".to_string())
+                })?,
+        };
+        keys.push(Key {
+            kid: None,
+            algorithm,
+            key,
+        });
+    }
+    if keys.is_empty() {
+        return Err(RustQLError::Config(
+            "PEM contains no PUBLIC KEY entries".to_string(),
+        ));
+    }
+    Ok(keys)
+}
+
+/// Verifies compact-serialized JWTs against static keys and an optional
+/// JWKS, then validates the registered claims.
+pub struct JwtVerifier {
+    config: JwtConfig,
+    keys: Vec<Key>,
+    jwks: Option<JwksSource>,
+    validation: Validation,
+}
+
+impl JwtVerifier {
+    pub fn from_config(config: &JwtConfig) -> Result<Self> {
+        let mut keys = Vec::new();
+        if let Some(secret) = &config.secret {
+            keys.push(Key {
+                kid: None,
+                algorithm: JwtAlgorithm::Hs256,
+                key: DecodingKey::from_secret(expand_env(secret).as_bytes()),
+            });
+        }
+        if let Some(path) = &config.public_key_path {
+            let pem = std::fs::read_to_string(path).map_err(|e| {
+                RustQLError::Config(format!("Failed to read JWT public key '{}': {}", path, e))
+            })?;
+            keys.extend(keys_from_pem(&pem)?);
+        }
+
+        let mut validation = Validation::default();
+        validation.leeway = config.leeway;
+        validation.validate_nbf = true;
+        let mut required = vec!["exp"];
+        if let Some(issuer) = &config.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        if config.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&config.audience);
+            required.push("aud");
+        }
+        validation.set_required_spec_claims(&required);
+
+        Ok(Self {
+            config: config.clone(),
+            keys,
+            jwks: JwksSource::from_config(config)?,
+            validation,
+        })
+    }
+
+    pub fn required(&self) -> bool {
+        self.config.required
+    }
+
+    pub async fn verify(&self, token: &str) -> std::result::Result<Claims, AuthError> {
+        let invalid = |message: &str| AuthError::InvalidToken(message.to_string());
+
+        let header = decode_header(token).map_err(|_| invalid("malformed token"))?;
+        let algorithm = match header.alg {
+            Algorithm::HS256 => JwtAlgorithm::Hs256,
+            Algorithm::RS256 => JwtAlgorithm::Rs256,
+            Algorithm::ES256 => JwtAlgorithm::Es256,
+            _ => return Err(invalid("unsupported algorithm")),
+        };
+        if !self.config.algorithms.is_empty() && !self.config.algorithms.contains(&algorithm) {
+            return Err(invalid("algorithm not allowed"));
+        }
+        let mut validation = self.validation.clone();
+        validation.algorithms = vec![header.alg];
+
+        let verify = |keys: &[Key]| {
+            keys.iter()
+                .filter(|key| key.algorithm == algorithm)
+                .filter(|key| header.kid.is_none() || key.kid.is_none() || key.kid == header.kid)
+                .find_map(|key| match decode::<Claims>(token, &key.key, &validation) {
+                    Err(e) if is_key_mismatch(e.kind()) => None,
+                    result => Some(result),
+                })
+        };
+
+        let mut verified = verify(&self.keys);
+        if verified.is_none() {
+            if let Some(jwks) = &self.jwks {
+                let keys = jwks.keys().await?;
+                verified = verify(&keys);
+                // An unknown kid usually means the keys were rotated
+                let known_kid = header
+                    .kid
+                    .as_ref()
+                    .is_none_or(|kid| keys.iter().any(|key| key.kid.as_ref() == Some(kid)));
+                if verified.is_none() && !known_kid {
+                    debug!(kid = ?header.kid, "Unknown kid, reloading JWKS");
+                    verified = verify(&jwks.reload().await?);
+                }
+            }
+        }
+        let claims = verified
+            .ok_or_else(|| invalid("signature verification failed"))?
+            .map_err(claims_error)?
+            .claims;
+
+        let now = chrono::Utc::now().timestamp();
+        if claims
+            .timestamp("iat")
+            .is_some_and(|iat| iat - self.config.leeway as i64 > now)
+        {
+            return Err(invalid("token was issued in the future"));
+        }
+        Ok(claims)
+    }
+}
+
+/// Whether `kind` means the token was not signed by the key tried, so the
+/// next candidate key should be.
+fn is_key_mismatch(kind: &ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::InvalidSignature
+            | ErrorKind::InvalidKeyFormat
+            | ErrorKind::InvalidRsaKey(_)
+            | ErrorKind::InvalidEcdsaKey
+    )
+}
+
+/// Maps a failure after the signature was verified to an `AuthError`.
+fn claims_error(error: jsonwebtoken::errors::Error) -> AuthError {
+    let message = match error.kind() {
+        ErrorKind::ExpiredSignature => return AuthError::Expired,
+        ErrorKind::ImmatureSignature => "token is not valid yet".to_string(),
+        ErrorKind::InvalidIssuer => "unexpected issuer".to_string(),
+        ErrorKind::InvalidAudience => "unexpected audience".to_string(),
+        ErrorKind::MissingRequiredClaim(claim) => format!("missing {} claim", claim),
+        _ => "malformed claims".to_string(),
+    };
+    AuthError::InvalidToken(message)
+}
@@ -0,0 +1,165 @@
+pub mod api_keys;
+pub mod jwks;
+pub mod jwt;
+
+use crate::config::settings::{AuthConfig, KeyRateLimit};
+use crate::utils::Result;
+use api_keys::ApiKeyStore;
+use jwt::JwtVerifier;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use warp::http::header::AUTHORIZATION;
+use warp::http::{HeaderMap, StatusCode};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("Missing credentials")]
+    MissingToken,
+
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+
+    #[error("Token has expired")]
+    Expired,
+
+    #[error("Invalid API key: {0}")]
+    InvalidApiKey(String),
+
+    #[error("Signing keys are unavailable: {0}")]
+    KeysUnavailable(String),
+}
+
+impl AuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::MissingToken => "UNAUTHENTICATED",
+            AuthError::InvalidToken(_) => "INVALID_TOKEN",
+            AuthError::Expired => "TOKEN_EXPIRED",
+            AuthError::InvalidApiKey(_) => "INVALID_API_KEY",
+            AuthError::KeysUnavailable(_) => "AUTH_UNAVAILABLE",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthError::KeysUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl warp::reject::Reject for AuthError {}
+
+/// The verified claims of a caller's token.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Claims(pub Map<String, Value>);
+
+impl Claims {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.get("sub").and_then(Value::as_str)
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.get("iss").and_then(Value::as_str)
+    }
+
+    /// `aud` as a list, whether it was sent as a string or an array.
+    pub fn audiences(&self) -> Vec<&str> {
+        match self.get("aud") {
+            Some(Value::String(audience)) => vec![audience.as_str()],
+            Some(Value::Array(audiences)) => audiences.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// OAuth scopes from a space-separated `scope` or an `scp` array.
+    pub fn scopes(&self) -> Vec<&str> {
+        match (self.get("scope"), self.get("scp")) {
+            (Some(Value::String(scope)), _) => scope.split_whitespace().collect(),
+            (_, Some(Value::Array(scopes))) => scopes.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Name of the API key the caller authenticated with.
+    pub fn api_key(&self) -> Option<&str> {
+        self.get("api_key").and_then(Value::as_str)
+    }
+
+    fn timestamp(&self, name: &str) -> Option<i64> {
+        self.get(name).and_then(Value::as_i64)
+    }
+}
+
+/// Verifies the credentials presented with a request. Without any auth
+/// configured every request is anonymous.
+#[derive(Clone, Default)]
+pub struct Authenticator {
+    jwt: Option<Arc<JwtVerifier>>,
+    api_keys: Option<ApiKeyStore>,
+}
+
+impl Authenticator {
+    pub fn from_config(config: &AuthConfig) -> Result<Self> {
+        let jwt = match &config.jwt {
+            Some(jwt) => Some(Arc::new(JwtVerifier::from_config(jwt)?)),
+            None => None,
+        };
+        let api_keys = match &config.api_keys {
+            Some(api_keys) => Some(ApiKeyStore::from_config(api_keys)?),
+            None => None,
+        };
+        Ok(Self { jwt, api_keys })
+    }
+
+    /// Claims of the request's bearer token or, failing that, its API key;
+    /// `None` for anonymous requests when credentials are not required.
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+        query: &HashMap<String, String>,
+    ) -> std::result::Result<Option<Claims>, AuthError> {
+        if let (Some(jwt), Some(token)) = (&self.jwt, bearer_token(headers)) {
+            return jwt.verify(token).await.map(Some);
+        }
+        if let Some(api_keys) = &self.api_keys {
+            if let Some(key) = api_keys.presented(headers, query) {
+                return api_keys.verify(key).map(Some);
+            }
+        }
+
+        let required = self.jwt.as_ref().is_some_and(|jwt| jwt.required())
+            || self.api_keys.as_ref().is_some_and(|keys| keys.required());
+        if required {
+            return Err(AuthError::MissingToken);
+        }
+        Ok(None)
+    }
+
+    /// The quota override of the API key the claims were issued for.
+    pub fn rate_limit(&self, claims: &Claims) -> Option<KeyRateLimit> {
+        self.api_keys.as_ref()?.rate_limit(claims.api_key()?)
+    }
+
+    pub fn spawn_reload_task(&self) {
+        if let Some(api_keys) = &self.api_keys {
+            api_keys.spawn_reload_task();
+        }
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
@@ -0,0 +1,113 @@
+use reqwest::header::{
+    CACHE_CONTROL, ETAG, HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The `Cache-Control` directives of an upstream response that matter to a
+/// shared cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheDirectives {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+}
+
+impl CacheDirectives {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut directives = Self::default();
+        for value in headers.get_all(CACHE_CONTROL) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for directive in value.split(',') {
+                let (name, argument) = match directive.split_once('=') {
+                    Some((name, argument)) => (name, Some(argument.trim().trim_matches('"'))),
+                    None => (directive, None),
+                };
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "no-store" => directives.no_store = true,
+                    "no-cache" => directives.no_cache = true,
+                    "private" => directives.private = true,
+                    "max-age" => directives.max_age = argument.and_then(|a| a.parse().ok()),
+                    "s-maxage" => directives.s_maxage = argument.and_then(|a| a.parse().ok()),
+                    _ => {}
+                }
+            }
+        }
+        directives
+    }
+
+    /// Whether a shared cache may store the response at all.
+    pub fn storable(&self) -> bool {
+        !self.no_store && !self.private
+    }
+
+    /// Freshness lifetime the upstream asked for; `s-maxage` wins over
+    /// `max-age` as we are a shared cache, and `no-cache` means the response
+    /// must be revalidated before every use.
+    pub fn ttl(&self) -> Option<Duration> {
+        if self.no_cache {
+            return Some(Duration::ZERO);
+        }
+        self.s_maxage.or(self.max_age).map(Duration::from_secs)
+    }
+
+    /// Whether the response may be served stale while it is revalidated or
+    /// when the upstream fails; `no-cache` rules out both.
+    pub fn allows_stale(&self) -> bool {
+        !self.no_cache
+    }
+}
+
+/// `ETag` and `Last-Modified` of a cached response, sent back as
+/// `If-None-Match` and `If-Modified-Since` when it is revalidated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Conditional request headers for revalidating with these validators.
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = self.etag.as_ref().and_then(|v| v.parse().ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = self.last_modified.as_ref().and_then(|v| v.parse().ok()) {
+            headers.insert(IF_MODIFIED_SINCE, modified);
+        }
+        headers
+    }
+
+    /// Validators of a 304 response, falling back to the ones it confirmed.
+    pub fn or(self, previous: &Validators) -> Self {
+        Self {
+            etag: self.etag.or_else(|| previous.etag.clone()),
+            last_modified: self
+                .last_modified
+                .or_else(|| previous.last_modified.clone()),
+        }
+    }
+}
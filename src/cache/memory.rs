@@ -0,0 +1,116 @@
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+struct Entry {
+    value: String,
+    inserted_at: Instant,
+    expires_at: Instant,
+}
+
+/// In-process cache bounded by the total size of its keys and values.
+#[derive(Debug)]
+pub struct MemoryCache {
+    entries: DashMap<String, Entry>,
+    size: AtomicUsize,
+    max_size: usize,
+}
+
+impl MemoryCache {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            size: AtomicUsize::new(0),
+            max_size,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        let entry = self.entries.get(key)?;
+        if entry.expires_at > Instant::now() {
+            return Some(entry.value.clone());
+        }
+        drop(entry);
+        self.remove(key);
+        None
+    }
+
+    /// Remaining time to live of `key`, if it is cached and fresh.
+    pub fn ttl(&self, key: &str) -> Option<Duration> {
+        let entry = self.entries.get(key)?;
+        entry.expires_at.checked_duration_since(Instant::now())
+    }
+
+    pub fn set(&self, key: &str, value: &str, ttl: Duration) {
+        let entry_size = key.len() + value.len();
+        if entry_size > self.max_size {
+            return;
+        }
+
+        let now = Instant::now();
+        let previous = self.entries.insert(
+            key.to_string(),
+            Entry {
+                value: value.to_string(),
+                inserted_at: now,
+                expires_at: now + ttl,
+            },
+        );
+        if let Some(previous) = previous {
+            self.size
+                .fetch_sub(key.len() + previous.value.len(), Ordering::Relaxed);
+        }
+        self.size.fetch_add(entry_size, Ordering::Relaxed);
+
+        if self.size.load(Ordering::Relaxed) > self.max_size {
+            self.evict();
+        }
+    }
+
+    pub fn remove(&self, key: &str) {
+        if let Some((key, entry)) = self.entries.remove(key) {
+            self.size
+                .fetch_sub(key.len() + entry.value.len(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drops expired entries, then the oldest ones until the cache fits.
+    fn evict(&self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.expires_at <= now)
+            .map(|entry| entry.key().clone())
+            .collect();
+        for key in expired {
+            self.remove(&key);
+        }
+
+        if self.size.load(Ordering::Relaxed) <= self.max_size {
+            return;
+        }
+
+        let mut by_age: Vec<(Instant, String)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.inserted_at, entry.key().clone()))
+            .collect();
+        by_age.sort();
+        for (_, key) in by_age {
+            if self.size.load(Ordering::Relaxed) <= self.max_size {
+                break;
+            }
+            self.remove(&key);
+        }
+    }
+}
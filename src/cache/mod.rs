@@ -1,18 +1,377 @@
+pub mod http;
+pub mod memory;
 pub mod redis_cache;
+pub mod response;
 
-// Placeholder for Day 3 implementation
-pub struct CacheManager;
+use crate::config::settings::CacheConfig;
+use crate::utils::{Result, RustQLError, parse_size};
+use dashmap::DashSet;
+use http::Validators;
+use memory::MemoryCache;
+use redis_cache::RedisCache;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const DEFAULT_MAX_SIZE: usize = 64 << 20;
+const REDIS_KEY_PREFIX: &str = "rustql:";
+
+/// Freshness windows of a cached upstream response, after RFC 5861. `ttl`
+/// applies when the upstream does not send its own freshness lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    pub ttl: Duration,
+    pub stale_while_revalidate: Duration,
+    pub stale_if_error: Duration,
+}
+
+impl CachePolicy {
+    pub fn from_config(config: &CacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.default_ttl),
+            stale_while_revalidate: Duration::from_secs(config.stale_while_revalidate),
+            stale_if_error: Duration::from_secs(config.stale_if_error),
+        }
+    }
+
+    /// How long an entry fresh for `ttl` is kept. Entries with validators are
+    /// kept at least the policy TTL past expiry so they can be revalidated.
+    fn retention(&self, ttl: Duration, validators: &Validators) -> Duration {
+        let mut stale = self.stale_while_revalidate.max(self.stale_if_error);
+        if !validators.is_empty() {
+            stale = stale.max(self.ttl);
+        }
+        ttl + stale
+    }
+
+    /// This policy without its stale windows, for responses that must be
+    /// revalidated before every use.
+    fn without_stale(self) -> Self {
+        Self {
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+            ..self
+        }
+    }
+}
+
+/// The outcome of a fetch for `CacheManager::get_or_refresh`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fetched {
+    /// `None` when the upstream confirmed the cached value (304 Not Modified)
+    pub value: Option<String>,
+    /// Freshness lifetime overriding the policy TTL
+    pub ttl: Option<Duration>,
+    /// `false` for responses that must not be cached (`no-store`, `private`)
+    pub storable: bool,
+    /// `false` for responses that must not be served stale (`no-cache`)
+    pub allows_stale: bool,
+    pub validators: Validators,
+}
+
+impl Fetched {
+    /// A new value cached under the policy TTL.
+    pub fn value(value: String) -> Self {
+        Self {
+            value: Some(value),
+            ttl: None,
+            storable: true,
+            allows_stale: true,
+            validators: Validators::default(),
+        }
+    }
+}
+
+/// A value served from `CacheManager::get_or_refresh`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedValue {
+    pub value: String,
+    /// How long ago the value stopped being fresh, when it is served stale
+    pub stale_for: Option<Duration>,
+}
+
+/// Stored form of an entry with freshness windows. Times are Unix milliseconds.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    value: String,
+    fresh_until: i64,
+    revalidate_until: i64,
+    error_until: i64,
+    #[serde(default)]
+    validators: Validators,
+}
+
+impl Envelope {
+    fn new(value: String, ttl: Duration, validators: Validators, policy: &CachePolicy) -> Self {
+        let now = now_millis();
+        let fresh_until = now + ttl.as_millis() as i64;
+        Self {
+            value,
+            fresh_until,
+            revalidate_until: fresh_until + policy.stale_while_revalidate.as_millis() as i64,
+            error_until: fresh_until + policy.stale_if_error.as_millis() as i64,
+            validators,
+        }
+    }
+
+    fn stale_for(&self, now: i64) -> Duration {
+        Duration::from_millis((now - self.fresh_until).max(0) as u64)
+    }
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Two-tier cache: a bounded in-process map in front of an optional shared
+/// Redis. Reads fall through to Redis and repopulate memory; writes go to
+/// both. Redis failures are logged and never fail the caller's read.
+pub struct CacheManager {
+    memory: MemoryCache,
+    redis: Option<RedisCache>,
+    default_ttl: Duration,
+    /// Keys with a background refresh in flight
+    refreshing: DashSet<String>,
+    /// Longest a background refresh may take; it answers no caller, so it
+    /// gets no more time than a request would
+    refresh_timeout: Option<Duration>,
+}
+
+/// A key's entry in `CacheManager::refreshing`, removed when the refresh
+/// task ends, including by panicking.
+struct Refreshing {
+    cache: Arc<CacheManager>,
+    key: String,
+}
+
+impl Drop for Refreshing {
+    fn drop(&mut self) {
+        self.cache.refreshing.remove(&self.key);
+    }
+}
+
+impl Default for CacheManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl CacheManager {
+    /// Memory-only cache with default limits.
     pub fn new() -> Self {
-        Self
+        Self {
+            memory: MemoryCache::new(DEFAULT_MAX_SIZE),
+            redis: None,
+            default_ttl: Duration::from_secs(300),
+            refreshing: DashSet::new(),
+            refresh_timeout: None,
+        }
     }
 
-    pub async fn get(&self, _key: &str) -> Option<String> {
-        None // Placeholder
+    pub fn from_config(config: &CacheConfig) -> Result<Self> {
+        let max_size = parse_size(&config.max_size).ok_or_else(|| {
+            RustQLError::Config(format!("Invalid cache max_size '{}'", config.max_size))
+        })?;
+        let redis = config
+            .redis_url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .map(|url| RedisCache::new(url, REDIS_KEY_PREFIX))
+            .transpose()?;
+
+        Ok(Self {
+            memory: MemoryCache::new(max_size),
+            redis,
+            default_ttl: Duration::from_secs(config.default_ttl),
+            refreshing: DashSet::new(),
+            refresh_timeout: None,
+        })
+    }
+
+    /// Abandons background refreshes still running after `timeout`.
+    pub fn with_refresh_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.refresh_timeout = timeout;
+        self
+    }
+
+    pub fn default_ttl(&self) -> Duration {
+        self.default_ttl
+    }
+
+    pub async fn get(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.memory.get(key) {
+            debug!(key = %key, "Memory cache hit");
+            return Some(value);
+        }
+
+        let (value, ttl) = self.redis.as_ref()?.get_with_ttl(key).await?;
+        debug!(key = %key, "Redis cache hit");
+        self.memory.set(key, &value, ttl);
+        Some(value)
     }
 
-    pub async fn set(&self, _key: &str, _value: &str, _ttl: u64) -> crate::utils::Result<()> {
-        Ok(()) // Placeholder
+    /// Stores `value` for `ttl` seconds.
+    pub async fn set(&self, key: &str, value: &str, ttl: u64) -> Result<()> {
+        let ttl = Duration::from_secs(ttl);
+        self.memory.set(key, value, ttl);
+        if let Some(redis) = &self.redis {
+            if let Err(e) = redis.set(key, value, ttl).await {
+                warn!(key = %key, error = %e, "Failed to write to Redis cache");
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        self.memory.remove(key);
+        if let Some(redis) = &self.redis {
+            redis.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the value for `key`, calling `fetch` when it is missing or no
+    /// longer fresh. `fetch` gets the validators of the expired entry, if any,
+    /// and may answer that it is unchanged:
+    ///
+    /// - within `stale_while_revalidate`, the stale value is returned at once
+    ///   and a single background task per key refreshes it;
+    /// - within `stale_if_error`, `fetch` runs inline and the stale value is
+    ///   returned if the upstream is unavailable (5xx, network, open circuit).
+    pub async fn get_or_refresh<F, Fut>(
+        self: &Arc<Self>,
+        key: &str,
+        policy: CachePolicy,
+        fetch: F,
+    ) -> Result<CachedValue>
+    where
+        F: FnOnce(Validators) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Fetched>> + Send + 'static,
+    {
+        let now = now_millis();
+        let entry = self
+            .get(key)
+            .await
+            .and_then(|cached| serde_json::from_str::<Envelope>(&cached).ok());
+
+        let Some(entry) = entry else {
+            let fetched = fetch(Validators::default()).await?;
+            let value = fetched.value.clone().ok_or_else(|| {
+                RustQLError::Internal("Upstream answered 304 without a cached value".to_string())
+            })?;
+            self.store(key, &value, fetched, &policy).await;
+            return Ok(CachedValue {
+                value,
+                stale_for: None,
+            });
+        };
+
+        if now < entry.fresh_until {
+            return Ok(CachedValue {
+                value: entry.value,
+                stale_for: None,
+            });
+        }
+        if now < entry.revalidate_until {
+            let stale_for = entry.stale_for(now);
+            let value = entry.value.clone();
+            self.refresh_in_background(key, policy, entry, fetch);
+            return Ok(CachedValue {
+                value,
+                stale_for: Some(stale_for),
+            });
+        }
+
+        match fetch(entry.validators.clone()).await {
+            Ok(fetched) => {
+                let value = fetched.value.clone().unwrap_or(entry.value);
+                self.store(key, &value, fetched, &policy).await;
+                Ok(CachedValue {
+                    value,
+                    stale_for: None,
+                })
+            }
+            Err(e) if e.is_upstream_unavailable() && now < entry.error_until => {
+                warn!(key = %key, error = %e, "Serving stale value after upstream failure");
+                Ok(CachedValue {
+                    stale_for: Some(entry.stale_for(now)),
+                    value: entry.value,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn refresh_in_background<F, Fut>(
+        self: &Arc<Self>,
+        key: &str,
+        policy: CachePolicy,
+        entry: Envelope,
+        fetch: F,
+    ) where
+        F: FnOnce(Validators) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Fetched>> + Send + 'static,
+    {
+        if !self.refreshing.insert(key.to_string()) {
+            return;
+        }
+
+        let refresh = Refreshing {
+            cache: self.clone(),
+            key: key.to_string(),
+        };
+        tokio::spawn(async move {
+            let (cache, key) = (&refresh.cache, &refresh.key);
+            let refreshed = fetch(entry.validators.clone());
+            let refreshed = match cache.refresh_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, refreshed).await {
+                    Ok(refreshed) => refreshed,
+                    Err(_) => {
+                        warn!(key = %key, "Background refresh timed out");
+                        return;
+                    }
+                },
+                None => refreshed.await,
+            };
+            match refreshed {
+                Ok(fetched) => {
+                    let value = fetched.value.clone().unwrap_or(entry.value);
+                    cache.store(key, &value, fetched, &policy).await;
+                }
+                Err(e) => warn!(key = %key, error = %e, "Background refresh failed"),
+            }
+        });
+    }
+
+    /// Stores `value` with the freshness the upstream sent. Unstorable
+    /// responses evict any previous entry instead.
+    async fn store(&self, key: &str, value: &str, fetched: Fetched, policy: &CachePolicy) {
+        if !fetched.storable {
+            if let Err(e) = self.delete(key).await {
+                warn!(key = %key, error = %e, "Failed to evict uncacheable response");
+            }
+            return;
+        }
+
+        let policy = if fetched.allows_stale {
+            *policy
+        } else {
+            policy.without_stale()
+        };
+        let ttl = fetched.ttl.unwrap_or(policy.ttl);
+        let retention = policy.retention(ttl, &fetched.validators);
+        if retention.is_zero() {
+            return;
+        }
+
+        let envelope = Envelope::new(value.to_string(), ttl, fetched.validators, &policy);
+        let Ok(envelope) = serde_json::to_string(&envelope) else {
+            return;
+        };
+        if let Err(e) = self.set(key, &envelope, retention.as_secs().max(1)).await {
+            warn!(key = %key, error = %e, "Failed to cache upstream response");
+        }
     }
 }
@@ -1,2 +1,138 @@
-// Placeholder for Day 3 implementation
-pub struct RedisCache;
+use crate::utils::Result;
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Shared Redis tier. Connects lazily and, while Redis is unreachable, waits
+/// `RECONNECT_BACKOFF` between attempts so requests are not slowed down.
+pub struct RedisCache {
+    client: redis::Client,
+    prefix: String,
+    state: Mutex<ConnectionState>,
+}
+
+#[derive(Default)]
+struct ConnectionState {
+    connection: Option<MultiplexedConnection>,
+    last_failure: Option<Instant>,
+}
+
+impl RedisCache {
+    pub fn new(url: &str, prefix: &str) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url)?,
+            prefix: prefix.to_string(),
+            state: Mutex::new(ConnectionState::default()),
+        })
+    }
+
+    async fn connection(&self) -> Option<MultiplexedConnection> {
+        let mut state = self.state.lock().await;
+        if let Some(connection) = &state.connection {
+            return Some(connection.clone());
+        }
+        if state
+            .last_failure
+            .is_some_and(|failed| failed.elapsed() < RECONNECT_BACKOFF)
+        {
+            return None;
+        }
+
+        match tokio::time::timeout(
+            CONNECT_TIMEOUT,
+            self.client.get_multiplexed_async_connection(),
+        )
+        .await
+        {
+            Ok(Ok(connection)) => {
+                state.connection = Some(connection.clone());
+                state.last_failure = None;
+                Some(connection)
+            }
+            Ok(Err(e)) => {
+                warn!(error = %e, "Failed to connect to Redis");
+                state.last_failure = Some(Instant::now());
+                None
+            }
+            Err(_) => {
+                warn!("Timed out connecting to Redis");
+                state.last_failure = Some(Instant::now());
+                None
+            }
+        }
+    }
+
+    /// Forgets a broken connection so the next call reconnects.
+    async fn reset(&self, error: &redis::RedisError) {
+        warn!(error = %error, "Redis command failed");
+        let mut state = self.state.lock().await;
+        state.connection = None;
+        state.last_failure = Some(Instant::now());
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let mut connection = self.connection().await?;
+        match connection.get::<_, Option<String>>(self.key(key)).await {
+            Ok(value) => value,
+            Err(e) => {
+                self.reset(&e).await;
+                None
+            }
+        }
+    }
+
+    /// Value and remaining time to live of `key`.
+    pub async fn get_with_ttl(&self, key: &str) -> Option<(String, Duration)> {
+        let mut connection = self.connection().await?;
+        let key = self.key(key);
+        let result: redis::RedisResult<(Option<String>, i64)> = redis::pipe()
+            .get(&key)
+            .pttl(&key)
+            .query_async(&mut connection)
+            .await;
+
+        match result {
+            Ok((Some(value), ttl)) if ttl > 0 => Some((value, Duration::from_millis(ttl as u64))),
+            Ok(_) => None,
+            Err(e) => {
+                self.reset(&e).await;
+                None
+            }
+        }
+    }
+
+    pub async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        let Some(mut connection) = self.connection().await else {
+            return Ok(());
+        };
+        let ttl_ms = ttl.as_millis().max(1) as u64;
+        if let Err(e) = connection
+            .pset_ex::<_, _, ()>(self.key(key), value, ttl_ms)
+            .await
+        {
+            self.reset(&e).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        let Some(mut connection) = self.connection().await else {
+            return Ok(());
+        };
+        if let Err(e) = connection.del::<_, ()>(self.key(key)).await {
+            self.reset(&e).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
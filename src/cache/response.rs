@@ -0,0 +1,110 @@
+use crate::cache::CacheManager;
+use crate::config::settings::ResponseCacheConfig;
+use crate::graphql::document::normalize_operation;
+use crate::utils::sha256_hex;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+const CACHE_PREFIX: &str = "response:";
+
+/// A cached GraphQL response with what is needed to rebuild its
+/// `Cache-Control` and `Age` headers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedResponse {
+    pub body: serde_json::Value,
+    pub cache_control: String,
+    /// Unix timestamp the response was produced at
+    pub stored_at: i64,
+}
+
+impl CachedResponse {
+    /// Seconds since the response was produced.
+    pub fn age(&self) -> u64 {
+        (chrono::Utc::now().timestamp() - self.stored_at).max(0) as u64
+    }
+}
+
+/// Caches whole query responses in the `CacheManager`. Entries are keyed by
+/// the normalized operation, its variables and the caller's auth scope, and
+/// live for the response's `Cache-Control` maxAge.
+#[derive(Clone)]
+pub struct ResponseCache {
+    cache: Arc<CacheManager>,
+    enabled: bool,
+}
+
+impl ResponseCache {
+    pub fn new(config: &ResponseCacheConfig, cache: Arc<CacheManager>) -> Self {
+        Self {
+            cache,
+            enabled: config.enabled,
+        }
+    }
+
+    /// Cache key for a query operation; `None` when caching is disabled, the
+    /// operation is not a query or the document doesn't parse.
+    pub fn key(
+        &self,
+        request: &async_graphql::Request,
+        auth_scope: Option<&str>,
+    ) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        let operation =
+            normalize_operation(&request.query, request.operation_name.as_deref()).ok()?;
+        // Printed operations start with their type
+        if !operation.starts_with("query") {
+            return None;
+        }
+
+        let variables = serde_json::to_string(&request.variables).ok()?;
+        let key = format!(
+            "{}\n{}\n{}",
+            operation,
+            variables,
+            auth_scope.unwrap_or_default()
+        );
+        Some(format!("{}{}", CACHE_PREFIX, sha256_hex(&key)))
+    }
+
+    pub async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let cached = self.cache.get(key).await?;
+        match serde_json::from_str(&cached) {
+            Ok(response) => {
+                debug!(key = %key, "Response cache hit");
+                Some(response)
+            }
+            Err(e) => {
+                warn!(key = %key, error = %e, "Discarding unreadable cached response");
+                None
+            }
+        }
+    }
+
+    /// Stores a successful response for its maxAge. Responses with errors or
+    /// without a positive maxAge are not cached.
+    pub async fn store(&self, key: &str, response: &async_graphql::Response) {
+        let max_age = response.cache_control.max_age;
+        if response.is_err() || max_age <= 0 {
+            return;
+        }
+        let Some(cache_control) = response.cache_control.value() else {
+            return;
+        };
+
+        let cached = CachedResponse {
+            body: serde_json::to_value(response).unwrap_or_default(),
+            cache_control,
+            stored_at: chrono::Utc::now().timestamp(),
+        };
+        let Ok(cached) = serde_json::to_string(&cached) else {
+            return;
+        };
+        if let Err(e) = self.cache.set(key, &cached, max_age as u64).await {
+            warn!(key = %key, error = %e, "Failed to cache response");
+        }
+    }
+}
@@ -1,5 +1,6 @@
+use crate::utils::parse_size;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
@@ -8,6 +9,53 @@ pub struct Settings {
     pub rate_limiting: RateLimitConfig,
     pub apis: ApisConfig,
     pub monitoring: MonitoringConfig,
+    #[serde(default)]
+    pub queries: Vec<QueryConfig>,
+    #[serde(default)]
+    pub types: Vec<TypeConfig>,
+    #[serde(default)]
+    pub mutations: Vec<MutationConfig>,
+    #[serde(default)]
+    pub inputs: Vec<InputTypeConfig>,
+    #[serde(default)]
+    pub subscriptions: Vec<SubscriptionConfig>,
+    #[serde(default)]
+    pub persisted_queries: PersistedQueriesConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub errors: ErrorsConfig,
+    #[serde(default)]
+    pub planner: PlannerConfig,
+}
+
+/// How the REST calls of an operation are scheduled and reported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlannerConfig {
+    /// Most upstream calls one operation has in flight
+    pub max_concurrency: usize,
+    /// Request header asking for the plan and its timings in
+    /// `extensions.queryPlan`; plans are never reported when unset
+    pub debug_header: Option<String>,
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 16,
+            debug_header: None,
+        }
+    }
+}
+
+/// How resolver errors are reported to clients.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErrorsConfig {
+    /// Replace the messages of internal, Redis and IO errors with a generic
+    /// one; the full error is still logged with the request id
+    pub mask_internal: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +67,89 @@ pub struct ServerConfig {
     pub request_timeout: Option<u64>,
     pub enable_playground: bool,
     pub cors_origins: Vec<String>,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub playground: PlaygroundConfig,
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub http2: Http2Config,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub batching: BatchingConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Http2Config {
+    /// Offer HTTP/2 via ALPN on TLS connections
+    pub enabled: bool,
+    /// Accept cleartext HTTP/2 (h2c prior knowledge) on plain TCP connections
+    pub h2c: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// Responses smaller than this many bytes are sent uncompressed
+    pub min_size: usize,
+    /// Supported encodings in server preference order: "br", "zstd", "gzip"
+    pub algorithms: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchingConfig {
+    /// Accept a JSON array of operations in a single POST
+    pub enabled: bool,
+    /// Maximum number of operations in one batch
+    pub max_batch_size: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    pub client_ca_path: Option<String>,
+    #[serde(default)]
+    pub client_auth: ClientAuthMode,
+    pub reload_interval: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuthMode {
+    #[default]
+    Required,
+    Optional,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    pub allow_credentials: bool,
+    pub allowed_headers: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub exposed_headers: Vec<String>,
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaygroundUi {
+    GraphiQL,
+    Playground,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaygroundConfig {
+    pub ui: PlaygroundUi,
+    pub title: String,
+    pub endpoint: String,
+    pub headers: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +158,133 @@ pub struct CacheConfig {
     pub default_ttl: u64,
     pub max_size: String,
     pub enable_compression: bool,
+    /// Seconds past `default_ttl` during which a stale upstream response is
+    /// served while it is refreshed in the background
+    #[serde(default)]
+    pub stale_while_revalidate: u64,
+    /// Seconds past `default_ttl` during which a stale upstream response is
+    /// served when the upstream fails with 5xx or its circuit is open
+    #[serde(default)]
+    pub stale_if_error: u64,
+    #[serde(default)]
+    pub response: ResponseCacheConfig,
+}
+
+/// Whole-response caching for query operations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponseCacheConfig {
+    pub enabled: bool,
+    /// maxAge of root fields without a hint; 0 makes them uncacheable
+    pub default_max_age: u64,
+    /// `@cacheControl(maxAge)` hints in seconds keyed by `Type.field`,
+    /// e.g. `"QueryRoot.apiInfo" = 60`
+    pub hints: HashMap<String, u64>,
+}
+
+/// Apollo-compatible automatic persisted queries and the manifest allowlist.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedQueriesConfig {
+    /// Accept `extensions.persistedQuery` and register unknown hashes
+    pub apq: bool,
+    /// Seconds a registered query stays in the cache
+    pub ttl: u64,
+    /// Operation manifest loaded at startup, either Apollo's
+    /// `persisted-query-manifest` format or a `{ "<sha256>": "<query>" }` map
+    pub manifest_path: Option<String>,
+    /// Only execute operations listed in the manifest
+    pub allowlist_only: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub jwt: Option<JwtConfig>,
+    pub api_keys: Option<ApiKeysConfig>,
+    /// Field authorization rules per `Type` or `Type.field`
+    pub rules: HashMap<String, AuthRule>,
+    /// Leave fields the caller may not resolve out of introspection
+    pub hide_unauthorized: bool,
+}
+
+/// Requires an authenticated caller holding one of `requires` (any caller
+/// when empty). Denied fields resolve to null with a `FORBIDDEN` error.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuthRule {
+    #[serde(default)]
+    pub requires: Vec<String>,
+}
+
+/// Bearer JWT verification. Keys come from `secret` (HS256),
+/// `public_key_path` (PEM, RS256/ES256) and/or a JWKS document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JwtConfig {
+    /// Reject requests without a token instead of running them anonymously
+    pub required: bool,
+    /// Accepted `alg` values; empty accepts any algorithm a key supports
+    pub algorithms: Vec<JwtAlgorithm>,
+    /// Required `iss` claim
+    pub issuer: Option<String>,
+    /// Accepted `aud` values; the token must carry at least one of them
+    pub audience: Vec<String>,
+    /// Clock skew in seconds tolerated for `exp`, `nbf` and `iat`
+    pub leeway: u64,
+    /// HMAC secret; `${VAR}` references are expanded
+    pub secret: Option<String>,
+    pub public_key_path: Option<String>,
+    pub jwks_path: Option<String>,
+    pub jwks_url: Option<String>,
+    /// Seconds between JWKS reloads
+    pub jwks_refresh_interval: u64,
+}
+
+/// Static API keys for service-to-service callers. Keys are stored as SHA-256
+/// hex digests, inline and/or in a `keys_path` TOML file of `[[keys]]` entries
+/// that is reloaded when it changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiKeysConfig {
+    /// Reject requests without credentials instead of running them anonymously
+    pub required: bool,
+    /// Header carrying the key
+    pub header: String,
+    /// Query parameter also accepted as the key, e.g. `api_key`
+    pub query_param: Option<String>,
+    pub keys: Vec<ApiKeyConfig>,
+    pub keys_path: Option<String>,
+    /// Seconds between checks of `keys_path` for changes
+    pub reload_interval: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    /// Identifies the caller; used as the `sub` claim and rate limit bucket
+    pub name: String,
+    /// SHA-256 hex digest of the key
+    pub key_hash: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Replaces the global `rate_limiting` quota for this key
+    pub rate_limit: Option<KeyRateLimit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyRateLimit {
+    pub requests_per_minute: u32,
+    pub burst_size: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JwtAlgorithm {
+    #[serde(rename = "HS256")]
+    Hs256,
+    #[serde(rename = "RS256")]
+    Rs256,
+    #[serde(rename = "ES256")]
+    Es256,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,7 +299,7 @@ pub struct ApisConfig {
     pub rest: Vec<RestApiConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RestApiConfig {
     pub name: String,
     pub base_url: String,
@@ -49,6 +307,453 @@ pub struct RestApiConfig {
     pub headers: Option<HashMap<String, String>>,
     pub timeout: Option<u64>,
     pub retry_attempts: Option<u32>,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Caller values copied onto every request to this API
+    #[serde(default)]
+    pub propagate: Vec<HeaderPropagation>,
+    /// How the gateway authenticates itself to this API
+    pub auth: Option<UpstreamAuthConfig>,
+    /// Resolve fields to null instead of an error when this API answers 404
+    #[serde(default)]
+    pub not_found_as_null: bool,
+    /// Query parameter limiting responses to the fields a query selects
+    pub sparse_fields: Option<SparseFieldsConfig>,
+}
+
+/// Pushes the selected fields of typed results down to the API, e.g.
+/// `?fields=id,name` or, with `param = "fields[{type}]"`, JSON:API sparse
+/// fieldsets. `{type}` is the `resource` of the returned `[[types]]` entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SparseFieldsConfig {
+    pub param: String,
+    pub separator: String,
+}
+
+impl Default for SparseFieldsConfig {
+    fn default() -> Self {
+        Self {
+            param: "fields".to_string(),
+            separator: ",".to_string(),
+        }
+    }
+}
+
+/// Credentials the REST client applies to every outbound request, replacing
+/// any `Authorization` header from `headers` or `propagate`. Secrets expand
+/// `${VAR}` references.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpstreamAuthConfig {
+    Bearer {
+        token: String,
+    },
+    Basic {
+        username: String,
+        password: String,
+    },
+    #[serde(rename = "oauth2")]
+    OAuth2(OAuth2Config),
+    Hmac(HmacConfig),
+}
+
+/// OAuth2 client credentials grant. Tokens are cached and refreshed
+/// `refresh_before` seconds ahead of their expiry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub audience: Option<String>,
+    #[serde(default = "default_refresh_before")]
+    pub refresh_before: u64,
+}
+
+/// Signs a canonical form of each request (method, path, sorted query,
+/// signed headers and body digest) with HMAC-SHA256, in the style of AWS
+/// SigV4. The signature is sent as
+/// `Authorization: <scheme> Credential=<key_id>, SignedHeaders=..., Signature=...`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HmacConfig {
+    pub key_id: String,
+    pub secret: String,
+    #[serde(default = "default_hmac_scheme")]
+    pub scheme: String,
+    /// Header carrying the signing time, always signed along with `host`
+    #[serde(default = "default_hmac_date_header")]
+    pub date_header: String,
+    /// Further request headers to sign when present
+    #[serde(default)]
+    pub signed_headers: Vec<String>,
+}
+
+fn default_refresh_before() -> u64 {
+    60
+}
+
+fn default_hmac_scheme() -> String {
+    "HMAC-SHA256".to_string()
+}
+
+fn default_hmac_date_header() -> String {
+    "x-date".to_string()
+}
+
+/// Sets an upstream request header from the caller's request. Hop-by-hop
+/// headers are never forwarded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeaderPropagation {
+    /// `header:<name>` (a trailing `*` forwards every header with that
+    /// prefix), `claim:<name>` or `request_id`
+    pub from: String,
+    /// Upstream header name; defaults to the incoming header's name
+    pub to: Option<String>,
+    /// Value sent when the caller did not provide one
+    pub default: Option<String>,
+}
+
+/// Stops calling an upstream after repeated failures and lets a single trial
+/// request through once `reset_timeout` has passed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub enabled: bool,
+    /// Consecutive 5xx or network failures that open the circuit
+    pub failure_threshold: u32,
+    /// Seconds the circuit stays open before a trial request
+    pub reset_timeout: u64,
+}
+
+/// A query field resolved by a GET against a REST API.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryConfig {
+    pub field: String,
+    pub description: Option<String>,
+    /// Name of the `apis.rest` entry to call
+    pub api: String,
+    /// Path template; `{arg}` placeholders are filled from field arguments
+    pub path: String,
+    #[serde(default)]
+    pub arguments: Vec<String>,
+    /// Scopes of which the caller needs one to resolve the field; an empty
+    /// list only requires an authenticated caller
+    pub requires: Option<Vec<String>>,
+    /// How the upstream response is reshaped into the field's value; with
+    /// `pagination`, applied to each node
+    pub result: Option<ResultMappingConfig>,
+    /// Exposes the endpoint as a Relay connection
+    pub pagination: Option<PaginationConfig>,
+    /// `[[types]]` entry the field returns, as `Type` or `[Type]`; JSON when
+    /// unset. Paginated queries name the type of their connection's nodes.
+    pub returns: Option<String>,
+}
+
+/// An object type over upstream payloads, whose relationships resolve
+/// through other APIs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TypeConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// Upstream name of the type, filling `{type}` in sparse field
+    /// parameters; the type name when unset
+    pub resource: Option<String>,
+    /// Fields read from the payload by key, with their scalar types
+    #[serde(default)]
+    pub fields: HashMap<String, FieldType>,
+    #[serde(default)]
+    pub relationships: Vec<RelationshipConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    #[serde(rename = "ID")]
+    Id,
+    String,
+    Int,
+    Float,
+    Boolean,
+    #[serde(rename = "JSON")]
+    Json,
+}
+
+/// A field resolved by a GET against another API. `{parent.path}`
+/// placeholders are filled from the parent object and `{arg}` ones from
+/// field arguments.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelationshipConfig {
+    pub field: String,
+    pub description: Option<String>,
+    pub api: String,
+    pub path: String,
+    #[serde(default)]
+    pub arguments: Vec<String>,
+    /// `[[types]]` entry the field returns, as `Type` or `[Type]`; JSON when
+    /// unset
+    pub returns: Option<String>,
+    pub result: Option<ResultMappingConfig>,
+    /// Fetches the relationship for many parents in one request; required on
+    /// types that can appear in a list
+    pub batch: Option<BatchConfig>,
+}
+
+/// Batches the lookups of a relationship whose path has a single
+/// `{parent.path}` placeholder: it is filled with the parent values joined
+/// by `separator`, and the returned items are handed to the parents whose
+/// value matches their `key`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Path of the item value matched against the parent value
+    pub key: String,
+    pub separator: String,
+    /// Most parent values in one request
+    pub max_size: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            key: String::new(),
+            separator: ",".to_string(),
+            max_size: 50,
+        }
+    }
+}
+
+/// How a list endpoint pages through its items. `first`/`after` and
+/// `last`/`before` are translated into `offset`/`limit`, `page`/`limit`
+/// (pages of `page_size` items), an upstream cursor read from the body, or
+/// the `next`/`prev` targets of RFC 8288 `Link` headers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaginationConfig {
+    pub style: PaginationStyle,
+    /// Path of the item list in the body; the body itself when unset
+    pub items: Option<String>,
+    /// Path of the total number of items in the body; lets `last` be used
+    /// without `before` for `offset` and `page`
+    pub total_count: Option<String>,
+    /// Parameter carrying the page size; for `link`, only sent on the first
+    /// request
+    pub limit_param: String,
+    pub offset_param: String,
+    pub page_param: String,
+    /// Number of the first page
+    pub first_page: u64,
+    /// Items per upstream page for `page`; slices spanning several pages
+    /// fetch each of them
+    pub page_size: u64,
+    pub cursor_param: String,
+    /// Paths of the next and previous page cursors in the body, for `cursor`
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    /// Items returned when neither `first` nor `last` is given
+    pub default_page_size: u64,
+    /// Upper bound on `first` and `last`
+    pub max_page_size: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaginationStyle {
+    #[default]
+    Offset,
+    Page,
+    Cursor,
+    Link,
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        Self {
+            style: PaginationStyle::default(),
+            items: None,
+            total_count: None,
+            limit_param: "limit".to_string(),
+            offset_param: "offset".to_string(),
+            page_param: "page".to_string(),
+            first_page: 1,
+            page_size: 20,
+            cursor_param: "cursor".to_string(),
+            next_cursor: None,
+            prev_cursor: None,
+            default_page_size: 20,
+            max_page_size: 100,
+        }
+    }
+}
+
+/// Reshapes an upstream payload, in order: `select` extracts it, `aliases`
+/// rename keys, `camel_case` converts the remaining snake_case keys, then
+/// `coerce` and `defaults` apply. Paths are dot-separated keys, with `[n]`
+/// indexing arrays; arrays along a path are otherwise traversed element-wise.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResultMappingConfig {
+    /// Path of the payload within the response, e.g. `data.items`
+    pub select: Option<String>,
+    /// Convert snake_case keys to camelCase
+    pub camel_case: bool,
+    /// New key per upstream path, e.g. `"author.user_name" = "login"`
+    pub aliases: HashMap<String, String>,
+    /// Scalar conversion per (renamed) path
+    pub coerce: HashMap<String, Coercion>,
+    /// Value per (renamed) path for keys that are missing or null
+    pub defaults: HashMap<String, serde_json::Value>,
+}
+
+/// Target of a scalar coercion. `datetime` turns RFC 3339 strings,
+/// `YYYY-MM-DD[ HH:MM:SS]` strings and Unix timestamps into RFC 3339 UTC
+/// strings; the others parse numeric and boolean strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Coercion {
+    Int,
+    Float,
+    Boolean,
+    String,
+    DateTime,
+}
+
+/// A mutation field sending its `input` argument to a REST endpoint. The
+/// field returns `<Field>Payload`, holding the mapped response as `result`
+/// and the validation errors of rejected writes as `userErrors`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutationConfig {
+    pub field: String,
+    pub description: Option<String>,
+    /// Name of the `apis.rest` entry to call
+    pub api: String,
+    #[serde(default)]
+    pub method: WriteMethod,
+    /// Path template; `{arg}` placeholders are filled from field arguments
+    /// and `{input.key}` ones from required fields of the input
+    pub path: String,
+    #[serde(default)]
+    pub arguments: Vec<String>,
+    /// `[[inputs]]` entry of the `input` argument; requests have no body
+    /// when unset
+    pub input: Option<String>,
+    #[serde(default)]
+    pub body: BodyEncoding,
+    /// Header carrying the optional `idempotencyKey` argument, e.g.
+    /// `Idempotency-Key`
+    pub idempotency_header: Option<String>,
+    /// Scopes of which the caller needs one to run the mutation
+    pub requires: Option<Vec<String>>,
+    /// How a successful response is reshaped into `result`
+    pub result: Option<ResultMappingConfig>,
+    /// `[[types]]` entry of `result`, as `Type` or `[Type]`; JSON when unset
+    pub returns: Option<String>,
+    /// Upstream statuses whose body is reported as `userErrors` instead of
+    /// an error
+    #[serde(default = "default_user_error_statuses")]
+    pub user_error_statuses: Vec<u16>,
+}
+
+impl Default for MutationConfig {
+    fn default() -> Self {
+        Self {
+            field: String::new(),
+            description: None,
+            api: String::new(),
+            method: WriteMethod::default(),
+            path: String::new(),
+            arguments: vec![],
+            input: None,
+            body: BodyEncoding::default(),
+            idempotency_header: None,
+            requires: None,
+            result: None,
+            returns: None,
+            user_error_statuses: default_user_error_statuses(),
+        }
+    }
+}
+
+impl MutationConfig {
+    /// Name of the payload type the field returns, e.g. `CreateOrderPayload`.
+    pub fn payload_type(&self) -> String {
+        let mut chars = self.field.chars();
+        match chars.next() {
+            Some(first) => format!("{}{}Payload", first.to_uppercase(), chars.as_str()),
+            None => "Payload".to_string(),
+        }
+    }
+}
+
+fn default_user_error_statuses() -> Vec<u16> {
+    vec![400, 409, 422]
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum WriteMethod {
+    #[default]
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+/// How the input is sent: as JSON, `application/x-www-form-urlencoded` or
+/// `multipart/form-data`. Nested values are flattened into `a[b]` and
+/// `a[]` form keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyEncoding {
+    #[default]
+    Json,
+    Form,
+    Multipart,
+}
+
+/// A GraphQL input object, for the `input` argument of mutations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputTypeConfig {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub fields: HashMap<String, FieldType>,
+    /// Fields that must be given and not null
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+/// A GraphQL subscription field backed either by polling a REST endpoint or by
+/// events POSTed to the webhook route.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionConfig {
+    pub field: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<String>,
+    pub poll: Option<PollConfig>,
+    pub webhook: Option<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollConfig {
+    /// Name of the `apis.rest` entry to poll
+    pub api: String,
+    /// Path template; `{arg}` placeholders are filled from field arguments
+    pub path: String,
+    /// Seconds between polls
+    pub interval: u64,
+    /// JSON pointers excluded from change detection, e.g. "/updatedAt"
+    #[serde(default)]
+    pub ignore_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// Topic name; upstreams POST events to `/webhooks/{topic}`
+    pub topic: String,
+    /// Shared secret expected in the `X-Webhook-Token` header
+    pub secret: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,12 +775,21 @@ impl Default for Settings {
                 request_timeout: Some(30),
                 enable_playground: true,
                 cors_origins: vec!["*".to_string()],
+                cors: CorsConfig::default(),
+                playground: PlaygroundConfig::default(),
+                tls: None,
+                http2: Http2Config::default(),
+                compression: CompressionConfig::default(),
+                batching: BatchingConfig::default(),
             },
             cache: CacheConfig {
                 redis_url: None,
                 default_ttl: 300,
                 max_size: "100MB".to_string(),
                 enable_compression: true,
+                stale_while_revalidate: 0,
+                stale_if_error: 0,
+                response: ResponseCacheConfig::default(),
             },
             rate_limiting: RateLimitConfig {
                 requests_per_minute: 1000,
@@ -91,6 +805,121 @@ impl Default for Settings {
                 metrics_port: 9090,
                 log_level: "info".to_string(),
             },
+            queries: vec![],
+            types: vec![],
+            mutations: vec![],
+            inputs: vec![],
+            subscriptions: vec![],
+            persisted_queries: PersistedQueriesConfig::default(),
+            auth: AuthConfig::default(),
+            errors: ErrorsConfig::default(),
+            planner: PlannerConfig::default(),
+        }
+    }
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            required: false,
+            algorithms: vec![],
+            issuer: None,
+            audience: vec![],
+            leeway: 60,
+            secret: None,
+            public_key_path: None,
+            jwks_path: None,
+            jwks_url: None,
+            jwks_refresh_interval: 300,
+        }
+    }
+}
+
+impl Default for ApiKeysConfig {
+    fn default() -> Self {
+        Self {
+            required: false,
+            header: "x-api-key".to_string(),
+            query_param: None,
+            keys: vec![],
+            keys_path: None,
+            reload_interval: 30,
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allow_credentials: false,
+            allowed_headers: vec![
+                "content-type".to_string(),
+                "authorization".to_string(),
+                "x-request-id".to_string(),
+            ],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string(), "OPTIONS".to_string()],
+            exposed_headers: vec![],
+            max_age: Some(600),
+        }
+    }
+}
+
+impl Default for PlaygroundConfig {
+    fn default() -> Self {
+        Self {
+            ui: PlaygroundUi::GraphiQL,
+            title: "RustQL GraphQL Playground".to_string(),
+            endpoint: "/graphql".to_string(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+impl Default for Http2Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            h2c: false,
+        }
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size: 1024,
+            algorithms: vec!["br".to_string(), "zstd".to_string(), "gzip".to_string()],
+        }
+    }
+}
+
+impl Default for BatchingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_batch_size: 10,
+        }
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: 5,
+            reset_timeout: 30,
+        }
+    }
+}
+
+impl Default for PersistedQueriesConfig {
+    fn default() -> Self {
+        Self {
+            apq: true,
+            ttl: 86400,
+            manifest_path: None,
+            allowlist_only: false,
         }
     }
 }
@@ -115,16 +944,673 @@ impl Settings {
             return Err("Server port cannot be 0".to_string());
         }
 
+        if self.server.workers == Some(0) {
+            return Err("Server workers cannot be 0".to_string());
+        }
+
+        if self.server.max_connections == Some(0) {
+            return Err("Server max_connections cannot be 0".to_string());
+        }
+
+        if self.server.request_timeout == Some(0) {
+            return Err("Server request_timeout cannot be 0".to_string());
+        }
+
         if self.rate_limiting.requests_per_minute == 0 {
             return Err("Rate limit requests per minute cannot be 0".to_string());
         }
 
+        self.validate_cors()?;
+
+        if let Some(tls) = &self.server.tls {
+            if tls.cert_path.is_empty() || tls.key_path.is_empty() {
+                return Err("TLS cert_path and key_path cannot be empty".to_string());
+            }
+            if tls.reload_interval == Some(0) {
+                return Err("TLS reload_interval cannot be 0".to_string());
+            }
+        }
+
+        for algorithm in &self.server.compression.algorithms {
+            if !matches!(algorithm.as_str(), "br" | "zstd" | "gzip") {
+                return Err(format!("Unsupported compression algorithm '{}'", algorithm));
+            }
+        }
+
+        if self.server.batching.enabled && self.server.batching.max_batch_size == 0 {
+            return Err("Batching max_batch_size cannot be 0".to_string());
+        }
+
+        if parse_size(&self.cache.max_size).is_none() {
+            return Err(format!("Invalid cache max_size '{}'", self.cache.max_size));
+        }
+
+        if let Some(hint) = self.cache.response.hints.keys().find(|hint| {
+            !hint
+                .split_once('.')
+                .is_some_and(|(ty, field)| is_graphql_name(ty) && is_graphql_name(field))
+        }) {
+            return Err(format!(
+                "Cache hint '{}' must be of the form Type.field",
+                hint
+            ));
+        }
+
+        if self.persisted_queries.allowlist_only && self.persisted_queries.manifest_path.is_none() {
+            return Err("Persisted query allowlist requires a manifest_path".to_string());
+        }
+
+        if self.server.enable_playground && self.server.playground.endpoint.is_empty() {
+            return Err("Playground endpoint cannot be empty".to_string());
+        }
+
         for api in &self.apis.rest {
             if api.base_url.is_empty() {
                 return Err(format!("Base URL for API '{}' cannot be empty", api.name));
             }
+            if api.circuit_breaker.enabled && api.circuit_breaker.failure_threshold == 0 {
+                return Err(format!(
+                    "Circuit breaker failure_threshold for API '{}' cannot be 0",
+                    api.name
+                ));
+            }
+            match &api.auth {
+                Some(UpstreamAuthConfig::OAuth2(oauth2))
+                    if oauth2.token_url.is_empty() || oauth2.client_id.is_empty() =>
+                {
+                    return Err(format!(
+                        "OAuth2 auth for API '{}' needs a token_url and client_id",
+                        api.name
+                    ));
+                }
+                Some(UpstreamAuthConfig::Hmac(hmac))
+                    if hmac.key_id.is_empty() || hmac.secret.is_empty() =>
+                {
+                    return Err(format!(
+                        "HMAC auth for API '{}' needs a key_id and secret",
+                        api.name
+                    ));
+                }
+                _ => {}
+            }
+            if let Some(sparse) = &api.sparse_fields {
+                if sparse.param.is_empty() || sparse.separator.is_empty() {
+                    return Err(format!(
+                        "Sparse fields of API '{}' need a param and separator",
+                        api.name
+                    ));
+                }
+            }
+        }
+
+        if let Some(jwt) = &self.auth.jwt {
+            if jwt.secret.is_none()
+                && jwt.public_key_path.is_none()
+                && jwt.jwks_path.is_none()
+                && jwt.jwks_url.is_none()
+            {
+                return Err(
+                    "JWT auth needs a secret, public_key_path, jwks_path or jwks_url".to_string(),
+                );
+            }
+            if jwt.jwks_refresh_interval == 0 {
+                return Err("JWT jwks_refresh_interval cannot be 0".to_string());
+            }
         }
 
+        self.validate_api_keys()?;
+
+        if let Some(target) = self.auth.rules.keys().find(|target| {
+            let (ty, field) = target.split_once('.').unwrap_or((target.as_str(), "_"));
+            !is_graphql_name(ty) || !is_graphql_name(field)
+        }) {
+            return Err(format!(
+                "Auth rule '{}' must be of the form Type or Type.field",
+                target
+            ));
+        }
+        if self.planner.max_concurrency == 0 {
+            return Err("Planner max_concurrency cannot be 0".to_string());
+        }
+        if let Some(header) = &self.planner.debug_header {
+            if warp::http::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                return Err(format!("Invalid planner debug_header '{}'", header));
+            }
+        }
+
+        self.validate_queries()?;
+        self.validate_types()?;
+        self.validate_mutations()?;
+        self.validate_subscriptions()?;
+
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn validate_queries(&self) -> Result<(), String> {
+        for query in &self.queries {
+            if !is_graphql_name(&query.field) {
+                return Err(format!(
+                    "Query field '{}' is not a valid GraphQL name",
+                    query.field
+                ));
+            }
+
+            if let Some(argument) = query.arguments.iter().find(|a| !is_graphql_name(a)) {
+                return Err(format!(
+                    "Query '{}' argument '{}' is not a valid GraphQL name",
+                    query.field, argument
+                ));
+            }
+
+            if !self.apis.rest.iter().any(|api| api.name == query.api) {
+                return Err(format!(
+                    "Query '{}' calls unknown API '{}'",
+                    query.field, query.api
+                ));
+            }
+
+            if let Some(placeholder) = unbound_placeholder(&query.path, &query.arguments) {
+                return Err(format!(
+                    "Query '{}' has no argument for '{{{}}}'",
+                    query.field, placeholder
+                ));
+            }
+
+            if let Some(pagination) = &query.pagination {
+                if pagination.default_page_size == 0
+                    || pagination.max_page_size == 0
+                    || pagination.page_size == 0
+                {
+                    return Err(format!(
+                        "Pagination page sizes of query '{}' cannot be 0",
+                        query.field
+                    ));
+                }
+                if pagination.style == PaginationStyle::Cursor && pagination.next_cursor.is_none()
+                {
+                    return Err(format!(
+                        "Cursor pagination of query '{}' needs a next_cursor path",
+                        query.field
+                    ));
+                }
+                if let Some(node) = &query.returns {
+                    if node.starts_with('[') {
+                        return Err(format!(
+                            "Paginated query '{}' must name its node type without brackets",
+                            query.field
+                        ));
+                    }
+                    let taken = |name: &str| {
+                        self.types.iter().any(|ty| ty.name == name)
+                            || self.inputs.iter().any(|input| input.name == name)
+                    };
+                    if let Some(name) = [format!("{}Connection", node), format!("{}Edge", node)]
+                        .into_iter()
+                        .find(|name| taken(name))
+                    {
+                        return Err(format!(
+                            "Connection type '{}' of query '{}' is already declared",
+                            name, query.field
+                        ));
+                    }
+                }
+                if let Some(argument) = ["first", "after", "last", "before"]
+                    .into_iter()
+                    .find(|name| query.arguments.iter().any(|a| a == name))
+                {
+                    return Err(format!(
+                        "Paginated query '{}' cannot declare argument '{}'",
+                        query.field, argument
+                    ));
+                }
+            }
+
+            self.validate_returns(&query.field, query.returns.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_types(&self) -> Result<(), String> {
+        for (i, ty) in self.types.iter().enumerate() {
+            if !is_graphql_name(&ty.name) || RESERVED_TYPE_NAMES.contains(&ty.name.as_str()) {
+                return Err(format!("Type name '{}' is invalid or reserved", ty.name));
+            }
+            if self.types[..i].iter().any(|other| other.name == ty.name) {
+                return Err(format!("Type '{}' is declared twice", ty.name));
+            }
+            if let Some(field) = ty.fields.keys().find(|field| !is_graphql_name(field)) {
+                return Err(format!(
+                    "Type '{}' field '{}' is not a valid GraphQL name",
+                    ty.name, field
+                ));
+            }
+
+            for relationship in &ty.relationships {
+                let field = format!("{}.{}", ty.name, relationship.field);
+                if !is_graphql_name(&relationship.field) {
+                    return Err(format!(
+                        "Relationship '{}' is not a valid GraphQL name",
+                        field
+                    ));
+                }
+                if ty.fields.contains_key(&relationship.field)
+                    || ty
+                        .relationships
+                        .iter()
+                        .filter(|other| other.field == relationship.field)
+                        .count()
+                        > 1
+                {
+                    return Err(format!("Field '{}' is declared twice", field));
+                }
+                if let Some(argument) = relationship.arguments.iter().find(|a| !is_graphql_name(a)) {
+                    return Err(format!(
+                        "Relationship '{}' argument '{}' is not a valid GraphQL name",
+                        field, argument
+                    ));
+                }
+                if !self.apis.rest.iter().any(|api| api.name == relationship.api) {
+                    return Err(format!(
+                        "Relationship '{}' calls unknown API '{}'",
+                        field, relationship.api
+                    ));
+                }
+                // `{parent.*}` placeholders are filled from the parent object
+                let unbound = placeholders(&relationship.path)
+                    .filter(|name| !name.starts_with("parent."))
+                    .find(|name| !relationship.arguments.iter().any(|a| a == name));
+                if let Some(placeholder) = unbound {
+                    return Err(format!(
+                        "Relationship '{}' has no argument for '{{{}}}'",
+                        field, placeholder
+                    ));
+                }
+                if let Some(batch) = &relationship.batch {
+                    if batch.key.is_empty() || batch.max_size == 0 {
+                        return Err(format!(
+                            "Batching of relationship '{}' needs a key and a max_size above 0",
+                            field
+                        ));
+                    }
+                    if relationship.path.matches("{parent.").count() != 1 {
+                        return Err(format!(
+                            "Batched relationship '{}' needs exactly one {{parent.*}} placeholder",
+                            field
+                        ));
+                    }
+                }
+                self.validate_returns(&field, relationship.returns.as_deref())?;
+            }
+        }
+
+        let listed = self.listed_types();
+        for ty in self.types.iter().filter(|ty| listed.contains(ty.name.as_str())) {
+            if let Some(relationship) = ty.relationships.iter().find(|r| r.batch.is_none()) {
+                return Err(format!(
+                    "Relationship '{}.{}' resolves once per list item and needs a batch",
+                    ty.name, relationship.field
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Types whose objects can appear in a list: those returned as `[Type]`
+    /// and those reached from such objects through relationships.
+    fn listed_types(&self) -> HashSet<&str> {
+        fn list_item(returns: Option<&str>) -> Option<&str> {
+            returns
+                .and_then(|returns| returns.strip_prefix('['))
+                .and_then(|name| name.strip_suffix(']'))
+        }
+        let mut listed: HashSet<&str> = self
+            .queries
+            .iter()
+            .filter_map(|query| list_item(query.returns.as_deref()))
+            .chain(
+                self.types
+                    .iter()
+                    .flat_map(|ty| &ty.relationships)
+                    .filter_map(|relationship| list_item(relationship.returns.as_deref())),
+            )
+            .collect();
+        loop {
+            let reached: Vec<&str> = self
+                .types
+                .iter()
+                .filter(|ty| listed.contains(ty.name.as_str()))
+                .flat_map(|ty| &ty.relationships)
+                .filter_map(|relationship| relationship.returns.as_deref())
+                .map(|returns| list_item(Some(returns)).unwrap_or(returns))
+                .filter(|name| !listed.contains(name))
+                .collect();
+            if reached.is_empty() {
+                return listed;
+            }
+            listed.extend(reached);
+        }
+    }
+
+    /// Checks that a `returns` entry names a declared type.
+    fn validate_returns(&self, field: &str, returns: Option<&str>) -> Result<(), String> {
+        let Some(returns) = returns else {
+            return Ok(());
+        };
+        let name = returns
+            .strip_prefix('[')
+            .and_then(|name| name.strip_suffix(']'))
+            .unwrap_or(returns);
+        if !self.types.iter().any(|ty| ty.name == name) {
+            return Err(format!(
+                "Field '{}' returns undeclared type '{}'",
+                field, returns
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_mutations(&self) -> Result<(), String> {
+        let type_declared = |name: &str| {
+            RESERVED_TYPE_NAMES.contains(&name) || self.types.iter().any(|ty| ty.name == name)
+        };
+
+        for (i, input) in self.inputs.iter().enumerate() {
+            if !is_graphql_name(&input.name) || type_declared(&input.name) {
+                return Err(format!("Input name '{}' is invalid or taken", input.name));
+            }
+            if self.inputs[..i]
+                .iter()
+                .any(|other| other.name == input.name)
+            {
+                return Err(format!("Input '{}' is declared twice", input.name));
+            }
+            if let Some(field) = input.fields.keys().find(|field| !is_graphql_name(field)) {
+                return Err(format!(
+                    "Input '{}' field '{}' is not a valid GraphQL name",
+                    input.name, field
+                ));
+            }
+            if let Some(field) = input
+                .required
+                .iter()
+                .find(|field| !input.fields.contains_key(*field))
+            {
+                return Err(format!(
+                    "Input '{}' requires undeclared field '{}'",
+                    input.name, field
+                ));
+            }
+        }
+
+        for (i, mutation) in self.mutations.iter().enumerate() {
+            if !is_graphql_name(&mutation.field) || mutation.field == "testMutation" {
+                return Err(format!(
+                    "Mutation field '{}' is invalid or reserved",
+                    mutation.field
+                ));
+            }
+            if self.mutations[..i]
+                .iter()
+                .any(|other| other.field == mutation.field)
+            {
+                return Err(format!("Mutation '{}' is declared twice", mutation.field));
+            }
+            let payload = mutation.payload_type();
+            if type_declared(&payload) || self.inputs.iter().any(|input| input.name == payload) {
+                return Err(format!(
+                    "Payload type '{}' of mutation '{}' is already declared",
+                    payload, mutation.field
+                ));
+            }
+            if let Some(argument) = mutation
+                .arguments
+                .iter()
+                .find(|a| !is_graphql_name(a) || matches!(a.as_str(), "input" | "idempotencyKey"))
+            {
+                return Err(format!(
+                    "Mutation '{}' argument '{}' is invalid or reserved",
+                    mutation.field, argument
+                ));
+            }
+            if !self.apis.rest.iter().any(|api| api.name == mutation.api) {
+                return Err(format!(
+                    "Mutation '{}' calls unknown API '{}'",
+                    mutation.field, mutation.api
+                ));
+            }
+            if let Some(input) = &mutation.input {
+                if !self.inputs.iter().any(|other| &other.name == input) {
+                    return Err(format!(
+                        "Mutation '{}' takes undeclared input '{}'",
+                        mutation.field, input
+                    ));
+                }
+            }
+            // Every placeholder must have a value, or the write would go to
+            // a path with an empty segment
+            let input = mutation
+                .input
+                .as_ref()
+                .and_then(|input| self.inputs.iter().find(|other| &other.name == input));
+            let has_value = |name: &str| match name.strip_prefix("input.") {
+                Some(key) => input.is_some_and(|input| input.required.iter().any(|r| r == key)),
+                None => mutation.arguments.iter().any(|a| a == name),
+            };
+            if let Some(placeholder) = placeholders(&mutation.path).find(|name| !has_value(name)) {
+                return Err(format!(
+                    "Mutation '{}' has no argument or required input for '{{{}}}'",
+                    mutation.field, placeholder
+                ));
+            }
+            if let Some(header) = &mutation.idempotency_header {
+                if warp::http::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                    return Err(format!(
+                        "Invalid idempotency_header '{}' for mutation '{}'",
+                        header, mutation.field
+                    ));
+                }
+            }
+            if let Some(status) = mutation
+                .user_error_statuses
+                .iter()
+                .find(|status| !(400..500).contains(*status))
+            {
+                return Err(format!(
+                    "User error status {} of mutation '{}' is not a 4xx status",
+                    status, mutation.field
+                ));
+            }
+            self.validate_returns(&mutation.field, mutation.returns.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_subscriptions(&self) -> Result<(), String> {
+        for subscription in &self.subscriptions {
+            if !is_graphql_name(&subscription.field) {
+                return Err(format!(
+                    "Subscription field '{}' is not a valid GraphQL name",
+                    subscription.field
+                ));
+            }
+
+            if let Some(argument) = subscription.arguments.iter().find(|a| !is_graphql_name(a)) {
+                return Err(format!(
+                    "Subscription '{}' argument '{}' is not a valid GraphQL name",
+                    subscription.field, argument
+                ));
+            }
+
+            match (&subscription.poll, &subscription.webhook) {
+                (Some(poll), None) => {
+                    if !self.apis.rest.iter().any(|api| api.name == poll.api) {
+                        return Err(format!(
+                            "Subscription '{}' polls unknown API '{}'",
+                            subscription.field, poll.api
+                        ));
+                    }
+                    if poll.interval == 0 {
+                        return Err(format!(
+                            "Subscription '{}' poll interval cannot be 0",
+                            subscription.field
+                        ));
+                    }
+                    if let Some(placeholder) =
+                        unbound_placeholder(&poll.path, &subscription.arguments)
+                    {
+                        return Err(format!(
+                            "Subscription '{}' has no argument for '{{{}}}'",
+                            subscription.field, placeholder
+                        ));
+                    }
+                }
+                (None, Some(webhook)) => {
+                    if webhook.topic.is_empty() || webhook.topic.contains('/') {
+                        return Err(format!(
+                            "Subscription '{}' webhook topic must be a non-empty path segment",
+                            subscription.field
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "Subscription '{}' must set exactly one of poll or webhook",
+                        subscription.field
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_cors(&self) -> Result<(), String> {
+        let cors = &self.server.cors;
+
+        for origin in &self.server.cors_origins {
+            if origin == "*" {
+                if cors.allow_credentials {
+                    return Err(
+                        "CORS origin '*' cannot be combined with allow_credentials = true"
+                            .to_string(),
+                    );
+                }
+                continue;
+            }
+
+            let (scheme, host) = origin
+                .split_once("://")
+                .ok_or_else(|| format!("CORS origin '{}' must include a scheme", origin))?;
+
+            if scheme.is_empty() || host.is_empty() || host.contains('/') {
+                return Err(format!(
+                    "CORS origin '{}' must be of the form scheme://host[:port]",
+                    origin
+                ));
+            }
+
+            if host.contains('*') && (!host.starts_with("*.") || host[2..].contains('*')) {
+                return Err(format!(
+                    "CORS origin '{}' may only use '*' as the leading subdomain label",
+                    origin
+                ));
+            }
+        }
+
+        for method in &cors.allowed_methods {
+            if method.parse::<warp::http::Method>().is_err() {
+                return Err(format!("Invalid CORS method '{}'", method));
+            }
+        }
+
+        for header in cors.allowed_headers.iter().chain(&cors.exposed_headers) {
+            if warp::http::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                return Err(format!("Invalid CORS header name '{}'", header));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_api_keys(&self) -> Result<(), String> {
+        let Some(api_keys) = &self.auth.api_keys else {
+            return Ok(());
+        };
+
+        if warp::http::HeaderName::from_bytes(api_keys.header.as_bytes()).is_err() {
+            return Err(format!("Invalid API key header name '{}'", api_keys.header));
+        }
+        if api_keys.query_param.as_deref() == Some("") {
+            return Err("API key query_param cannot be empty".to_string());
+        }
+        if api_keys.keys_path.is_some() && api_keys.reload_interval == 0 {
+            return Err("API key reload_interval cannot be 0".to_string());
+        }
+
+        api_keys.keys.iter().try_for_each(ApiKeyConfig::validate)
+    }
+}
+
+impl ApiKeyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("API key name cannot be empty".to_string());
+        }
+        if self.key_hash.len() != 64 || !self.key_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!(
+                "API key '{}' key_hash must be a SHA-256 hex digest",
+                self.name
+            ));
+        }
+        if self
+            .rate_limit
+            .is_some_and(|limit| limit.requests_per_minute == 0)
+        {
+            return Err(format!(
+                "API key '{}' requests_per_minute cannot be 0",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Types the gateway registers itself.
+const RESERVED_TYPE_NAMES: &[&str] = &[
+    "QueryRoot",
+    "MutationRoot",
+    "SubscriptionRoot",
+    "ApiInfo",
+    "SystemStatus",
+    "JSON",
+    "PageInfo",
+    "JSONEdge",
+    "JSONConnection",
+    "UserError",
+];
+
+/// GraphQL names match `[_A-Za-z][_0-9A-Za-z]*`.
+pub fn is_graphql_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Names of the `{name}` placeholders in a path template.
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.find('}').map(|end| &rest[..end]))
+}
+
+/// The first placeholder of `template` that none of the field's `arguments`
+/// fills.
+fn unbound_placeholder<'a>(template: &'a str, arguments: &[String]) -> Option<&'a str> {
+    placeholders(template).find(|name| !arguments.iter().any(|argument| argument == name))
+}
@@ -0,0 +1,383 @@
+use crate::auth::Claims;
+use crate::config::Settings;
+use crate::config::settings::AuthRule;
+use crate::graphql::partial::response_path;
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::schema::{MUTATION_ROOT, QUERY_ROOT, SUBSCRIPTION_ROOT};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextResolve,
+    ResolveInfo,
+};
+use async_graphql::indexmap::IndexMap;
+use async_graphql::parser::types::{
+    ExecutableDocument, Field, FragmentDefinition, Selection, SelectionSet,
+};
+use async_graphql::{
+    ErrorExtensionValues, ErrorExtensions, Name, Positioned, Response, ServerError, ServerResult,
+    Value, Variables,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// `@auth(requires: [...])` rules per `Type` and `Type.field`, from
+/// `[auth.rules]` and the `requires` of `[[queries]]` and `[[mutations]]`
+/// entries. A field is resolvable when its own rule and its parent type's
+/// rule both allow the caller.
+#[derive(Debug, Clone, Default)]
+pub struct AuthRules {
+    rules: HashMap<String, AuthRule>,
+    hide_unauthorized: bool,
+}
+
+impl AuthRules {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let mut rules = settings.auth.rules.clone();
+        for query in &settings.queries {
+            if let Some(requires) = &query.requires {
+                rules.insert(
+                    format!("{}.{}", QUERY_ROOT, query.field),
+                    AuthRule {
+                        requires: requires.clone(),
+                    },
+                );
+            }
+        }
+        for mutation in &settings.mutations {
+            if let Some(requires) = &mutation.requires {
+                rules.insert(
+                    format!("{}.{}", MUTATION_ROOT, mutation.field),
+                    AuthRule {
+                        requires: requires.clone(),
+                    },
+                );
+            }
+        }
+
+        Self {
+            rules,
+            hide_unauthorized: settings.auth.hide_unauthorized,
+        }
+    }
+
+    pub fn allows(&self, parent_type: &str, field: &str, claims: Option<&Claims>) -> bool {
+        [
+            self.rules.get(parent_type),
+            self.rules.get(&format!("{}.{}", parent_type, field)),
+        ]
+        .into_iter()
+        .flatten()
+        .all(|rule| satisfies(rule, claims))
+    }
+
+    /// Names of the fields of `parent_type` the caller may not resolve, or of
+    /// any type when `parent_type` is unknown.
+    fn denied_fields(&self, parent_type: Option<&str>, claims: Option<&Claims>) -> HashSet<&str> {
+        self.rules
+            .iter()
+            .filter(|(_, rule)| !satisfies(rule, claims))
+            .filter_map(|(target, _)| {
+                let (ty, field) = target.split_once('.')?;
+                parent_type
+                    .is_none_or(|parent| parent == ty)
+                    .then_some(field)
+            })
+            .collect()
+    }
+
+    /// Removes denied fields from the `fields` of the `__Type` values in
+    /// `value`, identifying each type by its `name`. `keys` gives the
+    /// response keys those selections were returned under.
+    fn hide_fields(&self, value: &mut Value, keys: &TypeKeys, claims: Option<&Claims>) {
+        match value {
+            Value::List(types) => {
+                for ty in types {
+                    self.hide_fields(ty, keys, claims);
+                }
+            }
+            Value::Object(ty) => {
+                let name = keys.name.iter().find_map(|key| match ty.get(key.as_str()) {
+                    Some(Value::String(name)) => Some(name.clone()),
+                    _ => None,
+                });
+                self.hide_type_fields(ty, name.as_deref(), keys, claims);
+            }
+            _ => {}
+        }
+    }
+
+    fn hide_type_fields(
+        &self,
+        ty: &mut IndexMap<Name, Value>,
+        name: Option<&str>,
+        keys: &TypeKeys,
+        claims: Option<&Claims>,
+    ) {
+        let type_denied = name
+            .and_then(|name| self.rules.get(name))
+            .is_some_and(|rule| !satisfies(rule, claims));
+        let denied = self.denied_fields(name, claims);
+
+        for (key, name_keys) in &keys.fields {
+            let Some(Value::List(fields)) = ty.get_mut(key.as_str()) else {
+                continue;
+            };
+            if type_denied {
+                fields.clear();
+                continue;
+            }
+            fields.retain(|field| match field {
+                Value::Object(field) => !name_keys.iter().any(|key| {
+                    matches!(
+                        field.get(key.as_str()),
+                        Some(Value::String(name)) if denied.contains(name.as_str())
+                    )
+                }),
+                _ => true,
+            });
+        }
+    }
+}
+
+type Fragments = HashMap<Name, Positioned<FragmentDefinition>>;
+
+/// Response keys of the `name` and `fields { name }` selections of a
+/// `__Type`, so aliased selections are filtered like unaliased ones.
+#[derive(Debug, Default)]
+struct TypeKeys {
+    name: Vec<String>,
+    /// Each `fields` selection with the response keys of its `name`s
+    fields: Vec<(String, Vec<String>)>,
+}
+
+impl TypeKeys {
+    fn of(selection_set: &SelectionSet, fragments: &Fragments) -> Self {
+        let mut keys = Self::default();
+        for field in selected_fields(selection_set, fragments) {
+            let key = field.response_key().node.to_string();
+            match field.name.node.as_str() {
+                "name" => keys.name.push(key),
+                "fields" => {
+                    let names = selected_fields(&field.selection_set.node, fragments)
+                        .into_iter()
+                        .filter(|field| field.name.node == "name")
+                        .map(|field| field.response_key().node.to_string())
+                        .collect();
+                    keys.fields.push((key, names));
+                }
+                _ => {}
+            }
+        }
+        keys
+    }
+}
+
+/// The fields of `selection_set`, with fragments expanded.
+fn selected_fields<'a>(
+    selection_set: &'a SelectionSet,
+    fragments: &'a Fragments,
+) -> Vec<&'a Field> {
+    let mut fields = Vec::new();
+    for selection in &selection_set.items {
+        match &selection.node {
+            Selection::Field(field) => fields.push(&field.node),
+            Selection::InlineFragment(fragment) => {
+                fields.extend(selected_fields(
+                    &fragment.node.selection_set.node,
+                    fragments,
+                ));
+            }
+            Selection::FragmentSpread(spread) => {
+                if let Some(fragment) = fragments.get(&spread.node.fragment_name.node) {
+                    fields.extend(selected_fields(
+                        &fragment.node.selection_set.node,
+                        fragments,
+                    ));
+                }
+            }
+        }
+    }
+    fields
+}
+
+fn satisfies(rule: &AuthRule, claims: Option<&Claims>) -> bool {
+    let Some(claims) = claims else {
+        return false;
+    };
+    rule.requires.is_empty() || {
+        let scopes = claims.scopes();
+        rule.requires
+            .iter()
+            .any(|required| scopes.contains(&required.as_str()))
+    }
+}
+
+/// Response keys, type names and selections of the root `__type` fields in
+/// `document`. Their `__Type` is resolved outside the resolve hook, so it is
+/// filtered once the operation has executed.
+fn root_type_fields(
+    document: &ExecutableDocument,
+    variables: &Variables,
+) -> Vec<(String, String, SelectionSet)> {
+    document
+        .operations
+        .iter()
+        .flat_map(|(_, operation)| &operation.node.selection_set.node.items)
+        .filter_map(|selection| match &selection.node {
+            Selection::Field(field) if field.node.name.node == "__type" => {
+                let name = field.node.get_argument("name")?;
+                let name = name
+                    .node
+                    .clone()
+                    .into_const_with(|variable| variables.get(&variable).cloned().ok_or(()));
+                match name {
+                    Ok(Value::String(name)) => Some((
+                        field.node.response_key().node.to_string(),
+                        name,
+                        field.node.selection_set.node.clone(),
+                    )),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn forbidden(info: &ResolveInfo<'_>) -> ServerError {
+    let mut extensions = ErrorExtensionValues::default();
+    extensions.set("code", "FORBIDDEN");
+    let mut error = ServerError::new(
+        format!(
+            "Not authorized to access {}.{}",
+            info.parent_type, info.name
+        ),
+        Some(info.field.name.pos),
+    );
+    error.path = response_path(info.path_node);
+    error.extensions = Some(extensions);
+    error
+}
+
+/// Refuses a subscription root field the caller may not resolve. The
+/// resolve hook only runs for the events a subscription yields, so
+/// subscription resolvers check this before they start polling or listening.
+pub fn authorize_subscription(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+) -> async_graphql::Result<()> {
+    let Some(rules) = ctx.data_opt::<Arc<AuthRules>>() else {
+        return Ok(());
+    };
+    let claims = ctx
+        .data_opt::<ResolverContext>()
+        .and_then(|context| context.claims.as_deref());
+    let field = ctx.field().name();
+    if rules.allows(SUBSCRIPTION_ROOT, field, claims) {
+        return Ok(());
+    }
+    Err(async_graphql::Error::new(format!(
+        "Not authorized to access {}.{}",
+        SUBSCRIPTION_ROOT, field
+    ))
+    .extend_with(|_, extensions| extensions.set("code", "FORBIDDEN")))
+}
+
+/// Enforces `AuthRules` against the claims in the request's
+/// `ResolverContext`. Denied fields resolve to null with a `FORBIDDEN` error
+/// so the rest of the operation still returns data.
+pub struct AuthorizationExtension(pub Arc<AuthRules>);
+
+impl ExtensionFactory for AuthorizationExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(AuthorizationExtensionImpl {
+            rules: self.0.clone(),
+            introspection: Mutex::default(),
+        })
+    }
+}
+
+/// What filtering introspection needs from the operation's document.
+#[derive(Default)]
+struct Introspection {
+    fragments: Fragments,
+    root_types: Vec<(String, String, SelectionSet)>,
+}
+
+struct AuthorizationExtensionImpl {
+    rules: Arc<AuthRules>,
+    introspection: Mutex<Introspection>,
+}
+
+#[async_trait::async_trait]
+impl Extension for AuthorizationExtensionImpl {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        if self.rules.hide_unauthorized {
+            *self.introspection.lock().expect("introspection lock") = Introspection {
+                fragments: document.fragments.clone(),
+                root_types: root_type_fields(&document, variables),
+            };
+        }
+        Ok(document)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let mut response = next.run(ctx, operation_name).await;
+
+        let introspection =
+            std::mem::take(&mut *self.introspection.lock().expect("introspection lock"));
+        if let Value::Object(data) = &mut response.data {
+            let claims = ctx
+                .data_opt::<ResolverContext>()
+                .and_then(|context| context.claims.as_deref());
+            for (key, name, selection_set) in &introspection.root_types {
+                if let Some(Value::Object(ty)) = data.get_mut(key.as_str()) {
+                    let keys = TypeKeys::of(selection_set, &introspection.fragments);
+                    self.rules.hide_type_fields(ty, Some(name), &keys, claims);
+                }
+            }
+        }
+        response
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let claims = ctx
+            .data_opt::<ResolverContext>()
+            .and_then(|context| context.claims.as_deref());
+
+        if info.is_for_introspection {
+            let returns_type = info.return_type.trim_matches(['[', ']', '!']) == "__Type";
+            if !self.rules.hide_unauthorized || !returns_type {
+                return next.run(ctx, info).await;
+            }
+            let selection_set = &info.field.selection_set.node;
+            let mut value = next.run(ctx, info).await?;
+            if let Some(value) = &mut value {
+                let introspection = self.introspection.lock().expect("introspection lock");
+                let keys = TypeKeys::of(selection_set, &introspection.fragments);
+                self.rules.hide_fields(value, &keys, claims);
+            }
+            return Ok(value);
+        }
+
+        if !self.rules.allows(info.parent_type, info.name, claims) {
+            return Err(forbidden(&info));
+        }
+        next.run(ctx, info).await
+    }
+}
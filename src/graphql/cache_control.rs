@@ -0,0 +1,156 @@
+use crate::config::settings::ResponseCacheConfig;
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::schema::QUERY_ROOT;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextPrepareRequest, NextResolve,
+    ResolveInfo,
+};
+use async_graphql::{CacheControl, Request, Response, ServerResult, Value};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// `@cacheControl(maxAge)` hints per `Type.field`. Root fields without a
+/// hint get `default_max_age`; nested fields without one inherit from their
+/// parent, i.e. don't constrain the response.
+#[derive(Debug, Clone, Default)]
+pub struct CacheHints {
+    hints: HashMap<String, u64>,
+    default_max_age: u64,
+}
+
+impl CacheHints {
+    pub fn from_config(config: &ResponseCacheConfig) -> Self {
+        Self {
+            hints: config.hints.clone(),
+            default_max_age: config.default_max_age,
+        }
+    }
+
+    pub fn max_age(&self, parent_type: &str, field: &str) -> Option<u64> {
+        self.hints
+            .get(&format!("{}.{}", parent_type, field))
+            .copied()
+            .or((parent_type == QUERY_ROOT).then_some(self.default_max_age))
+    }
+}
+
+/// Upstream values served stale while executing one operation, reported
+/// under `extensions.stale`.
+#[derive(Debug, Default)]
+pub struct StaleData(Mutex<Vec<serde_json::Value>>);
+
+impl StaleData {
+    pub fn record(&self, path: Option<String>, api: &str, stale_for: Duration) {
+        self.0.lock().expect("stale data lock").push(json!({
+            "path": path,
+            "api": api,
+            "staleFor": stale_for.as_secs(),
+        }));
+    }
+
+    fn take(&self) -> Vec<serde_json::Value> {
+        std::mem::take(&mut *self.0.lock().expect("stale data lock"))
+    }
+}
+
+/// Set when a field called an upstream with headers propagated from the
+/// caller, so the response must not be shared with other callers.
+#[derive(Debug, Default)]
+pub struct CallerScoped(AtomicBool);
+
+impl CallerScoped {
+    pub fn mark(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_marked(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Sets `Response::cache_control` to the smallest maxAge hint among the
+/// fields the operation resolved. Responses to authenticated callers or
+/// built from caller-specific upstream requests are marked private, and
+/// responses containing stale upstream data are reported in
+/// `extensions.stale` and never cacheable.
+pub struct CacheControlExtension(pub Arc<CacheHints>);
+
+impl ExtensionFactory for CacheControlExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(CacheControlExtensionImpl {
+            hints: self.0.clone(),
+            max_age: Mutex::new(None),
+        })
+    }
+}
+
+struct CacheControlExtensionImpl {
+    hints: Arc<CacheHints>,
+    max_age: Mutex<Option<u64>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for CacheControlExtensionImpl {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        request.data.insert(StaleData::default());
+        request.data.insert(CallerScoped::default());
+        next.run(ctx, request).await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let mut response = next.run(ctx, operation_name).await;
+
+        let mut max_age = self.max_age.lock().expect("cache hint lock").unwrap_or(0);
+        let stale = ctx
+            .data_opt::<StaleData>()
+            .map(StaleData::take)
+            .unwrap_or_default();
+        if !stale.is_empty() {
+            response.extensions.insert(
+                "stale".to_string(),
+                Value::from_json(serde_json::Value::Array(stale)).unwrap_or_default(),
+            );
+            max_age = 0;
+        }
+
+        let public = ctx
+            .data_opt::<ResolverContext>()
+            .is_none_or(|context| context.auth_scope().is_none())
+            && !ctx
+                .data_opt::<CallerScoped>()
+                .is_some_and(CallerScoped::is_marked);
+        response.cache_control = CacheControl {
+            public,
+            max_age: i32::try_from(max_age).unwrap_or(i32::MAX),
+        };
+        response
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if !info.is_for_introspection {
+            if let Some(hint) = self.hints.max_age(info.parent_type, info.name) {
+                let mut max_age = self.max_age.lock().expect("cache hint lock");
+                *max_age = Some(max_age.map_or(hint, |current| current.min(hint)));
+            }
+        }
+        next.run(ctx, info).await
+    }
+}
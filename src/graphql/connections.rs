@@ -0,0 +1,168 @@
+use crate::config::Settings;
+use crate::config::settings::QueryConfig;
+use crate::graphql::cache_control::CallerScoped;
+use crate::graphql::incremental::memoized;
+use crate::graphql::planner::upstream_call;
+use crate::graphql::queries::{map_result, missing_placeholder, upstream_error};
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::schema::{JSON_SCALAR, value_field};
+use crate::graphql::subscriptions::argument_values;
+use crate::rest::adapter::ResultMapping;
+use crate::rest::pagination::{PageArgs, Paginator};
+use crate::rest::{RestClient, render_path};
+use crate::utils::RustQLError;
+use async_graphql::Value;
+use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, Object, TypeRef};
+use serde_json::json;
+use std::sync::Arc;
+
+pub const PAGE_INFO_TYPE: &str = "PageInfo";
+
+/// Names of the connection and edge types over `node`, e.g. `PostConnection`
+/// and `PostEdge`; `JSONConnection` and `JSONEdge` for untyped nodes.
+pub fn connection_type_names(node: Option<&str>) -> (String, String) {
+    let node = node.unwrap_or(JSON_SCALAR);
+    (format!("{}Connection", node), format!("{}Edge", node))
+}
+
+/// The Relay `PageInfo` type and the edge and connection types of each node
+/// type paginated queries return.
+pub fn connection_types(settings: &Settings) -> Vec<Object> {
+    let mut nodes: Vec<Option<&str>> = Vec::new();
+    for query in settings.queries.iter().filter(|q| q.pagination.is_some()) {
+        if !nodes.contains(&query.returns.as_deref()) {
+            nodes.push(query.returns.as_deref());
+        }
+    }
+    if nodes.is_empty() {
+        return vec![];
+    }
+
+    let mut types = vec![
+        Object::new(PAGE_INFO_TYPE)
+            .field(value_field(
+                "hasNextPage",
+                TypeRef::named_nn(TypeRef::BOOLEAN),
+            ))
+            .field(value_field(
+                "hasPreviousPage",
+                TypeRef::named_nn(TypeRef::BOOLEAN),
+            ))
+            .field(value_field("startCursor", TypeRef::named(TypeRef::STRING)))
+            .field(value_field("endCursor", TypeRef::named(TypeRef::STRING))),
+    ];
+    for node in nodes {
+        let (connection, edge) = connection_type_names(node);
+        types.push(
+            Object::new(edge.as_str())
+                .field(value_field("cursor", TypeRef::named_nn(TypeRef::STRING)))
+                .field(value_field(
+                    "node",
+                    TypeRef::named(node.unwrap_or(JSON_SCALAR)),
+                )),
+        );
+        types.push(
+            Object::new(connection)
+                .field(value_field("edges", TypeRef::named_nn_list_nn(edge)))
+                .field(value_field("pageInfo", TypeRef::named_nn(PAGE_INFO_TYPE)))
+                .field(value_field("totalCount", TypeRef::named(TypeRef::INT))),
+        );
+    }
+    types
+}
+
+/// A connection field over a paginated list endpoint, whose nodes have the
+/// query's `returns` type. Pages are fetched directly rather than through
+/// the cache, and the query's result mapping applies to each node.
+pub fn connection_field(
+    config: &QueryConfig,
+    client: RestClient,
+    paginator: Arc<Paginator>,
+    not_found_as_null: bool,
+    mapping: Option<Arc<ResultMapping>>,
+) -> Field {
+    let arguments = config.arguments.clone();
+    let template = config.path.clone();
+    let (connection, _) = connection_type_names(config.returns.as_deref());
+    let mut field = Field::new(
+        config.field.as_str(),
+        TypeRef::named(connection),
+        move |ctx| {
+            let client = match ctx.data_opt::<ResolverContext>() {
+                Some(context) => client.for_caller(context),
+                None => client.clone(),
+            };
+            let paginator = paginator.clone();
+            let mapping = mapping.clone();
+            let template = template.clone();
+            let values = argument_values(&ctx, &arguments);
+            FieldFuture::new(async move {
+                let values = values?;
+                let path = render_path(&template, |name| values.get(name).cloned().flatten())
+                    .map_err(|name| missing_placeholder(&ctx, &name))?;
+                let args = PageArgs {
+                    first: ctx.args.get("first").map(|v| v.i64()).transpose()?,
+                    after: ctx
+                        .args
+                        .get("after")
+                        .map(|v| v.string().map(str::to_string))
+                        .transpose()?,
+                    last: ctx.args.get("last").map(|v| v.i64()).transpose()?,
+                    before: ctx
+                        .args
+                        .get("before")
+                        .map(|v| v.string().map(str::to_string))
+                        .transpose()?,
+                };
+                if client.vary().is_some() {
+                    if let Some(scoped) = ctx.data_opt::<CallerScoped>() {
+                        scoped.mark();
+                    }
+                }
+
+                let key = format!("{}#{:?}", client.cache_key(&path), args);
+                let fetched =
+                    upstream_call(&ctx, &path, false, paginator.fetch(&client, &path, &args));
+                let page = match memoized(&ctx, key, fetched).await {
+                    Ok(page) => page,
+                    Err(RustQLError::RestApi { status: 404, .. }) if not_found_as_null => {
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(upstream_error(&ctx, client.name(), &e)),
+                };
+
+                let mut edges = Vec::with_capacity(page.edges.len());
+                for (cursor, node) in page.edges {
+                    let node = map_result(&ctx, client.name(), mapping.as_deref(), node)?;
+                    edges.push(json!({ "cursor": cursor, "node": node }));
+                }
+                let connection = json!({
+                    "pageInfo": {
+                        "hasNextPage": page.has_next_page,
+                        "hasPreviousPage": page.has_previous_page,
+                        "startCursor": edges.first().map(|edge| edge["cursor"].clone()),
+                        "endCursor": edges.last().map(|edge| edge["cursor"].clone()),
+                    },
+                    "edges": edges,
+                    "totalCount": page.total_count,
+                });
+                Ok(Some(FieldValue::value(Value::from_json(connection)?)))
+            })
+        },
+    )
+    .argument(InputValue::new("first", TypeRef::named(TypeRef::INT)))
+    .argument(InputValue::new("after", TypeRef::named(TypeRef::STRING)))
+    .argument(InputValue::new("last", TypeRef::named(TypeRef::INT)))
+    .argument(InputValue::new("before", TypeRef::named(TypeRef::STRING)));
+
+    for argument in &config.arguments {
+        field = field.argument(InputValue::new(
+            argument.as_str(),
+            TypeRef::named_nn(TypeRef::ID),
+        ));
+    }
+    match &config.description {
+        Some(description) => field.description(description.as_str()),
+        None => field,
+    }
+}
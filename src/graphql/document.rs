@@ -0,0 +1,310 @@
+use crate::utils::{Result, RustQLError};
+use async_graphql::parser::types::{
+    Directive, DocumentOperations, ExecutableDocument, Field, FragmentDefinition, InlineFragment,
+    OperationDefinition, Selection, SelectionSet, TypeCondition,
+};
+use async_graphql::{Name, Positioned};
+use async_graphql_value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Picks the operation a request runs: the only one in the document, or the
+/// one named by `operation_name`.
+pub fn select_operation<'a>(
+    document: &'a ExecutableDocument,
+    operation_name: Option<&str>,
+) -> Result<(Option<&'a Name>, &'a OperationDefinition)> {
+    match (&document.operations, operation_name) {
+        (DocumentOperations::Single(operation), _) => Ok((None, &operation.node)),
+        (DocumentOperations::Multiple(operations), Some(name)) => operations
+            .get_key_value(name)
+            .map(|(name, operation)| (Some(name), &operation.node))
+            .ok_or_else(|| RustQLError::GraphQL(format!("Unknown operation named \"{}\"", name))),
+        (DocumentOperations::Multiple(operations), None) if operations.len() == 1 => {
+            let (name, operation) = operations.iter().next().expect("one operation");
+            Ok((Some(name), &operation.node))
+        }
+        (DocumentOperations::Multiple(_), None) => Err(RustQLError::GraphQL(
+            "Operation name required in request".to_string(),
+        )),
+    }
+}
+
+/// Replaces every fragment spread with an equivalent inline fragment so the
+/// operation no longer depends on the document's fragment definitions.
+pub fn inline_fragments(
+    operation: &OperationDefinition,
+    fragments: &HashMap<Name, Positioned<FragmentDefinition>>,
+) -> Result<OperationDefinition> {
+    let mut operation = operation.clone();
+    operation.selection_set.node =
+        inline_selection_set(&operation.selection_set.node, fragments, &mut Vec::new())?;
+    Ok(operation)
+}
+
+fn inline_selection_set(
+    selection_set: &SelectionSet,
+    fragments: &HashMap<Name, Positioned<FragmentDefinition>>,
+    visiting: &mut Vec<Name>,
+) -> Result<SelectionSet> {
+    let mut items = Vec::with_capacity(selection_set.items.len());
+
+    for item in &selection_set.items {
+        let selection = match &item.node {
+            Selection::Field(field) => {
+                let mut field = field.clone();
+                field.node.selection_set.node =
+                    inline_selection_set(&field.node.selection_set.node, fragments, visiting)?;
+                Selection::Field(field)
+            }
+            Selection::InlineFragment(fragment) => {
+                let mut fragment = fragment.clone();
+                fragment.node.selection_set.node =
+                    inline_selection_set(&fragment.node.selection_set.node, fragments, visiting)?;
+                Selection::InlineFragment(fragment)
+            }
+            Selection::FragmentSpread(spread) => {
+                let name = &spread.node.fragment_name.node;
+                let definition = fragments.get(name).ok_or_else(|| {
+                    RustQLError::GraphQL(format!("Unknown fragment \"{}\"", name))
+                })?;
+                if visiting.contains(name) {
+                    return Err(RustQLError::GraphQL(format!(
+                        "Cannot spread fragment \"{}\" within itself",
+                        name
+                    )));
+                }
+
+                visiting.push(name.clone());
+                let selection_set =
+                    inline_selection_set(&definition.node.selection_set.node, fragments, visiting)?;
+                visiting.pop();
+
+                Selection::InlineFragment(Positioned::new(
+                    InlineFragment {
+                        type_condition: Some(definition.node.type_condition.clone()),
+                        directives: spread.node.directives.clone(),
+                        selection_set: Positioned::new(selection_set, definition.pos),
+                    },
+                    spread.pos,
+                ))
+            }
+        };
+        items.push(Positioned::new(selection, item.pos));
+    }
+
+    Ok(SelectionSet { items })
+}
+
+/// Variables referenced anywhere in the selection set's arguments and directives.
+pub fn used_variables(selection_set: &SelectionSet) -> HashSet<Name> {
+    let mut names = HashSet::new();
+    collect_selection_variables(selection_set, &mut names);
+    names
+}
+
+fn collect_selection_variables(selection_set: &SelectionSet, names: &mut HashSet<Name>) {
+    for item in &selection_set.items {
+        match &item.node {
+            Selection::Field(field) => {
+                for (_, value) in &field.node.arguments {
+                    collect_value_variables(&value.node, names);
+                }
+                collect_directive_variables(&field.node.directives, names);
+                collect_selection_variables(&field.node.selection_set.node, names);
+            }
+            Selection::InlineFragment(fragment) => {
+                collect_directive_variables(&fragment.node.directives, names);
+                collect_selection_variables(&fragment.node.selection_set.node, names);
+            }
+            Selection::FragmentSpread(spread) => {
+                collect_directive_variables(&spread.node.directives, names);
+            }
+        }
+    }
+}
+
+fn collect_directive_variables(directives: &[Positioned<Directive>], names: &mut HashSet<Name>) {
+    for directive in directives {
+        for (_, value) in &directive.node.arguments {
+            collect_value_variables(&value.node, names);
+        }
+    }
+}
+
+fn collect_value_variables(value: &Value, names: &mut HashSet<Name>) {
+    match value {
+        Value::Variable(name) => {
+            names.insert(name.clone());
+        }
+        Value::List(items) => items.iter().for_each(|v| collect_value_variables(v, names)),
+        Value::Object(fields) => fields
+            .values()
+            .for_each(|v| collect_value_variables(v, names)),
+        _ => {}
+    }
+}
+
+/// Prints an operation whose fragments have been inlined. Variable
+/// definitions not used by the selection set are dropped so the printed
+/// operation always validates.
+pub fn print_operation(name: Option<&Name>, operation: &OperationDefinition) -> String {
+    let mut out = String::new();
+    out.push_str(&operation.ty.to_string());
+    if let Some(name) = name {
+        let _ = write!(out, " {}", name);
+    }
+
+    let used = used_variables(&operation.selection_set.node);
+    let definitions: Vec<String> = operation
+        .variable_definitions
+        .iter()
+        .filter(|definition| used.contains(&definition.node.name.node))
+        .map(|definition| {
+            let mut printed = format!(
+                "${}: {}",
+                definition.node.name.node, definition.node.var_type.node
+            );
+            if let Some(default) = &definition.node.default_value {
+                let _ = write!(printed, " = {}", default.node);
+            }
+            printed
+        })
+        .collect();
+    if !definitions.is_empty() {
+        let _ = write!(out, "({})", definitions.join(", "));
+    }
+
+    print_directives(&mut out, &operation.directives);
+    out.push(' ');
+    print_selection_set(&mut out, &operation.selection_set.node);
+    out
+}
+
+pub fn print_selection_set(out: &mut String, selection_set: &SelectionSet) {
+    out.push('{');
+    for item in &selection_set.items {
+        out.push(' ');
+        match &item.node {
+            Selection::Field(field) => print_field(out, &field.node),
+            Selection::InlineFragment(fragment) => {
+                out.push_str("...");
+                if let Some(condition) = &fragment.node.type_condition {
+                    print_type_condition(out, &condition.node);
+                }
+                print_directives(out, &fragment.node.directives);
+                out.push(' ');
+                print_selection_set(out, &fragment.node.selection_set.node);
+            }
+            Selection::FragmentSpread(spread) => {
+                let _ = write!(out, "...{}", spread.node.fragment_name.node);
+                print_directives(out, &spread.node.directives);
+            }
+        }
+    }
+    out.push_str(" }");
+}
+
+fn print_field(out: &mut String, field: &Field) {
+    if let Some(alias) = &field.alias {
+        let _ = write!(out, "{}: ", alias.node);
+    }
+    out.push_str(&field.name.node);
+    print_arguments(out, &field.arguments);
+    print_directives(out, &field.directives);
+    if !field.selection_set.node.items.is_empty() {
+        out.push(' ');
+        print_selection_set(out, &field.selection_set.node);
+    }
+}
+
+fn print_type_condition(out: &mut String, condition: &TypeCondition) {
+    let _ = write!(out, " on {}", condition.on.node);
+}
+
+fn print_arguments(out: &mut String, arguments: &[(Positioned<Name>, Positioned<Value>)]) {
+    if arguments.is_empty() {
+        return;
+    }
+    let printed: Vec<String> = arguments
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name.node, value.node))
+        .collect();
+    let _ = write!(out, "({})", printed.join(", "));
+}
+
+fn print_directives(out: &mut String, directives: &[Positioned<Directive>]) {
+    for directive in directives {
+        let _ = write!(out, " @{}", directive.node.name.node);
+        print_arguments(out, &directive.node.arguments);
+    }
+}
+
+/// The key a field appears under in the response: its alias or its name.
+pub fn response_key(field: &Field) -> &Name {
+    field
+        .alias
+        .as_ref()
+        .map(|alias| &alias.node)
+        .unwrap_or(&field.name.node)
+}
+
+/// Prints the operation a request runs in a canonical form: fragments are
+/// inlined and arguments, directive arguments and input object fields are
+/// sorted by name. Whitespace, comments and unused fragments or operations
+/// therefore don't change the result, while field order (which shapes the
+/// response) does.
+pub fn normalize_operation(query: &str, operation_name: Option<&str>) -> Result<String> {
+    let document = async_graphql::parser::parse_query(query)
+        .map_err(|e| RustQLError::GraphQL(e.to_string()))?;
+    let (name, operation) = select_operation(&document, operation_name)?;
+    let mut operation = inline_fragments(operation, &document.fragments)?;
+
+    operation
+        .variable_definitions
+        .sort_by(|a, b| a.node.name.node.cmp(&b.node.name.node));
+    sort_directives(&mut operation.directives);
+    sort_selection_set(&mut operation.selection_set.node);
+    Ok(print_operation(name, &operation))
+}
+
+fn sort_selection_set(selection_set: &mut SelectionSet) {
+    for item in &mut selection_set.items {
+        match &mut item.node {
+            Selection::Field(field) => {
+                sort_arguments(&mut field.node.arguments);
+                sort_directives(&mut field.node.directives);
+                sort_selection_set(&mut field.node.selection_set.node);
+            }
+            Selection::InlineFragment(fragment) => {
+                sort_directives(&mut fragment.node.directives);
+                sort_selection_set(&mut fragment.node.selection_set.node);
+            }
+            Selection::FragmentSpread(spread) => sort_directives(&mut spread.node.directives),
+        }
+    }
+}
+
+fn sort_directives(directives: &mut [Positioned<Directive>]) {
+    for directive in directives {
+        sort_arguments(&mut directive.node.arguments);
+    }
+}
+
+fn sort_arguments(arguments: &mut [(Positioned<Name>, Positioned<Value>)]) {
+    arguments.sort_by(|(a, _), (b, _)| a.node.cmp(&b.node));
+    for (_, value) in arguments {
+        sort_value(&mut value.node);
+    }
+}
+
+fn sort_value(value: &mut Value) {
+    match value {
+        Value::List(items) => items.iter_mut().for_each(sort_value),
+        Value::Object(fields) => {
+            fields.sort_keys();
+            fields.values_mut().for_each(sort_value);
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,567 @@
+use crate::graphql::RustQLSchema;
+use crate::graphql::document::{inline_fragments, print_operation, response_key, select_operation};
+use crate::graphql::resolvers::ResolverContext;
+use async_graphql::parser::types::{
+    Directive, Field, InlineFragment, OperationDefinition, OperationType, Selection, SelectionSet,
+};
+use async_graphql::{Name, Positioned, Request, Variables};
+use async_graphql_value::{ConstValue, Value};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde_json::{Map, json};
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Deferred fragments executed concurrently per request.
+const MAX_CONCURRENT_DEFERRED: usize = 8;
+
+/// How a query with `@defer`/`@stream` is split into an initial operation and
+/// follow-up parts for incremental delivery.
+#[derive(Debug, Clone)]
+pub struct IncrementalPlan {
+    operation_name: Option<String>,
+    initial: String,
+    deferred: Vec<DeferredPart>,
+    streams: Vec<StreamPart>,
+}
+
+/// A deferred fragment, re-selected from the root along its path.
+#[derive(Debug, Clone)]
+struct DeferredPart {
+    label: Option<String>,
+    path: Vec<Name>,
+    query: String,
+}
+
+/// A list field whose items past `initial_count` are sent after the initial payload.
+#[derive(Debug, Clone)]
+struct StreamPart {
+    label: Option<String>,
+    path: Vec<Name>,
+    initial_count: usize,
+}
+
+#[derive(Clone)]
+enum Ancestor {
+    Field(Field),
+    Fragment(InlineFragment),
+}
+
+impl IncrementalPlan {
+    /// Plans incremental delivery for `request`. Returns `None` when the
+    /// operation is not a query or has no active `@defer`/`@stream`; mutations
+    /// are never split because re-selecting would repeat their side effects.
+    pub fn new(request: &Request) -> Option<Self> {
+        if !mentions_incremental_directives(&request.query) {
+            return None;
+        }
+
+        let document = async_graphql::parser::parse_query(&request.query).ok()?;
+        let (name, operation) =
+            select_operation(&document, request.operation_name.as_deref()).ok()?;
+        if operation.ty != OperationType::Query {
+            return None;
+        }
+        let operation = inline_fragments(operation, &document.fragments).ok()?;
+
+        let mut planner = Planner {
+            operation: &operation,
+            name,
+            variables: &request.variables,
+            deferred: Vec::new(),
+            streams: Vec::new(),
+        };
+        let selection_set = planner.split(
+            &operation.selection_set.node,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            true,
+        );
+
+        if planner.deferred.is_empty() && planner.streams.is_empty() {
+            return None;
+        }
+
+        let mut initial = operation.clone();
+        initial.selection_set.node = selection_set;
+
+        Some(Self {
+            operation_name: name.map(|name| name.to_string()),
+            initial: print_operation(name, &initial),
+            deferred: planner.deferred.into_iter().flatten().collect(),
+            streams: planner.streams,
+        })
+    }
+
+    /// Executes the plan, yielding the initial payload followed by one
+    /// payload per deferred fragment or streamed item in the incremental
+    /// delivery format. The last payload always has `hasNext: false`.
+    ///
+    /// Each deferred fragment is executed as its own operation from the root
+    /// along its path. Its ancestors resolve again, but their upstream results
+    /// come from an `UpstreamMemo` shared by every execution of the request,
+    /// so only the fragment's own fields call REST APIs. Streamed items are
+    /// split from the fully resolved list: they arrive after the initial
+    /// payload but do not make it faster.
+    pub fn execute(
+        self,
+        schema: RustQLSchema,
+        request: &Request,
+        context: ResolverContext,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = serde_json::Value> + Send + 'static {
+        let variables = request.variables.clone();
+        let memo = UpstreamMemo::default();
+        let build = move |query: String, operation_name: Option<String>| {
+            let mut request = Request::new(query)
+                .variables(variables.clone())
+                .data(context.clone())
+                .data(memo.clone());
+            request.operation_name = operation_name;
+            request
+        };
+
+        let plan = self;
+        let initial_request = build(plan.initial.clone(), plan.operation_name.clone());
+        let initial_schema = schema.clone();
+
+        stream::once(async move {
+            execute_with_timeout(&initial_schema, initial_request, timeout).await
+        })
+        .flat_map(move |mut initial| {
+            if initial.get("errors").is_some() && initial["data"].is_null() {
+                initial["hasNext"] = json!(false);
+                return stream::iter(vec![initial]).boxed();
+            }
+
+            let mut streamed = Vec::new();
+            for part in &plan.streams {
+                for (path, items) in
+                    split_streamed_items(&mut initial["data"], &part.path, part.initial_count)
+                {
+                    for (offset, item) in items.into_iter().enumerate() {
+                        let mut item_path = path.clone();
+                        item_path.push(json!(part.initial_count + offset));
+                        streamed.push(incremental_payload(
+                            json!({
+                                "items": [item],
+                                "path": item_path,
+                            }),
+                            part.label.as_deref(),
+                        ));
+                    }
+                }
+            }
+            initial["hasNext"] = json!(true);
+
+            let schema = schema.clone();
+            let operation_name = plan.operation_name.clone();
+            let build = build.clone();
+            let deferred = stream::iter(plan.deferred.clone())
+                .map(move |part| {
+                    let request = build(part.query.clone(), operation_name.clone());
+                    let schema = schema.clone();
+                    async move {
+                        let response = execute_with_timeout(&schema, request, timeout).await;
+                        deferred_payloads(response, &part)
+                    }
+                })
+                .buffer_unordered(MAX_CONCURRENT_DEFERRED)
+                .flat_map(stream::iter);
+
+            stream::iter(std::iter::once(initial).chain(streamed))
+                .chain(deferred)
+                .chain(stream::once(async { json!({ "hasNext": false }) }))
+                .boxed()
+        })
+    }
+}
+
+/// Upstream results of one incremental request, shared by its initial and
+/// deferred executions. Failed calls are not kept.
+#[derive(Clone, Default)]
+pub struct UpstreamMemo {
+    results: Arc<Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>>,
+}
+
+impl UpstreamMemo {
+    fn get<T: Clone + 'static>(&self, key: &str) -> Option<T> {
+        let results = self.results.lock().expect("upstream memo lock");
+        results.get(key)?.downcast_ref::<T>().cloned()
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        let results = self.results.lock().expect("upstream memo lock");
+        results.contains_key(key)
+    }
+
+    fn insert<T: Send + Sync + 'static>(&self, key: String, value: T) {
+        let mut results = self.results.lock().expect("upstream memo lock");
+        results.insert(key, Arc::new(value));
+    }
+}
+
+/// Runs `call` unless an earlier execution of the request already produced
+/// a result under `key`.
+pub(crate) async fn memoized<T, E>(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    key: String,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let Some(memo) = ctx.data_opt::<UpstreamMemo>() else {
+        return call.await;
+    };
+    if let Some(value) = memo.get::<T>(&key) {
+        return Ok(value);
+    }
+    let result = call.await;
+    if let Ok(value) = &result {
+        memo.insert(key, value.clone());
+    }
+    result
+}
+
+struct Planner<'a> {
+    operation: &'a OperationDefinition,
+    name: Option<&'a Name>,
+    variables: &'a Variables,
+    deferred: Vec<Option<DeferredPart>>,
+    streams: Vec<StreamPart>,
+}
+
+impl Planner<'_> {
+    /// Returns `selection_set` without deferred fragments and `@stream`
+    /// directives, recording each as a part. Streams are only honoured outside
+    /// deferred fragments, where their items are available in the initial result.
+    fn split(
+        &mut self,
+        selection_set: &SelectionSet,
+        path: &mut Vec<Name>,
+        ancestors: &mut Vec<Ancestor>,
+        collect_streams: bool,
+    ) -> SelectionSet {
+        let mut items = Vec::with_capacity(selection_set.items.len());
+
+        for item in &selection_set.items {
+            match &item.node {
+                Selection::Field(field) => {
+                    let mut field = field.node.clone();
+                    let key = response_key(&field).clone();
+                    if let Some(stream) = take_directive(&mut field.directives, "stream") {
+                        if collect_streams && is_active(&stream, self.variables) {
+                            let mut stream_path = path.clone();
+                            stream_path.push(key.clone());
+                            self.streams.push(StreamPart {
+                                label: label(&stream),
+                                path: stream_path,
+                                initial_count: argument(&stream, "initialCount", self.variables)
+                                    .and_then(|v| match v {
+                                        ConstValue::Number(n) => n.as_u64(),
+                                        _ => None,
+                                    })
+                                    .unwrap_or(0)
+                                    as usize,
+                            });
+                        }
+                    }
+
+                    let children = std::mem::take(&mut field.selection_set.node);
+                    path.push(key);
+                    ancestors.push(Ancestor::Field(field.clone()));
+                    field.selection_set.node =
+                        self.split(&children, path, ancestors, collect_streams);
+                    ancestors.pop();
+                    path.pop();
+
+                    items.push(Positioned::new(
+                        Selection::Field(Positioned::new(field, item.pos)),
+                        item.pos,
+                    ));
+                }
+                Selection::InlineFragment(fragment) => {
+                    let mut fragment = fragment.node.clone();
+                    let defer = take_directive(&mut fragment.directives, "defer");
+                    let children = std::mem::take(&mut fragment.selection_set.node);
+
+                    ancestors.push(Ancestor::Fragment(fragment.clone()));
+                    match defer.filter(|d| is_active(d, self.variables)) {
+                        Some(defer) => {
+                            // Reserve the slot first so parents are delivered before nested parts
+                            let index = self.deferred.len();
+                            self.deferred.push(None);
+                            let content = self.split(&children, path, ancestors, false);
+                            ancestors.pop();
+
+                            self.deferred[index] = Some(DeferredPart {
+                                label: label(&defer),
+                                path: path.clone(),
+                                query: self.reselect(ancestors, &fragment, content),
+                            });
+                        }
+                        None => {
+                            fragment.selection_set.node =
+                                self.split(&children, path, ancestors, collect_streams);
+                            ancestors.pop();
+                            items.push(Positioned::new(
+                                Selection::InlineFragment(Positioned::new(fragment, item.pos)),
+                                item.pos,
+                            ));
+                        }
+                    }
+                }
+                Selection::FragmentSpread(_) => items.push(item.clone()),
+            }
+        }
+
+        SelectionSet { items }
+    }
+
+    /// Prints an operation selecting only `content` under the given ancestors.
+    fn reselect(
+        &self,
+        ancestors: &[Ancestor],
+        fragment: &InlineFragment,
+        content: SelectionSet,
+    ) -> String {
+        let mut fragment = fragment.clone();
+        fragment.selection_set.node = content;
+        let mut selection_set = single(Selection::InlineFragment(Positioned::new(
+            fragment,
+            Default::default(),
+        )));
+
+        for ancestor in ancestors.iter().rev() {
+            let selection = match ancestor.clone() {
+                Ancestor::Field(mut field) => {
+                    field.selection_set.node = selection_set;
+                    Selection::Field(Positioned::new(field, Default::default()))
+                }
+                Ancestor::Fragment(mut fragment) => {
+                    fragment.selection_set.node = selection_set;
+                    Selection::InlineFragment(Positioned::new(fragment, Default::default()))
+                }
+            };
+            selection_set = single(selection);
+        }
+
+        let mut operation = self.operation.clone();
+        operation.selection_set.node = selection_set;
+        print_operation(self.name, &operation)
+    }
+}
+
+fn single(selection: Selection) -> SelectionSet {
+    SelectionSet {
+        items: vec![Positioned::new(selection, Default::default())],
+    }
+}
+
+/// Cheap pre-check so ordinary requests skip parsing.
+pub fn mentions_incremental_directives(query: &str) -> bool {
+    query.contains("@defer") || query.contains("@stream")
+}
+
+/// Rewrites `request` without `@defer`/`@stream` so the full result is
+/// delivered in a single response, for transports without incremental delivery.
+pub fn strip_incremental_directives(request: &mut Request) {
+    if !mentions_incremental_directives(&request.query) {
+        return;
+    }
+    let Ok(document) = async_graphql::parser::parse_query(&request.query) else {
+        return;
+    };
+    let Ok((name, operation)) = select_operation(&document, request.operation_name.as_deref())
+    else {
+        return;
+    };
+    let Ok(mut operation) = inline_fragments(operation, &document.fragments) else {
+        return;
+    };
+
+    strip_selection_set(&mut operation.selection_set.node);
+    request.query = print_operation(name, &operation);
+}
+
+fn strip_selection_set(selection_set: &mut SelectionSet) {
+    for item in &mut selection_set.items {
+        match &mut item.node {
+            Selection::Field(field) => {
+                take_directive(&mut field.node.directives, "stream");
+                strip_selection_set(&mut field.node.selection_set.node);
+            }
+            Selection::InlineFragment(fragment) => {
+                take_directive(&mut fragment.node.directives, "defer");
+                strip_selection_set(&mut fragment.node.selection_set.node);
+            }
+            Selection::FragmentSpread(spread) => {
+                take_directive(&mut spread.node.directives, "defer");
+            }
+        }
+    }
+}
+
+fn take_directive(directives: &mut Vec<Positioned<Directive>>, name: &str) -> Option<Directive> {
+    let index = directives.iter().position(|d| d.node.name.node == name)?;
+    Some(directives.remove(index).node)
+}
+
+fn argument(directive: &Directive, name: &str, variables: &Variables) -> Option<ConstValue> {
+    let value = directive.get_argument(name)?;
+    match &value.node {
+        Value::Variable(variable) => variables.get(variable).cloned(),
+        value => value.clone().into_const(),
+    }
+}
+
+fn is_active(directive: &Directive, variables: &Variables) -> bool {
+    !matches!(
+        argument(directive, "if", variables),
+        Some(ConstValue::Boolean(false))
+    )
+}
+
+fn label(directive: &Directive) -> Option<String> {
+    match directive.get_argument("label").map(|v| &v.node) {
+        Some(Value::String(label)) => Some(label.clone()),
+        _ => None,
+    }
+}
+
+async fn execute_with_timeout(
+    schema: &RustQLSchema,
+    request: Request,
+    timeout: Option<Duration>,
+) -> serde_json::Value {
+    let execution = schema.execute(request);
+    let response = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, execution).await {
+            Ok(response) => response,
+            Err(_) => {
+                return json!({
+                    "data": null,
+                    "errors": [{
+                        "message": format!("Request timed out after {}s", timeout.as_secs()),
+                        "extensions": { "code": "TIMEOUT" }
+                    }]
+                });
+            }
+        },
+        None => execution.await,
+    };
+    serde_json::to_value(&response).unwrap_or_else(|_| json!({ "data": null }))
+}
+
+fn incremental_payload(mut entry: serde_json::Value, label: Option<&str>) -> serde_json::Value {
+    if let Some(label) = label {
+        entry["label"] = json!(label);
+    }
+    json!({ "incremental": [entry], "hasNext": true })
+}
+
+/// One payload per object the deferred fragment applies to; lists along
+/// the path fan out into one payload per item.
+fn deferred_payloads(response: serde_json::Value, part: &DeferredPart) -> Vec<serde_json::Value> {
+    let errors = response.get("errors").cloned();
+    let mut targets = Vec::new();
+    collect_targets(&response["data"], &part.path, Vec::new(), &mut targets);
+
+    if targets.is_empty() {
+        return match errors {
+            Some(errors) => vec![incremental_payload(
+                json!({ "data": null, "path": part.path, "errors": errors }),
+                part.label.as_deref(),
+            )],
+            None => Vec::new(),
+        };
+    }
+
+    targets
+        .into_iter()
+        .map(|(path, data)| {
+            let mut entry = json!({ "data": data, "path": path });
+            if let Some(errors) = &errors {
+                entry["errors"] = errors.clone();
+            }
+            incremental_payload(entry, part.label.as_deref())
+        })
+        .collect()
+}
+
+fn collect_targets(
+    value: &serde_json::Value,
+    keys: &[Name],
+    path: Vec<serde_json::Value>,
+    targets: &mut Vec<(Vec<serde_json::Value>, serde_json::Value)>,
+) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let mut item_path = path.clone();
+                item_path.push(json!(index));
+                collect_targets(item, keys, item_path, targets);
+            }
+        }
+        serde_json::Value::Object(object) => match keys.split_first() {
+            Some((key, rest)) => {
+                if let Some(child) = object.get(key.as_str()) {
+                    let mut child_path = path;
+                    child_path.push(json!(key.as_str()));
+                    collect_targets(child, rest, child_path, targets);
+                }
+            }
+            None => targets.push((path, value.clone())),
+        },
+        _ => {}
+    }
+}
+
+/// Truncates every list at `keys` to `initial_count` items and returns the
+/// removed items with the path of the list they came from.
+fn split_streamed_items(
+    data: &mut serde_json::Value,
+    keys: &[Name],
+    initial_count: usize,
+) -> Vec<(Vec<serde_json::Value>, Vec<serde_json::Value>)> {
+    let mut removed = Vec::new();
+    split_at(data, keys, initial_count, Vec::new(), &mut removed);
+    removed
+}
+
+fn split_at(
+    value: &mut serde_json::Value,
+    keys: &[Name],
+    initial_count: usize,
+    path: Vec<serde_json::Value>,
+    removed: &mut Vec<(Vec<serde_json::Value>, Vec<serde_json::Value>)>,
+) {
+    match (value, keys.split_first()) {
+        (serde_json::Value::Array(items), None) if items.len() > initial_count => {
+            removed.push((path, items.split_off(initial_count)));
+        }
+        (serde_json::Value::Array(items), Some(_)) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                let mut item_path = path.clone();
+                item_path.push(json!(index));
+                split_at(item, keys, initial_count, item_path, removed);
+            }
+        }
+        (serde_json::Value::Object(object), Some((key, rest))) => {
+            if let Some(child) = object.get_mut(key.as_str()) {
+                let mut child_path = path;
+                child_path.push(json!(key.as_str()));
+                split_at(child, rest, initial_count, child_path, removed);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Serializes a GraphQL response as a `next` payload for streaming transports.
+pub fn response_payload(response: &async_graphql::Response) -> serde_json::Value {
+    serde_json::to_value(response).unwrap_or_else(|_| serde_json::Value::Object(Map::new()))
+}
@@ -1,4 +1,18 @@
+pub mod authorization;
+pub mod cache_control;
+pub mod connections;
+pub mod document;
+pub mod incremental;
+pub mod mutations;
+pub mod partial;
+pub mod persisted;
+pub mod planner;
+pub mod queries;
 pub mod resolvers;
 pub mod schema;
+pub mod sparse_fields;
+pub mod subscriptions;
+pub mod types;
 
-pub use schema::{RustQLSchema, create_schema};
+pub use schema::{RustQLSchema, build_schema, create_schema};
+pub use subscriptions::WebhookBroker;
@@ -0,0 +1,231 @@
+use crate::config::Settings;
+use crate::config::settings::{InputTypeConfig, MutationConfig};
+use crate::graphql::planner::upstream_call;
+use crate::graphql::queries::{map_result, missing_placeholder, upstream_error};
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::schema::value_field;
+use crate::graphql::subscriptions::argument_values;
+use crate::graphql::types::{return_type, scalar_type};
+use crate::rest::adapter::ResultMapping;
+use crate::rest::batch::scalar_string;
+use crate::rest::write::{RequestBody, http_method, user_errors};
+use crate::rest::{RestClient, render_path, status_error};
+use crate::services::Services;
+use crate::utils::{Result, RustQLError};
+use async_graphql::Value;
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputObject, InputValue, Object, TypeRef,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::json;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Validation error of a write the upstream rejected.
+pub const USER_ERROR_TYPE: &str = "UserError";
+
+/// Builds one mutation field per `[[mutations]]` entry.
+pub fn mutation_fields(settings: &Settings, services: &Services) -> Result<Vec<Field>> {
+    settings
+        .mutations
+        .iter()
+        .map(|mutation| {
+            let client = services.upstream(&mutation.api).ok_or_else(|| {
+                RustQLError::Config(format!(
+                    "Mutation '{}' calls unknown API '{}'",
+                    mutation.field, mutation.api
+                ))
+            })?;
+            let mapping = mutation
+                .result
+                .as_ref()
+                .map(ResultMapping::from_config)
+                .transpose()
+                .map_err(|e| {
+                    RustQLError::Config(format!(
+                        "Invalid result mapping for mutation '{}': {}",
+                        mutation.field, e
+                    ))
+                })?;
+            let idempotency_header = mutation
+                .idempotency_header
+                .as_deref()
+                .map(|header| HeaderName::from_bytes(header.as_bytes()))
+                .transpose()
+                .map_err(|e| {
+                    RustQLError::Config(format!(
+                        "Invalid idempotency_header for mutation '{}': {}",
+                        mutation.field, e
+                    ))
+                })?;
+            Ok(write_field(
+                mutation,
+                client.clone(),
+                mapping.map(Arc::new),
+                idempotency_header,
+            ))
+        })
+        .collect()
+}
+
+/// The input objects of `[[inputs]]`, the payload type of each mutation and
+/// `UserError`, when mutations are configured.
+pub fn mutation_types(settings: &Settings) -> (Vec<InputObject>, Vec<Object>) {
+    if settings.mutations.is_empty() {
+        return (vec![], vec![]);
+    }
+
+    let inputs = settings.inputs.iter().map(input_type).collect();
+    let mut objects: Vec<Object> = settings
+        .mutations
+        .iter()
+        .map(|mutation| {
+            Object::new(mutation.payload_type())
+                .description(format!("Result of the `{}` mutation", mutation.field))
+                .field(value_field(
+                    "result",
+                    return_type(mutation.returns.as_deref()),
+                ))
+                .field(value_field(
+                    "userErrors",
+                    TypeRef::named_nn_list_nn(USER_ERROR_TYPE),
+                ))
+        })
+        .collect();
+    objects.push(
+        Object::new(USER_ERROR_TYPE)
+            .description("A validation error reported by the upstream API")
+            .field(
+                value_field("field", TypeRef::named_nn_list(TypeRef::STRING))
+                    .description("Path of the input field at fault, if any"),
+            )
+            .field(value_field("message", TypeRef::named_nn(TypeRef::STRING)))
+            .field(value_field("code", TypeRef::named(TypeRef::STRING))),
+    );
+    (inputs, objects)
+}
+
+fn input_type(config: &InputTypeConfig) -> InputObject {
+    let mut input = InputObject::new(config.name.as_str());
+    if let Some(description) = &config.description {
+        input = input.description(description.as_str());
+    }
+    let mut fields: Vec<_> = config.fields.iter().collect();
+    fields.sort_by_key(|(name, _)| name.as_str());
+    for (name, ty) in fields {
+        let type_ref = match config.required.contains(name) {
+            true => TypeRef::named_nn(scalar_type(*ty)),
+            false => TypeRef::named(scalar_type(*ty)),
+        };
+        input = input.field(InputValue::new(name.as_str(), type_ref));
+    }
+    input
+}
+
+/// A mutation sending its `input` to the API with the configured method and
+/// encoding. Successful responses are mapped into the payload's `result`;
+/// rejections with one of the `user_error_statuses` become `userErrors`,
+/// while other failures null the field with an error at its path. The
+/// `idempotencyKey` argument is forwarded in the configured header.
+fn write_field(
+    config: &MutationConfig,
+    client: RestClient,
+    mapping: Option<Arc<ResultMapping>>,
+    idempotency_header: Option<HeaderName>,
+) -> Field {
+    let arguments = config.arguments.clone();
+    let template = config.path.clone();
+    let method = config.method;
+    let encoding = config.body;
+    let user_error_statuses = config.user_error_statuses.clone();
+    let mut field = Field::new(
+        config.field.as_str(),
+        TypeRef::named_nn(config.payload_type()),
+        move |ctx| {
+            let client = match ctx.data_opt::<ResolverContext>() {
+                Some(context) => client.for_caller(context),
+                None => client.clone(),
+            };
+            let mapping = mapping.clone();
+            let template = template.clone();
+            let idempotency_header = idempotency_header.clone();
+            let user_error_statuses = user_error_statuses.clone();
+            let values = argument_values(&ctx, &arguments);
+            FieldFuture::new(async move {
+                let values = values?;
+                let input = match ctx.args.get("input") {
+                    Some(input) => Some(input.as_value().clone().into_json()?),
+                    None => None,
+                };
+                let path = render_path(&template, |name| match name.strip_prefix("input.") {
+                    Some(key) => input
+                        .as_ref()
+                        .and_then(|input| input.get(key))
+                        .and_then(scalar_string),
+                    None => values.get(name).cloned().flatten(),
+                })
+                .map_err(|name| missing_placeholder(&ctx, &name))?;
+
+                let mut headers = HeaderMap::new();
+                if let Some(header) = idempotency_header {
+                    if let Some(key) = ctx.args.get("idempotencyKey") {
+                        let key = HeaderValue::from_str(key.string()?)
+                            .map_err(|_| async_graphql::Error::new("Invalid idempotencyKey"))?;
+                        headers.insert(header, key);
+                    }
+                }
+                let body = input
+                    .as_ref()
+                    .map(|input| RequestBody::encode(encoding, input));
+
+                let written = upstream_call(
+                    &ctx,
+                    &path,
+                    false,
+                    client.write(method, &path, body, headers),
+                )
+                .await
+                .map_err(|e| upstream_error(&ctx, client.name(), &e))?;
+
+                let payload = if (200..300).contains(&written.status) {
+                    let result = map_result(&ctx, client.name(), mapping.as_deref(), written.body)?;
+                    json!({ "result": result, "userErrors": [] })
+                } else if user_error_statuses.contains(&written.status) {
+                    json!({ "result": null, "userErrors": user_errors(&written.body) })
+                } else {
+                    warn!(
+                        api = %client.name(),
+                        method = %http_method(method),
+                        path = %path,
+                        status = written.status,
+                        body = %written.body,
+                        "Upstream write rejected"
+                    );
+                    let e = status_error(client.name(), written.status);
+                    return Err(upstream_error(&ctx, client.name(), &e));
+                };
+                Ok(Some(FieldValue::value(Value::from_json(payload)?)))
+            })
+        },
+    );
+
+    for argument in &config.arguments {
+        field = field.argument(InputValue::new(
+            argument.as_str(),
+            TypeRef::named_nn(TypeRef::ID),
+        ));
+    }
+    if let Some(input) = &config.input {
+        field = field.argument(InputValue::new("input", TypeRef::named_nn(input.as_str())));
+    }
+    if config.idempotency_header.is_some() {
+        field = field.argument(InputValue::new(
+            "idempotencyKey",
+            TypeRef::named(TypeRef::STRING),
+        ));
+    }
+    match &config.description {
+        Some(description) => field.description(description.as_str()),
+        None => field,
+    }
+}
@@ -0,0 +1,117 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextResolve,
+    ResolveInfo,
+};
+use async_graphql::parser::types::{ExecutableDocument, OperationType};
+use async_graphql::{
+    PathSegment, QueryPathNode, QueryPathSegment, Response, ServerError, ServerResult, Value,
+    Variables,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Response path of the field at `node`, for errors raised outside the
+/// executor, which leaves `ServerError::path` empty.
+pub(crate) fn response_path(node: &QueryPathNode<'_>) -> Vec<PathSegment> {
+    let mut path: Vec<PathSegment> = std::iter::once(node)
+        .chain(node.parents())
+        .map(|node| match node.segment {
+            QueryPathSegment::Name(name) => PathSegment::Field(name.to_string()),
+            QueryPathSegment::Index(index) => PathSegment::Index(index),
+        })
+        .collect();
+    path.reverse();
+    path
+}
+
+/// Marks that an operation reached execution, telling requests rejected
+/// while parsing or validating apart from operations whose data is null.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionStarted(Arc<AtomicBool>);
+
+impl ExecutionStarted {
+    pub fn is_started(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// GraphQL null propagation for the dynamic schema, which otherwise drops a
+/// failed field from its parent and, when every root field fails, the whole
+/// `data`. A nullable field whose resolver fails resolves to null and its
+/// error is reported at the field's path; errors of non-null fields carry
+/// their path up to the nearest nullable parent.
+///
+/// Subscription operations are left as they are, since their events are not
+/// produced by `execute`.
+pub struct PartialResultsExtension;
+
+impl ExtensionFactory for PartialResultsExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PartialResultsExtensionImpl {
+            subscription: AtomicBool::new(false),
+            errors: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+struct PartialResultsExtensionImpl {
+    subscription: AtomicBool,
+    errors: Mutex<Vec<ServerError>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for PartialResultsExtensionImpl {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        let subscription = document
+            .operations
+            .iter()
+            .any(|(_, operation)| operation.node.ty == OperationType::Subscription);
+        self.subscription.store(subscription, Ordering::Relaxed);
+        Ok(document)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        if let Some(started) = ctx.data_opt::<ExecutionStarted>() {
+            started.0.store(true, Ordering::Relaxed);
+        }
+        let mut response = next.run(ctx, operation_name).await;
+        let errors = std::mem::take(&mut *self.errors.lock().expect("errors lock"));
+        response.errors.extend(errors);
+        response
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let path_node = info.path_node;
+        let nullable = !info.return_type.ends_with('!');
+        let mut error = match next.run(ctx, info).await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        if error.path.is_empty() {
+            error.path = response_path(path_node);
+        }
+
+        if !nullable || self.subscription.load(Ordering::Relaxed) {
+            return Err(error);
+        }
+        self.errors.lock().expect("errors lock").push(error);
+        Ok(Some(Value::Null))
+    }
+}
@@ -0,0 +1,241 @@
+use crate::cache::CacheManager;
+use crate::config::settings::PersistedQueriesConfig;
+use crate::utils::{Result, RustQLError, sha256_hex};
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest};
+use async_graphql::{ErrorExtensionValues, Request, ServerError, ServerResult, Value};
+use serde::Deserialize;
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+use warp::http::StatusCode;
+
+const CACHE_PREFIX: &str = "apq:";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PersistedQueryError {
+    #[error("PersistedQueryNotSupported")]
+    NotSupported,
+
+    #[error("PersistedQueryNotFound")]
+    NotFound,
+
+    #[error("provided sha does not match query")]
+    HashMismatch,
+
+    #[error("Operation is not in the persisted query allowlist")]
+    NotAllowed,
+}
+
+impl PersistedQueryError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PersistedQueryError::NotSupported => "PERSISTED_QUERY_NOT_SUPPORTED",
+            PersistedQueryError::NotFound => "PERSISTED_QUERY_NOT_FOUND",
+            PersistedQueryError::HashMismatch => "BAD_REQUEST",
+            PersistedQueryError::NotAllowed => "PERSISTED_QUERY_NOT_ALLOWED",
+        }
+    }
+
+    /// Apollo clients expect the not-found and not-supported errors with a
+    /// 200 so they retry with the full query.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            PersistedQueryError::NotSupported | PersistedQueryError::NotFound => StatusCode::OK,
+            PersistedQueryError::HashMismatch => StatusCode::BAD_REQUEST,
+            PersistedQueryError::NotAllowed => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// Marks a request whose persisted query has already been resolved, so the
+/// schema extension does not resolve it a second time.
+struct Resolved;
+
+/// Resolves `extensions.persistedQuery` hashes to query text. Hashes are
+/// looked up in the startup manifest first and then in the `CacheManager`,
+/// where APQ registrations are stored. In allowlist mode only operations
+/// from the manifest are executable and nothing is registered.
+#[derive(Clone)]
+pub struct PersistedQueries {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: PersistedQueriesConfig,
+    cache: Arc<CacheManager>,
+    operations: HashMap<String, String>,
+    allowed: HashSet<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Manifest {
+    Apollo { operations: Vec<ManifestOperation> },
+    Map(HashMap<String, String>),
+}
+
+#[derive(Deserialize)]
+struct ManifestOperation {
+    id: String,
+    body: String,
+}
+
+impl PersistedQueries {
+    pub fn new(config: &PersistedQueriesConfig, cache: Arc<CacheManager>) -> Result<Self> {
+        let operations = match &config.manifest_path {
+            Some(path) => {
+                let manifest = std::fs::read_to_string(path).map_err(|e| {
+                    RustQLError::Config(format!(
+                        "Failed to read persisted query manifest '{}': {}",
+                        path, e
+                    ))
+                })?;
+                let operations = parse_manifest(&manifest)?;
+                info!(path = %path, operations = operations.len(), "Loaded persisted query manifest");
+                operations
+            }
+            None => HashMap::new(),
+        };
+
+        Ok(Self::with_operations(config, cache, operations))
+    }
+
+    /// Builds the store from an already parsed manifest.
+    pub fn with_operations(
+        config: &PersistedQueriesConfig,
+        cache: Arc<CacheManager>,
+        operations: HashMap<String, String>,
+    ) -> Self {
+        let allowed = operations
+            .iter()
+            .map(|(id, body)| {
+                let hash = sha256_hex(body);
+                if *id != hash {
+                    warn!(id = %id, "Persisted query id is not the SHA-256 of its body");
+                }
+                hash
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(Inner {
+                config: config.clone(),
+                cache,
+                operations,
+                allowed,
+            }),
+        }
+    }
+
+    /// Fills in `request.query` from its persisted query hash, registers new
+    /// APQ queries and enforces the allowlist.
+    pub async fn resolve(
+        &self,
+        request: &mut Request,
+    ) -> std::result::Result<(), PersistedQueryError> {
+        if request.data.contains_key(&TypeId::of::<Resolved>()) {
+            return Ok(());
+        }
+
+        let inner = &self.inner;
+        if let Some(hash) = persisted_query_hash(request) {
+            if request.query.is_empty() {
+                request.query = self.lookup(&hash).await?;
+            } else if sha256_hex(&request.query) != hash {
+                return Err(PersistedQueryError::HashMismatch);
+            } else if inner.config.apq && !inner.config.allowlist_only {
+                let key = format!("{}{}", CACHE_PREFIX, hash);
+                if let Err(e) = inner.cache.set(&key, &request.query, inner.config.ttl).await {
+                    warn!(error = %e, "Failed to register persisted query");
+                }
+                debug!(hash = %hash, "Registered persisted query");
+            }
+        }
+
+        if inner.config.allowlist_only && !inner.allowed.contains(&sha256_hex(&request.query)) {
+            return Err(PersistedQueryError::NotAllowed);
+        }
+
+        request.data.insert(Resolved);
+        Ok(())
+    }
+
+    async fn lookup(&self, hash: &str) -> std::result::Result<String, PersistedQueryError> {
+        let inner = &self.inner;
+        if let Some(query) = inner.operations.get(hash) {
+            return Ok(query.clone());
+        }
+        if !inner.config.apq || inner.config.allowlist_only {
+            return Err(if inner.operations.is_empty() {
+                PersistedQueryError::NotSupported
+            } else {
+                PersistedQueryError::NotFound
+            });
+        }
+
+        inner
+            .cache
+            .get(&format!("{}{}", CACHE_PREFIX, hash))
+            .await
+            .ok_or(PersistedQueryError::NotFound)
+    }
+}
+
+/// Parses a manifest in Apollo's `persisted-query-manifest` format or as a
+/// plain `{ "<sha256>": "<query>" }` map.
+pub fn parse_manifest(manifest: &str) -> Result<HashMap<String, String>> {
+    let manifest: Manifest = serde_json::from_str(manifest).map_err(|e| {
+        RustQLError::Config(format!("Invalid persisted query manifest: {}", e))
+    })?;
+
+    Ok(match manifest {
+        Manifest::Apollo { operations } => operations
+            .into_iter()
+            .map(|operation| (operation.id, operation.body))
+            .collect(),
+        Manifest::Map(operations) => operations,
+    })
+}
+
+fn persisted_query_hash(request: &Request) -> Option<String> {
+    let Value::Object(persisted) = request.extensions.get("persistedQuery")? else {
+        return None;
+    };
+    match persisted.get("sha256Hash")? {
+        Value::String(hash) => Some(hash.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// Applies persisted query resolution to requests that did not come through
+/// the HTTP handlers, such as WebSocket subscriptions.
+pub struct PersistedQueryExtension(pub PersistedQueries);
+
+impl ExtensionFactory for PersistedQueryExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueryExtensionImpl(self.0.clone()))
+    }
+}
+
+struct PersistedQueryExtensionImpl(PersistedQueries);
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueryExtensionImpl {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if let Err(e) = self.0.resolve(&mut request).await {
+            let mut extensions = ErrorExtensionValues::default();
+            extensions.set("code", e.code());
+            let mut error = ServerError::new(e.to_string(), None);
+            error.extensions = Some(extensions);
+            return Err(error);
+        }
+        next.run(ctx, request).await
+    }
+}
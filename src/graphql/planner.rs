@@ -0,0 +1,669 @@
+use crate::cache::{CacheManager, CachePolicy, CachedValue};
+use crate::config::Settings;
+use crate::config::settings::{PlannerConfig, ResultMappingConfig};
+use crate::graphql::authorization::AuthRules;
+use crate::graphql::document::{inline_fragments, response_key, select_operation};
+use crate::graphql::incremental::UpstreamMemo;
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::schema::{MUTATION_ROOT, QUERY_ROOT};
+use crate::graphql::sparse_fields::SparseFields;
+use crate::rest::adapter::ResultMapping;
+use crate::rest::{RestClient, render_path};
+use crate::services::Services;
+use crate::utils::RustQLError;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextPrepareRequest,
+};
+use async_graphql::parser::types::{
+    ExecutableDocument, Field, OperationType, Selection, SelectionSet,
+};
+use async_graphql::{
+    QueryPathNode, QueryPathSegment, Request, Response, ServerResult, Value, Variables,
+};
+use futures_util::future::{BoxFuture, FutureExt, Shared, join_all};
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// A field resolved by a REST call.
+#[derive(Clone)]
+struct PlannedField {
+    api: String,
+    path: String,
+    /// Declared type the field returns, whose relationships depend on it
+    returns: Option<String>,
+    /// Whether the returned type is a connection over `returns` nodes
+    connection: bool,
+    batched: bool,
+    /// Whether the path reads `{parent.*}` values
+    reads_parent: bool,
+    /// How the call is made ahead of the executor, for cached GETs
+    prefetch: Option<Prefetch>,
+}
+
+/// What fetching a cached GET field needs, as its resolver does it.
+#[derive(Clone)]
+struct Prefetch {
+    client: RestClient,
+    cache: Arc<CacheManager>,
+    policy: CachePolicy,
+    sparse: Option<Arc<SparseFields>>,
+}
+
+/// The REST-backed fields of the schema by `Type.field`, from `[[queries]]`,
+/// `[[mutations]]` and the relationships of `[[types]]`.
+#[derive(Clone, Default)]
+pub struct PlanIndex {
+    fields: HashMap<String, PlannedField>,
+}
+
+impl PlanIndex {
+    pub fn new(settings: &Settings, services: &Services) -> Self {
+        let type_name = |returns: &Option<String>| {
+            returns.as_ref().map(|returns| {
+                returns
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string()
+            })
+        };
+        let policy = CachePolicy::from_config(&settings.cache);
+        let prefetch = |api: &str, returns: Option<&str>, result: Option<&ResultMappingConfig>| {
+            let mapping = result.map(ResultMapping::from_config).transpose().ok()?;
+            let sparse = SparseFields::for_field(settings, api, returns, mapping.as_ref(), &[]);
+            Some(Prefetch {
+                client: services.upstream(api)?.clone(),
+                cache: services.cache.clone(),
+                policy,
+                sparse: sparse.map(Arc::new),
+            })
+        };
+
+        let mut fields = HashMap::new();
+        for query in &settings.queries {
+            // Pages are fetched past the cache
+            let prefetch = match query.pagination {
+                Some(_) => None,
+                None => prefetch(&query.api, query.returns.as_deref(), query.result.as_ref()),
+            };
+            fields.insert(
+                format!("{}.{}", QUERY_ROOT, query.field),
+                PlannedField {
+                    api: query.api.clone(),
+                    path: query.path.clone(),
+                    returns: type_name(&query.returns),
+                    connection: query.pagination.is_some(),
+                    batched: false,
+                    reads_parent: false,
+                    prefetch,
+                },
+            );
+        }
+        for mutation in &settings.mutations {
+            fields.insert(
+                format!("{}.{}", MUTATION_ROOT, mutation.field),
+                PlannedField {
+                    api: mutation.api.clone(),
+                    path: mutation.path.clone(),
+                    returns: None,
+                    connection: false,
+                    batched: false,
+                    reads_parent: false,
+                    prefetch: None,
+                },
+            );
+        }
+        for ty in &settings.types {
+            for relationship in &ty.relationships {
+                let reads_parent = relationship.path.contains("{parent.");
+                let prefetch = match reads_parent || relationship.batch.is_some() {
+                    true => None,
+                    false => prefetch(
+                        &relationship.api,
+                        relationship.returns.as_deref(),
+                        relationship.result.as_ref(),
+                    ),
+                };
+                fields.insert(
+                    format!("{}.{}", ty.name, relationship.field),
+                    PlannedField {
+                        api: relationship.api.clone(),
+                        path: relationship.path.clone(),
+                        returns: type_name(&relationship.returns),
+                        connection: false,
+                        batched: relationship.batch.is_some(),
+                        reads_parent,
+                        prefetch,
+                    },
+                );
+            }
+        }
+        Self { fields }
+    }
+
+    /// The REST calls `operation_name` in `document` makes, one step per
+    /// REST-backed field selected. A step depends on the nearest REST-backed
+    /// field above it when its path reads the parent object, or when its
+    /// call cannot be made up front. Calls can be made up front in queries
+    /// for cached GETs whose arguments are all given, outside `@skip` and
+    /// `@include`, when `allowed` lets the caller resolve the field and
+    /// every field above it.
+    pub fn plan(
+        &self,
+        document: &ExecutableDocument,
+        operation_name: Option<&str>,
+        variables: &Variables,
+        allowed: impl Fn(&str, &str) -> bool,
+    ) -> Vec<PlanStep> {
+        let mut planner = Planner {
+            index: self,
+            variables,
+            allowed: &allowed,
+            steps: Vec::new(),
+        };
+        let Ok((_, operation)) = select_operation(document, operation_name) else {
+            return planner.steps;
+        };
+        let root = match operation.ty {
+            OperationType::Query => QUERY_ROOT,
+            OperationType::Mutation => MUTATION_ROOT,
+            OperationType::Subscription => return planner.steps,
+        };
+        if let Ok(operation) = inline_fragments(operation, &document.fragments) {
+            let selection_set = &operation.selection_set.node;
+            let prefetch = operation.ty == OperationType::Query;
+            planner.plan_selection_set(selection_set, root, "", None, prefetch);
+        }
+        planner.steps
+    }
+}
+
+struct Planner<'a> {
+    index: &'a PlanIndex,
+    variables: &'a Variables,
+    allowed: &'a dyn Fn(&str, &str) -> bool,
+    steps: Vec<PlanStep>,
+}
+
+impl Planner<'_> {
+    fn plan_selection_set(
+        &mut self,
+        selection_set: &SelectionSet,
+        parent_type: &str,
+        parent_path: &str,
+        parent: Option<usize>,
+        prefetch: bool,
+    ) {
+        for selection in &selection_set.items {
+            let field = match &selection.node {
+                Selection::Field(field) => &field.node,
+                Selection::InlineFragment(fragment) => {
+                    let fragment = &fragment.node;
+                    let prefetch = prefetch && fragment.directives.is_empty();
+                    let selection_set = &fragment.selection_set.node;
+                    self.plan_selection_set(
+                        selection_set,
+                        parent_type,
+                        parent_path,
+                        parent,
+                        prefetch,
+                    );
+                    continue;
+                }
+                Selection::FragmentSpread(_) => continue,
+            };
+
+            let name = format!("{}.{}", parent_type, field.name.node);
+            let Some(planned) = self.index.fields.get(&name) else {
+                continue;
+            };
+            let path = match parent_path {
+                "" => response_key(field).to_string(),
+                parent => format!("{}.{}", parent, response_key(field)),
+            };
+            let prefetch = prefetch
+                && field.directives.is_empty()
+                && (self.allowed)(parent_type, &field.name.node);
+            let call = match (prefetch, &planned.prefetch) {
+                (true, Some(upstream)) if !planned.reads_parent => {
+                    self.call(field, planned, upstream)
+                }
+                _ => None,
+            };
+            let id = self.steps.len();
+            self.steps.push(PlanStep {
+                id,
+                field: name,
+                path: path.clone(),
+                api: planned.api.clone(),
+                upstream: planned.path.clone(),
+                batched: planned.batched,
+                depends_on: call.is_none().then_some(parent).flatten(),
+                prefetch: call,
+            });
+
+            let Some(returns) = &planned.returns else {
+                continue;
+            };
+            match planned.connection {
+                true => {
+                    for (nodes_path, nodes) in connection_nodes(field, &path) {
+                        self.plan_selection_set(nodes, returns, &nodes_path, Some(id), prefetch);
+                    }
+                }
+                false => {
+                    let selection_set = &field.selection_set.node;
+                    self.plan_selection_set(selection_set, returns, &path, Some(id), prefetch);
+                }
+            }
+        }
+    }
+
+    /// The path `field` calls, with its sparse fields, when every
+    /// placeholder has a string argument.
+    fn call(&self, field: &Field, planned: &PlannedField, prefetch: &Prefetch) -> Option<String> {
+        let path = render_path(&planned.path, |name| {
+            let value = field.get_argument(name)?.node.clone();
+            let value = value
+                .into_const_with(|variable| self.variables.get(&variable).cloned().ok_or(()))
+                .ok()?;
+            match value {
+                Value::String(value) => Some(value),
+                _ => None,
+            }
+        })
+        .ok()?;
+        Some(match &prefetch.sparse {
+            Some(sparse) => sparse.apply_to(selected_names(&field.selection_set.node), "", path),
+            None => path,
+        })
+    }
+}
+
+/// Names of the fields `selection_set` selects, through inline fragments.
+fn selected_names(selection_set: &SelectionSet) -> Vec<&str> {
+    let mut names = Vec::new();
+    for selection in &selection_set.items {
+        match &selection.node {
+            Selection::Field(field) => names.push(field.node.name.node.as_str()),
+            Selection::InlineFragment(fragment) => {
+                names.extend(selected_names(&fragment.node.selection_set.node));
+            }
+            Selection::FragmentSpread(_) => {}
+        }
+    }
+    names
+}
+
+/// The node selections of a connection `field` at `path`, under its
+/// `edges` and their `node`, with their response paths.
+fn connection_nodes<'a>(field: &'a Field, path: &str) -> Vec<(String, &'a SelectionSet)> {
+    fn fields<'a>(selection_set: &'a SelectionSet, name: &str, found: &mut Vec<&'a Field>) {
+        for selection in &selection_set.items {
+            match &selection.node {
+                Selection::Field(field) if field.node.name.node == name => found.push(&field.node),
+                Selection::Field(_) | Selection::FragmentSpread(_) => {}
+                Selection::InlineFragment(fragment) => {
+                    fields(&fragment.node.selection_set.node, name, found);
+                }
+            }
+        }
+    }
+
+    let mut edges = Vec::new();
+    fields(&field.selection_set.node, "edges", &mut edges);
+    let mut nodes = Vec::new();
+    for edge in edges {
+        let mut found = Vec::new();
+        fields(&edge.selection_set.node, "node", &mut found);
+        for node in found {
+            let node_path = format!("{}.{}.{}", path, response_key(edge), response_key(node));
+            nodes.push((node_path, &node.selection_set.node));
+        }
+    }
+    nodes
+}
+
+/// A REST call an operation makes, once per parent object it resolves on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanStep {
+    pub id: usize,
+    /// `Type.field` making the call
+    pub field: String,
+    /// Response path of the field, without list indexes
+    pub path: String,
+    pub api: String,
+    /// Path template of the call
+    pub upstream: String,
+    pub batched: bool,
+    /// Step whose result the call needs
+    pub depends_on: Option<usize>,
+    /// Path of the call made before execution reaches the field
+    pub prefetch: Option<String>,
+}
+
+/// Groups steps into stages: each stage's calls need only results of
+/// earlier stages and run concurrently.
+pub fn stages(steps: &[PlanStep]) -> Vec<Vec<usize>> {
+    let mut levels: Vec<usize> = Vec::with_capacity(steps.len());
+    let mut stages: Vec<Vec<usize>> = Vec::new();
+    for step in steps {
+        // Steps only depend on earlier ones
+        let level = step.depends_on.map_or(0, |parent| levels[parent] + 1);
+        levels.push(level);
+        if stages.len() <= level {
+            stages.resize_with(level + 1, Vec::new);
+        }
+        stages[level].push(step.id);
+    }
+    stages
+}
+
+#[derive(Debug, Clone)]
+struct CallTiming {
+    step: String,
+    path: String,
+    start: Duration,
+    duration: Duration,
+    failed: bool,
+}
+
+/// The upstream calls of one operation: bounds how many are in flight and
+/// records when each ran.
+#[derive(Debug, Clone)]
+pub struct UpstreamCalls {
+    permits: Arc<Semaphore>,
+    started: Instant,
+    calls: Arc<Mutex<Vec<CallTiming>>>,
+}
+
+impl UpstreamCalls {
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+            started: Instant::now(),
+            calls: Arc::default(),
+        }
+    }
+
+    /// Permits for calls made outside a resolver, such as batches.
+    pub fn permits(&self) -> Arc<Semaphore> {
+        self.permits.clone()
+    }
+
+    /// Runs the call `call` for the step at `step` once a permit is free,
+    /// unless `batched`, in which case the batch takes the permit.
+    pub async fn run<T, E>(
+        &self,
+        step: String,
+        path: &str,
+        batched: bool,
+        call: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let _permit = match batched {
+            true => None,
+            false => self.permits.acquire().await.ok(),
+        };
+        let start = self.started.elapsed();
+        let result = call.await;
+        let timing = CallTiming {
+            step,
+            path: path.to_string(),
+            start,
+            duration: self.started.elapsed() - start,
+            failed: result.is_err(),
+        };
+        self.calls.lock().expect("upstream calls lock").push(timing);
+        result
+    }
+
+    fn timings(&self) -> Vec<CallTiming> {
+        self.calls.lock().expect("upstream calls lock").clone()
+    }
+}
+
+/// Response path of the field at `node` without list indexes, as used by
+/// `PlanStep::path`.
+fn step_path(node: &QueryPathNode<'_>) -> String {
+    let mut names: Vec<&str> = std::iter::once(node)
+        .chain(node.parents())
+        .filter_map(|node| match node.segment {
+            QueryPathSegment::Name(name) => Some(name),
+            QueryPathSegment::Index(_) => None,
+        })
+        .collect();
+    names.reverse();
+    names.join(".")
+}
+
+/// Runs `call` through the operation's `UpstreamCalls`, when the request
+/// has them.
+pub(crate) async fn upstream_call<T, E>(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    path: &str,
+    batched: bool,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    match ctx.data_opt::<UpstreamCalls>() {
+        Some(calls) => {
+            let step = ctx.path_node.as_ref().map(step_path).unwrap_or_default();
+            calls.run(step, path, batched, call).await
+        }
+        None => call.await,
+    }
+}
+
+/// A cached GET started before execution reached its field.
+pub(crate) type PrefetchedCall = Shared<BoxFuture<'static, Result<CachedValue, Arc<RustQLError>>>>;
+
+/// The calls an operation's plan started ahead of its resolvers, by cache
+/// key.
+#[derive(Clone, Default)]
+struct Prefetched(Arc<Mutex<HashMap<String, PrefetchedCall>>>);
+
+/// The call the plan started for the cache key `key`, for its resolver to
+/// await instead of fetching again.
+pub(crate) fn prefetched(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    key: &str,
+) -> Option<PrefetchedCall> {
+    let prefetched = ctx.data_opt::<Prefetched>()?;
+    prefetched
+        .0
+        .lock()
+        .expect("prefetch lock")
+        .get(key)
+        .cloned()
+}
+
+/// Plans the REST calls of each operation and runs them: calls of the first
+/// stage start together before execution, and their resolvers await them;
+/// later calls are made as the executor resolves the fields they depend on.
+/// At most `max_concurrency` calls of an operation are in flight. Requests
+/// carrying the configured debug header get the plan, grouped into stages,
+/// and the timing of every call in `extensions.queryPlan`.
+pub struct QueryPlanExtension {
+    pub index: Arc<PlanIndex>,
+    pub config: PlannerConfig,
+}
+
+impl ExtensionFactory for QueryPlanExtension {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryPlanExtensionImpl {
+            index: self.index.clone(),
+            config: self.config.clone(),
+            prefetched: Prefetched::default(),
+            document: Mutex::new(None),
+        })
+    }
+}
+
+struct QueryPlanExtensionImpl {
+    index: Arc<PlanIndex>,
+    config: PlannerConfig,
+    prefetched: Prefetched,
+    document: Mutex<Option<(ExecutableDocument, Variables)>>,
+}
+
+impl QueryPlanExtensionImpl {
+    fn wants_plan(&self, ctx: &ExtensionContext<'_>) -> bool {
+        let Some(header) = &self.config.debug_header else {
+            return false;
+        };
+        ctx.data_opt::<ResolverContext>()
+            .is_some_and(|context| context.headers.contains_key(header.as_str()))
+    }
+
+    /// Starts the call of `step` when it is made up front, sharing one call
+    /// between steps with the same cache key. Results an earlier execution
+    /// of the request memoized are not fetched again.
+    fn prefetch(
+        &self,
+        step: &PlanStep,
+        context: Option<&ResolverContext>,
+        calls: Option<&UpstreamCalls>,
+        memo: Option<&UpstreamMemo>,
+    ) -> Option<PrefetchedCall> {
+        let path = step.prefetch.clone()?;
+        let upstream = self.index.fields.get(&step.field)?.prefetch.clone()?;
+        let client = match context {
+            Some(context) => upstream.client.for_caller(context),
+            None => upstream.client,
+        };
+        let key = client.cache_key(&path);
+        if memo.is_some_and(|memo| memo.contains(&key)) {
+            return None;
+        }
+        let mut prefetched = self.prefetched.0.lock().expect("prefetch lock");
+        if prefetched.contains_key(&key) {
+            return None;
+        }
+
+        let (cache, policy) = (upstream.cache, upstream.policy);
+        let (calls, step) = (calls.cloned(), step.path.clone());
+        let cache_key = key.clone();
+        let call = async move {
+            let call = path.clone();
+            let fetch = cache.get_or_refresh(&cache_key, policy, move |validators| async move {
+                client.get_cacheable(&path, &validators).await
+            });
+            match calls {
+                Some(calls) => calls.run(step, &call, false, fetch).await,
+                None => fetch.await,
+            }
+            .map_err(Arc::new)
+        }
+        .boxed()
+        .shared();
+        prefetched.insert(key, call.clone());
+        Some(call)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryPlanExtensionImpl {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        request
+            .data
+            .insert(UpstreamCalls::new(self.config.max_concurrency));
+        request.data.insert(self.prefetched.clone());
+        next.run(ctx, request).await
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        if !self.index.fields.is_empty() || self.wants_plan(ctx) {
+            *self.document.lock().expect("plan document lock") =
+                Some((document.clone(), variables.clone()));
+        }
+        Ok(document)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let document = self.document.lock().expect("plan document lock").take();
+        let Some((document, variables)) = document else {
+            return next.run(ctx, operation_name).await;
+        };
+        let context = ctx.data_opt::<ResolverContext>();
+        let claims = context.and_then(|context| context.claims.as_deref());
+        let rules = ctx.data_opt::<Arc<AuthRules>>();
+        let steps = self
+            .index
+            .plan(&document, operation_name, &variables, |ty, field| {
+                rules.is_none_or(|rules| rules.allows(ty, field, claims))
+            });
+
+        // Prefetched calls nobody awaited are dropped with the operation
+        let calls = ctx.data_opt::<UpstreamCalls>();
+        let memo = ctx.data_opt::<UpstreamMemo>();
+        let prefetches: Vec<_> = steps
+            .iter()
+            .filter_map(|step| self.prefetch(step, context, calls, memo))
+            .collect();
+        let execution = next.run(ctx, operation_name);
+        let prefetches = join_all(prefetches);
+        tokio::pin!(execution, prefetches);
+        let mut response = tokio::select! {
+            response = &mut execution => response,
+            _ = &mut prefetches => execution.await,
+        };
+
+        if !self.wants_plan(ctx) {
+            return response;
+        }
+        let timings = calls.map(UpstreamCalls::timings).unwrap_or_default();
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let plan = json!({
+            "maxConcurrency": self.config.max_concurrency,
+            "stages": stages(&steps),
+            "steps": steps.iter().map(|step| {
+                let calls: Vec<_> = timings.iter().filter(|call| call.step == step.path).collect();
+                let start = calls.iter().map(|call| call.start).min();
+                let end = calls.iter().map(|call| call.start + call.duration).max();
+                json!({
+                    "id": step.id,
+                    "field": step.field,
+                    "path": step.path,
+                    "api": step.api,
+                    "upstream": step.upstream,
+                    "batched": step.batched,
+                    "dependsOn": step.depends_on,
+                    "prefetched": step.prefetch.is_some(),
+                    "startMs": start.map(millis),
+                    "durationMs": start.zip(end).map(|(start, end)| millis(end - start)),
+                    "calls": calls.iter().map(|call| json!({
+                        "path": call.path,
+                        "startMs": millis(call.start),
+                        "durationMs": millis(call.duration),
+                        "failed": call.failed,
+                    })).collect::<Vec<_>>(),
+                })
+            }).collect::<Vec<_>>(),
+        });
+        response.extensions.insert(
+            "queryPlan".to_string(),
+            Value::from_json(plan).unwrap_or_default(),
+        );
+        response
+    }
+}
@@ -0,0 +1,256 @@
+use crate::cache::{CacheManager, CachePolicy};
+use crate::config::Settings;
+use crate::config::settings::QueryConfig;
+use crate::graphql::cache_control::{CallerScoped, StaleData};
+use crate::graphql::connections::connection_field;
+use crate::graphql::incremental::memoized;
+use crate::graphql::planner::{prefetched, upstream_call};
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::sparse_fields::SparseFields;
+use crate::graphql::subscriptions::argument_values;
+use crate::graphql::types::return_type;
+use crate::rest::adapter::ResultMapping;
+use crate::rest::pagination::Paginator;
+use crate::rest::{RestClient, render_path};
+use crate::services::Services;
+use crate::utils::{Result, RustQLError};
+use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, TypeRef};
+use async_graphql::{ErrorExtensions, Value};
+use futures_util::TryFutureExt;
+use futures_util::future::Either;
+use std::sync::Arc;
+
+/// Builds one query field per `[[queries]]` entry.
+pub fn query_fields(settings: &Settings, services: &Services) -> Result<Vec<Field>> {
+    let policy = CachePolicy::from_config(&settings.cache);
+    settings
+        .queries
+        .iter()
+        .map(|query| {
+            let client = services.upstream(&query.api).ok_or_else(|| {
+                RustQLError::Config(format!(
+                    "Query '{}' calls unknown API '{}'",
+                    query.field, query.api
+                ))
+            })?;
+            let not_found_as_null = settings
+                .apis
+                .rest
+                .iter()
+                .any(|api| api.name == query.api && api.not_found_as_null);
+            let mapping = query
+                .result
+                .as_ref()
+                .map(ResultMapping::from_config)
+                .transpose()
+                .map_err(|e| {
+                    RustQLError::Config(format!(
+                        "Invalid result mapping for query '{}': {}",
+                        query.field, e
+                    ))
+                })?;
+            if let Some(pagination) = &query.pagination {
+                let paginator = Paginator::from_config(pagination).map_err(|e| {
+                    RustQLError::Config(format!(
+                        "Invalid pagination for query '{}': {}",
+                        query.field, e
+                    ))
+                })?;
+                return Ok(connection_field(
+                    query,
+                    client.clone(),
+                    Arc::new(paginator),
+                    not_found_as_null,
+                    mapping.map(Arc::new),
+                ));
+            }
+            let sparse = SparseFields::for_field(
+                settings,
+                &query.api,
+                query.returns.as_deref(),
+                mapping.as_ref(),
+                &[],
+            );
+            Ok(rest_field(
+                query,
+                client.clone(),
+                services.cache.clone(),
+                policy,
+                not_found_as_null,
+                mapping.map(Arc::new),
+                sparse.map(Arc::new),
+            ))
+        })
+        .collect()
+}
+
+/// A field that GETs its path through the cache, honouring the upstream's
+/// caching headers. Responses past their TTL may still be served within the
+/// policy's stale windows; such fields are recorded in `StaleData`. Headers
+/// propagated from the caller are cached separately per distinct value.
+/// Upstream failures null the field and report an error at its path, leaving
+/// the rest of the operation to resolve. The cache holds upstream payloads;
+/// the query's result mapping is applied on the way out. Typed results ask
+/// APIs taking sparse fields for only the keys the selection reads.
+fn rest_field(
+    config: &QueryConfig,
+    client: RestClient,
+    cache: Arc<CacheManager>,
+    policy: CachePolicy,
+    not_found_as_null: bool,
+    mapping: Option<Arc<ResultMapping>>,
+    sparse: Option<Arc<SparseFields>>,
+) -> Field {
+    let arguments = config.arguments.clone();
+    let template = config.path.clone();
+    let mut field = Field::new(
+        config.field.as_str(),
+        return_type(config.returns.as_deref()),
+        move |ctx| {
+            let client = match ctx.data_opt::<ResolverContext>() {
+                Some(context) => client.for_caller(context),
+                None => client.clone(),
+            };
+            let cache = cache.clone();
+            let mapping = mapping.clone();
+            let sparse = sparse.clone();
+            let template = template.clone();
+            let values = argument_values(&ctx, &arguments);
+            FieldFuture::new(async move {
+                let values = values?;
+                let mut path = render_path(&template, |name| values.get(name).cloned().flatten())
+                    .map_err(|name| missing_placeholder(&ctx, &name))?;
+                if let Some(sparse) = &sparse {
+                    path = sparse.apply(&ctx, "", path);
+                }
+                let fetched =
+                    fetch_cached(&ctx, &client, &cache, policy, path, not_found_as_null).await?;
+                let Some(json) = fetched else {
+                    return Ok(None);
+                };
+                let json = map_result(&ctx, client.name(), mapping.as_deref(), json)?;
+                Ok(Some(FieldValue::value(Value::from_json(json)?)))
+            })
+        },
+    );
+
+    for argument in &config.arguments {
+        field = field.argument(InputValue::new(
+            argument.as_str(),
+            TypeRef::named_nn(TypeRef::ID),
+        ));
+    }
+    match &config.description {
+        Some(description) => field.description(description.as_str()),
+        None => field,
+    }
+}
+
+/// GETs `path` through the cache for a resolver, marking the operation
+/// caller-scoped when the client propagates caller headers and recording
+/// stale values. Awaits the call instead when the query plan prefetched it.
+/// `None` for a 404 from an API treating it as null.
+pub(crate) async fn fetch_cached(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    client: &RestClient,
+    cache: &Arc<CacheManager>,
+    policy: CachePolicy,
+    path: String,
+    not_found_as_null: bool,
+) -> async_graphql::Result<Option<serde_json::Value>> {
+    let key = client.cache_key(&path);
+    if client.vary().is_some() {
+        if let Some(scoped) = ctx.data_opt::<CallerScoped>() {
+            scoped.mark();
+        }
+    }
+
+    let upstream = client.clone();
+    let call = path.clone();
+    let fetch = match prefetched(ctx, &key) {
+        Some(prefetched) => Either::Left(prefetched),
+        None => Either::Right(
+            upstream_call(
+                ctx,
+                &call,
+                false,
+                cache.get_or_refresh(&key, policy, move |validators| async move {
+                    upstream.get_cacheable(&path, &validators).await
+                }),
+            )
+            .map_err(Arc::new),
+        ),
+    };
+    let cached = match memoized(ctx, key.clone(), fetch).await {
+        Ok(cached) => cached,
+        Err(e) if not_found_as_null && matches!(*e, RustQLError::RestApi { status: 404, .. }) => {
+            return Ok(None);
+        }
+        Err(e) => return Err(upstream_error(ctx, client.name(), &e)),
+    };
+
+    if let Some(stale_for) = cached.stale_for {
+        if let Some(stale) = ctx.data_opt::<StaleData>() {
+            let path = ctx.path_node.map(|node| node.to_string());
+            stale.record(path, client.name(), stale_for);
+        }
+    }
+
+    serde_json::from_str(&cached.value)
+        .map(Some)
+        .map_err(|e| upstream_error(ctx, client.name(), &e.into()))
+}
+
+/// Applies a result mapping, reporting payloads it cannot convert as an
+/// unexpected response rather than an upstream failure.
+pub(crate) fn map_result(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    api: &str,
+    mapping: Option<&ResultMapping>,
+    json: serde_json::Value,
+) -> async_graphql::Result<serde_json::Value> {
+    let Some(mapping) = mapping else {
+        return Ok(json);
+    };
+    mapping.apply(json).map_err(|message| {
+        let e = RustQLError::UnexpectedResponse {
+            api: api.to_string(),
+            message,
+        };
+        upstream_error(ctx, api, &e)
+    })
+}
+
+/// A failed upstream call as a resolver error naming the API.
+pub(crate) fn upstream_error(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    api: &str,
+    err: &RustQLError,
+) -> async_graphql::Error {
+    resolver_error(ctx, err).extend_with(|_, extensions| extensions.set("api", api))
+}
+
+/// `err` as a resolver error carrying the request ID.
+pub(crate) fn resolver_error(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    err: &RustQLError,
+) -> async_graphql::Error {
+    match ctx.data_opt::<ResolverContext>() {
+        Some(context) => context.graphql_error(err),
+        None => err.extend(),
+    }
+}
+
+/// The error for a path whose `{placeholder}` has no value; the request is
+/// not sent.
+pub(crate) fn missing_placeholder(
+    ctx: &async_graphql::dynamic::ResolverContext<'_>,
+    placeholder: &str,
+) -> async_graphql::Error {
+    let e = RustQLError::Validation(format!(
+        "No value for '{{{}}}' in the path of '{}'",
+        placeholder,
+        ctx.field().name()
+    ));
+    resolver_error(ctx, &e)
+}
@@ -1,16 +1,80 @@
 use async_graphql::{Context, Result};
+use crate::auth::Claims;
 use crate::config::Settings;
+use crate::server::listener::ClientIdentity;
+use crate::utils::{RustQLError, sha256_hex};
+use async_graphql::ErrorExtensions;
 use std::sync::Arc;
-use tracing::{info, instrument};
+use warp::http::{HeaderMap, header::AUTHORIZATION};
+use tracing::{error, info, instrument};
 
+#[derive(Clone)]
 pub struct ResolverContext {
     pub settings: Arc<Settings>,
     pub request_id: String,
+    /// Verified client certificate subject or remote address of the caller
+    pub client: Option<ClientIdentity>,
+    /// Request headers, or the `connection_init` payload for WebSocket clients
+    pub headers: HeaderMap,
+    /// Verified token claims; `None` for anonymous callers
+    pub claims: Option<Arc<Claims>>,
 }
 
 impl ResolverContext {
     pub fn new(settings: Arc<Settings>, request_id: String) -> Self {
-        Self { settings, request_id }
+        Self { settings, request_id, client: None, headers: HeaderMap::new(), claims: None }
+    }
+
+    pub fn with_client(mut self, client: ClientIdentity) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    pub fn with_claims(mut self, claims: Option<Claims>) -> Self {
+        self.claims = claims.map(Arc::new);
+        self
+    }
+
+    /// Rate limit bucket of the caller: the API key or token subject when
+    /// authenticated, the client identity otherwise.
+    pub fn rate_limit_key(&self) -> Option<String> {
+        let claims = self.claims.as_deref();
+        if let Some(name) = claims.and_then(Claims::api_key) {
+            return Some(format!("key:{}", name));
+        }
+        match claims.and_then(Claims::subject) {
+            Some(subject) => Some(format!("sub:{}", subject)),
+            None => self.client.as_ref().map(|client| client.rate_limit_key()),
+        }
+    }
+
+    /// Identifies the credentials the caller presented, so responses built
+    /// for one caller are never served to another. `None` for anonymous callers.
+    pub fn auth_scope(&self) -> Option<String> {
+        if let Some(value) = self.headers.get(AUTHORIZATION) {
+            return Some(sha256_hex(&String::from_utf8_lossy(value.as_bytes())));
+        }
+        let name = self.claims.as_deref().and_then(Claims::api_key)?;
+        Some(sha256_hex(&format!("api_key:{}", name)))
+    }
+
+    /// `error` as a resolver error tagged with this request's id. With
+    /// `errors.mask_internal` set, internal errors are logged in full and
+    /// reported with a generic message.
+    pub fn graphql_error(&self, err: &RustQLError) -> async_graphql::Error {
+        let mut graphql_error = err.extend();
+        if err.is_internal() && self.settings.errors.mask_internal {
+            error!(request_id = %self.request_id, error = %err, "Resolver failed");
+            graphql_error.message = "Internal server error".to_string();
+        }
+        graphql_error.extend_with(|_, extensions| {
+            extensions.set("requestId", self.request_id.as_str())
+        })
     }
 }
 
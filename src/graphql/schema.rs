@@ -1,10 +1,32 @@
 use crate::config::Settings;
-use async_graphql::{EmptySubscription, Object, Schema, SimpleObject};
+use crate::graphql::authorization::{AuthRules, AuthorizationExtension, authorize_subscription};
+use crate::graphql::cache_control::{CacheControlExtension, CacheHints};
+use crate::graphql::partial::PartialResultsExtension;
+use crate::graphql::persisted::PersistedQueryExtension;
+use crate::graphql::planner::{PlanIndex, QueryPlanExtension};
+use crate::graphql::{connections, mutations, queries, subscriptions, types};
+use crate::services::Services;
+use crate::utils::{Result, RustQLError};
+use async_graphql::Value;
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputValue, Object, Scalar, Schema, SubscriptionField,
+    SubscriptionFieldFuture, TypeRef,
+};
+use serde::Serialize;
 use std::sync::Arc;
+use std::time::Duration;
 
-pub type RustQLSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+pub type RustQLSchema = Schema;
 
-#[derive(SimpleObject)]
+pub const QUERY_ROOT: &str = "QueryRoot";
+pub const MUTATION_ROOT: &str = "MutationRoot";
+pub const SUBSCRIPTION_ROOT: &str = "SubscriptionRoot";
+
+/// Arbitrary JSON passed through from upstream APIs.
+pub const JSON_SCALAR: &str = "JSON";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ApiInfo {
     pub name: String,
     pub version: String,
@@ -12,7 +34,8 @@ pub struct ApiInfo {
     pub uptime: String,
 }
 
-#[derive(SimpleObject)]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SystemStatus {
     pub status: String,
     pub timestamp: String,
@@ -20,12 +43,8 @@ pub struct SystemStatus {
     pub active_connections: i32,
 }
 
-pub struct QueryRoot;
-
-#[Object]
-impl QueryRoot {
-    /// Get API information
-    async fn api_info(&self) -> ApiInfo {
+impl ApiInfo {
+    fn current() -> Self {
         ApiInfo {
             name: "RustQL".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
@@ -38,9 +57,10 @@ impl QueryRoot {
             ),
         }
     }
+}
 
-    /// Get system status
-    async fn system_status(&self) -> SystemStatus {
+impl SystemStatus {
+    fn current() -> Self {
         SystemStatus {
             status: "healthy".to_string(),
             timestamp: chrono::Utc::now().to_rfc3339(),
@@ -48,30 +68,182 @@ impl QueryRoot {
             active_connections: 0, // Will be implemented with metrics
         }
     }
+}
+
+/// Converts a serializable value into a GraphQL value for object resolution.
+pub fn to_value<T: Serialize>(value: &T) -> Value {
+    let json = serde_json::to_value(value).unwrap_or(serde_json::Value::Null);
+    Value::from_json(json).unwrap_or(Value::Null)
+}
 
-    /// Health check endpoint
-    async fn health(&self) -> String {
-        "OK".to_string()
+pub fn to_field_value<T: Serialize>(value: &T) -> FieldValue<'static> {
+    FieldValue::value(to_value(value))
+}
+
+/// A field that reads `name` from its parent object value.
+pub fn value_field(name: &str, ty: TypeRef) -> Field {
+    let key = name.to_string();
+    Field::new(name, ty, move |ctx| {
+        let key = key.clone();
+        FieldFuture::new(async move {
+            let value = match ctx.parent_value.try_to_value()? {
+                Value::Object(object) => object.get(key.as_str()).cloned(),
+                _ => None,
+            };
+            Ok(value.filter(|v| *v != Value::Null).map(FieldValue::value))
+        })
+    })
+}
+
+fn query_root(settings: &Settings, services: &Services) -> Result<Object> {
+    let mut query = Object::new(QUERY_ROOT)
+        .field(
+            Field::new("apiInfo", TypeRef::named_nn("ApiInfo"), |_| {
+                FieldFuture::new(async { Ok(Some(to_field_value(&ApiInfo::current()))) })
+            })
+            .description("Get API information"),
+        )
+        .field(
+            Field::new("systemStatus", TypeRef::named_nn("SystemStatus"), |_| {
+                FieldFuture::new(async { Ok(Some(to_field_value(&SystemStatus::current()))) })
+            })
+            .description("Get system status"),
+        )
+        .field(
+            Field::new("health", TypeRef::named_nn(TypeRef::STRING), |_| {
+                FieldFuture::new(async { Ok(Some(Value::from("OK"))) })
+            })
+            .description("Health check endpoint"),
+        )
+        .field(
+            Field::new("echo", TypeRef::named_nn(TypeRef::STRING), |ctx| {
+                FieldFuture::new(async move {
+                    let message = ctx.args.try_get("message")?.string()?;
+                    Ok(Some(Value::from(format!("Echo: {}", message))))
+                })
+            })
+            .argument(InputValue::new("message", TypeRef::named_nn(TypeRef::STRING)))
+            .description("Echo query for testing"),
+        );
+    for field in queries::query_fields(settings, services)? {
+        query = query.field(field);
     }
 
-    /// Echo query for testing
-    async fn echo(&self, message: String) -> String {
-        format!("Echo: {}", message)
+    Ok(query)
+}
+
+fn mutation_root(settings: &Settings, services: &Services) -> Result<Object> {
+    let mut mutation = Object::new(MUTATION_ROOT).field(
+        Field::new("testMutation", TypeRef::named_nn(TypeRef::STRING), |ctx| {
+            FieldFuture::new(async move {
+                let input = ctx.args.try_get("input")?.string()?;
+                Ok(Some(Value::from(format!("Processed: {}", input))))
+            })
+        })
+        .argument(InputValue::new("input", TypeRef::named_nn(TypeRef::STRING)))
+        .description("Test mutation"),
+    );
+    for field in mutations::mutation_fields(settings, services)? {
+        mutation = mutation.field(field);
     }
+
+    Ok(mutation)
 }
 
-pub struct MutationRoot;
+fn subscription_root(
+    settings: &Settings,
+    services: &Services,
+) -> Result<async_graphql::dynamic::Subscription> {
+    let system_status = SubscriptionField::new(
+        "systemStatus",
+        TypeRef::named_nn("SystemStatus"),
+        |ctx| {
+            let authorized = authorize_subscription(&ctx);
+            let seconds = ctx.args.get("interval").map(|v| v.u64()).transpose();
+            SubscriptionFieldFuture::new(async move {
+                authorized?;
+                let seconds = seconds?.unwrap_or(5).max(1);
+                let interval = tokio::time::interval(Duration::from_secs(seconds));
+                Ok(futures_util::stream::unfold(interval, |mut interval| async move {
+                    interval.tick().await;
+                    Some((Ok(to_value(&SystemStatus::current())), interval))
+                }))
+            })
+        },
+    )
+    .argument(InputValue::new("interval", TypeRef::named(TypeRef::INT)).default_value(5))
+    .description("Periodic system status updates");
 
-#[Object]
-impl MutationRoot {
-    /// Test mutation
-    async fn test_mutation(&self, input: String) -> String {
-        format!("Processed: {}", input)
+    let mut subscription =
+        async_graphql::dynamic::Subscription::new(SUBSCRIPTION_ROOT).field(system_status);
+    for field in subscriptions::subscription_fields(settings, services)? {
+        subscription = subscription.field(field);
     }
+
+    Ok(subscription)
+}
+
+fn api_info_type() -> Object {
+    Object::new("ApiInfo")
+        .field(value_field("name", TypeRef::named_nn(TypeRef::STRING)))
+        .field(value_field("version", TypeRef::named_nn(TypeRef::STRING)))
+        .field(value_field("description", TypeRef::named_nn(TypeRef::STRING)))
+        .field(value_field("uptime", TypeRef::named_nn(TypeRef::STRING)))
 }
 
-pub fn create_schema(settings: Arc<Settings>) -> RustQLSchema {
-    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+fn system_status_type() -> Object {
+    Object::new("SystemStatus")
+        .field(value_field("status", TypeRef::named_nn(TypeRef::STRING)))
+        .field(value_field("timestamp", TypeRef::named_nn(TypeRef::STRING)))
+        .field(value_field("requestCount", TypeRef::named_nn(TypeRef::INT)))
+        .field(value_field("activeConnections", TypeRef::named_nn(TypeRef::INT)))
+}
+
+pub fn create_schema(settings: Arc<Settings>) -> Result<RustQLSchema> {
+    let services = Services::from_settings(&settings)?;
+    build_schema(settings, services)
+}
+
+/// Builds the schema around the shared services, e.g. the broker that the
+/// webhook route publishes into.
+pub fn build_schema(settings: Arc<Settings>, services: Services) -> Result<RustQLSchema> {
+    let mut schema = Schema::build(QUERY_ROOT, Some(MUTATION_ROOT), Some(SUBSCRIPTION_ROOT))
+        .register(query_root(&settings, &services)?)
+        .register(mutation_root(&settings, &services)?)
+        .register(subscription_root(&settings, &services)?)
+        .register(api_info_type())
+        .register(system_status_type())
+        .register(Scalar::new(JSON_SCALAR).description("Arbitrary JSON value"));
+    for ty in types::object_types(&settings, &services)? {
+        schema = schema.register(ty);
+    }
+    let (inputs, payloads) = mutations::mutation_types(&settings);
+    for input in inputs {
+        schema = schema.register(input);
+    }
+    for payload in payloads {
+        schema = schema.register(payload);
+    }
+    for ty in connections::connection_types(&settings) {
+        schema = schema.register(ty);
+    }
+
+    let auth_rules = Arc::new(AuthRules::from_settings(&settings));
+    let plan_index = Arc::new(PlanIndex::new(&settings, &services));
+    schema
+        .extension(PersistedQueryExtension(services.persisted_queries))
+        .extension(PartialResultsExtension)
+        .extension(AuthorizationExtension(auth_rules.clone()))
+        .extension(CacheControlExtension(Arc::new(CacheHints::from_config(
+            &settings.cache.response,
+        ))))
+        .extension(QueryPlanExtension {
+            index: plan_index,
+            config: settings.planner.clone(),
+        })
+        .data(auth_rules)
         .data(settings)
+        .data(services.broker)
         .finish()
+        .map_err(|e| RustQLError::GraphQL(format!("Failed to build schema: {}", e)))
 }
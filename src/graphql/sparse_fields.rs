@@ -0,0 +1,130 @@
+use crate::config::Settings;
+use crate::rest::PATH_SEGMENT;
+use crate::rest::adapter::ResultMapping;
+use async_graphql::dynamic::ResolverContext;
+use percent_encoding::utf8_percent_encode;
+use std::collections::HashMap;
+
+/// The upstream keys a field returning a `[[types]]` entry needs for the
+/// sub-fields a query selects, sent as the API's sparse fields parameter.
+#[derive(Debug, Clone)]
+pub struct SparseFields {
+    /// Parameter name, with `{type}` filled
+    param: String,
+    separator: String,
+    /// Upstream keys read by each field of the returned type; `None` when
+    /// they are not known exactly
+    keys: HashMap<String, Option<Vec<String>>>,
+    /// Keys needed whatever is selected, such as a batch key
+    required: Vec<String>,
+}
+
+impl SparseFields {
+    /// The sparse fields of a field calling `api` and mapping its response
+    /// with `mapping`, when the API takes them and the field returns a
+    /// declared type. `required` are mapped keys always requested, so there
+    /// are no sparse fields when one of their upstream keys is unknown.
+    pub fn for_field(
+        settings: &Settings,
+        api: &str,
+        returns: Option<&str>,
+        mapping: Option<&ResultMapping>,
+        required: &[&str],
+    ) -> Option<Self> {
+        let config = settings
+            .apis
+            .rest
+            .iter()
+            .find(|config| config.name == api)?
+            .sparse_fields
+            .as_ref()?;
+        let name = returns?.trim_start_matches('[').trim_end_matches(']');
+        let ty = settings.types.iter().find(|ty| ty.name == name)?;
+        let upstream = |key: &str| match mapping {
+            Some(mapping) => mapping.upstream_key(key),
+            None => Some(key.to_string()),
+        };
+        let required = required
+            .iter()
+            .map(|key| upstream(key))
+            .collect::<Option<_>>()?;
+
+        let mut keys: HashMap<String, Option<Vec<String>>> = ty
+            .fields
+            .keys()
+            .map(|field| (field.clone(), upstream(field).map(|key| vec![key])))
+            .collect();
+        for relationship in &ty.relationships {
+            let parent = parent_keys(&relationship.path).map(upstream).collect();
+            keys.insert(relationship.field.clone(), parent);
+        }
+
+        let resource = ty.resource.as_deref().unwrap_or(&ty.name);
+        Some(Self {
+            param: config.param.replace("{type}", resource),
+            separator: config.separator.clone(),
+            keys,
+            required,
+        })
+    }
+
+    /// `path` with the parameter listing the keys the sub-fields `ctx`
+    /// selects read, unchanged when they read none or one whose keys are
+    /// unknown. `prefix` precedes `path` in the request URL, as with batch
+    /// suffixes.
+    pub fn apply(&self, ctx: &ResolverContext<'_>, prefix: &str, path: String) -> String {
+        let field = ctx.field();
+        self.apply_to(
+            field.selection_set().map(|field| field.name()),
+            prefix,
+            path,
+        )
+    }
+
+    /// `apply` for the sub-fields named by `selected`.
+    pub fn apply_to<'a>(
+        &self,
+        selected: impl IntoIterator<Item = &'a str>,
+        prefix: &str,
+        path: String,
+    ) -> String {
+        let mut fields: Vec<&str> = self.required.iter().map(String::as_str).collect();
+        for field in selected {
+            match self.keys.get(field) {
+                Some(Some(keys)) => fields.extend(keys.iter().map(String::as_str)),
+                Some(None) => return path,
+                None => {}
+            }
+        }
+        if fields.is_empty() {
+            return path;
+        }
+        // Sorted so that equivalent selections share cache entries
+        fields.sort_unstable();
+        fields.dedup();
+
+        let fields: Vec<String> = fields
+            .into_iter()
+            .map(|field| utf8_percent_encode(field, PATH_SEGMENT).to_string())
+            .collect();
+        let delimiter = match prefix.contains('?') || path.contains('?') {
+            true => '&',
+            false => '?',
+        };
+        format!(
+            "{}{}{}={}",
+            path,
+            delimiter,
+            self.param,
+            fields.join(&self.separator)
+        )
+    }
+}
+
+/// First keys of the `{parent.path}` placeholders in a path template.
+fn parent_keys(template: &str) -> impl Iterator<Item = &str> {
+    template.split("{parent.").skip(1).filter_map(|rest| {
+        let path = &rest[..rest.find('}')?];
+        path.split(['.', '[']).next().filter(|key| !key.is_empty())
+    })
+}
@@ -0,0 +1,300 @@
+use crate::config::Settings;
+use crate::config::settings::{PollConfig, SubscriptionConfig, WebhookConfig};
+use crate::graphql::authorization::authorize_subscription;
+use crate::graphql::queries::missing_placeholder;
+use crate::graphql::resolvers;
+use crate::graphql::schema::JSON_SCALAR;
+use crate::rest::{RestClient, render_path};
+use crate::services::Services;
+use crate::utils::{Result, RustQLError};
+use async_graphql::Value;
+use async_graphql::dynamic::{
+    InputValue, ResolverContext, SubscriptionField, SubscriptionFieldFuture, TypeRef,
+};
+use dashmap::DashMap;
+use futures_util::StreamExt;
+use futures_util::stream::{self, Stream};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+/// Events buffered per topic before slow subscribers start skipping.
+const WEBHOOK_CHANNEL_CAPACITY: usize = 256;
+
+/// Fans events received on the webhook route out to subscribers by topic.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookBroker {
+    topics: Arc<DashMap<String, broadcast::Sender<serde_json::Value>>>,
+}
+
+impl WebhookBroker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sender(&self, topic: &str) -> broadcast::Sender<serde_json::Value> {
+        self.topics
+            .entry(topic.to_string())
+            .or_insert_with(|| broadcast::channel(WEBHOOK_CHANNEL_CAPACITY).0)
+            .clone()
+    }
+
+    /// Publishes an event and returns how many subscribers received it.
+    pub fn publish(&self, topic: &str, event: serde_json::Value) -> usize {
+        self.sender(topic).send(event).unwrap_or(0)
+    }
+
+    pub fn subscribe(&self, topic: &str) -> broadcast::Receiver<serde_json::Value> {
+        self.sender(topic).subscribe()
+    }
+}
+
+/// Builds one subscription field per `[[subscriptions]]` entry.
+pub fn subscription_fields(
+    settings: &Settings,
+    services: &Services,
+) -> Result<Vec<SubscriptionField>> {
+    settings
+        .subscriptions
+        .iter()
+        .map(|subscription| match (&subscription.poll, &subscription.webhook) {
+            (Some(poll), None) => {
+                let client = services.upstream(&poll.api).ok_or_else(|| {
+                    RustQLError::Config(format!(
+                        "Subscription '{}' polls unknown API '{}'",
+                        subscription.field, poll.api
+                    ))
+                })?;
+                Ok(poll_field(subscription, poll.clone(), client.clone()))
+            }
+            (None, Some(webhook)) => Ok(webhook_field(subscription, webhook.clone(), services.broker.clone())),
+            _ => Err(RustQLError::Config(format!(
+                "Subscription '{}' must set exactly one of poll or webhook",
+                subscription.field
+            ))),
+        })
+        .collect()
+}
+
+fn poll_field(config: &SubscriptionConfig, poll: PollConfig, client: RestClient) -> SubscriptionField {
+    let arguments = config.arguments.clone();
+    let mut field = SubscriptionField::new(
+        config.field.as_str(),
+        TypeRef::named_nn(JSON_SCALAR),
+        move |ctx| {
+            let authorized = authorize_subscription(&ctx);
+            let poll = poll.clone();
+            let client = match ctx.data_opt::<resolvers::ResolverContext>() {
+                Some(context) => client.for_caller(context),
+                None => client.clone(),
+            };
+            let path = argument_values(&ctx, &arguments).and_then(|values| {
+                render_path(&poll.path, |name| values.get(name).cloned().flatten())
+                    .map_err(|name| missing_placeholder(&ctx, &name))
+            });
+            SubscriptionFieldFuture::new(async move {
+                authorized?;
+                let path = path?;
+                let interval = Duration::from_secs(poll.interval);
+                Ok(into_field_values(poll_changes(
+                    client,
+                    path,
+                    interval,
+                    poll.ignore_paths,
+                )))
+            })
+        },
+    );
+
+    for argument in &config.arguments {
+        field = field.argument(InputValue::new(argument.as_str(), TypeRef::named_nn(TypeRef::ID)));
+    }
+    with_description(field, config)
+}
+
+fn webhook_field(
+    config: &SubscriptionConfig,
+    webhook: WebhookConfig,
+    broker: WebhookBroker,
+) -> SubscriptionField {
+    let arguments = config.arguments.clone();
+    let mut field = SubscriptionField::new(
+        config.field.as_str(),
+        TypeRef::named_nn(JSON_SCALAR),
+        move |ctx| {
+            let receiver = authorize_subscription(&ctx).map(|()| broker.subscribe(&webhook.topic));
+            let values = argument_values(&ctx, &arguments);
+            SubscriptionFieldFuture::new(async move {
+                let receiver = receiver?;
+                let filters: Vec<(String, String)> = values?
+                    .into_iter()
+                    .filter_map(|(name, value)| value.map(|value| (name, value)))
+                    .collect();
+                Ok(into_field_values(webhook_events(receiver, filters)))
+            })
+        },
+    );
+
+    // Webhook arguments are optional filters on top-level event fields
+    for argument in &config.arguments {
+        field = field.argument(InputValue::new(argument.as_str(), TypeRef::named(TypeRef::ID)));
+    }
+    with_description(field, config)
+}
+
+fn with_description(field: SubscriptionField, config: &SubscriptionConfig) -> SubscriptionField {
+    match &config.description {
+        Some(description) => field.description(description.as_str()),
+        None => field,
+    }
+}
+
+pub(crate) fn argument_values(
+    ctx: &ResolverContext<'_>,
+    arguments: &[String],
+) -> async_graphql::Result<HashMap<String, Option<String>>> {
+    let mut values = HashMap::new();
+    for name in arguments {
+        let value = match ctx.args.get(name) {
+            Some(accessor) if !accessor.is_null() => Some(accessor.string()?.to_string()),
+            _ => None,
+        };
+        values.insert(name.clone(), value);
+    }
+    Ok(values)
+}
+
+/// Polls `path` every `interval` and yields the response whenever it differs
+/// from the previous one outside of `ignore_paths`. The first successful
+/// response is always yielded; failed polls are logged and retried.
+pub fn poll_changes(
+    client: RestClient,
+    path: String,
+    interval: Duration,
+    ignore_paths: Vec<String>,
+) -> impl Stream<Item = serde_json::Value> + Send + 'static {
+    let ticker = tokio::time::interval(interval);
+    stream::unfold(
+        (ticker, None::<serde_json::Value>),
+        move |(mut ticker, last)| {
+            let client = client.clone();
+            let path = path.clone();
+            let ignore_paths = ignore_paths.clone();
+            async move {
+                let mut last = last;
+                loop {
+                    ticker.tick().await;
+                    match client.get(&path).await {
+                        Ok(value) => {
+                            let changed = match &last {
+                                Some(previous) => !json_diff(previous, &value, &ignore_paths).is_empty(),
+                                None => true,
+                            };
+                            if changed {
+                                last = Some(value.clone());
+                                return Some((value, (ticker, last)));
+                            }
+                            debug!(api = %client.name(), path = %path, "Poll returned no changes");
+                        }
+                        Err(e) => {
+                            warn!(api = %client.name(), path = %path, error = %e, "Subscription poll failed");
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Yields events from `receiver` whose top-level fields match every filter.
+/// Subscribers that fall behind skip the missed events.
+pub fn webhook_events(
+    receiver: broadcast::Receiver<serde_json::Value>,
+    filters: Vec<(String, String)>,
+) -> impl Stream<Item = serde_json::Value> + Send + 'static {
+    stream::unfold(receiver, move |mut receiver| {
+        let filters = filters.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if matches_filters(&event, &filters) => return Some((event, receiver)),
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Webhook subscriber lagged behind");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        }
+    })
+}
+
+fn matches_filters(event: &serde_json::Value, filters: &[(String, String)]) -> bool {
+    filters.iter().all(|(name, expected)| match event.get(name) {
+        Some(serde_json::Value::String(s)) => s == expected,
+        Some(other) => serde_json::from_str::<serde_json::Value>(expected).is_ok_and(|v| v == *other),
+        None => false,
+    })
+}
+
+/// JSON pointers of every leaf that differs between `old` and `new`, skipping
+/// anything at or below one of `ignore_paths`.
+pub fn json_diff(old: &serde_json::Value, new: &serde_json::Value, ignore_paths: &[String]) -> Vec<String> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), old, new, ignore_paths, &mut changes);
+    changes
+}
+
+fn diff_at(
+    pointer: String,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    ignore_paths: &[String],
+    changes: &mut Vec<String>,
+) {
+    if ignore_paths.contains(&pointer) {
+        return;
+    }
+
+    match (old, new) {
+        (serde_json::Value::Object(a), serde_json::Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys().filter(|k| !a.contains_key(*k))).collect();
+            keys.sort();
+            for key in keys {
+                let child = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) => diff_at(child, x, y, ignore_paths, changes),
+                    _ => {
+                        if !ignore_paths.contains(&child) {
+                            changes.push(child);
+                        }
+                    }
+                }
+            }
+        }
+        (serde_json::Value::Array(a), serde_json::Value::Array(b)) => {
+            for index in 0..a.len().max(b.len()) {
+                let child = format!("{}/{}", pointer, index);
+                match (a.get(index), b.get(index)) {
+                    (Some(x), Some(y)) => diff_at(child, x, y, ignore_paths, changes),
+                    _ => {
+                        if !ignore_paths.contains(&child) {
+                            changes.push(child);
+                        }
+                    }
+                }
+            }
+        }
+        (a, b) if a != b => changes.push(pointer),
+        _ => {}
+    }
+}
+
+/// Wraps each JSON value as a `JSON` scalar field result.
+fn into_field_values(
+    values: impl Stream<Item = serde_json::Value> + Send + 'static,
+) -> impl Stream<Item = async_graphql::Result<Value>> + Send + 'static {
+    values.map(|json| Ok(Value::from_json(json).unwrap_or(Value::Null)))
+}
@@ -0,0 +1,323 @@
+use crate::cache::{CacheManager, CachePolicy};
+use crate::config::Settings;
+use crate::config::settings::{FieldType, RelationshipConfig, TypeConfig};
+use crate::graphql::cache_control::CallerScoped;
+use crate::graphql::incremental::memoized;
+use crate::graphql::planner::{UpstreamCalls, upstream_call};
+use crate::graphql::queries::{fetch_cached, map_result, missing_placeholder, upstream_error};
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::schema::JSON_SCALAR;
+use crate::graphql::sparse_fields::SparseFields;
+use crate::graphql::subscriptions::argument_values;
+use crate::rest::adapter::{JsonPath, ResultMapping};
+use crate::rest::batch::{BatchLoader, scalar_string};
+use crate::rest::{RestClient, render_path};
+use crate::services::Services;
+use crate::utils::{Result, RustQLError};
+use async_graphql::Value;
+use async_graphql::dynamic::{Field, FieldFuture, FieldValue, InputValue, Object, TypeRef};
+use std::sync::Arc;
+
+/// The type a `returns` entry names, `Type` or `[Type]`; JSON when unset.
+pub fn return_type(returns: Option<&str>) -> TypeRef {
+    match returns {
+        Some(returns) => match returns.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            Some(name) => TypeRef::named_list(name),
+            None => TypeRef::named(returns),
+        },
+        None => TypeRef::named(JSON_SCALAR),
+    }
+}
+
+/// Builds one object type per `[[types]]` entry.
+pub fn object_types(settings: &Settings, services: &Services) -> Result<Vec<Object>> {
+    let policy = CachePolicy::from_config(&settings.cache);
+    settings
+        .types
+        .iter()
+        .map(|ty| object_type(ty, settings, services, policy))
+        .collect()
+}
+
+fn object_type(
+    config: &TypeConfig,
+    settings: &Settings,
+    services: &Services,
+    policy: CachePolicy,
+) -> Result<Object> {
+    let mut object = Object::new(config.name.as_str());
+    if let Some(description) = &config.description {
+        object = object.description(description.as_str());
+    }
+
+    let mut fields: Vec<_> = config.fields.iter().collect();
+    fields.sort_by_key(|(name, _)| name.as_str());
+    for (name, ty) in fields {
+        object = object.field(scalar_field(name, *ty));
+    }
+
+    for relationship in &config.relationships {
+        let field = format!("{}.{}", config.name, relationship.field);
+        let invalid = |what: &str, e: String| {
+            RustQLError::Config(format!(
+                "Invalid {} for relationship '{}': {}",
+                what, field, e
+            ))
+        };
+        let client = services.upstream(&relationship.api).ok_or_else(|| {
+            RustQLError::Config(format!(
+                "Relationship '{}' calls unknown API '{}'",
+                field, relationship.api
+            ))
+        })?;
+        let not_found_as_null = settings
+            .apis
+            .rest
+            .iter()
+            .any(|api| api.name == relationship.api && api.not_found_as_null);
+        let mapping = relationship
+            .result
+            .as_ref()
+            .map(ResultMapping::from_config)
+            .transpose()
+            .map_err(|e| invalid("result mapping", e))?
+            .map(Arc::new);
+        // Batched items are matched by their key, so it is always requested
+        let batch_key = relationship
+            .batch
+            .as_ref()
+            .and_then(|batch| batch.key.split(['.', '[']).next());
+        let sparse = SparseFields::for_field(
+            settings,
+            &relationship.api,
+            relationship.returns.as_deref(),
+            mapping.as_deref(),
+            batch_key.as_slice(),
+        );
+
+        let lookup = match &relationship.batch {
+            Some(batch) => {
+                let (prefix, parent, suffix) =
+                    split_parent(&relationship.path).map_err(|e| invalid("path", e))?;
+                let loader = BatchLoader::from_config(batch, mapping.clone())
+                    .map_err(|e| invalid("batch key", e))?;
+                Lookup::Batched {
+                    loader: Arc::new(loader),
+                    prefix,
+                    parent,
+                    suffix,
+                }
+            }
+            None => Lookup::Cached {
+                cache: services.cache.clone(),
+                policy,
+            },
+        };
+        object = object.field(relationship_field(
+            relationship,
+            client.clone(),
+            Arc::new(lookup),
+            not_found_as_null,
+            mapping,
+            sparse.map(Arc::new),
+        ));
+    }
+
+    Ok(object)
+}
+
+/// The GraphQL scalar a field type names.
+pub fn scalar_type(ty: FieldType) -> &'static str {
+    match ty {
+        FieldType::Id => TypeRef::ID,
+        FieldType::String => TypeRef::STRING,
+        FieldType::Int => TypeRef::INT,
+        FieldType::Float => TypeRef::FLOAT,
+        FieldType::Boolean => TypeRef::BOOLEAN,
+        FieldType::Json => JSON_SCALAR,
+    }
+}
+
+/// A field reading `name` from the parent payload. Numbers are accepted as
+/// IDs and strings, and booleans as strings.
+fn scalar_field(name: &str, ty: FieldType) -> Field {
+    let key = name.to_string();
+    Field::new(name, TypeRef::named(scalar_type(ty)), move |ctx| {
+        let key = key.clone();
+        FieldFuture::new(async move {
+            let value = match ctx.parent_value.try_to_value()? {
+                Value::Object(object) => object.get(key.as_str()).cloned(),
+                _ => None,
+            };
+            let value = match (ty, value) {
+                (FieldType::Id | FieldType::String, Some(Value::Number(n))) => {
+                    Some(Value::String(n.to_string()))
+                }
+                (FieldType::String, Some(Value::Boolean(b))) => Some(Value::String(b.to_string())),
+                (_, value) => value,
+            };
+            Ok(value.filter(|v| *v != Value::Null).map(FieldValue::value))
+        })
+    })
+}
+
+/// How a relationship fetches its value.
+enum Lookup {
+    /// One GET through the cache per parent
+    Cached {
+        cache: Arc<CacheManager>,
+        policy: CachePolicy,
+    },
+    /// Through the batch loader, with the path split around its parent
+    /// placeholder
+    Batched {
+        loader: Arc<BatchLoader>,
+        prefix: String,
+        parent: JsonPath,
+        suffix: String,
+    },
+}
+
+/// Splits a path template around its single `{parent.path}` placeholder.
+fn split_parent(template: &str) -> std::result::Result<(String, JsonPath, String), String> {
+    let start = template
+        .find("{parent.")
+        .ok_or_else(|| "no {parent.*} placeholder".to_string())?;
+    let end = template[start..]
+        .find('}')
+        .map(|end| start + end)
+        .ok_or_else(|| "unterminated placeholder".to_string())?;
+    let parent = JsonPath::parse(&template[start + "{parent.".len()..end])?;
+    Ok((
+        template[..start].to_string(),
+        parent,
+        template[end + 1..].to_string(),
+    ))
+}
+
+/// A field resolved against another API from the parent object. Parents
+/// lacking a value the path needs resolve to null without a request. Batched
+/// relationships bypass the cache and return every item matching the parent,
+/// or the first one for a single-valued field. Sparse fields are appended to
+/// the path, after the parent values of a batch.
+fn relationship_field(
+    config: &RelationshipConfig,
+    client: RestClient,
+    lookup: Arc<Lookup>,
+    not_found_as_null: bool,
+    mapping: Option<Arc<ResultMapping>>,
+    sparse: Option<Arc<SparseFields>>,
+) -> Field {
+    let arguments = config.arguments.clone();
+    let template = config.path.clone();
+    let list = config
+        .returns
+        .as_deref()
+        .is_none_or(|returns| returns.starts_with('['));
+    let mut field = Field::new(
+        config.field.as_str(),
+        return_type(config.returns.as_deref()),
+        move |ctx| {
+            let client = match ctx.data_opt::<ResolverContext>() {
+                Some(context) => client.for_caller(context),
+                None => client.clone(),
+            };
+            let lookup = lookup.clone();
+            let mapping = mapping.clone();
+            let sparse = sparse.clone();
+            let template = template.clone();
+            let values = argument_values(&ctx, &arguments);
+            FieldFuture::new(async move {
+                let values = values?;
+                let parent = ctx.parent_value.try_to_value()?.clone().into_json()?;
+                let argument = |name: &str| values.get(name).cloned().flatten();
+
+                let (loader, prefix, key, suffix) = match &*lookup {
+                    Lookup::Cached { cache, policy } => {
+                        let path = match render_path(&template, |name| {
+                            match name.strip_prefix("parent.") {
+                                Some(path) => JsonPath::parse(path)
+                                    .ok()
+                                    .and_then(|path| path.get(&parent).and_then(scalar_string)),
+                                None => argument(name),
+                            }
+                        }) {
+                            Ok(path) => path,
+                            Err(name) if name.starts_with("parent.") => return Ok(None),
+                            Err(name) => return Err(missing_placeholder(&ctx, &name)),
+                        };
+                        let path = match &sparse {
+                            Some(sparse) => sparse.apply(&ctx, "", path),
+                            None => path,
+                        };
+
+                        let fetched =
+                            fetch_cached(&ctx, &client, cache, *policy, path, not_found_as_null)
+                                .await?;
+                        let Some(json) = fetched else {
+                            return Ok(None);
+                        };
+                        let json = map_result(&ctx, client.name(), mapping.as_deref(), json)?;
+                        return Ok(Some(FieldValue::value(Value::from_json(json)?)));
+                    }
+                    Lookup::Batched {
+                        loader,
+                        prefix,
+                        parent: key,
+                        suffix,
+                    } => (loader, prefix, key, suffix),
+                };
+
+                let Some(key) = key.get(&parent).and_then(scalar_string) else {
+                    return Ok(None);
+                };
+                if client.vary().is_some() {
+                    if let Some(scoped) = ctx.data_opt::<CallerScoped>() {
+                        scoped.mark();
+                    }
+                }
+                let prefix = render_path(prefix, argument)
+                    .map_err(|name| missing_placeholder(&ctx, &name))?;
+                let mut suffix = render_path(suffix, argument)
+                    .map_err(|name| missing_placeholder(&ctx, &name))?;
+                if let Some(sparse) = &sparse {
+                    suffix = sparse.apply(&ctx, &prefix, suffix);
+                }
+                let permits = ctx.data_opt::<UpstreamCalls>().map(UpstreamCalls::permits);
+                let call = format!("{}{}{}", prefix, key, suffix);
+                let load = loader.load(client.clone(), permits, prefix, suffix, key);
+                let load = upstream_call(&ctx, &call, true, load);
+                let items = match memoized(&ctx, client.cache_key(&call), load).await {
+                    Ok(items) => items,
+                    Err(e)
+                        if not_found_as_null
+                            && matches!(*e, RustQLError::RestApi { status: 404, .. }) =>
+                    {
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(upstream_error(&ctx, client.name(), &e)),
+                };
+
+                let json = match list {
+                    true => serde_json::Value::Array(items),
+                    false => match items.into_iter().next() {
+                        Some(item) => item,
+                        None => return Ok(None),
+                    },
+                };
+                Ok(Some(FieldValue::value(Value::from_json(json)?)))
+            })
+        },
+    );
+
+    for argument in &config.arguments {
+        field = field.argument(InputValue::new(
+            argument.as_str(),
+            TypeRef::named_nn(TypeRef::ID),
+        ));
+    }
+    match &config.description {
+        Some(description) => field.description(description.as_str()),
+        None => field,
+    }
+}
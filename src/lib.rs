@@ -1,3 +1,4 @@
+pub mod auth;
 pub mod cache;
 pub mod config;
 pub mod graphql;
@@ -5,10 +6,13 @@ pub mod metrics;
 pub mod rate_limit;
 pub mod rest;
 pub mod server;
+pub mod services;
 pub mod utils;
 
 pub use config::Settings;
+use config::settings::ServerConfig;
 pub use server::Server;
+pub use services::Services;
 pub use utils::{Result, RustQLError};
 
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
@@ -26,14 +30,35 @@ pub fn init_tracing(log_level: &str) -> Result<()> {
     Ok(())
 }
 
-pub async fn create_app() -> Result<Server> {
+pub fn load_settings() -> Result<Settings> {
     // Load configuration
     let settings = Settings::load()
         .map_err(|e| RustQLError::Config(format!("Failed to load configuration: {}", e)))?;
 
     // Validate configuration
-    settings.validate().map_err(|e| RustQLError::Config(e))?;
+    settings.validate().map_err(RustQLError::Config)?;
+
+    Ok(settings)
+}
+
+/// Builds the multi-threaded Tokio runtime, sized from `ServerConfig::workers`
+/// when set and from the number of CPUs otherwise.
+pub fn build_runtime(config: &ServerConfig) -> Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().thread_name("rustql-worker");
+
+    if let Some(workers) = config.workers {
+        builder.worker_threads(workers);
+    }
+
+    Ok(builder.build()?)
+}
+
+pub async fn create_app() -> Result<Server> {
+    create_app_with_settings(load_settings()?).await
+}
 
+pub async fn create_app_with_settings(settings: Settings) -> Result<Server> {
     // Initialize tracing
     init_tracing(&settings.monitoring.log_level)?;
 
@@ -1,14 +1,21 @@
-use rustql::{Result, create_app};
+use rustql::{Result, Settings, build_runtime, create_app_with_settings, load_settings};
 use tracing::{error, info};
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
+    // Settings are needed before the runtime exists to size its worker pool
+    let settings = load_settings()?;
+    let runtime = build_runtime(&settings.server)?;
+
+    runtime.block_on(async_main(settings))
+}
+
+async fn async_main(settings: Settings) -> Result<()> {
     // Handle graceful shutdown
     let shutdown = tokio::signal::ctrl_c();
 
     // Create and start the application
     let app_result = tokio::select! {
-        result = run_app() => result,
+        result = run_app(settings) => result,
         _ = shutdown => {
             info!("Received shutdown signal, gracefully shutting down...");
             Ok(())
@@ -24,9 +31,9 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run_app() -> Result<()> {
+async fn run_app(settings: Settings) -> Result<()> {
     // Create application
-    let server = create_app().await?;
+    let server = create_app_with_settings(settings).await?;
 
     // Start server
     server.start().await?;
@@ -1,2 +1,33 @@
-// Placeholder for Day 3 implementation
-pub struct GovernorRateLimiter;
+use ::governor::clock::{Clock, DefaultClock};
+use ::governor::{DefaultKeyedRateLimiter, Quota};
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// GCRA buckets sharing one quota, one bucket per key.
+pub struct GovernorRateLimiter {
+    limiter: DefaultKeyedRateLimiter<String>,
+}
+
+impl GovernorRateLimiter {
+    /// A zero `burst_size` allows bursts of a single request.
+    pub fn new(requests_per_minute: NonZeroU32, burst_size: u32) -> Self {
+        let burst = NonZeroU32::new(burst_size).unwrap_or(NonZeroU32::MIN);
+        let quota = Quota::per_minute(requests_per_minute).allow_burst(burst);
+        Self {
+            limiter: DefaultKeyedRateLimiter::keyed(quota),
+        }
+    }
+
+    /// Takes one request from the key's bucket, or returns how long until the
+    /// next one is allowed.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.limiter
+            .check_key(&key.to_string())
+            .map_err(|not_until| not_until.wait_time_from(DefaultClock::default().now()))
+    }
+
+    /// Drops buckets that have refilled, so idle keys do not accumulate.
+    pub fn retain_recent(&self) {
+        self.limiter.retain_recent();
+    }
+}
@@ -1,14 +1,89 @@
 pub mod governor;
 
-// Placeholder for Day 3 implementation
-pub struct RateLimiter;
+use crate::config::settings::{KeyRateLimit, RateLimitConfig};
+use dashmap::DashMap;
+use self::governor::GovernorRateLimiter;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Rejection for callers that ran out of requests.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl warp::reject::Reject for RateLimited {}
+
+/// Per-caller request quotas. Callers share the `rate_limiting` quota unless
+/// their API key overrides it, in which case they get a bucket in a limiter
+/// for that quota.
+#[derive(Clone)]
+pub struct RateLimiter {
+    default: Arc<GovernorRateLimiter>,
+    overrides: Arc<DashMap<KeyRateLimit, Arc<GovernorRateLimiter>>>,
+    per_ip: bool,
+}
 
 impl RateLimiter {
-    pub fn new() -> Self {
-        Self
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        let requests_per_minute =
+            NonZeroU32::new(config.requests_per_minute).unwrap_or(NonZeroU32::MIN);
+        Self {
+            default: Arc::new(GovernorRateLimiter::new(
+                requests_per_minute,
+                config.burst_size,
+            )),
+            overrides: Arc::new(DashMap::new()),
+            per_ip: config.enable_per_ip,
+        }
+    }
+
+    /// Counts a request against `key`, a `ResolverContext::rate_limit_key`.
+    /// Without `enable_per_ip`, all callers identified only by their address
+    /// share one bucket.
+    pub fn check(&self, key: &str, quota: Option<KeyRateLimit>) -> Result<(), RateLimited> {
+        let key = if !self.per_ip && key.starts_with("ip:") {
+            "ip"
+        } else {
+            key
+        };
+
+        let limiter = match quota {
+            Some(quota) => self
+                .overrides
+                .entry(quota)
+                .or_insert_with(|| {
+                    let requests_per_minute =
+                        NonZeroU32::new(quota.requests_per_minute).unwrap_or(NonZeroU32::MIN);
+                    Arc::new(GovernorRateLimiter::new(
+                        requests_per_minute,
+                        quota.burst_size,
+                    ))
+                })
+                .clone(),
+            None => self.default.clone(),
+        };
+
+        limiter
+            .check(key)
+            .map_err(|retry_after| RateLimited { retry_after })
     }
 
-    pub async fn check_rate_limit(&self, _key: &str) -> bool {
-        true // Placeholder - always allow
+    /// Periodically forgets buckets of callers that have gone idle.
+    pub fn spawn_cleanup_task(&self) {
+        let limiter = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                ticker.tick().await;
+                limiter.default.retain_recent();
+                for entry in limiter.overrides.iter() {
+                    entry.value().retain_recent();
+                }
+            }
+        });
     }
 }
@@ -1,8 +1,339 @@
-// Placeholder for Day 2 implementation
-pub struct RestToGraphQLAdapter;
+use crate::config::settings::{Coercion, ResultMappingConfig};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value};
 
-impl RestToGraphQLAdapter {
-    pub fn new() -> Self {
-        Self
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A path whose last segment is a key, addressing that key in each object
+/// the leading segments lead to.
+#[derive(Debug, Clone)]
+struct KeyPath {
+    parents: Vec<Segment>,
+    key: String,
+}
+
+/// A parsed `a.b[0].c` path into a JSON document.
+#[derive(Debug, Clone, Default)]
+pub struct JsonPath(Vec<Segment>);
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        parse_path(path).map(Self)
+    }
+
+    /// The value at the path, or null; keys applied to an array select
+    /// from each of its elements.
+    pub fn select(&self, value: Value) -> Value {
+        select(value, &self.0)
+    }
+
+    /// The value at the path, without descending into arrays.
+    pub fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.get(index),
+            })
+    }
+}
+
+/// A query's `result` mapping, turning upstream payloads into field values.
+#[derive(Debug, Clone, Default)]
+pub struct ResultMapping {
+    select: JsonPath,
+    camel_case: bool,
+    aliases: Vec<(KeyPath, String)>,
+    coerce: Vec<(KeyPath, Coercion)>,
+    defaults: Vec<(KeyPath, Value)>,
+}
+
+impl ResultMapping {
+    pub fn from_config(config: &ResultMappingConfig) -> Result<Self, String> {
+        // Nested keys are renamed before their parents, since alias paths
+        // name upstream keys, while defaults fill parents before the keys
+        // nested in them
+        let mut aliases = key_paths(&config.aliases)?;
+        aliases.sort_by_key(|(path, _)| std::cmp::Reverse(path.parents.len()));
+        let mut defaults = key_paths(&config.defaults)?;
+        defaults.sort_by_key(|(path, _)| path.parents.len());
+
+        Ok(Self {
+            select: match &config.select {
+                Some(select) => JsonPath::parse(select)?,
+                None => JsonPath::default(),
+            },
+            camel_case: config.camel_case,
+            aliases,
+            coerce: key_paths(&config.coerce)?,
+            defaults,
+        })
+    }
+
+    pub fn apply(&self, value: Value) -> Result<Value, String> {
+        let mut value = self.select.select(value);
+
+        for (path, alias) in &self.aliases {
+            for_each_object(&mut value, &path.parents, &mut |object| {
+                if let Some(renamed) = object.remove(&path.key) {
+                    object.insert(alias.clone(), renamed);
+                }
+                Ok(())
+            })?;
+        }
+        if self.camel_case {
+            camel_case_keys(&mut value);
+        }
+        for (path, coercion) in &self.coerce {
+            for_each_object(
+                &mut value,
+                &path.parents,
+                &mut |object| match object.get_mut(&path.key) {
+                    Some(value) => {
+                        coerce(value, *coercion).map_err(|e| format!("field '{}': {}", path.key, e))
+                    }
+                    None => Ok(()),
+                },
+            )?;
+        }
+        for (path, default) in &self.defaults {
+            for_each_object(&mut value, &path.parents, &mut |object| {
+                let value = object.entry(path.key.clone()).or_insert(Value::Null);
+                if value.is_null() {
+                    *value = default.clone();
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(value)
+    }
+
+    /// The upstream key top-level `key` of a mapped item is read from, when
+    /// it is known exactly: the key aliased to it, or `key` itself when no
+    /// renaming produces it. A camelCased `firstName` may come from
+    /// `first_name` or `firstName`, so it has none.
+    pub fn upstream_key(&self, key: &str) -> Option<String> {
+        let renamed = |name: &str| match self.camel_case {
+            true => camel_case(name),
+            false => name.to_string(),
+        };
+        let mut aliases = self
+            .aliases
+            .iter()
+            .filter(|(path, _)| path.parents.is_empty());
+        if let Some((path, _)) = aliases.clone().find(|(_, alias)| renamed(alias) == key) {
+            return Some(path.key.clone());
+        }
+
+        let aliased_away = aliases.any(|(path, _)| path.key == key);
+        let unchanged = !self.camel_case
+            || (camel_case(key) == key && !key.chars().any(|c| c.is_ascii_uppercase()));
+        (unchanged && !aliased_away).then(|| key.to_string())
+    }
+}
+
+/// Parses `a.b[0].c`; a leading `$` or `$.` (as in JSONPath) is ignored.
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let path = path.strip_prefix('.').unwrap_or(path);
+    if path.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+        if key.is_empty() && indexes.is_empty() {
+            return Err(format!("empty segment in path '{}'", path));
+        }
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        }
+        while !indexes.is_empty() {
+            let index = indexes
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once(']'))
+                .and_then(|(index, rest)| Some((index.parse().ok()?, rest)));
+            let Some((index, rest)) = index else {
+                return Err(format!("invalid index in path '{}'", path));
+            };
+            segments.push(Segment::Index(index));
+            indexes = rest;
+        }
+    }
+    Ok(segments)
+}
+
+fn key_paths<T: Clone>(
+    entries: &std::collections::HashMap<String, T>,
+) -> Result<Vec<(KeyPath, T)>, String> {
+    entries
+        .iter()
+        .map(|(path, value)| {
+            let mut parents = parse_path(path)?;
+            match parents.pop() {
+                Some(Segment::Key(key)) => Ok((KeyPath { parents, key }, value.clone())),
+                _ => Err(format!("path '{}' must end with a key", path)),
+            }
+        })
+        .collect()
+}
+
+fn select(value: Value, path: &[Segment]) -> Value {
+    let Some((segment, rest)) = path.split_first() else {
+        return value;
+    };
+    match (segment, value) {
+        (Segment::Key(key), Value::Object(mut object)) => {
+            select(object.remove(key).unwrap_or(Value::Null), rest)
+        }
+        (Segment::Key(_), Value::Array(items)) => {
+            Value::Array(items.into_iter().map(|item| select(item, path)).collect())
+        }
+        (Segment::Index(index), Value::Array(mut items)) if *index < items.len() => {
+            select(items.swap_remove(*index), rest)
+        }
+        _ => Value::Null,
+    }
+}
+
+type ObjectVisitor<'a> = dyn FnMut(&mut Map<String, Value>) -> Result<(), String> + 'a;
+
+/// Calls `f` with every object `path` leads to, descending into array
+/// elements along the way and at the end.
+fn for_each_object(
+    value: &mut Value,
+    path: &[Segment],
+    f: &mut ObjectVisitor<'_>,
+) -> Result<(), String> {
+    match (path.split_first(), value) {
+        (None, Value::Object(object)) => f(object),
+        (Some((Segment::Key(key), rest)), Value::Object(object)) => match object.get_mut(key) {
+            Some(value) => for_each_object(value, rest, f),
+            None => Ok(()),
+        },
+        (Some((Segment::Index(index), rest)), Value::Array(items)) => match items.get_mut(*index) {
+            Some(value) => for_each_object(value, rest, f),
+            None => Ok(()),
+        },
+        (_, Value::Array(items)) => items
+            .iter_mut()
+            .try_for_each(|item| for_each_object(item, path, f)),
+        _ => Ok(()),
     }
 }
+
+fn camel_case_keys(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            *object = std::mem::take(object)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    camel_case_keys(&mut value);
+                    (camel_case(&key), value)
+                })
+                .collect();
+        }
+        Value::Array(items) => items.iter_mut().for_each(camel_case_keys),
+        _ => {}
+    }
+}
+
+/// `user_name` → `userName`; leading underscores are kept.
+fn camel_case(key: &str) -> String {
+    let name = key.trim_start_matches('_');
+    let mut result = key[..key.len() - name.len()].to_string();
+    for (i, word) in name.split('_').filter(|word| !word.is_empty()).enumerate() {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => {
+                result.extend(first.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+            _ => result.push_str(word),
+        }
+    }
+    result
+}
+
+/// Converts `value` (or each element of an array) in place; null is left
+/// as-is.
+fn coerce(value: &mut Value, coercion: Coercion) -> Result<(), String> {
+    if let Value::Array(items) = value {
+        return items.iter_mut().try_for_each(|item| coerce(item, coercion));
+    }
+    if value.is_null() {
+        return Ok(());
+    }
+
+    let coerced = match (coercion, &*value) {
+        (Coercion::Int, Value::Number(n)) => n
+            .as_i64()
+            .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+            .map(Value::from),
+        (Coercion::Int, Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+        (Coercion::Float, Value::Number(n)) => n.as_f64().map(Value::from),
+        (Coercion::Float, Value::String(s)) => s.trim().parse::<f64>().ok().map(Value::from),
+        (Coercion::Boolean, Value::Bool(b)) => Some(Value::Bool(*b)),
+        (Coercion::Boolean, Value::String(s)) => match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => Some(Value::Bool(true)),
+            "false" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        (Coercion::Boolean, Value::Number(n)) => match n.as_i64() {
+            Some(0) => Some(Value::Bool(false)),
+            Some(1) => Some(Value::Bool(true)),
+            _ => None,
+        },
+        (Coercion::String, Value::String(_)) => Some(value.clone()),
+        (Coercion::String, Value::Number(_) | Value::Bool(_)) => {
+            Some(Value::String(value.to_string()))
+        }
+        (Coercion::DateTime, Value::Number(n)) => n.as_i64().and_then(from_timestamp),
+        (Coercion::DateTime, Value::String(s)) => parse_datetime(s.trim()),
+        _ => None,
+    };
+
+    match coerced {
+        Some(coerced) => {
+            *value = coerced;
+            Ok(())
+        }
+        None => Err(format!(
+            "cannot convert {} to {}",
+            value,
+            serde_json::to_value(coercion).unwrap_or_default()
+        )),
+    }
+}
+
+fn from_timestamp(seconds: i64) -> Option<Value> {
+    DateTime::from_timestamp(seconds, 0).map(rfc3339)
+}
+
+fn parse_datetime(s: &str) -> Option<Value> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(rfc3339(datetime.with_timezone(&Utc)));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(rfc3339(datetime.and_utc()));
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return date
+            .and_hms_opt(0, 0, 0)
+            .map(|datetime| rfc3339(datetime.and_utc()));
+    }
+    s.parse().ok().and_then(from_timestamp)
+}
+
+fn rfc3339(datetime: DateTime<Utc>) -> Value {
+    Value::String(datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
@@ -0,0 +1,313 @@
+use super::{PATH_SEGMENT, expand_env};
+use crate::config::settings::{OAuth2Config, RestApiConfig, UpstreamAuthConfig};
+use crate::utils::{Result, RustQLError, sha256_hex};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use percent_encoding::utf8_percent_encode;
+use reqwest::StatusCode;
+use reqwest::header::{AUTHORIZATION, HeaderName, HeaderValue};
+use ring::hmac;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Credentials a `RestClient` adds to each outbound request.
+pub struct UpstreamAuth {
+    api: String,
+    strategy: Strategy,
+}
+
+enum Strategy {
+    /// A fixed `Authorization` value (static bearer or basic)
+    Static(HeaderValue),
+    OAuth2(OAuth2),
+    Hmac(Hmac),
+}
+
+impl fmt::Debug for UpstreamAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strategy = match &self.strategy {
+            Strategy::Static(_) => "static",
+            Strategy::OAuth2(_) => "oauth2",
+            Strategy::Hmac(_) => "hmac",
+        };
+        f.debug_struct("UpstreamAuth")
+            .field("api", &self.api)
+            .field("strategy", &strategy)
+            .finish()
+    }
+}
+
+impl UpstreamAuth {
+    pub fn from_config(config: &RestApiConfig) -> Result<Option<Self>> {
+        let Some(auth) = &config.auth else {
+            return Ok(None);
+        };
+        let invalid = |e: &dyn fmt::Display| {
+            RustQLError::Config(format!("Invalid auth for API '{}': {}", config.name, e))
+        };
+
+        let strategy = match auth {
+            UpstreamAuthConfig::Bearer { token } => {
+                let value = format!("Bearer {}", expand_env(token));
+                Strategy::Static(HeaderValue::from_str(&value).map_err(|e| invalid(&e))?)
+            }
+            UpstreamAuthConfig::Basic { username, password } => {
+                let credentials = format!("{}:{}", expand_env(username), expand_env(password));
+                let value = format!("Basic {}", STANDARD.encode(credentials));
+                Strategy::Static(HeaderValue::from_str(&value).map_err(|e| invalid(&e))?)
+            }
+            UpstreamAuthConfig::OAuth2(oauth2) => Strategy::OAuth2(OAuth2 {
+                config: OAuth2Config {
+                    client_id: expand_env(&oauth2.client_id),
+                    client_secret: expand_env(&oauth2.client_secret),
+                    ..oauth2.clone()
+                },
+                http: reqwest::Client::builder().timeout(TOKEN_TIMEOUT).build()?,
+                token: Mutex::new(None),
+            }),
+            UpstreamAuthConfig::Hmac(config) => {
+                let date_header = HeaderName::from_bytes(config.date_header.as_bytes())
+                    .map_err(|e| invalid(&e))?;
+                let mut signed_headers = config
+                    .signed_headers
+                    .iter()
+                    .map(|name| HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e)))
+                    .collect::<Result<Vec<_>>>()?;
+                signed_headers.retain(|name| name != "host" && *name != date_header);
+                Strategy::Hmac(Hmac {
+                    key_id: expand_env(&config.key_id),
+                    key: hmac::Key::new(hmac::HMAC_SHA256, expand_env(&config.secret).as_bytes()),
+                    scheme: config.scheme.clone(),
+                    date_header,
+                    signed_headers,
+                })
+            }
+        };
+
+        Ok(Some(Self {
+            api: config.name.clone(),
+            strategy,
+        }))
+    }
+
+    /// Sets the request's `Authorization` header, fetching an OAuth2 token
+    /// first when the cached one is due for refresh.
+    pub async fn apply(&self, request: &mut reqwest::Request) -> Result<()> {
+        let value = match &self.strategy {
+            Strategy::Static(value) => value.clone(),
+            Strategy::OAuth2(oauth2) => oauth2.token(&self.api).await?,
+            Strategy::Hmac(hmac) => hmac.sign(request)?,
+        };
+        request.headers_mut().insert(AUTHORIZATION, value);
+        Ok(())
+    }
+
+    /// Drops a cached OAuth2 token the upstream rejected so the next request
+    /// fetches a new one.
+    pub async fn rejected(&self) {
+        if let Strategy::OAuth2(oauth2) = &self.strategy {
+            *oauth2.token.lock().await = None;
+        }
+    }
+}
+
+struct OAuth2 {
+    config: OAuth2Config,
+    http: reqwest::Client,
+    token: Mutex<Option<Token>>,
+}
+
+struct Token {
+    value: HeaderValue,
+    /// When to fetch a replacement; `None` when the token server gave no
+    /// lifetime, so the token is kept until an upstream rejects it
+    refresh_at: Option<Instant>,
+    expires_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl OAuth2 {
+    /// The cached token, or a new one once it is within `refresh_before`
+    /// seconds of expiring. A failed refresh keeps using the cached token
+    /// until it actually expires.
+    async fn token(&self, api: &str) -> Result<HeaderValue> {
+        let mut token = self.token.lock().await;
+        let now = Instant::now();
+        if let Some(token) = token
+            .as_ref()
+            .filter(|t| t.refresh_at.is_none_or(|at| now < at))
+        {
+            return Ok(token.value.clone());
+        }
+
+        match self.fetch(api).await {
+            Ok(fresh) => {
+                let value = fresh.value.clone();
+                *token = Some(fresh);
+                Ok(value)
+            }
+            Err(e) => match token
+                .as_ref()
+                .filter(|t| t.expires_at.is_none_or(|at| now < at))
+            {
+                Some(token) => {
+                    warn!(api = %api, error = %e, "Failed to refresh OAuth2 token, keeping current token");
+                    Ok(token.value.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn fetch(&self, api: &str) -> Result<Token> {
+        let config = &self.config;
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !config.scopes.is_empty() {
+            form.push(("scope", config.scopes.join(" ")));
+        }
+        if let Some(audience) = &config.audience {
+            form.push(("audience", audience.clone()));
+        }
+
+        let requested_at = Instant::now();
+        let response = self
+            .http
+            .post(&config.token_url)
+            .basic_auth(&config.client_id, Some(&config.client_secret))
+            .form(&form)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            warn!(api = %api, status = %status, body = %body, "OAuth2 token request failed");
+            return Err(RustQLError::RestApi {
+                api: api.to_string(),
+                message: format!("OAuth2 token request for API '{}' failed with {}", api, status),
+                status: StatusCode::BAD_GATEWAY.as_u16(),
+            });
+        }
+
+        let response: TokenResponse = response.json().await?;
+        let value =
+            HeaderValue::from_str(&format!("Bearer {}", response.access_token)).map_err(|e| {
+                RustQLError::RestApi {
+                    api: api.to_string(),
+                    message: format!("Invalid OAuth2 token for API '{}': {}", api, e),
+                    status: StatusCode::BAD_GATEWAY.as_u16(),
+                }
+            })?;
+        debug!(api = %api, expires_in = ?response.expires_in, "Fetched OAuth2 token");
+
+        // Short-lived tokens are refreshed halfway through their lifetime
+        // rather than on every request
+        let lifetime = response.expires_in.map(Duration::from_secs);
+        let refresh_in = lifetime.map(|lifetime| {
+            lifetime
+                .saturating_sub(Duration::from_secs(config.refresh_before))
+                .max(lifetime / 2)
+        });
+        Ok(Token {
+            value,
+            refresh_at: refresh_in.map(|refresh_in| requested_at + refresh_in),
+            expires_at: lifetime.map(|lifetime| requested_at + lifetime),
+        })
+    }
+}
+
+struct Hmac {
+    key_id: String,
+    key: hmac::Key,
+    scheme: String,
+    date_header: HeaderName,
+    signed_headers: Vec<HeaderName>,
+}
+
+impl Hmac {
+    /// Stamps the request with the signing time and returns its
+    /// `Authorization` value. The string to sign is
+    /// `<scheme>\n<timestamp>\n<hex sha256 of canonical request>`, where the
+    /// canonical request is the method, path, sorted query, signed headers,
+    /// their names and the hex sha256 of the body, one per line.
+    fn sign(&self, request: &mut reqwest::Request) -> Result<HeaderValue> {
+        let timestamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        request.headers_mut().insert(
+            self.date_header.clone(),
+            HeaderValue::from_str(&timestamp).expect("timestamp is a valid header value"),
+        );
+
+        let url = request.url();
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => String::new(),
+        };
+
+        let mut headers = vec![
+            ("host".to_string(), host),
+            (self.date_header.to_string(), timestamp.clone()),
+        ];
+        for name in &self.signed_headers {
+            if let Some(value) = request.headers().get(name) {
+                let value = String::from_utf8_lossy(value.as_bytes()).trim().to_string();
+                headers.push((name.to_string(), value));
+            }
+        }
+        headers.sort();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let mut query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(name, PATH_SEGMENT),
+                    utf8_percent_encode(value, PATH_SEGMENT)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .unwrap_or_default();
+        let canonical = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method(),
+            url.path(),
+            query,
+            headers
+                .iter()
+                .map(|(name, value)| format!("{}:{}\n", name, value))
+                .collect::<String>(),
+            signed_headers,
+            hex::encode(Sha256::digest(body))
+        );
+        let string_to_sign = format!("{}\n{}\n{}", self.scheme, timestamp, sha256_hex(&canonical));
+        let signature = hex::encode(hmac::sign(&self.key, string_to_sign.as_bytes()));
+
+        let value = format!(
+            "{} Credential={}, SignedHeaders={}, Signature={}",
+            self.scheme, self.key_id, signed_headers, signature
+        );
+        HeaderValue::from_str(&value).map_err(|e| RustQLError::Config(e.to_string()))
+    }
+}
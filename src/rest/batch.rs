@@ -0,0 +1,227 @@
+use super::adapter::{JsonPath, ResultMapping};
+use super::{PATH_SEGMENT, RestClient};
+use crate::config::settings::BatchConfig;
+use crate::utils::RustQLError;
+use futures_util::future::join_all;
+use percent_encoding::utf8_percent_encode;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Semaphore, oneshot};
+use tracing::debug;
+
+/// How long a batch collects values after its first one.
+const BATCH_DELAY: Duration = Duration::from_millis(1);
+
+/// The items matching one value, or the error of the batch it was part of.
+pub type BatchResult = std::result::Result<Vec<Value>, Arc<RustQLError>>;
+
+/// Coalesces the lookups of one relationship issued while a response
+/// resolves into GETs for many parent values at once. Lookups are grouped
+/// by the rendered path around the parent placeholder and by the caller
+/// headers the client propagates, so batches never mix callers.
+#[derive(Debug)]
+pub struct BatchLoader {
+    key: JsonPath,
+    separator: String,
+    max_size: usize,
+    mapping: Option<Arc<ResultMapping>>,
+    pending: Mutex<HashMap<String, Batch>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Batch {
+    id: u64,
+    client: RestClient,
+    /// Call limits of the operations with lookups in the batch
+    permits: Vec<Arc<Semaphore>>,
+    prefix: String,
+    suffix: String,
+    values: Vec<String>,
+    waiters: Vec<(String, oneshot::Sender<BatchResult>)>,
+}
+
+impl BatchLoader {
+    pub fn from_config(
+        config: &BatchConfig,
+        mapping: Option<Arc<ResultMapping>>,
+    ) -> std::result::Result<Self, String> {
+        Ok(Self {
+            key: JsonPath::parse(&config.key)?,
+            separator: config.separator.clone(),
+            max_size: config.max_size,
+            mapping,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// The items of `prefix{values}suffix` whose key equals `value`, once
+    /// the batch `value` joined has been fetched. The batch waits for one of
+    /// the `permits` of every operation with a lookup in it.
+    pub async fn load(
+        self: &Arc<Self>,
+        client: RestClient,
+        permits: Option<Arc<Semaphore>>,
+        prefix: String,
+        suffix: String,
+        value: String,
+    ) -> BatchResult {
+        let (sender, receiver) = oneshot::channel();
+        let group = client.cache_key(&format!("{}{{}}{}", prefix, suffix));
+
+        let full = {
+            let mut pending = self.pending.lock().expect("batch lock");
+            let batch = match pending.entry(group.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let loader = self.clone();
+                    let group = group.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(BATCH_DELAY).await;
+                        loader.dispatch(&group, id).await;
+                    });
+                    entry.insert(Batch {
+                        id,
+                        client,
+                        permits: vec![],
+                        prefix,
+                        suffix,
+                        values: vec![],
+                        waiters: vec![],
+                    })
+                }
+            };
+            if let Some(permits) = permits {
+                if !batch.permits.iter().any(|held| Arc::ptr_eq(held, &permits)) {
+                    batch.permits.push(permits);
+                }
+            }
+            if !batch.values.contains(&value) {
+                batch.values.push(value.clone());
+            }
+            batch.waiters.push((value, sender));
+
+            if batch.values.len() >= self.max_size {
+                pending.remove(&group)
+            } else {
+                None
+            }
+        };
+        if let Some(batch) = full {
+            tokio::spawn(self.clone().fetch(batch));
+        }
+
+        receiver.await.unwrap_or_else(|_| {
+            Err(Arc::new(RustQLError::Internal(
+                "Batched request was dropped".to_string(),
+            )))
+        })
+    }
+
+    /// Fetches batch `id` of `group` unless it already filled up.
+    async fn dispatch(self: Arc<Self>, group: &str, id: u64) {
+        let batch = {
+            let mut pending = self.pending.lock().expect("batch lock");
+            match pending.get(group) {
+                Some(batch) if batch.id == id => pending.remove(group),
+                _ => None,
+            }
+        };
+        if let Some(batch) = batch {
+            self.fetch(batch).await;
+        }
+    }
+
+    /// Fetches `batch` for its waiters, giving up as soon as none of them is
+    /// left, e.g. because their requests timed out, so abandoned lookups do
+    /// not keep calling the upstream or holding call permits.
+    async fn fetch(self: Arc<Self>, mut batch: Batch) {
+        let values: Vec<String> = batch
+            .values
+            .iter()
+            .map(|value| utf8_percent_encode(value, PATH_SEGMENT).to_string())
+            .collect();
+        let path = format!(
+            "{}{}{}",
+            batch.prefix,
+            values.join(&self.separator),
+            batch.suffix
+        );
+        let permits = std::mem::take(&mut batch.permits);
+        let request = async {
+            // Acquired in a fixed order so batches sharing operations cannot
+            // each hold a permit the other waits for
+            let mut permits = permits;
+            permits.sort_by_key(|permits| Arc::as_ptr(permits) as usize);
+            let mut held = Vec::with_capacity(permits.len());
+            for permits in permits {
+                held.extend(permits.acquire_owned().await.ok());
+            }
+            debug!(api = %batch.client.name(), path = %path, values = values.len(), "Fetching batch");
+            self.items(&batch.client, &path).await
+        };
+        let abandoned = join_all(batch.waiters.iter_mut().map(|(_, waiter)| waiter.closed()));
+        let result = tokio::select! {
+            result = request => result,
+            _ = abandoned => {
+                debug!(api = %batch.client.name(), path = %path, "Batch abandoned by its waiters");
+                return;
+            }
+        };
+
+        match result {
+            Ok(items) => {
+                let mut matches: HashMap<String, Vec<Value>> = HashMap::new();
+                for item in items {
+                    if let Some(key) = self.key.get(&item).and_then(scalar_string) {
+                        matches.entry(key).or_default().push(item);
+                    }
+                }
+                for (value, waiter) in batch.waiters {
+                    let items = matches.get(&value).cloned().unwrap_or_default();
+                    let _ = waiter.send(Ok(items));
+                }
+            }
+            Err(e) => {
+                let e = Arc::new(e);
+                for (_, waiter) in batch.waiters {
+                    let _ = waiter.send(Err(e.clone()));
+                }
+            }
+        }
+    }
+
+    /// The mapped items of a batch response, which must be a list.
+    async fn items(&self, client: &RestClient, path: &str) -> crate::utils::Result<Vec<Value>> {
+        let unexpected = |message: String| RustQLError::UnexpectedResponse {
+            api: client.name().to_string(),
+            message,
+        };
+
+        let mut json = client.get(path).await?;
+        if let Some(mapping) = &self.mapping {
+            json = mapping.apply(json).map_err(unexpected)?;
+        }
+        match json {
+            Value::Array(items) => Ok(items),
+            Value::Null => Ok(vec![]),
+            _ => Err(unexpected("expected a list of items".to_string())),
+        }
+    }
+}
+
+/// A string, number or boolean as the string it is matched by.
+pub fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
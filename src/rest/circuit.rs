@@ -0,0 +1,96 @@
+use crate::config::settings::CircuitBreakerConfig;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single trial request is in flight
+    HalfOpen,
+}
+
+/// Per-API circuit breaker. Opens after `failure_threshold` consecutive
+/// upstream failures, rejects calls while open and, after `reset_timeout`,
+/// lets one trial call decide whether to close again.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    enabled: bool,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            failure_threshold: config.failure_threshold.max(1),
+            reset_timeout: Duration::from_secs(config.reset_timeout),
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may be made now.
+    pub fn allow(&self) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let mut state = self.state.lock().expect("circuit breaker lock");
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } if Instant::now() >= until => {
+                *state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(
+            *self.state.lock().expect("circuit breaker lock"),
+            State::Open { .. }
+        )
+    }
+
+    /// Reopens a half-open circuit whose trial call ended without a result,
+    /// e.g. because the caller's future was dropped, so another trial is let
+    /// through after `reset_timeout` instead of never.
+    pub fn abandon_trial(&self) {
+        let mut state = self.state.lock().expect("circuit breaker lock");
+        if *state == State::HalfOpen {
+            *state = State::Open {
+                until: Instant::now() + self.reset_timeout,
+            };
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().expect("circuit breaker lock") = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self, api: &str) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().expect("circuit breaker lock");
+        let failures = match *state {
+            State::Closed { failures } => failures + 1,
+            State::Open { .. } | State::HalfOpen => self.failure_threshold,
+        };
+        *state = if failures >= self.failure_threshold {
+            warn!(api = %api, failures, "Opening circuit");
+            State::Open {
+                until: Instant::now() + self.reset_timeout,
+            }
+        } else {
+            State::Closed { failures }
+        };
+    }
+}
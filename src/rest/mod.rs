@@ -1,22 +1,422 @@
 pub mod adapter;
+pub mod auth;
+pub mod batch;
+pub mod circuit;
 pub mod client;
+pub mod pagination;
+pub mod propagation;
+pub mod write;
 
-// Placeholder for Day 2 implementation
+use crate::cache::Fetched;
+use crate::cache::http::{CacheDirectives, Validators};
+use crate::config::settings::{CircuitBreakerConfig, RestApiConfig, WriteMethod};
+use crate::graphql::resolvers::ResolverContext;
+use crate::utils::{Result, RustQLError};
+use auth::UpstreamAuth;
+use circuit::CircuitBreaker;
+use propagation::{HeaderRules, Propagated};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, LINK};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, instrument, warn};
+use write::{RequestBody, Written, http_method};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// RFC 3986 unreserved characters are left as-is in path segments.
+pub(crate) const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// JSON client for a single upstream REST API.
+#[derive(Debug, Clone)]
 pub struct RestClient {
+    name: String,
     base_url: String,
+    http: reqwest::Client,
+    /// Static headers of the API, set on each request before it is signed
+    headers: HeaderMap,
+    breaker: Arc<CircuitBreaker>,
+    propagation: Arc<HeaderRules>,
+    auth: Option<Arc<UpstreamAuth>>,
+    /// Headers propagated from the caller this client was bound to
+    caller: Propagated,
 }
 
 impl RestClient {
     pub fn new(base_url: String) -> Self {
-        Self { base_url }
+        Self {
+            name: base_url.clone(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            headers: HeaderMap::new(),
+            breaker: Arc::new(CircuitBreaker::new(&CircuitBreakerConfig::default())),
+            propagation: Arc::new(HeaderRules::default()),
+            auth: None,
+            caller: Propagated::default(),
+        }
+    }
+
+    /// Builds a client with the API's static headers (after `${VAR}` expansion),
+    /// timeout and upstream credentials applied to every request.
+    pub fn from_config(config: &RestApiConfig) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in config.headers.iter().flatten() {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| {
+                RustQLError::Config(format!("Invalid header '{}' for API '{}': {}", name, config.name, e))
+            })?;
+            let value = HeaderValue::from_str(&expand_env(value)).map_err(|e| {
+                RustQLError::Config(format!("Invalid value for header '{}' of API '{}': {}", name, config.name, e))
+            })?;
+            headers.insert(name, value);
+        }
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS)))
+            .build()?;
+
+        Ok(Self {
+            name: config.name.clone(),
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            http,
+            headers,
+            breaker: Arc::new(CircuitBreaker::new(&config.circuit_breaker)),
+            propagation: Arc::new(HeaderRules::from_config(config)?),
+            auth: UpstreamAuth::from_config(config)?.map(Arc::new),
+            caller: Propagated::default(),
+        })
+    }
+
+    /// A client sending the headers the API's `propagate` rules take from
+    /// the caller.
+    pub fn for_caller(&self, context: &ResolverContext) -> Self {
+        if self.propagation.is_empty() {
+            return self.clone();
+        }
+        Self {
+            caller: self.propagation.apply(context),
+            ..self.clone()
+        }
+    }
+
+    /// Digest of the caller-specific headers this client sends.
+    pub fn vary(&self) -> Option<&str> {
+        self.caller.vary.as_deref()
+    }
+
+    /// Cache key of a GET of `path`, separating callers whose propagated
+    /// headers differ.
+    pub fn cache_key(&self, path: &str) -> String {
+        match &self.caller.vary {
+            Some(vary) => format!("rest:{}:{}:{}", self.name, path, vary),
+            None => format!("rest:{}:{}", self.name, path),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// GETs `path` as JSON. Fails fast with `CircuitOpen` while the API's
+    /// circuit is open; 5xx and network failures count towards opening it.
+    #[instrument(skip(self), fields(api = %self.name))]
+    pub async fn get(&self, path: &str) -> Result<serde_json::Value> {
+        self.call(async {
+            let response = self.send_get(path, &Validators::default()).await?;
+            Ok(response.json().await?)
+        })
+        .await
+    }
+
+    /// GETs `path` as JSON along with the targets of its `Link` headers by
+    /// relation type, as paths under the API's base URL. Links leaving the
+    /// base URL are dropped.
+    #[instrument(skip(self), fields(api = %self.name))]
+    pub async fn get_with_links(
+        &self,
+        path: &str,
+    ) -> Result<(serde_json::Value, HashMap<String, String>)> {
+        self.call(async {
+            let response = self.send_get(path, &Validators::default()).await?;
+            let links = response
+                .headers()
+                .get_all(LINK)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(parse_link_header)
+                .filter_map(|(rel, target)| Some((rel, self.relative_path(&target)?)))
+                .collect();
+            Ok((response.json().await?, links))
+        })
+        .await
+    }
+
+    /// `target`, resolved against the base URL, as a path under it.
+    fn relative_path(&self, target: &str) -> Option<String> {
+        let base = format!("{}/", self.base_url);
+        let url = reqwest::Url::parse(&base).ok()?.join(target).ok()?;
+        url.as_str()
+            .strip_prefix(&base)
+            .map(|path| format!("/{}", path))
+    }
+
+    /// GETs `path` for the cache: sends the validators of the cached copy as
+    /// conditional headers and reports the response's `Cache-Control`
+    /// freshness, with a 304 confirming the cached copy.
+    #[instrument(skip(self, validators), fields(api = %self.name))]
+    pub async fn get_cacheable(&self, path: &str, validators: &Validators) -> Result<Fetched> {
+        self.call(async {
+            let response = self.send_get(path, validators).await?;
+            let directives = CacheDirectives::from_headers(response.headers());
+            let fresh_validators = Validators::from_headers(response.headers());
+
+            let value = if response.status() == StatusCode::NOT_MODIFIED {
+                debug!(path = %path, "Upstream response not modified");
+                None
+            } else {
+                let value: serde_json::Value = response.json().await?;
+                Some(value.to_string())
+            };
+
+            Ok(Fetched {
+                value,
+                ttl: directives.ttl(),
+                storable: directives.storable(),
+                allows_stale: directives.allows_stale(),
+                validators: fresh_validators.or(validators),
+            })
+        })
+        .await
+    }
+
+    /// Sends a write of `body` to `path` with `headers` added. Responses
+    /// below 500 are returned for the caller to interpret; 5xx and network
+    /// failures are errors and count towards opening the circuit. Writes are
+    /// never retried.
+    #[instrument(skip(self, body, headers), fields(api = %self.name))]
+    pub async fn write(
+        &self,
+        method: WriteMethod,
+        path: &str,
+        body: Option<RequestBody>,
+        headers: HeaderMap,
+    ) -> Result<Written> {
+        self.call(async {
+            let url = self.url(path);
+            let method = http_method(method);
+            debug!(url = %url, method = %method, "Write upstream");
+
+            let mut request = self
+                .http
+                .request(method.clone(), &url)
+                .headers(self.headers.clone())
+                .headers(self.caller.headers.clone())
+                .headers(headers);
+            if let Some(body) = body {
+                request = request
+                    .header(CONTENT_TYPE, body.content_type)
+                    .body(body.bytes);
+            }
+            let mut request = request.build()?;
+            if let Some(auth) = &self.auth {
+                auth.apply(&mut request).await?;
+            }
+
+            let response = self.http.execute(request).await?;
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED {
+                if let Some(auth) = &self.auth {
+                    auth.rejected().await;
+                }
+            }
+            let text = response.text().await?;
+            if status.is_server_error() {
+                warn!(
+                    url = %url,
+                    method = %method,
+                    status = %status,
+                    body = %text,
+                    "Upstream write failed"
+                );
+                return Err(status_error(&self.name, status.as_u16()));
+            }
+
+            let body = match text.trim() {
+                "" => serde_json::Value::Null,
+                trimmed => {
+                    serde_json::from_str(trimmed).unwrap_or(serde_json::Value::String(text.clone()))
+                }
+            };
+            Ok(Written {
+                status: status.as_u16(),
+                body,
+            })
+        })
+        .await
+    }
+
+    async fn call<T>(&self, request: impl Future<Output = Result<T>>) -> Result<T> {
+        if !self.breaker.allow() {
+            return Err(RustQLError::CircuitOpen(self.name.clone()));
+        }
+
+        let pending = PendingCall(&self.breaker);
+        let result = request.await;
+        std::mem::forget(pending);
+        match &result {
+            Err(e) if e.is_upstream_unavailable() => self.breaker.record_failure(&self.name),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+
+    async fn send_get(&self, path: &str, validators: &Validators) -> Result<reqwest::Response> {
+        let url = self.url(path);
+        debug!(url = %url, "GET upstream");
+
+        let mut request = self
+            .http
+            .get(&url)
+            .headers(self.headers.clone())
+            .headers(self.caller.headers.clone())
+            .headers(validators.conditional_headers())
+            .build()?;
+        if let Some(auth) = &self.auth {
+            auth.apply(&mut request).await?;
+        }
+
+        let response = self.http.execute(request).await?;
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            if let Some(auth) = &self.auth {
+                auth.rejected().await;
+            }
+        }
+        if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+            let body = response.text().await.unwrap_or_default();
+            debug!(url = %url, status = %status, body = %body, "Upstream GET failed");
+            return Err(status_error(&self.name, status.as_u16()));
+        }
+
+        Ok(response)
+    }
+}
+
+/// A call admitted by the circuit breaker; dropped only when the call is
+/// cancelled before recording its outcome.
+struct PendingCall<'a>(&'a CircuitBreaker);
+
+impl Drop for PendingCall<'_> {
+    fn drop(&mut self) {
+        self.0.abandon_trial();
+    }
+}
+
+/// The error for an upstream answering `status`. Clients only see the API
+/// name and status; the URL and response body are logged instead, as they
+/// may reveal internal addresses and data.
+pub(crate) fn status_error(api: &str, status: u16) -> RustQLError {
+    RustQLError::RestApi {
+        api: api.to_string(),
+        message: format!("API '{}' responded with status {}", api, status),
+        status,
     }
+}
 
-    pub async fn get(&self, path: &str) -> crate::utils::Result<serde_json::Value> {
-        // Placeholder implementation
-        Ok(serde_json::json!({
-            "message": "REST client placeholder",
-            "url": format!("{}/{}", self.base_url, path),
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        }))
+/// `(rel, target)` pairs of an RFC 8288 `Link` header value; a link with
+/// several relation types yields one pair per type.
+fn parse_link_header(value: &str) -> Vec<(String, String)> {
+    let mut links = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let target = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        let params_end = rest.find('<').unwrap_or(rest.len());
+        for param in rest[..params_end].split(';') {
+            let Some((name, value)) = param.split_once('=') else {
+                continue;
+            };
+            if name.trim().eq_ignore_ascii_case("rel") {
+                for rel in value.trim().trim_matches('"').split_whitespace() {
+                    links.push((rel.to_ascii_lowercase(), target.to_string()));
+                }
+            }
+        }
+        rest = &rest[params_end..];
     }
+    links
+}
+
+/// Replaces `${VAR}` with the value of the environment variable `VAR`, or an
+/// empty string when it is unset.
+pub fn expand_env(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        match rest[start + 2..].find('}') {
+            Some(end) => {
+                let var = &rest[start + 2..start + 2 + end];
+                result.push_str(&std::env::var(var).unwrap_or_default());
+                rest = &rest[start + 3 + end..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Fills `{name}` placeholders in a path template, percent-encoding each value.
+/// Fails with the name of the first placeholder `lookup` has no value for, as
+/// leaving its segment empty would address another resource.
+pub fn render_path(
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<String>,
+) -> std::result::Result<String, String> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        match rest[start + 1..].find('}') {
+            Some(end) => {
+                let name = &rest[start + 1..start + 1 + end];
+                let Some(value) = lookup(name) else {
+                    return Err(name.to_string());
+                };
+                result.extend(utf8_percent_encode(&value, PATH_SEGMENT));
+                rest = &rest[start + 2 + end..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
 }
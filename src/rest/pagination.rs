@@ -0,0 +1,463 @@
+use super::adapter::JsonPath;
+use super::{PATH_SEGMENT, RestClient};
+use crate::config::settings::{PaginationConfig, PaginationStyle};
+use crate::utils::{Result, RustQLError};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use percent_encoding::utf8_percent_encode;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use tracing::debug;
+
+/// Upstream pages followed past ones the `after` cursor skips entirely.
+const MAX_PAGE_HOPS: usize = 10;
+
+/// Relay pagination arguments of a connection field.
+#[derive(Debug, Clone, Default)]
+pub struct PageArgs {
+    pub first: Option<i64>,
+    pub after: Option<String>,
+    pub last: Option<i64>,
+    pub before: Option<String>,
+}
+
+/// A slice of a connection: the nodes with their cursors and the page info.
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    pub edges: Vec<(String, Value)>,
+    pub has_next_page: bool,
+    pub has_previous_page: bool,
+    pub total_count: Option<i64>,
+}
+
+/// Position of an item in an offset or page paginated list.
+#[derive(Serialize, Deserialize)]
+struct OffsetCursor {
+    offset: u64,
+}
+
+/// Position of an item within the upstream page reached through `page`
+/// (an upstream cursor or `Link` target; `None` for the first page).
+#[derive(Serialize, Deserialize)]
+struct PageCursor {
+    page: Option<String>,
+    index: usize,
+}
+
+/// One upstream response.
+struct UpstreamPage {
+    items: Vec<Value>,
+    total_count: Option<i64>,
+    next: Option<String>,
+    prev: Option<String>,
+}
+
+/// Translates Relay arguments into requests against a list endpoint.
+#[derive(Debug, Clone)]
+pub struct Paginator {
+    config: PaginationConfig,
+    items: Option<JsonPath>,
+    total_count: Option<JsonPath>,
+    next_cursor: Option<JsonPath>,
+    prev_cursor: Option<JsonPath>,
+}
+
+impl Paginator {
+    pub fn from_config(config: &PaginationConfig) -> std::result::Result<Self, String> {
+        let path = |path: &Option<String>| path.as_deref().map(JsonPath::parse).transpose();
+        Ok(Self {
+            config: config.clone(),
+            items: path(&config.items)?,
+            total_count: path(&config.total_count)?,
+            next_cursor: path(&config.next_cursor)?,
+            prev_cursor: path(&config.prev_cursor)?,
+        })
+    }
+
+    pub async fn fetch(&self, client: &RestClient, path: &str, args: &PageArgs) -> Result<Page> {
+        let size = |count: Option<i64>| match count {
+            Some(count) if count < 0 => Err(RustQLError::Validation(
+                "`first` and `last` cannot be negative".to_string(),
+            )),
+            Some(count) => Ok(Some((count as u64).min(self.config.max_page_size) as usize)),
+            None => Ok(None),
+        };
+        let first = size(args.first)?;
+        let last = size(args.last)?;
+
+        match self.config.style {
+            PaginationStyle::Offset | PaginationStyle::Page => {
+                self.fetch_positional(client, path, args, first, last).await
+            }
+            PaginationStyle::Cursor | PaginationStyle::Link => match (first, last) {
+                (None, Some(last)) => self.fetch_backward(client, path, args, last).await,
+                (first, _) => {
+                    let count = first.unwrap_or(self.config.default_page_size as usize);
+                    self.fetch_forward(client, path, args, count).await
+                }
+            },
+        }
+    }
+
+    /// Offset and page pagination, where cursors are item offsets. A slice
+    /// spanning several upstream pages fetches each of them; `last` without
+    /// `before` counts back from the upstream's total count.
+    async fn fetch_positional(
+        &self,
+        client: &RestClient,
+        path: &str,
+        args: &PageArgs,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Page> {
+        let after = args
+            .after
+            .as_deref()
+            .map(decode::<OffsetCursor>)
+            .transpose()?;
+        let before = args
+            .before
+            .as_deref()
+            .map(decode::<OffsetCursor>)
+            .transpose()?;
+        let before = before.map(|cursor| cursor.offset as usize);
+        let mut start = after.map_or(0, |cursor| cursor.offset as usize + 1);
+
+        let end = match (first, last, before) {
+            (None, Some(_), None) => Some(self.fetch_total_count(client, path).await?),
+            _ => before,
+        };
+        let count = match (first, last, end) {
+            (Some(first), ..) => first,
+            (None, Some(last), Some(end)) => {
+                start = start.max(end.saturating_sub(last));
+                last
+            }
+            _ => self.config.default_page_size as usize,
+        };
+        let count = match end {
+            Some(end) => count.min(end.saturating_sub(start)),
+            None => count,
+        };
+        if count == 0 {
+            return Ok(Page {
+                has_next_page: before.is_some(),
+                has_previous_page: start > 0,
+                ..Default::default()
+            });
+        }
+
+        let mut nodes = Vec::with_capacity(count);
+        let mut total_count = None;
+        let mut has_more = false;
+        while nodes.len() < count {
+            let offset = start + nodes.len();
+            let (request, skip, requested) =
+                self.positional_request(path, offset, count - nodes.len());
+            let upstream = self.fetch_page(client, &request).await?;
+            let received = upstream.items.len();
+            let wanted = count - nodes.len();
+            nodes.extend(upstream.items.into_iter().skip(skip).take(wanted));
+            let taken = start + nodes.len() - offset;
+
+            total_count = upstream.total_count;
+            has_more = match total_count {
+                Some(total) => ((offset + taken) as i64) < total,
+                None => received > skip + taken || received >= requested,
+            };
+            if !has_more || taken == 0 {
+                break;
+            }
+        }
+
+        let mut edges: Vec<(String, Value)> = nodes
+            .into_iter()
+            .enumerate()
+            .map(|(i, node)| {
+                (
+                    encode(&OffsetCursor {
+                        offset: (start + i) as u64,
+                    }),
+                    node,
+                )
+            })
+            .collect();
+        let taken = edges.len();
+        if let (Some(_), Some(last)) = (first, last) {
+            edges.drain(..edges.len().saturating_sub(last));
+        }
+        let first_offset = start + (taken - edges.len());
+
+        Ok(Page {
+            edges,
+            has_next_page: has_more || before.is_some(),
+            has_previous_page: first_offset > 0,
+            total_count,
+        })
+    }
+
+    /// The request for up to `count` items from `offset`, with the number of
+    /// leading items to skip and the number of items asked for.
+    fn positional_request(
+        &self,
+        path: &str,
+        offset: usize,
+        count: usize,
+    ) -> (String, usize, usize) {
+        match self.config.style {
+            PaginationStyle::Page => {
+                let size = self.config.page_size as usize;
+                let page = offset / size + self.config.first_page as usize;
+                let params = [
+                    (&self.config.page_param, page.to_string()),
+                    (&self.config.limit_param, size.to_string()),
+                ];
+                (with_params(path, &params), offset % size, size)
+            }
+            _ => {
+                let params = [
+                    (&self.config.offset_param, offset.to_string()),
+                    (&self.config.limit_param, count.to_string()),
+                ];
+                (with_params(path, &params), 0, count)
+            }
+        }
+    }
+
+    /// The upstream's total count, read from its first page.
+    async fn fetch_total_count(&self, client: &RestClient, path: &str) -> Result<usize> {
+        if self.total_count.is_none() {
+            return Err(RustQLError::Validation(
+                "`last` needs `before` on this field".to_string(),
+            ));
+        }
+        let (request, ..) = self.positional_request(path, 0, 1);
+        let upstream = self.fetch_page(client, &request).await?;
+        upstream
+            .total_count
+            .map(|total| total.max(0) as usize)
+            .ok_or_else(|| RustQLError::UnexpectedResponse {
+                api: client.name().to_string(),
+                message: "expected a total count".to_string(),
+            })
+    }
+
+    /// Cursor and `Link` pagination moving forward from `after`.
+    async fn fetch_forward(
+        &self,
+        client: &RestClient,
+        path: &str,
+        args: &PageArgs,
+        count: usize,
+    ) -> Result<Page> {
+        let (mut page, mut skip) = match args.after.as_deref().map(decode::<PageCursor>) {
+            Some(cursor) => {
+                let cursor = cursor?;
+                (cursor.page, cursor.index + 1)
+            }
+            None => (None, 0),
+        };
+
+        let mut upstream = self
+            .fetch_sequential(client, path, page.as_deref(), skip + count)
+            .await?;
+        for _ in 0..MAX_PAGE_HOPS {
+            match &upstream.next {
+                Some(next) if skip >= upstream.items.len() => {
+                    skip -= upstream.items.len();
+                    page = Some(next.clone());
+                    upstream = self
+                        .fetch_sequential(client, path, page.as_deref(), skip + count)
+                        .await?;
+                }
+                _ => break,
+            }
+        }
+
+        let available = upstream.items.len();
+        let edges = self.page_edges(upstream.items, &page, skip, skip.saturating_add(count));
+        Ok(Page {
+            has_next_page: skip + edges.len() < available || upstream.next.is_some(),
+            has_previous_page: skip > 0 || page.is_some() || upstream.prev.is_some(),
+            total_count: upstream.total_count,
+            edges,
+        })
+    }
+
+    /// Cursor and `Link` pagination moving backward from `before`.
+    async fn fetch_backward(
+        &self,
+        client: &RestClient,
+        path: &str,
+        args: &PageArgs,
+        count: usize,
+    ) -> Result<Page> {
+        let Some(before) = args.before.as_deref() else {
+            return Err(RustQLError::Validation(
+                "`last` needs `before` on this field".to_string(),
+            ));
+        };
+        let PageCursor { page, index } = decode(before)?;
+
+        if index > 0 {
+            let upstream = self
+                .fetch_sequential(client, path, page.as_deref(), index)
+                .await?;
+            let start = index.saturating_sub(count);
+            let edges = self.page_edges(upstream.items, &page, start, index);
+            return Ok(Page {
+                edges,
+                has_next_page: true,
+                has_previous_page: start > 0 || page.is_some() || upstream.prev.is_some(),
+                total_count: upstream.total_count,
+            });
+        }
+
+        // Before the first item of a page: continue on the previous page
+        let current = self
+            .fetch_sequential(client, path, page.as_deref(), count)
+            .await?;
+        let Some(prev) = current.prev else {
+            return Ok(Page {
+                has_next_page: true,
+                total_count: current.total_count,
+                ..Default::default()
+            });
+        };
+        let page = Some(prev);
+        let upstream = self
+            .fetch_sequential(client, path, page.as_deref(), count)
+            .await?;
+        let end = upstream.items.len();
+        let start = end.saturating_sub(count);
+        let edges = self.page_edges(upstream.items, &page, start, end);
+        Ok(Page {
+            edges,
+            has_next_page: true,
+            has_previous_page: start > 0 || upstream.prev.is_some(),
+            total_count: upstream.total_count,
+        })
+    }
+
+    /// Items `start..end` of the upstream page reached through `page`.
+    fn page_edges(
+        &self,
+        items: Vec<Value>,
+        page: &Option<String>,
+        start: usize,
+        end: usize,
+    ) -> Vec<(String, Value)> {
+        items
+            .into_iter()
+            .enumerate()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .map(|(index, node)| {
+                let cursor = PageCursor {
+                    page: page.clone(),
+                    index,
+                };
+                (encode(&cursor), node)
+            })
+            .collect()
+    }
+
+    /// The first page, or the one `page` points to, asking for `limit`
+    /// items where the upstream takes a limit.
+    async fn fetch_sequential(
+        &self,
+        client: &RestClient,
+        path: &str,
+        page: Option<&str>,
+        limit: usize,
+    ) -> Result<UpstreamPage> {
+        let limit = limit.max(1).to_string();
+        let path = match (self.config.style, page) {
+            (PaginationStyle::Link, Some(target)) => target.to_string(),
+            (PaginationStyle::Link, None) => {
+                with_params(path, &[(&self.config.limit_param, limit)])
+            }
+            (_, Some(cursor)) => with_params(
+                path,
+                &[
+                    (&self.config.cursor_param, cursor.to_string()),
+                    (&self.config.limit_param, limit),
+                ],
+            ),
+            (_, None) => with_params(path, &[(&self.config.limit_param, limit)]),
+        };
+        self.fetch_page(client, &path).await
+    }
+
+    async fn fetch_page(&self, client: &RestClient, path: &str) -> Result<UpstreamPage> {
+        let (body, mut links) = match self.config.style {
+            PaginationStyle::Link => client.get_with_links(path).await?,
+            _ => (client.get(path).await?, Default::default()),
+        };
+
+        let cursor = |path: &Option<JsonPath>| match path.as_ref().and_then(|path| path.get(&body))
+        {
+            Some(Value::String(cursor)) if !cursor.is_empty() => Some(cursor.clone()),
+            Some(Value::Number(cursor)) => Some(cursor.to_string()),
+            _ => None,
+        };
+        let (next, prev) = match self.config.style {
+            PaginationStyle::Link => (links.remove("next"), links.remove("prev")),
+            _ => (cursor(&self.next_cursor), cursor(&self.prev_cursor)),
+        };
+        let total_count = self
+            .total_count
+            .as_ref()
+            .and_then(|path| path.get(&body))
+            .and_then(|total| match total {
+                Value::String(total) => total.parse().ok(),
+                total => total.as_i64(),
+            });
+
+        let items = match &self.items {
+            Some(items) => items.get(&body).cloned(),
+            None => Some(body),
+        };
+        let items = match items {
+            Some(Value::Array(items)) => items,
+            Some(Value::Null) | None => vec![],
+            Some(_) => {
+                debug!(url = %client.url(path), "Upstream page is not a list");
+                return Err(RustQLError::UnexpectedResponse {
+                    api: client.name().to_string(),
+                    message: "expected a list of items".to_string(),
+                });
+            }
+        };
+
+        Ok(UpstreamPage {
+            items,
+            total_count,
+            next,
+            prev,
+        })
+    }
+}
+
+fn with_params(path: &str, params: &[(&String, String)]) -> String {
+    let mut path = path.to_string();
+    for (name, value) in params {
+        path.push(if path.contains('?') { '&' } else { '?' });
+        path.extend(utf8_percent_encode(name, PATH_SEGMENT));
+        path.push('=');
+        path.extend(utf8_percent_encode(value, PATH_SEGMENT));
+    }
+    path
+}
+
+fn encode<T: Serialize>(cursor: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+fn decode<T: DeserializeOwned>(cursor: &str) -> Result<T> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| RustQLError::Validation(format!("Invalid cursor '{}'", cursor)))
+}
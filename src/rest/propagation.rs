@@ -0,0 +1,215 @@
+use crate::config::settings::{HeaderPropagation, RestApiConfig};
+use crate::graphql::resolvers::ResolverContext;
+use crate::utils::{Result, RustQLError, sha256_hex};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde_json::Value;
+
+/// Connection-level headers (RFC 9110 §7.6.1) plus the framing headers the
+/// upstream request sets itself.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "content-length",
+];
+
+#[derive(Debug, Clone)]
+enum Source {
+    Header(String),
+    HeaderPrefix(String),
+    Claim(String),
+    RequestId,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    source: Source,
+    target: Option<HeaderName>,
+    default: Option<HeaderValue>,
+}
+
+/// Caller headers computed for one upstream request.
+#[derive(Debug, Clone, Default)]
+pub struct Propagated {
+    pub headers: HeaderMap,
+    /// Digest of the values that make the upstream response caller-specific;
+    /// `None` when there are none. The request id does not count.
+    pub vary: Option<String>,
+}
+
+/// An API's `propagate` rules.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderRules {
+    rules: Vec<Rule>,
+}
+
+impl HeaderRules {
+    pub fn from_config(config: &RestApiConfig) -> Result<Self> {
+        let rules = config
+            .propagate
+            .iter()
+            .map(|rule| {
+                parse_rule(rule).map_err(|message| {
+                    RustQLError::Config(format!(
+                        "Invalid header propagation '{}' for API '{}': {}",
+                        rule.from, config.name, message
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn apply(&self, context: &ResolverContext) -> Propagated {
+        let incoming = &context.headers;
+        let connection_tokens: Vec<String> = incoming
+            .get_all("connection")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|token| token.trim().to_ascii_lowercase())
+            .collect();
+        let forwardable = |name: &str| {
+            !HOP_BY_HOP.contains(&name) && !connection_tokens.iter().any(|token| token == name)
+        };
+
+        let mut propagated = Propagated::default();
+        let mut vary = Vec::new();
+        for rule in &self.rules {
+            let values: Vec<(HeaderName, HeaderValue)> = match &rule.source {
+                Source::Header(name) if forwardable(name) => incoming
+                    .get_all(name.as_str())
+                    .iter()
+                    .filter_map(|value| HeaderValue::from_bytes(value.as_bytes()).ok())
+                    .filter_map(|value| Some((rule.target.clone()?, value)))
+                    .collect(),
+                Source::Header(_) => vec![],
+                Source::HeaderPrefix(prefix) => incoming
+                    .iter()
+                    .filter(|(name, _)| {
+                        name.as_str().starts_with(prefix.as_str()) && forwardable(name.as_str())
+                    })
+                    .filter_map(|(name, value)| {
+                        Some((
+                            HeaderName::from_bytes(name.as_str().as_bytes()).ok()?,
+                            HeaderValue::from_bytes(value.as_bytes()).ok()?,
+                        ))
+                    })
+                    .collect(),
+                Source::Claim(claim) => context
+                    .claims
+                    .as_ref()
+                    .and_then(|claims| claims.get(claim))
+                    .and_then(claim_value)
+                    .and_then(|value| HeaderValue::from_str(&value).ok())
+                    .and_then(|value| Some((rule.target.clone()?, value)))
+                    .into_iter()
+                    .collect(),
+                Source::RequestId => HeaderValue::from_str(&context.request_id)
+                    .ok()
+                    .and_then(|value| Some((rule.target.clone()?, value)))
+                    .into_iter()
+                    .collect(),
+            };
+
+            let values = match (&rule.default, &rule.target) {
+                (Some(default), Some(target)) if values.is_empty() => {
+                    vec![(target.clone(), default.clone())]
+                }
+                _ => values,
+            };
+            for (name, value) in values {
+                if !matches!(rule.source, Source::RequestId) {
+                    vary.push(format!(
+                        "{}:{}",
+                        name,
+                        String::from_utf8_lossy(value.as_bytes())
+                    ));
+                }
+                propagated.headers.append(name, value);
+            }
+        }
+
+        if !vary.is_empty() {
+            propagated.vary = Some(sha256_hex(&vary.join("\n")));
+        }
+        propagated
+    }
+}
+
+fn parse_rule(rule: &HeaderPropagation) -> std::result::Result<Rule, String> {
+    let source = match rule.from.split_once(':') {
+        Some(("header", name)) => {
+            let name = name.trim().to_ascii_lowercase();
+            match name.strip_suffix('*') {
+                Some(prefix) => Source::HeaderPrefix(prefix.to_string()),
+                None => Source::Header(name),
+            }
+        }
+        Some(("claim", claim)) if !claim.is_empty() => Source::Claim(claim.to_string()),
+        None if rule.from == "request_id" => Source::RequestId,
+        _ => return Err("expected header:<name>, claim:<name> or request_id".to_string()),
+    };
+
+    let target = match (&rule.to, &source) {
+        (Some(to), Source::HeaderPrefix(_)) => {
+            return Err(format!("prefix rules cannot be renamed to '{}'", to));
+        }
+        (Some(to), _) => Some(to.as_str()),
+        (None, Source::Header(name)) => Some(name.as_str()),
+        (None, Source::HeaderPrefix(_)) => None,
+        (None, _) => return Err("`to` is required".to_string()),
+    };
+    let target = target
+        .map(|name| HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string()))
+        .transpose()?;
+    if target
+        .as_ref()
+        .is_some_and(|name| HOP_BY_HOP.contains(&name.as_str()))
+    {
+        return Err("hop-by-hop headers cannot be set".to_string());
+    }
+
+    let default = match (&rule.default, &target) {
+        (Some(_), None) => return Err("prefix rules cannot have a default".to_string()),
+        (Some(default), Some(_)) => {
+            Some(HeaderValue::from_str(default).map_err(|e| e.to_string())?)
+        }
+        (None, _) => None,
+    };
+
+    Ok(Rule {
+        source,
+        target,
+        default,
+    })
+}
+
+/// Strings are sent as-is, numbers and booleans in their JSON form and
+/// arrays of those comma-separated.
+fn claim_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Array(_) | Value::Object(_) | Value::Null => None,
+                _ => claim_value(value),
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(",")),
+        Value::Object(_) | Value::Null => None,
+    }
+}
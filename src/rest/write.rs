@@ -0,0 +1,215 @@
+use crate::config::settings::{BodyEncoding, WriteMethod};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::{Value, json};
+
+/// Characters left as-is in `application/x-www-form-urlencoded` keys and
+/// values.
+const FORM_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'*')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
+
+/// An encoded request body and its content type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestBody {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+impl RequestBody {
+    pub fn encode(encoding: BodyEncoding, value: &Value) -> Self {
+        match encoding {
+            BodyEncoding::Json => Self {
+                content_type: "application/json".to_string(),
+                bytes: value.to_string().into_bytes(),
+            },
+            BodyEncoding::Form => {
+                let pairs: Vec<String> = form_pairs(value)
+                    .into_iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}={}",
+                            utf8_percent_encode(&key, FORM_COMPONENT),
+                            utf8_percent_encode(&value, FORM_COMPONENT)
+                        )
+                    })
+                    .collect();
+                Self {
+                    content_type: "application/x-www-form-urlencoded".to_string(),
+                    bytes: pairs.join("&").into_bytes(),
+                }
+            }
+            BodyEncoding::Multipart => {
+                let boundary = format!("rustql-{}", uuid::Uuid::new_v4().simple());
+                let mut bytes = Vec::new();
+                for (key, value) in form_pairs(value) {
+                    let part = format!(
+                        "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                        boundary,
+                        part_name(&key),
+                        value
+                    );
+                    bytes.extend(part.into_bytes());
+                }
+                bytes.extend(format!("--{}--\r\n", boundary).into_bytes());
+                Self {
+                    content_type: format!("multipart/form-data; boundary={}", boundary),
+                    bytes,
+                }
+            }
+        }
+    }
+}
+
+/// A form key as a quoted multipart part name, percent-encoding the quote and
+/// line breaks as browsers do so no key can end the header early.
+fn part_name(key: &str) -> String {
+    key.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// The leaves of `value` as form fields, nested keys as `a[b]` and array
+/// elements as `a[]`. Nulls are omitted.
+fn form_pairs(value: &Value) -> Vec<(String, String)> {
+    fn visit(key: &str, value: &Value, pairs: &mut Vec<(String, String)>) {
+        match value {
+            Value::Null => {}
+            Value::Object(object) => {
+                for (name, value) in object {
+                    let key = match key {
+                        "" => name.clone(),
+                        key => format!("{}[{}]", key, name),
+                    };
+                    visit(&key, value, pairs);
+                }
+            }
+            Value::Array(items) => {
+                let key = format!("{}[]", key);
+                items.iter().for_each(|item| visit(&key, item, pairs));
+            }
+            Value::String(value) => pairs.push((key.to_string(), value.clone())),
+            value => pairs.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    let mut pairs = Vec::new();
+    if value.is_object() {
+        visit("", value, &mut pairs);
+    }
+    pairs
+}
+
+pub fn http_method(method: WriteMethod) -> reqwest::Method {
+    match method {
+        WriteMethod::Post => reqwest::Method::POST,
+        WriteMethod::Put => reqwest::Method::PUT,
+        WriteMethod::Patch => reqwest::Method::PATCH,
+        WriteMethod::Delete => reqwest::Method::DELETE,
+    }
+}
+
+/// The response to a write: its status and JSON body, a string for other
+/// bodies and null when empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Written {
+    pub status: u16,
+    pub body: Value,
+}
+
+/// The validation errors of a rejected write as `UserError` values
+/// (`field`, `message`, `code`). Reads `errors` lists (plain, JSON:API
+/// `source.pointer`/`detail` or `path`), `errors` objects of messages per
+/// field and RFC 7807 `invalid-params`, falling back to a single error with
+/// the body's message.
+pub fn user_errors(body: &Value) -> Vec<Value> {
+    let listed = body
+        .get("errors")
+        .or_else(|| body.get("invalid-params"))
+        .or_else(|| body.get("invalid_params"));
+    match listed {
+        Some(Value::Array(errors)) if !errors.is_empty() => {
+            return errors.iter().map(listed_error).collect();
+        }
+        Some(Value::Object(fields)) if !fields.is_empty() => {
+            let mut errors = Vec::new();
+            for (field, messages) in fields {
+                let messages = match messages {
+                    Value::Array(messages) => messages.iter().collect(),
+                    message => vec![message],
+                };
+                for message in messages {
+                    errors.push(user_error(Some(field_path(field)), text(message), None));
+                }
+            }
+            return errors;
+        }
+        _ => {}
+    }
+
+    let message = ["message", "detail", "title", "error"]
+        .iter()
+        .find_map(|key| body.get(*key).and_then(Value::as_str))
+        .or_else(|| body.as_str().filter(|message| !message.is_empty()))
+        .unwrap_or("Request was rejected by the API");
+    let code = body.get("code").map(text);
+    vec![user_error(None, message.to_string(), code)]
+}
+
+fn listed_error(error: &Value) -> Value {
+    if let Value::String(message) = error {
+        return user_error(None, message.clone(), None);
+    }
+
+    let message = ["message", "detail", "reason", "title"]
+        .iter()
+        .find_map(|key| error.get(*key).and_then(Value::as_str))
+        .unwrap_or("Invalid value");
+    let field = match (error.get("field"), error.get("path"), error.get("name")) {
+        (Some(Value::String(field)), _, _) | (None, None, Some(Value::String(field))) => {
+            Some(field_path(field))
+        }
+        (Some(Value::Array(path)), _, _) | (None, Some(Value::Array(path)), _) => {
+            Some(path.iter().map(text).collect())
+        }
+        _ => error
+            .pointer("/source/pointer")
+            .and_then(Value::as_str)
+            .map(pointer_path)
+            .or_else(|| {
+                error
+                    .pointer("/source/parameter")
+                    .and_then(Value::as_str)
+                    .map(|parameter| vec![parameter.to_string()])
+            }),
+    };
+    let code = error.get("code").map(text);
+    user_error(field, message.to_string(), code)
+}
+
+fn user_error(field: Option<Vec<String>>, message: String, code: Option<String>) -> Value {
+    json!({ "field": field, "message": message, "code": code })
+}
+
+/// `user.email` → `["user", "email"]`.
+fn field_path(field: &str) -> Vec<String> {
+    field.split('.').map(str::to_string).collect()
+}
+
+/// A JSON:API pointer such as `/data/attributes/email` → `["email"]`.
+fn pointer_path(pointer: &str) -> Vec<String> {
+    let pointer = pointer.strip_prefix("/data/attributes").unwrap_or(pointer);
+    pointer
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::settings::CompressionConfig;
+use std::convert::Infallible;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::warn;
+use warp::http::{HeaderMap, HeaderValue, StatusCode, header};
+use warp::hyper::Body;
+use warp::reply::Response;
+use warp::Filter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "br" => Some(Encoding::Brotli),
+            "zstd" => Some(Encoding::Zstd),
+            "gzip" => Some(Encoding::Gzip),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut output = Vec::new();
+                {
+                    let mut writer = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                    writer.write_all(data)?;
+                }
+                Ok(output)
+            }
+            Encoding::Zstd => zstd::encode_all(data, 3),
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Response compression settings resolved from `ServerConfig::compression`.
+#[derive(Debug, Clone)]
+pub struct CompressionPolicy {
+    enabled: bool,
+    min_size: usize,
+    algorithms: Vec<Encoding>,
+}
+
+impl CompressionPolicy {
+    pub fn from_config(config: &CompressionConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            min_size: config.min_size,
+            algorithms: config
+                .algorithms
+                .iter()
+                .filter_map(|a| Encoding::from_token(a))
+                .collect(),
+        }
+    }
+
+    /// Picks the encoding with the highest `q` value in `Accept-Encoding`,
+    /// breaking ties with the configured server preference order.
+    pub fn negotiate(&self, accept_encoding: &str) -> Option<Encoding> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut preferences = Vec::new();
+        for part in accept_encoding.split(',') {
+            let mut params = part.split(';');
+            let token = params.next().unwrap_or("").trim().to_ascii_lowercase();
+            let q = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            preferences.push((token, q));
+        }
+
+        let quality = |encoding: Encoding| {
+            preferences
+                .iter()
+                .find(|(token, _)| token == encoding.as_str())
+                .or_else(|| preferences.iter().find(|(token, _)| token == "*"))
+                .map(|(_, q)| *q)
+                .unwrap_or(0.0)
+        };
+
+        let mut best: Option<(Encoding, f32)> = None;
+        for &encoding in &self.algorithms {
+            let q = quality(encoding);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((encoding, q));
+            }
+        }
+
+        best.map(|(encoding, _)| encoding)
+    }
+
+    fn should_compress(&self, response: &Response) -> bool {
+        if response.headers().contains_key(header::CONTENT_ENCODING)
+            || matches!(
+                response.status(),
+                StatusCode::NO_CONTENT | StatusCode::NOT_MODIFIED
+            )
+        {
+            return false;
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        is_compressible(content_type)
+    }
+}
+
+/// Streaming responses (SSE, multipart) are never buffered for compression.
+fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    mime.starts_with("text/") && mime != "text/event-stream"
+        || mime == "application/json"
+        || mime.ends_with("+json")
+        || mime == "application/javascript"
+        || mime == "application/graphql"
+        || mime == "image/svg+xml"
+}
+
+/// Compresses replies of `filter` according to the request's `Accept-Encoding`.
+pub fn with_compression<F>(
+    policy: Arc<CompressionPolicy>,
+    filter: F,
+) -> impl Filter<Extract = (Response,), Error = Infallible> + Clone
+where
+    F: Filter<Extract = (Response,), Error = Infallible> + Clone + Send + Sync + 'static,
+{
+    warp::header::headers_cloned()
+        .and(filter)
+        .and_then(move |headers: HeaderMap, response: Response| {
+            let policy = policy.clone();
+            async move { Ok::<_, Infallible>(compress_response(&policy, &headers, response).await) }
+        })
+}
+
+async fn compress_response(
+    policy: &CompressionPolicy,
+    request_headers: &HeaderMap,
+    response: Response,
+) -> Response {
+    let encoding = request_headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| policy.negotiate(v));
+
+    let Some(encoding) = encoding.filter(|_| policy.should_compress(&response)) else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match warp::hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!(error = %e, "Failed to buffer response body for compression");
+            return internal_error(parts);
+        }
+    };
+
+    if bytes.len() < policy.min_size {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    let compressed = tokio::task::spawn_blocking(move || {
+        encoding.compress(&bytes).map_err(|e| (e, bytes))
+    })
+    .await;
+
+    match compressed {
+        Ok(Ok(compressed)) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts.headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+            parts
+                .headers
+                .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            Response::from_parts(parts, Body::from(compressed))
+        }
+        Ok(Err((e, bytes))) => {
+            warn!(error = %e, encoding = encoding.as_str(), "Response compression failed");
+            Response::from_parts(parts, Body::from(bytes))
+        }
+        Err(e) => {
+            warn!(error = %e, "Response compression task failed");
+            internal_error(parts)
+        }
+    }
+}
+
+/// The original body is gone, so the response cannot be sent as produced.
+fn internal_error(mut parts: warp::http::response::Parts) -> Response {
+    parts.status = StatusCode::INTERNAL_SERVER_ERROR;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::empty())
+}
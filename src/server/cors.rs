@@ -0,0 +1,233 @@
+use crate::config::settings::ServerConfig;
+use serde_json::json;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tracing::debug;
+use warp::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+/// CORS policy built from `ServerConfig::cors_origins` and `ServerConfig::cors`.
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    any_origin: bool,
+    exact_origins: Vec<String>,
+    wildcard_origins: Vec<(String, String)>,
+    allow_credentials: bool,
+    allowed_headers: Vec<String>,
+    allowed_methods: Vec<Method>,
+    exposed_headers: Vec<String>,
+    max_age: Option<u64>,
+}
+
+impl CorsPolicy {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let mut any_origin = false;
+        let mut exact_origins = Vec::new();
+        let mut wildcard_origins = Vec::new();
+
+        for origin in &config.cors_origins {
+            let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+            if origin == "*" {
+                any_origin = true;
+            } else if let Some((scheme, rest)) = origin.split_once("://*.") {
+                // "https://*.example.com" matches "https://api.example.com"
+                wildcard_origins.push((format!("{}://", scheme), format!(".{}", rest)));
+            } else {
+                exact_origins.push(origin);
+            }
+        }
+
+        Self {
+            any_origin,
+            exact_origins,
+            wildcard_origins,
+            allow_credentials: config.cors.allow_credentials,
+            allowed_headers: config
+                .cors
+                .allowed_headers
+                .iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            allowed_methods: config
+                .cors
+                .allowed_methods
+                .iter()
+                .filter_map(|m| m.to_ascii_uppercase().parse().ok())
+                .collect(),
+            exposed_headers: config
+                .cors
+                .exposed_headers
+                .iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            max_age: config.cors.max_age,
+        }
+    }
+
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        if self.any_origin {
+            return true;
+        }
+
+        let origin = origin.to_ascii_lowercase();
+        if self.exact_origins.contains(&origin) {
+            return true;
+        }
+
+        self.wildcard_origins.iter().any(|(prefix, suffix)| {
+            origin.len() > prefix.len() + suffix.len()
+                && origin.starts_with(prefix.as_str())
+                && origin.ends_with(suffix.as_str())
+                && !origin[prefix.len()..origin.len() - suffix.len()].contains(['/', ':'])
+        })
+    }
+
+    pub fn is_method_allowed(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|m| m.as_str().eq_ignore_ascii_case(method))
+    }
+
+    pub fn are_headers_allowed(&self, requested: &str) -> bool {
+        requested
+            .split(',')
+            .map(|h| h.trim().to_ascii_lowercase())
+            .filter(|h| !h.is_empty())
+            .all(|h| self.allowed_headers.contains(&h))
+    }
+
+    fn sends_wildcard(&self) -> bool {
+        self.any_origin && !self.allow_credentials
+    }
+
+    /// Value for `Access-Control-Allow-Origin`. A literal `*` is only sent when any
+    /// origin is allowed and credentials are disabled; otherwise the origin is echoed.
+    fn allow_origin_value(&self, origin: &str) -> Option<HeaderValue> {
+        if self.sends_wildcard() {
+            return Some(HeaderValue::from_static("*"));
+        }
+        HeaderValue::from_str(origin).ok()
+    }
+
+    fn apply_common_headers(&self, origin: &str, headers: &mut HeaderMap) {
+        if let Some(value) = self.allow_origin_value(origin) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+        }
+        if !self.sends_wildcard() {
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+        if self.allow_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight_response(&self, origin: &str) -> Response {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        self.apply_common_headers(origin, headers);
+
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(Method::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Ok(value) = HeaderValue::from_str(&methods) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&self.allowed_headers.join(", ")) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+
+        response
+    }
+
+    fn decorate(&self, origin: Option<&str>, mut response: Response) -> Response {
+        let Some(origin) = origin.filter(|o| self.is_origin_allowed(o)) else {
+            return response;
+        };
+
+        let headers = response.headers_mut();
+        self.apply_common_headers(origin, headers);
+        if !self.exposed_headers.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&self.exposed_headers.join(", ")) {
+                headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, value);
+            }
+        }
+
+        response
+    }
+}
+
+/// Answers CORS preflight requests (`OPTIONS` with `Origin` and
+/// `Access-Control-Request-Method`). Other requests are rejected as not found
+/// so they fall through to the regular routes.
+pub fn preflight(
+    policy: Arc<CorsPolicy>,
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    warp::options()
+        .and(warp::header::headers_cloned())
+        .and_then(move |headers: HeaderMap| {
+            let policy = policy.clone();
+            async move {
+                let header_str =
+                    |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok());
+                let (Some(origin), Some(method)) = (
+                    header_str(header::ORIGIN),
+                    header_str(header::ACCESS_CONTROL_REQUEST_METHOD),
+                ) else {
+                    return Err(warp::reject::not_found());
+                };
+
+                let allowed = policy.is_origin_allowed(origin)
+                    && policy.is_method_allowed(method)
+                    && header_str(header::ACCESS_CONTROL_REQUEST_HEADERS)
+                        .is_none_or(|h| policy.are_headers_allowed(h));
+
+                if allowed {
+                    Ok(policy.preflight_response(origin))
+                } else {
+                    debug!(origin = %origin, method = %method, "CORS preflight rejected");
+                    Ok(forbidden())
+                }
+            }
+        })
+}
+
+/// Adds CORS response headers for allowed origins to every reply of `filter`.
+pub fn with_cors<F, R>(
+    policy: Arc<CorsPolicy>,
+    filter: F,
+) -> impl Filter<Extract = (Response,), Error = Infallible> + Clone
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    warp::header::headers_cloned()
+        .and(filter)
+        .map(move |headers: HeaderMap, reply: R| {
+            let origin = headers
+                .get(header::ORIGIN)
+                .and_then(|v| v.to_str().ok());
+            policy.decorate(origin, reply.into_response())
+        })
+}
+
+fn forbidden() -> Response {
+    let json = json!({
+        "error": {
+            "code": "CORS_FORBIDDEN",
+            "message": "CORS request not allowed",
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }
+    });
+
+    warp::reply::with_status(warp::reply::json(&json), StatusCode::FORBIDDEN).into_response()
+}
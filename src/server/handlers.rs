@@ -1,8 +1,32 @@
+use crate::auth::AuthError;
 use crate::config::Settings;
+use crate::graphql::incremental::{
+    IncrementalPlan, response_payload, strip_incremental_directives,
+};
+use crate::graphql::document::select_operation;
+use crate::graphql::partial::ExecutionStarted;
+use crate::graphql::persisted::PersistedQueryError;
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::{RustQLSchema, WebhookBroker};
+use crate::server::listener::ConnectionInfo;
+use crate::server::playground;
+use crate::server::transport::{self, ResponseFormat};
+use crate::rate_limit::RateLimited;
+use crate::rest::expand_env;
+use crate::services::Services;
+use crate::utils::generate_request_id;
+use async_graphql::Data;
+use async_graphql::parser::types::OperationType;
+use async_graphql::http::WebSocketProtocols;
+use async_graphql_warp::GraphQLWebSocket;
 use serde_json::{Value, json};
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
-use tracing::{error, info, instrument};
+use std::time::Duration;
+use tracing::{debug, error, info, instrument, warn};
+use futures_util::StreamExt;
+use warp::http::{HeaderMap, HeaderName, HeaderValue, header};
 use warp::{Rejection, Reply, http::StatusCode};
 
 #[instrument]
@@ -20,71 +44,508 @@ pub async fn handle_health() -> Result<impl Reply, Rejection> {
     ))
 }
 
-#[instrument(skip(_settings, _body))]
+/// Executes a GraphQL request, or a JSON array of requests when batching is
+/// enabled, and delivers the result in the format negotiated from `Accept`.
+#[instrument(skip_all, fields(request_id = %context.request_id))]
 pub async fn handle_graphql(
+    context: ResolverContext,
+    settings: Arc<Settings>,
+    schema: RustQLSchema,
+    services: Services,
+    body: Value,
+) -> Result<warp::reply::Response, Rejection> {
+    info!(request_id = %context.request_id, "Processing GraphQL request");
+
+    let request_id = context.request_id.clone();
+    let format = ResponseFormat::negotiate(
+        context.headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()),
+    );
+
+    if let Value::Array(operations) = body {
+        return Ok(execute_batch(operations, format, context, &settings, schema, &services).await);
+    }
+
+    match serde_json::from_value::<async_graphql::Request>(body) {
+        Ok(request) => {
+            Ok(execute_graphql(request, format, context, &settings, schema, &services).await)
+        }
+        Err(e) => Ok(transport::json_reply(
+            &graphql_error_response(
+                &format!("Invalid GraphQL request: {}", e),
+                "BAD_REQUEST",
+                &request_id,
+            ),
+            StatusCode::BAD_REQUEST,
+            format,
+        )),
+    }
+}
+
+/// Executes `GET /graphql?query=…&variables=…&operationName=…&extensions=…`.
+/// Mutations are rejected with 405 since GET must be safe.
+#[instrument(skip_all, fields(request_id = %context.request_id))]
+pub async fn handle_graphql_get(
+    context: ResolverContext,
+    settings: Arc<Settings>,
+    schema: RustQLSchema,
+    services: Services,
+    params: HashMap<String, String>,
+) -> Result<warp::reply::Response, Rejection> {
+    info!(request_id = %context.request_id, "Processing GraphQL GET request");
+
+    let request_id = context.request_id.clone();
+    let format = ResponseFormat::negotiate(
+        context.headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()),
+    );
+
+    let mut request = match request_from_params(&params) {
+        Ok(request) => request,
+        Err(message) => {
+            return Ok(transport::json_reply(
+                &graphql_error_response(&message, "BAD_REQUEST", &request_id),
+                StatusCode::BAD_REQUEST,
+                format,
+            ));
+        }
+    };
+
+    // Check before resolving, which registers the query of an APQ request
+    if operation_type(&request) == Some(OperationType::Mutation) {
+        return Ok(mutation_over_get_reply(&request_id, format));
+    }
+    // A persisted query sent as a hash only has a type once resolved
+    if let Err(e) = services.persisted_queries.resolve(&mut request).await {
+        return Ok(persisted_query_error_reply(&e, &request_id, format));
+    }
+    if operation_type(&request) == Some(OperationType::Mutation) {
+        return Ok(mutation_over_get_reply(&request_id, format));
+    }
+
+    Ok(execute_graphql(request, format, context, &settings, schema, &services).await)
+}
+
+fn mutation_over_get_reply(request_id: &str, format: ResponseFormat) -> warp::reply::Response {
+    let mut response = transport::json_reply(
+        &graphql_error_response(
+            "Mutations are not allowed over GET; use POST",
+            "METHOD_NOT_ALLOWED",
+            request_id,
+        ),
+        StatusCode::METHOD_NOT_ALLOWED,
+        format,
+    );
+    response
+        .headers_mut()
+        .insert(header::ALLOW, HeaderValue::from_static("POST"));
+    response
+}
+
+fn request_from_params(params: &HashMap<String, String>) -> std::result::Result<async_graphql::Request, String> {
+    let query = params.get("query").map(String::as_str).unwrap_or_default();
+    let mut request = async_graphql::Request::new(query);
+    request.operation_name = params.get("operationName").filter(|n| !n.is_empty()).cloned();
+
+    if let Some(variables) = params.get("variables").filter(|v| !v.is_empty()) {
+        let variables: Value = serde_json::from_str(variables)
+            .map_err(|e| format!("Invalid variables parameter: {}", e))?;
+        request.variables = async_graphql::Variables::from_json(variables);
+    }
+    if let Some(extensions) = params.get("extensions").filter(|e| !e.is_empty()) {
+        request.extensions = serde_json::from_str(extensions)
+            .map_err(|e| format!("Invalid extensions parameter: {}", e))?;
+    }
+
+    // Persisted queries are sent as a hash without the query text
+    if query.is_empty() && !request.extensions.contains_key("persistedQuery") {
+        return Err("Missing query parameter".to_string());
+    }
+
+    Ok(request)
+}
+
+/// Type of the operation the request would run, if its query parses.
+fn operation_type(request: &async_graphql::Request) -> Option<OperationType> {
+    let document = async_graphql::parser::parse_query(&request.query).ok()?;
+    select_operation(&document, request.operation_name.as_deref())
+        .ok()
+        .map(|(_, operation)| operation.ty)
+}
+
+/// Executes a single operation. Streaming formats are used for subscriptions
+/// and `@defer`/`@stream`; everything else gets one JSON document.
+async fn execute_graphql(
+    mut request: async_graphql::Request,
+    format: ResponseFormat,
+    context: ResolverContext,
+    settings: &Settings,
+    schema: RustQLSchema,
+    services: &Services,
+) -> warp::reply::Response {
+    let timeout = settings.server.request_timeout.map(Duration::from_secs);
+
+    if let Err(e) = services.persisted_queries.resolve(&mut request).await {
+        return persisted_query_error_reply(&e, &context.request_id, format);
+    }
+
+    match format {
+        ResponseFormat::EventStream => {
+            // Subscriptions stay open, so the request timeout does not apply
+            let payloads = match IncrementalPlan::new(&request) {
+                Some(plan) => plan.execute(schema, &request, context, timeout).boxed(),
+                None => {
+                    strip_incremental_directives(&mut request);
+                    schema
+                        .execute_stream(request.data(context))
+                        .map(|response| response_payload(&response))
+                        .boxed()
+                }
+            };
+            return transport::event_stream_reply(payloads);
+        }
+        ResponseFormat::Multipart => {
+            if let Some(plan) = IncrementalPlan::new(&request) {
+                return transport::multipart_reply(plan.execute(schema, &request, context, timeout));
+            }
+        }
+        ResponseFormat::Json | ResponseFormat::GraphQLResponseJson => {}
+    }
+
+    strip_incremental_directives(&mut request);
+    let cache_key = services
+        .response_cache
+        .key(&request, services.cache_scope(&context).as_deref());
+    if let Some(key) = &cache_key {
+        if let Some(cached) = services.response_cache.get(key).await {
+            let mut reply = transport::json_reply(&cached.body, StatusCode::OK, format);
+            set_cache_headers(reply.headers_mut(), &cached.cache_control, cached.age());
+            return reply;
+        }
+    }
+
+    let request_id = context.request_id.clone();
+    let started = ExecutionStarted::default();
+    let request = request.data(started.clone());
+    match execute_with_timeout(&schema, request, context, timeout).await {
+        Ok(response) => {
+            if let Some(key) = &cache_key {
+                services.response_cache.store(key, &response).await;
+            }
+            // With graphql-response+json, a request that never reached
+            // execution is a client error. Null data from execution, such as
+            // a failed non-null root field, is still a 200.
+            let status = if format == ResponseFormat::GraphQLResponseJson && !started.is_started() {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::OK
+            };
+            let mut reply = transport::json_reply(&response, status, format);
+            if let Some(cache_control) = response.cache_control.value().filter(|_| response.is_ok()) {
+                set_cache_headers(reply.headers_mut(), &cache_control, 0);
+            }
+            reply
+        }
+        Err(timeout) => transport::json_reply(
+            &timeout_response(timeout, &request_id),
+            StatusCode::GATEWAY_TIMEOUT,
+            format,
+        ),
+    }
+}
+
+/// Executes every operation of a batch concurrently and replies with the
+/// responses in request order.
+async fn execute_batch(
+    operations: Vec<Value>,
+    format: ResponseFormat,
+    context: ResolverContext,
+    settings: &Settings,
+    schema: RustQLSchema,
+    services: &Services,
+) -> warp::reply::Response {
+    let batching = &settings.server.batching;
+    let request_id = context.request_id.clone();
+
+    let rejection = if !batching.enabled {
+        Some("Batched requests are disabled".to_string())
+    } else if operations.is_empty() {
+        Some("Batch must contain at least one operation".to_string())
+    } else if operations.len() > batching.max_batch_size {
+        Some(format!(
+            "Batch of {} operations exceeds the maximum of {}",
+            operations.len(),
+            batching.max_batch_size
+        ))
+    } else {
+        None
+    };
+    if let Some(message) = rejection {
+        return transport::json_reply(
+            &graphql_error_response(&message, "BAD_REQUEST", &request_id),
+            StatusCode::BAD_REQUEST,
+            format,
+        );
+    }
+
+    let timeout = settings.server.request_timeout.map(Duration::from_secs);
+    let executions = operations.into_iter().map(|operation| {
+        let schema = schema.clone();
+        let context = context.clone();
+        let request_id = request_id.clone();
+        async move {
+            match serde_json::from_value::<async_graphql::Request>(operation) {
+                Ok(mut request) => {
+                    if let Err(e) = services.persisted_queries.resolve(&mut request).await {
+                        return graphql_error_response(&e.to_string(), e.code(), &request_id);
+                    }
+                    strip_incremental_directives(&mut request);
+                    match execute_with_timeout(&schema, request, context, timeout).await {
+                        Ok(response) => serde_json::to_value(&response).unwrap_or(Value::Null),
+                        Err(timeout) => timeout_response(timeout, &request_id),
+                    }
+                }
+                Err(e) => graphql_error_response(
+                    &format!("Invalid GraphQL request: {}", e),
+                    "BAD_REQUEST",
+                    &request_id,
+                ),
+            }
+        }
+    });
+
+    let responses = futures_util::future::join_all(executions).await;
+    transport::json_reply(&responses, StatusCode::OK, format)
+}
+
+/// Dropping the execution future on timeout cancels the resolvers and any
+/// upstream REST calls they are awaiting.
+async fn execute_with_timeout(
+    schema: &RustQLSchema,
+    request: async_graphql::Request,
+    context: ResolverContext,
+    timeout: Option<Duration>,
+) -> std::result::Result<async_graphql::Response, Duration> {
+    let request_id = context.request_id.clone();
+    let execution = schema.execute(request.data(context));
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, execution).await.map_err(|_| {
+            warn!(request_id = %request_id, timeout_secs = timeout.as_secs(), "GraphQL request timed out");
+            timeout
+        }),
+        None => Ok(execution.await),
+    }
+}
+
+fn set_cache_headers(headers: &mut HeaderMap, cache_control: &str, age: u64) {
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+        headers.insert(header::AGE, HeaderValue::from(age));
+    }
+}
+
+fn persisted_query_error_reply(
+    error: &PersistedQueryError,
+    request_id: &str,
+    format: ResponseFormat,
+) -> warp::reply::Response {
+    transport::json_reply(
+        &graphql_error_response(&error.to_string(), error.code(), request_id),
+        error.status(),
+        format,
+    )
+}
+
+fn timeout_response(timeout: Duration, request_id: &str) -> Value {
+    graphql_error_response(
+        &format!("Request timed out after {}s", timeout.as_secs()),
+        "TIMEOUT",
+        request_id,
+    )
+}
+
+/// Per-operation context shared by the HTTP and WebSocket transports.
+pub fn resolver_context(
+    settings: Arc<Settings>,
     request_id: String,
-    _settings: Arc<Settings>,
-    _body: Value,
+    connection: Option<&ConnectionInfo>,
+    headers: HeaderMap,
+) -> ResolverContext {
+    let context = ResolverContext::new(settings, request_id).with_headers(headers);
+    match connection {
+        Some(connection) => context.with_client(connection.identity()),
+        None => context,
+    }
+}
+
+/// Upgrades to a GraphQL WebSocket speaking `graphql-transport-ws` or the
+/// legacy `graphql-ws` protocol. The `connection_init` payload is treated as
+/// request headers, either at the top level or under `headers`, and overrides
+/// the headers of the upgrade request. Credentials are verified once the
+/// payload is known; failing them closes the connection.
+pub fn handle_graphql_ws(
+    ws: warp::ws::Ws,
+    protocol: WebSocketProtocols,
+    connection: Option<ConnectionInfo>,
+    headers: HeaderMap,
+    settings: Arc<Settings>,
+    schema: RustQLSchema,
+    services: Services,
+) -> impl Reply {
+    let reply = ws.on_upgrade(move |socket| async move {
+        let request_id = generate_request_id();
+        debug!(request_id = %request_id, protocol = protocol.sec_websocket_protocol(), "GraphQL WebSocket connected");
+
+        GraphQLWebSocket::new(socket, schema, protocol)
+            .on_connection_init(move |payload| async move {
+                let mut headers = headers;
+                headers.extend(connection_init_headers(&payload));
+                let claims = services
+                    .auth
+                    .authenticate(&headers, &HashMap::new())
+                    .await
+                    .map_err(|e| {
+                        warn!(request_id = %request_id, error = %e, "Rejected GraphQL WebSocket");
+                        async_graphql::Error::new(e.to_string())
+                    })?;
+
+                let mut data = Data::default();
+                data.insert(
+                    resolver_context(settings, request_id, connection.as_ref(), headers)
+                        .with_claims(claims),
+                );
+                Ok(data)
+            })
+            .serve()
+            .await
+    });
+
+    warp::reply::with_header(
+        reply,
+        "sec-websocket-protocol",
+        protocol.sec_websocket_protocol(),
+    )
+}
+
+/// Reads string entries of a `connection_init` payload as headers. Entries
+/// under a nested `headers` object take precedence over top-level ones.
+pub fn connection_init_headers(payload: &Value) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let Some(object) = payload.as_object() else {
+        return headers;
+    };
+
+    let nested = object.get("headers").and_then(Value::as_object);
+    for entries in [Some(object), nested].into_iter().flatten() {
+        for (name, value) in entries {
+            let Some(value) = value.as_str() else {
+                continue;
+            };
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    headers
+}
+
+/// Publishes the body to subscribers of `topic`. Topics must be configured
+/// under `[[subscriptions]]`; when the topic has a secret, it must be sent in
+/// `X-Webhook-Token`.
+#[instrument(skip(token, settings, broker, body))]
+pub async fn handle_webhook(
+    topic: String,
+    token: Option<String>,
+    settings: Arc<Settings>,
+    broker: WebhookBroker,
+    body: Value,
 ) -> Result<impl Reply, Rejection> {
-    info!(request_id = %request_id, "Processing GraphQL request");
+    let webhook = settings
+        .subscriptions
+        .iter()
+        .filter_map(|s| s.webhook.as_ref())
+        .find(|w| w.topic == topic);
 
-    // For now, return a placeholder response
-    // This will be replaced with actual GraphQL execution in the next step
-    let response = json!({
-        "data": {
-            "message": "GraphQL endpoint is ready",
-            "request_id": request_id,
+    let Some(webhook) = webhook else {
+        return Ok(error_response(StatusCode::NOT_FOUND, "NOT_FOUND", "Unknown webhook topic"));
+    };
+
+    if let Some(secret) = &webhook.secret {
+        let secret = expand_env(secret);
+        let authorized = token
+            .as_deref()
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), secret.as_bytes()));
+        if !authorized {
+            warn!(topic = %topic, "Rejected webhook with invalid token");
+            return Ok(error_response(
+                StatusCode::UNAUTHORIZED,
+                "UNAUTHORIZED",
+                "Invalid webhook token",
+            ));
+        }
+    }
+
+    let delivered = broker.publish(&topic, body);
+    info!(topic = %topic, delivered, "Webhook event published");
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&json!({ "delivered": delivered })),
+        StatusCode::ACCEPTED,
+    )
+    .into_response())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn error_response(status: StatusCode, code: &str, message: &str) -> warp::reply::Response {
+    let json = json!({
+        "error": {
+            "code": code,
+            "message": message,
             "timestamp": chrono::Utc::now().to_rfc3339()
         }
     });
 
-    Ok(warp::reply::with_status(
-        warp::reply::json(&response),
-        StatusCode::OK,
+    warp::reply::with_status(warp::reply::json(&json), status).into_response()
+}
+
+fn graphql_error_response(message: &str, code: &str, request_id: &str) -> Value {
+    json!({
+        "data": null,
+        "errors": [{
+            "message": message,
+            "extensions": {
+                "code": code,
+                "requestId": request_id
+            }
+        }]
+    })
+}
+
+#[instrument(skip(settings))]
+pub async fn handle_playground(settings: Arc<Settings>) -> Result<impl Reply, Rejection> {
+    let html = playground::render_html(&settings.server.playground);
+
+    Ok(warp::reply::with_header(
+        html,
+        "content-type",
+        "text/html; charset=utf-8",
     ))
 }
 
 #[instrument]
-pub async fn handle_playground() -> Result<impl Reply, Rejection> {
-    let playground_html = r#"
-<!DOCTYPE html>
-<html>
-<head>
-    <title>RustQL GraphQL Playground</title>
-    <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/graphql-playground-react/build/static/css/index.css" />
-</head>
-<body>
-    <div id="root">
-        <style>
-            body { margin: 0; font-family: "Open Sans", sans-serif; overflow: hidden; }
-            #root { height: 100vh; }
-        </style>
-    </div>
-    <script src="https://cdn.jsdelivr.net/npm/graphql-playground-react/build/static/js/middleware.js"></script>
-    <script>
-        window.addEventListener('load', function (event) {
-            GraphQLPlayground.init(document.getElementById('root'), {
-                endpoint: '/graphql',
-                settings: {
-                    'general.betaUpdates': false,
-                    'editor.theme': 'dark',
-                    'editor.reuseHeaders': true,
-                    'tracing.hideTracingResponse': true,
-                    'editor.fontSize': 14,
-                    'editor.fontFamily': '"Source Code Pro", "Consolas", "Inconsolata", "Droid Sans Mono", "Monaco", monospace',
-                    'request.credentials': 'omit',
-                }
-            })
-        })
-    </script>
-</body>
-</html>
-    "#;
+pub async fn handle_playground_asset(name: String) -> Result<impl Reply, Rejection> {
+    let bytes = playground::embedded_asset(&name).ok_or_else(warp::reject::not_found)?;
 
+    let reply = warp::reply::with_header(bytes, "content-type", playground::content_type(&name));
     Ok(warp::reply::with_header(
-        playground_html,
-        "content-type",
-        "text/html",
+        reply,
+        "cache-control",
+        "public, max-age=86400",
     ))
 }
 
@@ -101,11 +562,35 @@ pub async fn handle_metrics() -> Result<impl Reply, Rejection> {
 }
 
 pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    if let Some(e) = err.find::<AuthError>() {
+        let mut response = error_response(e.status(), e.code(), &e.to_string());
+        if e.status() == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Bearer"),
+            );
+        }
+        return Ok(response);
+    }
+
+    if let Some(limited) = err.find::<RateLimited>() {
+        let mut response = error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            "RATE_LIMITED",
+            "Rate limit exceeded",
+        );
+        let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return Ok(response);
+    }
+
     let (code, message, error_code) = if err.is_not_found() {
         (StatusCode::NOT_FOUND, "Not Found", "NOT_FOUND")
-    } else if let Some(_) = err.find::<warp::filters::body::BodyDeserializeError>() {
+    } else if err.find::<warp::filters::body::BodyDeserializeError>().is_some() {
         (StatusCode::BAD_REQUEST, "Invalid JSON body", "INVALID_JSON")
-    } else if let Some(_) = err.find::<warp::reject::MethodNotAllowed>() {
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
         (
             StatusCode::METHOD_NOT_ALLOWED,
             "Method Not Allowed",
@@ -120,13 +605,5 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
         )
     };
 
-    let json = json!({
-        "error": {
-            "code": error_code,
-            "message": message,
-            "timestamp": chrono::Utc::now().to_rfc3339()
-        }
-    });
-
-    Ok(warp::reply::with_status(warp::reply::json(&json), code))
+    Ok(error_response(code, error_code, message))
 }
@@ -0,0 +1,192 @@
+use crate::server::tls::{TlsReloader, certificate_subject};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+use warp::hyper::server::conn::Http;
+use warp::hyper::service::{Service, service_fn};
+use warp::{Filter, Reply};
+
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Per-connection information injected into every request's extensions.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub remote_addr: SocketAddr,
+    /// Subject of the verified TLS client certificate, when mTLS is enabled.
+    pub client_subject: Option<String>,
+}
+
+impl ConnectionInfo {
+    pub fn identity(&self) -> ClientIdentity {
+        match &self.client_subject {
+            Some(subject) => ClientIdentity::Certificate(subject.clone()),
+            None => ClientIdentity::Address(self.remote_addr.ip()),
+        }
+    }
+}
+
+/// Who is calling, as seen by resolvers and the rate limiter.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientIdentity {
+    /// Subject of a verified TLS client certificate.
+    Certificate(String),
+    /// Remote IP address of the connection.
+    Address(IpAddr),
+}
+
+impl ClientIdentity {
+    pub fn rate_limit_key(&self) -> String {
+        match self {
+            ClientIdentity::Certificate(subject) => format!("cert:{}", subject),
+            ClientIdentity::Address(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+/// Caps the number of concurrently open client connections.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimiter {
+    semaphore: Option<Arc<Semaphore>>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_connections: Option<usize>) -> Self {
+        Self {
+            semaphore: max_connections.map(|max| Arc::new(Semaphore::new(max))),
+        }
+    }
+
+    /// Waits until a connection slot is free. The slot is released when the
+    /// returned permit is dropped.
+    pub async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match &self.semaphore {
+            Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+            None => None,
+        }
+    }
+
+    pub fn available(&self) -> Option<usize> {
+        self.semaphore.as_ref().map(|s| s.available_permits())
+    }
+}
+
+/// Accepts connections from `listener` and serves `filter` on each of them,
+/// never holding more than the limiter allows open at once. When `tls` is set
+/// every connection is terminated with the reloader's current configuration
+/// and the protocol follows ALPN; plain connections speak HTTP/1.1 and, with
+/// `h2c` enabled, also HTTP/2 with prior knowledge.
+pub async fn serve<F, R>(
+    listener: TcpListener,
+    filter: F,
+    limiter: ConnectionLimiter,
+    tls: Option<TlsReloader>,
+    h2c: bool,
+) where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let service = warp::service(filter);
+
+    loop {
+        let permit = limiter.acquire().await;
+
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Usually EMFILE/ENFILE; back off instead of spinning.
+                warn!(error = %e, "Failed to accept connection");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+
+        let service = service.clone();
+        let acceptor = tls.as_ref().map(TlsReloader::acceptor);
+        tokio::spawn(async move {
+            match acceptor {
+                Some(acceptor) => {
+                    let stream = match tokio::time::timeout(
+                        TLS_HANDSHAKE_TIMEOUT,
+                        acceptor.accept(stream),
+                    )
+                    .await
+                    {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            debug!(remote_addr = %remote_addr, error = %e, "TLS handshake failed");
+                            return;
+                        }
+                        Err(_) => {
+                            debug!(remote_addr = %remote_addr, "TLS handshake timed out");
+                            return;
+                        }
+                    };
+
+                    let session = stream.get_ref().1;
+                    let client_subject = session
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .and_then(|cert| certificate_subject(cert));
+
+                    let mut http = Http::new();
+                    if session.alpn_protocol() == Some(b"h2") {
+                        http.http2_only(true);
+                    } else {
+                        http.http1_only(true);
+                    }
+
+                    let info = ConnectionInfo {
+                        remote_addr,
+                        client_subject,
+                    };
+                    serve_connection(http, stream, service, info).await;
+                }
+                None => {
+                    // Without h1-only, hyper detects the HTTP/2 connection preface
+                    let mut http = Http::new();
+                    if !h2c {
+                        http.http1_only(true);
+                    }
+
+                    let info = ConnectionInfo {
+                        remote_addr,
+                        client_subject: None,
+                    };
+                    serve_connection(http, stream, service, info).await;
+                }
+            }
+
+            drop(permit);
+        });
+    }
+}
+
+async fn serve_connection<IO, S>(http: Http, io: IO, mut service: S, info: ConnectionInfo)
+where
+    IO: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    S: Service<warp::hyper::Request<warp::hyper::Body>, Response = warp::reply::Response>
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let remote_addr = info.remote_addr;
+    let svc = service_fn(move |mut req| {
+        req.extensions_mut().insert(info.clone());
+        service.call(req)
+    });
+
+    if let Err(e) = http
+        .serve_connection(io, svc)
+        .with_upgrades()
+        .await
+    {
+        debug!(remote_addr = %remote_addr, error = %e, "Connection closed with error");
+    }
+}
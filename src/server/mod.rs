@@ -1,10 +1,26 @@
+pub mod compression;
+pub mod cors;
 pub mod handlers;
+pub mod listener;
+pub mod playground;
+pub mod tls;
+pub mod transport;
 
 use crate::config::Settings;
+use crate::graphql::resolvers::ResolverContext;
+use crate::graphql::{RustQLSchema, build_schema};
+use crate::services::Services;
 use crate::utils::{generate_request_id, Result};
+use compression::CompressionPolicy;
+use cors::CorsPolicy;
+use listener::{ConnectionInfo, ConnectionLimiter};
+use tls::TlsReloader;
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{info, instrument};
-use warp::{Filter, Reply};
+use warp::Filter;
 
 pub struct Server {
     settings: Arc<Settings>,
@@ -20,83 +36,266 @@ impl Server {
     #[instrument(skip(self))]
     pub async fn start(self) -> Result<()> {
         let settings = self.settings.clone();
-        
+
         info!(
             "Starting RustQL server on {}:{}",
             settings.server.host, settings.server.port
         );
 
         // Build routes
-        let routes = build_routes(settings.clone());
+        let services = Services::from_settings(&settings)?;
+        let schema = build_schema(settings.clone(), services.clone())?;
+        services.auth.spawn_reload_task();
+        services.rate_limiter.spawn_cleanup_task();
+        let routes = build_routes(settings.clone(), schema, services);
 
         // Start server
         let addr: std::net::SocketAddr = format!("{}:{}", settings.server.host, settings.server.port)
             .parse()
             .map_err(|e| crate::utils::RustQLError::Config(format!("Invalid server address: {}", e)))?;
 
-        warp::serve(routes)
-            .run(addr)
-            .await;
+        let tls = match &settings.server.tls {
+            Some(tls_config) => {
+                let reloader =
+                    TlsReloader::new(tls_config.clone(), settings.server.http2.enabled)?;
+                reloader.spawn_reload_task();
+                Some(reloader)
+            }
+            None => None,
+        };
+
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let limiter = ConnectionLimiter::new(settings.server.max_connections);
+
+        info!(
+            max_connections = ?settings.server.max_connections,
+            request_timeout = ?settings.server.request_timeout,
+            tls = tls.is_some(),
+            http2 = settings.server.http2.enabled,
+            h2c = settings.server.http2.h2c,
+            "Listening on {}",
+            addr
+        );
+
+        listener::serve(listener, routes, limiter, tls, settings.server.http2.h2c).await;
 
         Ok(())
     }
 }
 
-fn build_routes(settings: Arc<Settings>) -> impl Filter<Extract = impl Reply, Error = std::convert::Infallible> + Clone {
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_headers(vec!["content-type", "authorization", "x-request-id"])
-        .allow_methods(vec!["GET", "POST", "OPTIONS"]);
+pub fn build_routes(
+    settings: Arc<Settings>,
+    schema: RustQLSchema,
+    services: Services,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Infallible> + Clone {
+    let cors = Arc::new(CorsPolicy::from_config(&settings.server));
+    let compression = Arc::new(CompressionPolicy::from_config(&settings.server.compression));
 
     // Health check endpoint
     let health = warp::path("health")
         .and(warp::get())
         .and_then(handlers::handle_health);
 
-    // GraphQL endpoint
+    // GraphQL endpoint. The GraphQL routes are boxed to keep the combined
+    // filter type within the compiler's query depth limit.
     let graphql = warp::path("graphql")
         .and(warp::post())
-        .and(with_request_id())
+        .and(with_context(settings.clone(), services.clone()))
         .and(with_settings(settings.clone()))
+        .and(with_schema(schema.clone()))
+        .and(with_services(services.clone()))
         .and(warp::body::json())
-        .and_then(handlers::handle_graphql);
+        .and_then(handlers::handle_graphql)
+        .boxed();
+
+    // GraphQL subscriptions over WebSocket
+    let graphql_ws = warp::path("graphql")
+        .and(warp::path::end())
+        .and(warp::ws())
+        .and(async_graphql_warp::graphql_protocol())
+        .and(warp::ext::optional::<ConnectionInfo>())
+        .and(warp::header::headers_cloned())
+        .and(with_settings(settings.clone()))
+        .and(with_schema(schema.clone()))
+        .and(with_services(services.clone()))
+        .map(handlers::handle_graphql_ws);
 
-    // GraphQL playground
+    // Queries over GET
+    let graphql_get = warp::path("graphql")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_context(settings.clone(), services.clone()))
+        .and(with_settings(settings.clone()))
+        .and(with_schema(schema))
+        .and(with_services(services.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(handlers::handle_graphql_get)
+        .boxed();
+
+    // Inbound events for webhook-backed subscriptions
+    let webhooks = warp::path!("webhooks" / String)
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-webhook-token"))
+        .and(with_settings(settings.clone()))
+        .and(warp::any().map(move || services.broker.clone()))
+        .and(warp::body::json())
+        .and_then(handlers::handle_webhook);
+
+    // GraphQL playground, only mounted when enabled
+    let playground_enabled = settings.server.enable_playground;
     let playground = warp::path("playground")
+        .and(warp::path::end())
         .and(warp::get())
+        .and(enabled(playground_enabled))
+        .and(with_settings(settings.clone()))
         .and_then(handlers::handle_playground);
 
+    let playground_assets = warp::path!("playground" / "assets" / String)
+        .and(warp::get())
+        .and(enabled(playground_enabled))
+        .and_then(handlers::handle_playground_asset);
+
     // Metrics endpoint
     let metrics = warp::path("metrics")
         .and(warp::get())
         .and_then(handlers::handle_metrics);
 
-    health
+    let routes = cors::preflight(cors.clone())
+        .or(health)
         .or(graphql)
+        .or(graphql_ws)
+        .or(graphql_get)
+        .or(webhooks)
         .or(playground)
+        .or(playground_assets)
         .or(metrics)
-        .with(with_logging())
-        .with(cors)
-        .recover(handlers::handle_rejection)
+        .recover(handlers::handle_rejection);
+
+    with_logging(compression::with_compression(
+        compression,
+        cors::with_cors(cors, routes),
+    ))
 }
 
 fn with_settings(settings: Arc<Settings>) -> impl Filter<Extract = (Arc<Settings>,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || settings.clone())
 }
 
+fn with_schema(schema: RustQLSchema) -> impl Filter<Extract = (RustQLSchema,), Error = Infallible> + Clone {
+    warp::any().map(move || schema.clone())
+}
+
+fn with_services(services: Services) -> impl Filter<Extract = (Services,), Error = Infallible> + Clone {
+    warp::any().map(move || services.clone())
+}
+
+/// Builds the `ResolverContext` of an HTTP request after verifying its
+/// credentials and charging it against the rate limits; invalid credentials
+/// reject with an `AuthError`, exhausted quotas with `RateLimited`. Callers
+/// that authenticate are charged against their key or token subject only,
+/// with the key's own quota; anonymous callers and failed attempts are
+/// charged against the client address.
+fn with_context(
+    settings: Arc<Settings>,
+    services: Services,
+) -> impl Filter<Extract = (ResolverContext,), Error = warp::Rejection> + Clone {
+    with_request_id()
+        .and(warp::ext::optional::<ConnectionInfo>())
+        .and(warp::header::headers_cloned())
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(
+            move |request_id: String,
+                  connection: Option<ConnectionInfo>,
+                  headers: warp::http::HeaderMap,
+                  query: HashMap<String, String>| {
+                let settings = settings.clone();
+                let services = services.clone();
+                async move {
+                    let claims = match services.auth.authenticate(&headers, &query).await {
+                        Ok(claims) => claims,
+                        Err(e) => {
+                            let client_key = connection
+                                .as_ref()
+                                .map(|connection| connection.identity().rate_limit_key());
+                            if let Some(key) = client_key {
+                                services
+                                    .rate_limiter
+                                    .check(&key, None)
+                                    .map_err(warp::reject::custom)?;
+                            }
+                            return Err(warp::reject::custom(e));
+                        }
+                    };
+                    let quota = claims
+                        .as_ref()
+                        .and_then(|claims| services.auth.rate_limit(claims));
+                    let context = handlers::resolver_context(
+                        settings,
+                        request_id,
+                        connection.as_ref(),
+                        headers,
+                    )
+                    .with_claims(claims);
+
+                    if let Some(key) = context.rate_limit_key() {
+                        services
+                            .rate_limiter
+                            .check(&key, quota)
+                            .map_err(warp::reject::custom)?;
+                    }
+                    Ok::<_, warp::Rejection>(context)
+                }
+            },
+        )
+}
+
+/// Passes through when `flag` is set and rejects as not found otherwise, so
+/// disabled routes are indistinguishable from unmounted ones.
+fn enabled(flag: bool) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::any()
+        .and_then(move || async move {
+            if flag {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+}
+
 fn with_request_id() -> impl Filter<Extract = (String,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(|| generate_request_id())
+    warp::any().map(generate_request_id)
 }
 
-fn with_logging() -> warp::log::Log<impl Fn(warp::log::Info) + Clone> {
-    warp::log::custom(|info| {
-        info!(
-            method = %info.method(),
-            path = %info.path(),
-            status = %info.status(),
-            elapsed = ?info.elapsed(),
-            remote_addr = ?info.remote_addr(),
-            "HTTP request processed"
-        );
-    })
-}
\ No newline at end of file
+/// Logs every processed request, including ones answered by the rejection handler.
+/// The remote address comes from the `ConnectionInfo` set by the listener.
+fn with_logging<F>(
+    filter: F,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Infallible> + Clone
+where
+    F: Filter<Extract = (warp::reply::Response,), Error = Infallible> + Clone + Send + Sync + 'static,
+{
+    warp::any()
+        .map(Instant::now)
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(warp::ext::optional::<ConnectionInfo>())
+        .and(filter)
+        .map(
+            |start: Instant,
+             method: warp::http::Method,
+             path: warp::path::FullPath,
+             conn: Option<ConnectionInfo>,
+             response: warp::reply::Response| {
+                info!(
+                    method = %method,
+                    path = %path.as_str(),
+                    status = %response.status(),
+                    elapsed = ?start.elapsed(),
+                    remote_addr = ?conn.map(|c| c.remote_addr),
+                    "HTTP request processed"
+                );
+                response
+            },
+        )
+}
@@ -0,0 +1,95 @@
+use crate::config::settings::{PlaygroundConfig, PlaygroundUi};
+use serde_json::json;
+
+/// URL prefix under which embedded assets are served.
+pub const ASSET_PREFIX: &str = "/playground/assets";
+
+/// The playground's script and stylesheet, compiled into the binary so the
+/// page works without access to a CDN.
+const EMBEDDED_ASSETS: &[(&str, &[u8])] = &[
+    ("playground.js", include_bytes!("../../assets/playground/playground.js")),
+    ("playground.css", include_bytes!("../../assets/playground/playground.css")),
+];
+
+pub fn embedded_asset(name: &str) -> Option<&'static [u8]> {
+    EMBEDDED_ASSETS
+        .iter()
+        .find(|(asset, _)| *asset == name)
+        .map(|(_, bytes)| *bytes)
+}
+
+pub fn content_type(name: &str) -> &'static str {
+    match name.rsplit('.').next() {
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The playground page. Both UIs run the embedded editor; `graphiql` renders
+/// it light and `playground` dark.
+pub fn render_html(config: &PlaygroundConfig) -> String {
+    // Serialized as JSON and embedded in a <script>; escape "</" so a header value
+    // cannot close the script tag.
+    let options = json!({
+        "endpoint": config.endpoint,
+        "headers": config.headers,
+    })
+    .to_string()
+    .replace("</", "<\\/");
+
+    let title = html_escape(&config.title);
+    let theme = match config.ui {
+        PlaygroundUi::GraphiQL => "light",
+        PlaygroundUi::Playground => "dark",
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8" />
+    <title>{title}</title>
+    <link rel="stylesheet" href="{prefix}/playground.css" />
+</head>
+<body class="{theme}">
+    <header>
+        <h1>{title}</h1>
+        <button id="docs">Docs</button>
+        <button id="run" class="primary" title="Ctrl+Enter">Run</button>
+    </header>
+    <main>
+        <section>
+            <textarea id="query" spellcheck="false" aria-label="Query"></textarea>
+            <div class="tabs">
+                <button class="active" data-tab="variables">Variables</button>
+                <button data-tab="headers">Headers</button>
+            </div>
+            <textarea id="query-extra" spellcheck="false" aria-label="Variables or headers"></textarea>
+        </section>
+        <section>
+            <pre id="result"></pre>
+        </section>
+        <section id="explorer" hidden></section>
+    </main>
+    <script id="playground-options" type="application/json">{options}</script>
+    <script src="{prefix}/playground.js"></script>
+</body>
+</html>
+"#,
+        prefix = ASSET_PREFIX,
+    )
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
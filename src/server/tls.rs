@@ -0,0 +1,171 @@
+use crate::config::settings::{ClientAuthMode, TlsConfig};
+use crate::utils::{Result, RustQLError};
+use rustls::RootCertStore;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info};
+
+/// Holds the current TLS server configuration and swaps it when the
+/// certificate, key or client CA files change on disk. Connections that are
+/// already established keep the configuration they were accepted with.
+#[derive(Clone)]
+pub struct TlsReloader {
+    config: TlsConfig,
+    http2: bool,
+    current: Arc<RwLock<Arc<rustls::ServerConfig>>>,
+}
+
+impl TlsReloader {
+    pub fn new(config: TlsConfig, http2: bool) -> Result<Self> {
+        let server_config = load_server_config(&config, http2)?;
+
+        Ok(Self {
+            config,
+            http2,
+            current: Arc::new(RwLock::new(Arc::new(server_config))),
+        })
+    }
+
+    pub fn acceptor(&self) -> TlsAcceptor {
+        let config = self.current.read().expect("TLS config lock poisoned").clone();
+        TlsAcceptor::from(config)
+    }
+
+    pub fn reload(&self) -> Result<()> {
+        let server_config = load_server_config(&self.config, self.http2)?;
+        *self.current.write().expect("TLS config lock poisoned") = Arc::new(server_config);
+        Ok(())
+    }
+
+    /// Polls the configured files every `reload_interval` seconds and reloads
+    /// the configuration when any modification time changes.
+    pub fn spawn_reload_task(&self) {
+        let Some(interval) = self.config.reload_interval else {
+            return;
+        };
+
+        let reloader = self.clone();
+        tokio::spawn(async move {
+            let mut last_modified = reloader.modified_times();
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let modified = reloader.modified_times();
+                if modified == last_modified {
+                    continue;
+                }
+
+                match reloader.reload() {
+                    Ok(()) => {
+                        info!(cert_path = %reloader.config.cert_path, "Reloaded TLS certificates");
+                        last_modified = modified;
+                    }
+                    // Keep serving with the previous certificates; the files may
+                    // be mid-rotation and will be retried on the next tick.
+                    Err(e) => error!(error = %e, "Failed to reload TLS certificates"),
+                }
+            }
+        });
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        [
+            Some(&self.config.cert_path),
+            Some(&self.config.key_path),
+            self.config.client_ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+    }
+}
+
+/// Builds the rustls configuration. With `http2` set, `h2` is offered via ALPN
+/// ahead of `http/1.1`.
+pub fn load_server_config(config: &TlsConfig, http2: bool) -> Result<rustls::ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let certs = load_certs(&config.cert_path)?;
+    let key = load_private_key(&config.key_path)?;
+
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| RustQLError::Config(format!("Invalid TLS protocol configuration: {}", e)))?;
+
+    let builder = match &config.client_ca_path {
+        Some(ca_path) => {
+            let verifier = client_verifier(ca_path, config.client_auth, provider)?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| RustQLError::Config(format!("Invalid TLS certificate or key: {}", e)))?;
+
+    server_config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+
+    Ok(server_config)
+}
+
+fn client_verifier(
+    ca_path: &str,
+    mode: ClientAuthMode,
+    provider: Arc<CryptoProvider>,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_path)? {
+        roots
+            .add(cert)
+            .map_err(|e| RustQLError::Config(format!("Invalid client CA certificate: {}", e)))?;
+    }
+
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+    let builder = match mode {
+        ClientAuthMode::Required => builder,
+        ClientAuthMode::Optional => builder.allow_unauthenticated(),
+    };
+
+    builder
+        .build()
+        .map_err(|e| RustQLError::Config(format!("Invalid client CA configuration: {}", e)))
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<std::io::Result<Vec<_>>>()?;
+
+    if certs.is_empty() {
+        return Err(RustQLError::Config(format!("No certificates found in {}", path)));
+    }
+
+    Ok(certs)
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| RustQLError::Config(format!("No private key found in {}", path)))
+}
+
+/// Subject distinguished name of a DER-encoded certificate, e.g. `CN=client,O=Acme`.
+pub fn certificate_subject(der: &[u8]) -> Option<String> {
+    x509_parser::parse_x509_certificate(der)
+        .ok()
+        .map(|(_, cert)| cert.subject().to_string())
+}
@@ -0,0 +1,135 @@
+use futures_util::stream::{Stream, StreamExt};
+use std::convert::Infallible;
+use warp::Reply;
+use warp::http::{HeaderValue, StatusCode, header};
+use warp::hyper::Body;
+use warp::reply::Response;
+use warp::sse::Event;
+
+/// Boundary of incremental delivery responses, per the `deferSpec=20220824` format.
+const MULTIPART_CONTENT_TYPE: &str = "multipart/mixed; boundary=\"-\"; deferSpec=20220824";
+const MULTIPART_PART_HEADER: &str =
+    "\r\n---\r\ncontent-type: application/json; charset=utf-8\r\n\r\n";
+const MULTIPART_END: &str = "\r\n-----\r\n";
+
+/// How a `/graphql` response is delivered, chosen from the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// A single `application/json` document, always sent with 200 OK.
+    Json,
+    /// A single `application/graphql-response+json` document; requests that
+    /// fail before execution are answered with 4xx per GraphQL over HTTP.
+    GraphQLResponseJson,
+    /// Server-Sent Events following the graphql-sse protocol.
+    EventStream,
+    /// `multipart/mixed` incremental delivery for `@defer`/`@stream`.
+    Multipart,
+}
+
+impl ResponseFormat {
+    /// Picks the acceptable format with the highest `q` value, preferring
+    /// earlier entries on ties and JSON when nothing streaming is accepted.
+    pub fn negotiate(accept: Option<&str>) -> Self {
+        let Some(accept) = accept else {
+            return ResponseFormat::Json;
+        };
+
+        let mut best: Option<(ResponseFormat, f32)> = None;
+        for part in accept.split(',') {
+            let mut params = part.split(';');
+            let mime = params.next().unwrap_or("").trim().to_ascii_lowercase();
+            let q = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let format = match mime.as_str() {
+                "application/graphql-response+json" => ResponseFormat::GraphQLResponseJson,
+                "text/event-stream" => ResponseFormat::EventStream,
+                "multipart/mixed" => ResponseFormat::Multipart,
+                _ => ResponseFormat::Json,
+            };
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((format, q));
+            }
+        }
+
+        best.map(|(format, _)| format)
+            .unwrap_or(ResponseFormat::Json)
+    }
+}
+
+impl ResponseFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::GraphQLResponseJson => {
+                "application/graphql-response+json; charset=utf-8"
+            }
+            ResponseFormat::EventStream => "text/event-stream",
+            ResponseFormat::Multipart => MULTIPART_CONTENT_TYPE,
+            ResponseFormat::Json => "application/json",
+        }
+    }
+}
+
+/// Serializes `body` as a single JSON response in the negotiated content type.
+pub fn json_reply<T: serde::Serialize>(
+    body: &T,
+    status: StatusCode,
+    format: ResponseFormat,
+) -> Response {
+    let content_type = match format {
+        ResponseFormat::GraphQLResponseJson => format.content_type(),
+        _ => ResponseFormat::Json.content_type(),
+    };
+
+    let mut response = warp::reply::with_status(warp::reply::json(body), status).into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+/// Streams each payload as a `next` event and finishes with `complete`.
+pub fn event_stream_reply<S>(payloads: S) -> Response
+where
+    S: Stream<Item = serde_json::Value> + Send + 'static,
+{
+    let events = payloads
+        .map(|payload| Event::default().event("next").data(payload.to_string()))
+        .chain(futures_util::stream::once(async {
+            Event::default().event("complete").data("")
+        }))
+        .map(Ok::<_, Infallible>);
+
+    let mut response =
+        warp::reply::Reply::into_response(warp::sse::reply(warp::sse::keep_alive().stream(events)));
+    // Keep intermediaries such as nginx from buffering the stream
+    response
+        .headers_mut()
+        .insert("x-accel-buffering", HeaderValue::from_static("no"));
+    response
+}
+
+/// Writes each payload as a part of a `multipart/mixed` body.
+pub fn multipart_reply<S>(payloads: S) -> Response
+where
+    S: Stream<Item = serde_json::Value> + Send + 'static,
+{
+    let parts = payloads
+        .map(|payload| format!("{}{}", MULTIPART_PART_HEADER, payload))
+        .chain(futures_util::stream::once(async {
+            MULTIPART_END.to_string()
+        }))
+        .map(Ok::<_, Infallible>);
+
+    let mut response = Response::new(Body::wrap_stream(parts));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(MULTIPART_CONTENT_TYPE),
+    );
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
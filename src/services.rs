@@ -0,0 +1,80 @@
+use crate::auth::Authenticator;
+use crate::cache::CacheManager;
+use crate::cache::response::ResponseCache;
+use crate::config::Settings;
+use crate::graphql::WebhookBroker;
+use crate::graphql::persisted::PersistedQueries;
+use crate::graphql::resolvers::ResolverContext;
+use crate::rate_limit::RateLimiter;
+use crate::rest::RestClient;
+use crate::utils::{Result, sha256_hex};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Long-lived components shared by the schema and the HTTP handlers.
+#[derive(Clone)]
+pub struct Services {
+    pub auth: Authenticator,
+    pub broker: WebhookBroker,
+    pub cache: Arc<CacheManager>,
+    pub persisted_queries: PersistedQueries,
+    pub rate_limiter: RateLimiter,
+    pub response_cache: ResponseCache,
+    /// One client per `apis.rest` entry, so every field calling an API shares
+    /// its circuit breaker
+    pub upstreams: Arc<HashMap<String, RestClient>>,
+}
+
+impl Services {
+    pub fn upstream(&self, name: &str) -> Option<&RestClient> {
+        self.upstreams.get(name)
+    }
+
+    /// Separates cached responses between callers: their credentials and
+    /// whatever headers the upstreams' `propagate` rules take from them.
+    pub fn cache_scope(&self, context: &ResolverContext) -> Option<String> {
+        let mut names: Vec<&String> = self.upstreams.keys().collect();
+        names.sort();
+        let propagated: Vec<String> = names
+            .into_iter()
+            .filter_map(|name| {
+                let client = self.upstreams[name].for_caller(context);
+                Some(format!("{}={}", name, client.vary()?))
+            })
+            .collect();
+
+        match (context.auth_scope(), propagated.is_empty()) {
+            (scope, true) => scope,
+            (scope, false) => Some(sha256_hex(&format!(
+                "{}|{}",
+                scope.unwrap_or_default(),
+                propagated.join("|")
+            ))),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Result<Self> {
+        let cache = CacheManager::from_config(&settings.cache)?
+            .with_refresh_timeout(settings.server.request_timeout.map(Duration::from_secs));
+        let cache = Arc::new(cache);
+        let persisted_queries = PersistedQueries::new(&settings.persisted_queries, cache.clone())?;
+        let response_cache = ResponseCache::new(&settings.cache.response, cache.clone());
+        let upstreams = settings
+            .apis
+            .rest
+            .iter()
+            .map(|api| Ok((api.name.clone(), RestClient::from_config(api)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(Self {
+            auth: Authenticator::from_config(&settings.auth)?,
+            broker: WebhookBroker::new(),
+            cache,
+            persisted_queries,
+            rate_limiter: RateLimiter::from_config(&settings.rate_limiting),
+            response_cache,
+            upstreams: Arc::new(upstreams),
+        })
+    }
+}
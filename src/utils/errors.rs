@@ -1,3 +1,4 @@
+use async_graphql::ErrorExtensions;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,7 +10,18 @@ pub enum RustQLError {
     GraphQL(String),
 
     #[error("REST API error: {message} (status: {status})")]
-    RestApi { message: String, status: u16 },
+    RestApi {
+        api: String,
+        message: String,
+        status: u16,
+    },
+
+    /// An upstream payload the configured result mapping could not convert
+    #[error("Unexpected response from API '{api}': {message}")]
+    UnexpectedResponse { api: String, message: String },
+
+    #[error("Circuit open for API '{0}'")]
+    CircuitOpen(String),
 
     #[error("Cache error: {0}")]
     Cache(String),
@@ -47,6 +59,8 @@ impl RustQLError {
             RustQLError::Config(_) => 500,
             RustQLError::GraphQL(_) => 400,
             RustQLError::RestApi { status, .. } => *status,
+            RustQLError::UnexpectedResponse { .. } => 502,
+            RustQLError::CircuitOpen(_) => 503,
             RustQLError::Cache(_) => 500,
             RustQLError::RateLimit(_) => 429,
             RustQLError::Auth(_) => 401,
@@ -64,6 +78,8 @@ impl RustQLError {
             RustQLError::Config(_) => "CONFIG_ERROR",
             RustQLError::GraphQL(_) => "GRAPHQL_ERROR",
             RustQLError::RestApi { .. } => "REST_API_ERROR",
+            RustQLError::UnexpectedResponse { .. } => "UNEXPECTED_RESPONSE",
+            RustQLError::CircuitOpen(_) => "CIRCUIT_OPEN",
             RustQLError::Cache(_) => "CACHE_ERROR",
             RustQLError::RateLimit(_) => "RATE_LIMIT_EXCEEDED",
             RustQLError::Auth(_) => "AUTHENTICATION_ERROR",
@@ -75,4 +91,52 @@ impl RustQLError {
             RustQLError::Io(_) => "IO_ERROR",
         }
     }
+
+    /// Whether the upstream itself is failing (5xx, unreachable or behind an
+    /// open circuit), as opposed to rejecting this particular request.
+    pub fn is_upstream_unavailable(&self) -> bool {
+        match self {
+            RustQLError::RestApi { status, .. } => *status >= 500,
+            RustQLError::Network(_) | RustQLError::CircuitOpen(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the same request may succeed if retried later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RustQLError::RestApi { status, .. } => *status >= 500 || matches!(status, 408 | 429),
+            RustQLError::RateLimit(_) => true,
+            _ => self.is_upstream_unavailable(),
+        }
+    }
+
+    /// Failures of the gateway itself, whose messages may reveal internals.
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            RustQLError::Internal(_) | RustQLError::Redis(_) | RustQLError::Io(_)
+        )
+    }
+}
+
+/// Resolver errors carry `code` and `retryable` extensions, plus `api` for
+/// errors of an upstream and `upstreamStatus` for its error responses.
+impl ErrorExtensions for RustQLError {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", self.error_code());
+            match self {
+                RustQLError::RestApi { api, status, .. } => {
+                    extensions.set("api", api.as_str());
+                    extensions.set("upstreamStatus", *status);
+                }
+                RustQLError::UnexpectedResponse { api, .. } | RustQLError::CircuitOpen(api) => {
+                    extensions.set("api", api.as_str())
+                }
+                _ => {}
+            }
+            extensions.set("retryable", self.is_retryable());
+        })
+    }
 }
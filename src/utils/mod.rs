@@ -24,3 +24,28 @@ pub fn sanitize_query(query: &str) -> String {
         .collect::<Vec<_>>()
         .join(" ")
 }
+
+/// Parses a human-readable size such as `512KB`, `64MB` or `1GB` into bytes.
+pub fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: usize = number.parse().ok()?;
+
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "K" => 1 << 10,
+        "MB" | "M" => 1 << 20,
+        "GB" | "G" => 1 << 30,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+/// Lowercase hex SHA-256 digest, as used for persisted query hashes and cache keys.
+pub fn sha256_hex(input: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(input.as_bytes()))
+}
@@ -1,4 +1,14 @@
+use async_graphql::Request;
+use rustql::config::settings::{FieldType, QueryConfig, RelationshipConfig, RestApiConfig};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::graphql::{RustQLSchema, build_schema};
+use rustql::{Services, Settings};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use warp::Filter;
+use warp::http::HeaderMap;
 
 pub fn sample_graphql_query() -> &'static str {
     r#"
@@ -36,3 +46,83 @@ pub fn complex_graphql_query() -> &'static str {
     }
     "#
 }
+
+/// Serves `upstream` on an ephemeral local port.
+pub fn serve<F>(upstream: F) -> SocketAddr
+where
+    F: Filter + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+{
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+pub fn api(name: &str, addr: SocketAddr) -> RestApiConfig {
+    RestApiConfig {
+        name: name.to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    }
+}
+
+/// A query field on `api`, returning JSON when `returns` is unset.
+pub fn query(
+    field: &str,
+    api: &str,
+    path: &str,
+    arguments: &[&str],
+    returns: Option<&str>,
+) -> QueryConfig {
+    QueryConfig {
+        field: field.to_string(),
+        api: api.to_string(),
+        path: path.to_string(),
+        arguments: arguments.iter().map(|a| a.to_string()).collect(),
+        returns: returns.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+pub fn relationship(field: &str, api: &str, path: &str, returns: &str) -> RelationshipConfig {
+    RelationshipConfig {
+        field: field.to_string(),
+        api: api.to_string(),
+        path: path.to_string(),
+        returns: Some(returns.to_string()),
+        ..Default::default()
+    }
+}
+
+pub fn fields(fields: &[(&str, FieldType)]) -> HashMap<String, FieldType> {
+    fields
+        .iter()
+        .map(|(name, ty)| (name.to_string(), *ty))
+        .collect()
+}
+
+/// The schema of `settings`, which must be valid.
+pub fn schema(settings: Settings) -> RustQLSchema {
+    build(&Arc::new(settings))
+}
+
+/// Executes `query` against the schema of `settings`, returning the whole
+/// response.
+pub async fn execute(settings: Settings, query: &str) -> Value {
+    execute_with_headers(settings, query, HeaderMap::new()).await
+}
+
+/// Executes `query` as a request carrying `headers`.
+pub async fn execute_with_headers(settings: Settings, query: &str, headers: HeaderMap) -> Value {
+    let settings = Arc::new(settings);
+    let schema = build(&settings);
+    let context = ResolverContext::new(settings, "req-1".to_string()).with_headers(headers);
+    let response = schema.execute(Request::new(query).data(context)).await;
+    serde_json::to_value(response).unwrap()
+}
+
+fn build(settings: &Arc<Settings>) -> RustQLSchema {
+    settings.validate().unwrap();
+    let services = Services::from_settings(settings).unwrap();
+    build_schema(settings.clone(), services).unwrap()
+}
@@ -0,0 +1,344 @@
+use async_graphql::Value as GraphQLValue;
+use async_graphql::dynamic::{Field, FieldFuture, Object, Schema, TypeRef};
+use rustql::config::settings::{ApiKeyConfig, ApiKeysConfig, KeyRateLimit, RateLimitConfig};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::rate_limit::RateLimiter;
+use rustql::server::build_routes;
+use rustql::server::listener::ConnectionInfo;
+use rustql::utils::sha256_hex;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use warp::Filter;
+use warp::reply::Response;
+
+fn key(name: &str, secret: &str) -> ApiKeyConfig {
+    ApiKeyConfig {
+        name: name.to_string(),
+        key_hash: sha256_hex(secret),
+        scopes: vec!["read:orders".to_string()],
+        ..Default::default()
+    }
+}
+
+/// Routes over a schema whose `viewer` field returns the caller's subject,
+/// scopes and rate limit bucket, along with their services.
+fn routes(
+    api_keys: ApiKeysConfig,
+) -> (
+    impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone,
+    Services,
+) {
+    limited_routes(api_keys, Settings::default().rate_limiting)
+}
+
+/// `routes` with the given global rate limit.
+fn limited_routes(
+    api_keys: ApiKeysConfig,
+    rate_limiting: RateLimitConfig,
+) -> (
+    impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone,
+    Services,
+) {
+    let mut settings = Settings::default();
+    settings.auth.api_keys = Some(api_keys);
+    settings.rate_limiting = rate_limiting;
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+
+    let query = Object::new("Query").field(Field::new(
+        "viewer",
+        TypeRef::named_nn(TypeRef::STRING),
+        |ctx| {
+            FieldFuture::new(async move {
+                let context = ctx.data::<ResolverContext>()?;
+                let viewer = match &context.claims {
+                    Some(claims) => format!(
+                        "{} [{}] {}",
+                        claims.subject().unwrap_or_default(),
+                        claims.scopes().join(" "),
+                        context.rate_limit_key().unwrap_or_default()
+                    ),
+                    None => "anonymous".to_string(),
+                };
+                Ok(Some(GraphQLValue::from(viewer)))
+            })
+        },
+    ));
+    let schema = Schema::build("Query", None, None)
+        .register(query)
+        .finish()
+        .unwrap();
+
+    (build_routes(settings, schema, services.clone()), services)
+}
+
+async fn post(
+    routes: &(impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone + 'static),
+    path: &str,
+    api_key: Option<&str>,
+) -> (u16, Value, Option<String>) {
+    let mut request = warp::test::request()
+        .method("POST")
+        .path(path)
+        .json(&json!({ "query": "{ viewer }" }));
+    if let Some(api_key) = api_key {
+        request = request.header("x-api-key", api_key);
+    }
+    let response = request.reply(routes).await;
+    let retry_after = response
+        .headers()
+        .get("retry-after")
+        .map(|v| v.to_str().unwrap().to_string());
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(response.body()).unwrap(),
+        retry_after,
+    )
+}
+
+#[tokio::test]
+async fn test_api_key_from_header_exposes_scopes() {
+    let (routes, _) = routes(ApiKeysConfig {
+        keys: vec![key("partner-a", "secret-a")],
+        ..Default::default()
+    });
+
+    let (status, body, _) = post(&routes, "/graphql", Some("secret-a")).await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["data"]["viewer"],
+        "partner-a [read:orders] key:partner-a"
+    );
+
+    let (status, body, _) = post(&routes, "/graphql", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["viewer"], "anonymous");
+}
+
+#[tokio::test]
+async fn test_api_key_from_query_parameter() {
+    let (routes, _) = routes(ApiKeysConfig {
+        query_param: Some("api_key".to_string()),
+        keys: vec![key("partner-a", "secret-a")],
+        ..Default::default()
+    });
+
+    let (status, body, _) = post(&routes, "/graphql?api_key=secret-a", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["data"]["viewer"],
+        "partner-a [read:orders] key:partner-a"
+    );
+
+    let response = warp::test::request()
+        .method("GET")
+        .path("/graphql?api_key=secret-a&query=%7Bviewer%7D")
+        .reply(&routes)
+        .await;
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(
+        body["data"]["viewer"],
+        "partner-a [read:orders] key:partner-a"
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_and_expired_keys_rejected() {
+    let expired = ApiKeyConfig {
+        expires_at: Some(chrono::Utc::now() - chrono::Duration::minutes(1)),
+        ..key("partner-b", "secret-b")
+    };
+    let (routes, _) = routes(ApiKeysConfig {
+        keys: vec![key("partner-a", "secret-a"), expired],
+        ..Default::default()
+    });
+
+    for api_key in ["wrong", "secret-b"] {
+        let (status, body, _) = post(&routes, "/graphql", Some(api_key)).await;
+        assert_eq!(status, 401, "{}", api_key);
+        assert_eq!(body["error"]["code"], "INVALID_API_KEY");
+    }
+}
+
+#[tokio::test]
+async fn test_missing_key_rejected_when_required() {
+    let (routes, _) = routes(ApiKeysConfig {
+        required: true,
+        keys: vec![key("partner-a", "secret-a")],
+        ..Default::default()
+    });
+
+    let (status, body, _) = post(&routes, "/graphql", None).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["error"]["code"], "UNAUTHENTICATED");
+}
+
+#[tokio::test]
+async fn test_per_key_rate_limit_override() {
+    let limited = ApiKeyConfig {
+        rate_limit: Some(KeyRateLimit {
+            requests_per_minute: 1,
+            burst_size: 2,
+        }),
+        ..key("partner-a", "secret-a")
+    };
+    let (routes, _) = routes(ApiKeysConfig {
+        keys: vec![limited, key("partner-b", "secret-b")],
+        ..Default::default()
+    });
+
+    for _ in 0..2 {
+        let (status, _, _) = post(&routes, "/graphql", Some("secret-a")).await;
+        assert_eq!(status, 200);
+    }
+    let (status, body, retry_after) = post(&routes, "/graphql", Some("secret-a")).await;
+    assert_eq!(status, 429);
+    assert_eq!(body["error"]["code"], "RATE_LIMITED");
+    let retry_after: u64 = retry_after.unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after));
+
+    // Other keys keep the global quota
+    let (status, _, _) = post(&routes, "/graphql", Some("secret-b")).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_keys_file_reloaded_on_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.toml");
+    let write_keys = |name: &str, secret: &str| {
+        let document = format!(
+            "[[keys]]\nname = \"{}\"\nkey_hash = \"{}\"\nscopes = [\"read:orders\"]\n",
+            name,
+            sha256_hex(secret)
+        );
+        std::fs::write(&path, document).unwrap();
+    };
+    write_keys("partner-a", "secret-a");
+
+    let (routes, services) = routes(ApiKeysConfig {
+        keys_path: Some(path.display().to_string()),
+        reload_interval: 1,
+        ..Default::default()
+    });
+    services.auth.spawn_reload_task();
+
+    let (status, _, _) = post(&routes, "/graphql", Some("secret-a")).await;
+    assert_eq!(status, 200);
+
+    write_keys("partner-c", "secret-c");
+    let mut status = 0;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (status, _, _) = post(&routes, "/graphql", Some("secret-c")).await;
+        if status == 200 {
+            break;
+        }
+    }
+    assert_eq!(status, 200);
+
+    let (status, _, _) = post(&routes, "/graphql", Some("secret-a")).await;
+    assert_eq!(status, 401);
+}
+
+/// A global quota of three requests that does not refill during a test.
+fn slow_quota() -> RateLimitConfig {
+    RateLimitConfig {
+        requests_per_minute: 1,
+        burst_size: 3,
+        enable_per_ip: true,
+    }
+}
+
+/// A GraphQL request from `ip`, with `api_key` if given.
+fn request_from(ip: [u8; 4], api_key: Option<&str>) -> warp::test::RequestBuilder {
+    let mut request = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .extension(ConnectionInfo {
+            remote_addr: (ip, 4000).into(),
+            client_subject: None,
+        })
+        .json(&json!({ "query": "{ viewer }" }));
+    if let Some(api_key) = api_key {
+        request = request.header("x-api-key", api_key);
+    }
+    request
+}
+
+#[tokio::test]
+async fn test_ip_callers_limited_per_address() {
+    let (routes, _) = limited_routes(ApiKeysConfig::default(), slow_quota());
+
+    for _ in 0..3 {
+        let response = request_from([10, 0, 0, 1], None).reply(&routes).await;
+        assert_eq!(response.status(), 200);
+    }
+    let response = request_from([10, 0, 0, 1], None).reply(&routes).await;
+    assert_eq!(response.status(), 429);
+    let response = request_from([10, 0, 0, 2], None).reply(&routes).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_failed_credentials_limited_per_address() {
+    let (routes, _) = limited_routes(
+        ApiKeysConfig {
+            keys: vec![key("partner-a", "secret-a")],
+            ..Default::default()
+        },
+        slow_quota(),
+    );
+    let request = |secret| request_from([10, 0, 0, 1], Some(secret));
+
+    // Guessing keys exhausts the address's quota
+    for _ in 0..3 {
+        assert_eq!(request("guess").reply(&routes).await.status(), 401);
+    }
+    assert_eq!(request("guess").reply(&routes).await.status(), 429);
+    // A valid key is charged against its own bucket instead
+    assert_eq!(request("secret-a").reply(&routes).await.status(), 200);
+}
+
+#[tokio::test]
+async fn test_key_override_not_capped_by_address() {
+    let generous = ApiKeyConfig {
+        rate_limit: Some(KeyRateLimit {
+            requests_per_minute: 1,
+            burst_size: 5,
+        }),
+        ..key("partner-a", "secret-a")
+    };
+    let (routes, _) = limited_routes(
+        ApiKeysConfig {
+            keys: vec![generous],
+            ..Default::default()
+        },
+        slow_quota(),
+    );
+    let request = || request_from([10, 0, 0, 1], Some("secret-a"));
+
+    // More requests than the global burst, all from one address
+    for _ in 0..5 {
+        assert_eq!(request().reply(&routes).await.status(), 200);
+    }
+    assert_eq!(request().reply(&routes).await.status(), 429);
+    // The key's requests left the address's own quota untouched
+    let response = request_from([10, 0, 0, 1], None).reply(&routes).await;
+    assert_eq!(response.status(), 200);
+}
+
+#[test]
+fn test_shared_bucket_without_per_ip_limits() {
+    let limiter = RateLimiter::from_config(&RateLimitConfig {
+        requests_per_minute: 1,
+        burst_size: 1,
+        enable_per_ip: false,
+    });
+
+    assert!(limiter.check("ip:10.0.0.1", None).is_ok());
+    assert!(limiter.check("ip:10.0.0.2", None).is_err());
+    assert!(limiter.check("sub:alice", None).is_ok());
+}
@@ -0,0 +1,465 @@
+use async_graphql::Value as GraphQLValue;
+use async_graphql::dynamic::{
+    Field, FieldFuture, Object, Schema, Subscription, SubscriptionField, SubscriptionFieldFuture,
+    TypeRef,
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ring::rand::SystemRandom;
+use ring::signature::{self, EcdsaKeyPair, RsaKeyPair};
+use rustql::auth::Claims;
+use rustql::config::settings::{JwtAlgorithm, JwtConfig};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::server::build_routes;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use warp::Filter;
+use warp::reply::Response;
+
+const SECRET: &str = "test-secret";
+const RSA_PRIVATE_KEY: &str = include_str!("../fixtures/jwt_rsa_private.pem");
+const RSA_PUBLIC_KEY_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/jwt_rsa_public.pem"
+);
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn encode(header: Value, claims: &Value, sign: impl FnOnce(&[u8]) -> Vec<u8>) -> String {
+    let message = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = sign(message.as_bytes());
+    format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature))
+}
+
+fn hs256(claims: &Value) -> String {
+    encode(json!({ "alg": "HS256", "typ": "JWT" }), claims, |message| {
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, SECRET.as_bytes());
+        ring::hmac::sign(&key, message).as_ref().to_vec()
+    })
+}
+
+fn rsa_key_pair() -> RsaKeyPair {
+    let der = rustls_pemfile::private_key(&mut RSA_PRIVATE_KEY.as_bytes())
+        .unwrap()
+        .unwrap();
+    RsaKeyPair::from_pkcs8(der.secret_der()).unwrap()
+}
+
+fn rs256(kid: &str, claims: &Value) -> String {
+    let key_pair = rsa_key_pair();
+    encode(json!({ "alg": "RS256", "kid": kid }), claims, |message| {
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair
+            .sign(
+                &signature::RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message,
+                &mut signature,
+            )
+            .unwrap();
+        signature
+    })
+}
+
+fn es256(pkcs8: &[u8], claims: &Value) -> String {
+    let rng = SystemRandom::new();
+    let key_pair =
+        EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8, &rng).unwrap();
+    encode(json!({ "alg": "ES256" }), claims, |message| {
+        key_pair.sign(&rng, message).unwrap().as_ref().to_vec()
+    })
+}
+
+fn rsa_jwks(kid: &str) -> Value {
+    let components = ring::rsa::PublicKeyComponents::<Vec<u8>>::from(rsa_key_pair().public());
+    json!({
+        "keys": [{
+            "kty": "RSA",
+            "kid": kid,
+            "use": "sig",
+            "n": URL_SAFE_NO_PAD.encode(components.n),
+            "e": URL_SAFE_NO_PAD.encode(components.e),
+        }]
+    })
+}
+
+fn valid_claims() -> Value {
+    json!({
+        "sub": "alice",
+        "iss": "https://issuer.example",
+        "aud": ["other", "rustql"],
+        "exp": now() + 300,
+        "scope": "read:orders write:orders",
+    })
+}
+
+fn jwt_config() -> JwtConfig {
+    JwtConfig {
+        issuer: Some("https://issuer.example".to_string()),
+        audience: vec!["rustql".to_string()],
+        leeway: 0,
+        secret: Some(SECRET.to_string()),
+        ..Default::default()
+    }
+}
+
+/// The caller's subject and scopes, or `anonymous`.
+fn viewer(ctx: &ResolverContext) -> GraphQLValue {
+    let viewer = match &ctx.claims {
+        Some(claims) => format!(
+            "{} [{}]",
+            claims.subject().unwrap_or_default(),
+            claims.scopes().join(" ")
+        ),
+        None => "anonymous".to_string(),
+    };
+    GraphQLValue::from(viewer)
+}
+
+/// Routes over a schema whose `viewer` query and subscription fields return
+/// the caller's claims.
+fn routes(
+    jwt: JwtConfig,
+) -> impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone {
+    let mut settings = Settings::default();
+    settings.auth.jwt = Some(jwt);
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+
+    let query = Object::new("Query").field(Field::new(
+        "viewer",
+        TypeRef::named_nn(TypeRef::STRING),
+        |ctx| FieldFuture::new(async move { Ok(Some(viewer(ctx.data::<ResolverContext>()?))) }),
+    ));
+    let subscription = Subscription::new("Subscription").field(SubscriptionField::new(
+        "viewer",
+        TypeRef::named_nn(TypeRef::STRING),
+        |ctx| {
+            SubscriptionFieldFuture::new(async move {
+                let viewer = viewer(ctx.data::<ResolverContext>()?);
+                Ok(futures_util::stream::once(async move { Ok(viewer) }))
+            })
+        },
+    ));
+    let schema = Schema::build("Query", None, Some("Subscription"))
+        .register(query)
+        .register(subscription)
+        .finish()
+        .unwrap();
+
+    build_routes(settings, schema, services)
+}
+
+async fn post(
+    routes: &(impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone + 'static),
+    token: Option<&str>,
+) -> (u16, Value, Option<String>) {
+    let mut request = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .json(&json!({ "query": "{ viewer }" }));
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    let response = request.reply(routes).await;
+    let challenge = response
+        .headers()
+        .get("www-authenticate")
+        .map(|v| v.to_str().unwrap().to_string());
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(response.body()).unwrap(),
+        challenge,
+    )
+}
+
+#[test]
+fn test_claims_accessors() {
+    let claims: Claims = serde_json::from_value(json!({
+        "sub": "alice",
+        "aud": "rustql",
+        "scp": ["read", "write"],
+    }))
+    .unwrap();
+    assert_eq!(claims.subject(), Some("alice"));
+    assert_eq!(claims.audiences(), vec!["rustql"]);
+    assert_eq!(claims.scopes(), vec!["read", "write"]);
+}
+
+#[tokio::test]
+async fn test_valid_hs256_token_exposes_claims() {
+    let routes = routes(jwt_config());
+
+    let (status, body, _) = post(&routes, Some(&hs256(&valid_claims()))).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["viewer"], "alice [read:orders write:orders]");
+
+    let (status, body, _) = post(&routes, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["viewer"], "anonymous");
+}
+
+#[tokio::test]
+async fn test_missing_token_rejected_when_required() {
+    let routes = routes(JwtConfig {
+        required: true,
+        ..jwt_config()
+    });
+
+    let (status, body, challenge) = post(&routes, None).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["error"]["code"], "UNAUTHENTICATED");
+    assert_eq!(challenge.as_deref(), Some("Bearer"));
+}
+
+#[tokio::test]
+async fn test_invalid_tokens_rejected_with_401() {
+    let routes = routes(jwt_config());
+    let claims = valid_claims();
+
+    let mut expired = claims.clone();
+    expired["exp"] = json!(now() - 10);
+    let (status, body, _) = post(&routes, Some(&hs256(&expired))).await;
+    assert_eq!(status, 401);
+    assert_eq!(body["error"]["code"], "TOKEN_EXPIRED");
+    assert!(body["error"]["timestamp"].is_string());
+
+    let mut wrong_issuer = claims.clone();
+    wrong_issuer["iss"] = json!("https://evil.example");
+    let mut wrong_audience = claims.clone();
+    wrong_audience["aud"] = json!("someone-else");
+    let mut not_yet_valid = claims.clone();
+    not_yet_valid["nbf"] = json!(now() + 300);
+    let mut tampered = hs256(&claims);
+    tampered.pop();
+
+    let unsigned = encode(json!({ "alg": "none" }), &claims, |_| vec![]);
+    for token in [
+        hs256(&wrong_issuer),
+        hs256(&wrong_audience),
+        hs256(&not_yet_valid),
+        tampered,
+        unsigned,
+        "not-a-jwt".to_string(),
+    ] {
+        let (status, body, _) = post(&routes, Some(&token)).await;
+        assert_eq!(status, 401, "{}", token);
+        assert_eq!(body["error"]["code"], "INVALID_TOKEN");
+    }
+}
+
+#[tokio::test]
+async fn test_leeway_tolerates_clock_skew() {
+    let routes = routes(JwtConfig {
+        leeway: 60,
+        ..jwt_config()
+    });
+    let mut claims = valid_claims();
+    claims["exp"] = json!(now() - 10);
+
+    let (status, _, _) = post(&routes, Some(&hs256(&claims))).await;
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_rs256_with_static_public_key() {
+    let routes = routes(JwtConfig {
+        secret: None,
+        public_key_path: Some(RSA_PUBLIC_KEY_PATH.to_string()),
+        ..jwt_config()
+    });
+
+    let (status, _, _) = post(&routes, Some(&rs256("any", &valid_claims()))).await;
+    assert_eq!(status, 200);
+
+    // An HMAC token must not verify against the RSA key
+    let (status, _, _) = post(&routes, Some(&hs256(&valid_claims()))).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn test_es256_with_static_public_key() {
+    let key_pair = rcgen::KeyPair::generate().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ec.pem");
+    std::fs::write(&path, key_pair.public_key_pem()).unwrap();
+
+    let routes = routes(JwtConfig {
+        secret: None,
+        algorithms: vec![JwtAlgorithm::Es256],
+        public_key_path: Some(path.display().to_string()),
+        ..jwt_config()
+    });
+
+    let token = es256(&key_pair.serialize_der(), &valid_claims());
+    let (status, body, _) = post(&routes, Some(&token)).await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+#[tokio::test]
+async fn test_rs256_with_jwks_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("jwks.json");
+    std::fs::write(&path, rsa_jwks("key-1").to_string()).unwrap();
+
+    let routes = routes(JwtConfig {
+        secret: None,
+        jwks_path: Some(path.display().to_string()),
+        ..jwt_config()
+    });
+
+    let (status, _, _) = post(&routes, Some(&rs256("key-1", &valid_claims()))).await;
+    assert_eq!(status, 200);
+
+    let (status, _, _) = post(&routes, Some(&rs256("key-2", &valid_claims()))).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn test_jwks_url_cached_between_requests() {
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let jwks = warp::path!(".well-known" / "jwks.json").map(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        warp::reply::json(&rsa_jwks("key-1"))
+    });
+    let (addr, server) = warp::serve(jwks).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let routes = routes(JwtConfig {
+        secret: None,
+        jwks_url: Some(format!("http://{}/.well-known/jwks.json", addr)),
+        ..jwt_config()
+    });
+
+    for _ in 0..3 {
+        let (status, _, _) = post(&routes, Some(&rs256("key-1", &valid_claims()))).await;
+        assert_eq!(status, 200);
+    }
+    assert_eq!(fetches.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_jwks_refresh_keeps_serving_previous_keys() {
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let jwks = warp::path!("jwks.json").then(move || {
+        let fetch = counter.fetch_add(1, Ordering::SeqCst);
+        async move {
+            // Refreshes hang well past the request below
+            if fetch > 0 {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            warp::reply::json(&rsa_jwks("key-1"))
+        }
+    });
+    let (addr, server) = warp::serve(jwks).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let routes = routes(JwtConfig {
+        secret: None,
+        jwks_url: Some(format!("http://{}/jwks.json", addr)),
+        jwks_refresh_interval: 1,
+        ..jwt_config()
+    });
+    let (status, _, _) = post(&routes, Some(&rs256("key-1", &valid_claims()))).await;
+    assert_eq!(status, 200);
+
+    // Once the keys are due for refresh, one request waits for the reload
+    // while the others keep verifying against the previous keys
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let refreshing = routes.clone();
+    tokio::spawn(async move {
+        post(&refreshing, Some(&rs256("key-1", &valid_claims()))).await;
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+    let token = rs256("key-1", &valid_claims());
+    let (status, _, _) = tokio::time::timeout(Duration::from_secs(1), post(&routes, Some(&token)))
+        .await
+        .unwrap();
+    assert_eq!(status, 200);
+}
+
+#[tokio::test]
+async fn test_websocket_connection_init_authenticates() {
+    let routes = routes(JwtConfig {
+        required: true,
+        ..jwt_config()
+    });
+    let connect = |payload: Value| {
+        let routes = routes.clone();
+        async move {
+            let mut client = warp::test::ws()
+                .path("/graphql")
+                .header("sec-websocket-protocol", "graphql-transport-ws")
+                .handshake(routes)
+                .await
+                .unwrap();
+            client
+                .send_text(json!({ "type": "connection_init", "payload": payload }).to_string())
+                .await;
+            client
+        }
+    };
+
+    let token = format!("Bearer {}", hs256(&valid_claims()));
+    let mut client = connect(json!({ "headers": { "authorization": token } })).await;
+    let ack: Value = serde_json::from_str(client.recv().await.unwrap().to_str().unwrap()).unwrap();
+    assert_eq!(ack["type"], "connection_ack");
+    client
+        .send_text(
+            json!({
+                "id": "1",
+                "type": "subscribe",
+                "payload": { "query": "subscription { viewer }" }
+            })
+            .to_string(),
+        )
+        .await;
+    let message: Value =
+        serde_json::from_str(client.recv().await.unwrap().to_str().unwrap()).unwrap();
+    assert_eq!(
+        message["payload"]["data"]["viewer"],
+        "alice [read:orders write:orders]"
+    );
+
+    // Missing and invalid credentials close the connection instead of acking
+    let mut expired = valid_claims();
+    expired["exp"] = json!(now() - 10);
+    for payload in [
+        json!({}),
+        json!({ "Authorization": format!("Bearer {}", hs256(&expired)) }),
+    ] {
+        let mut client = connect(payload).await;
+        let message = client.recv().await;
+        assert!(
+            message.as_ref().map_or(true, |m| m.is_close()),
+            "{:?}",
+            message
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_unreachable_jwks_is_service_unavailable() {
+    let routes = routes(JwtConfig {
+        secret: None,
+        jwks_url: Some("http://127.0.0.1:1/jwks.json".to_string()),
+        ..jwt_config()
+    });
+
+    let (status, body, _) = post(&routes, Some(&rs256("key-1", &valid_claims()))).await;
+    assert_eq!(status, 503);
+    assert_eq!(body["error"]["code"], "AUTH_UNAVAILABLE");
+}
@@ -0,0 +1,232 @@
+use async_graphql::Request;
+use futures_util::StreamExt;
+use rustql::auth::Claims;
+use rustql::config::settings::{
+    AuthRule, PollConfig, QueryConfig, RestApiConfig, SubscriptionConfig,
+};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::graphql::{RustQLSchema, build_schema};
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use warp::Filter;
+
+/// Schema with an `order` query and `orderWatch` subscription requiring the
+/// `orders:read` scope, an admin-only `echo` and a `SystemStatus` type any
+/// authenticated caller may read, along with a counter of upstream calls.
+fn schema(hide_unauthorized: bool) -> (RustQLSchema, Arc<AtomicUsize>, Arc<Settings>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let upstream = warp::path!("orders" / String).map(move |id: String| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        warp::reply::json(&json!({ "id": id }))
+    });
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "order".to_string(),
+        api: "orders-api".to_string(),
+        path: "/orders/{id}".to_string(),
+        arguments: vec!["id".to_string()],
+        requires: Some(vec!["orders:read".to_string(), "admin".to_string()]),
+        ..Default::default()
+    });
+    settings.subscriptions.push(SubscriptionConfig {
+        field: "orderWatch".to_string(),
+        description: None,
+        arguments: vec!["id".to_string()],
+        poll: Some(PollConfig {
+            api: "orders-api".to_string(),
+            path: "/orders/{id}".to_string(),
+            interval: 1,
+            ignore_paths: vec![],
+        }),
+        webhook: None,
+    });
+    settings.auth.rules.insert(
+        "SubscriptionRoot.orderWatch".to_string(),
+        AuthRule {
+            requires: vec!["orders:read".to_string()],
+        },
+    );
+    settings.auth.rules.insert(
+        "QueryRoot.echo".to_string(),
+        AuthRule {
+            requires: vec!["admin".to_string()],
+        },
+    );
+    settings
+        .auth
+        .rules
+        .insert("SystemStatus".to_string(), AuthRule::default());
+    settings.auth.hide_unauthorized = hide_unauthorized;
+
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services).unwrap();
+    (schema, hits, settings)
+}
+
+/// The request context of a caller holding `scope`, if any.
+fn context(settings: &Arc<Settings>, scope: Option<&str>) -> ResolverContext {
+    let claims: Option<Claims> = scope
+        .map(|scope| serde_json::from_value(json!({ "sub": "alice", "scope": scope })).unwrap());
+    ResolverContext::new(settings.clone(), "req-1".to_string()).with_claims(claims)
+}
+
+async fn execute(
+    schema: &RustQLSchema,
+    settings: &Arc<Settings>,
+    scope: Option<&str>,
+    query: &str,
+) -> Value {
+    let request = Request::new(query).data(context(settings, scope));
+    let response = schema.execute(request).await;
+    serde_json::to_value(&response).unwrap()
+}
+
+#[tokio::test]
+async fn test_denied_field_returns_partial_data() {
+    let (schema, hits, settings) = schema(false);
+    let query = r#"{ order(id: "1") health }"#;
+
+    let body = execute(&schema, &settings, Some("profile"), query).await;
+    assert_eq!(body["data"]["order"], Value::Null);
+    assert_eq!(body["data"]["health"], "OK");
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    assert_eq!(body["errors"][0]["path"], json!(["order"]));
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+    let body = execute(&schema, &settings, Some("profile orders:read"), query).await;
+    assert_eq!(body["data"]["order"], json!({ "id": "1" }));
+    assert!(body.get("errors").is_none());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_any_listed_scope_grants_access() {
+    let (schema, _, settings) = schema(false);
+    let query = r#"{ echo(message: "hi") }"#;
+
+    let body = execute(&schema, &settings, Some("orders:read"), query).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+
+    let body = execute(&schema, &settings, Some("admin"), query).await;
+    assert_eq!(body["data"]["echo"], "Echo: hi");
+}
+
+#[tokio::test]
+async fn test_type_rule_requires_authenticated_caller() {
+    let (schema, _, settings) = schema(false);
+    let query = "{ systemStatus { status } }";
+
+    let body = execute(&schema, &settings, None, query).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    assert_eq!(body["errors"][0]["path"], json!(["systemStatus", "status"]));
+
+    let body = execute(&schema, &settings, Some(""), query).await;
+    assert_eq!(body["data"]["systemStatus"]["status"], "healthy");
+}
+
+fn field_names(ty: &Value) -> Vec<&str> {
+    ty["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["name"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_unauthorized_fields_hidden_from_introspection() {
+    let (schema, _, settings) = schema(true);
+    let query = r#"{ __type(name: "QueryRoot") { name fields { name } } }"#;
+
+    let body = execute(&schema, &settings, Some("orders:read"), query).await;
+    let fields = field_names(&body["data"]["__type"]);
+    assert!(fields.contains(&"order"));
+    assert!(fields.contains(&"health"));
+    assert!(!fields.contains(&"echo"));
+
+    let body = execute(&schema, &settings, Some("admin"), query).await;
+    let fields = field_names(&body["data"]["__type"]);
+    assert!(fields.contains(&"order") && fields.contains(&"echo"));
+
+    let query = "{ __schema { types { name fields { name } } } }";
+    let body = execute(&schema, &settings, None, query).await;
+    let types = body["data"]["__schema"]["types"].as_array().unwrap();
+    let type_named = |name: &str| types.iter().find(|ty| ty["name"] == name).unwrap();
+    assert!(!field_names(type_named("QueryRoot")).contains(&"order"));
+    assert!(field_names(type_named("SystemStatus")).is_empty());
+    assert!(field_names(type_named("ApiInfo")).contains(&"name"));
+}
+
+#[tokio::test]
+async fn test_aliased_introspection_fields_hidden() {
+    let (schema, _, settings) = schema(true);
+    let names = |value: &Value, key: &str| -> Vec<String> {
+        value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field[key].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let query = r#"{ __type(name: "QueryRoot") { name f: fields { name } } }"#;
+    let body = execute(&schema, &settings, Some("orders:read"), query).await;
+    let fields = names(&body["data"]["__type"]["f"], "name");
+    assert!(fields.contains(&"health".to_string()));
+    assert!(!fields.contains(&"echo".to_string()));
+
+    let query = r#"{ t: __type(name: "QueryRoot") { ...Fields } }
+        fragment Fields on __Type { f: fields { n: name } }"#;
+    let body = execute(&schema, &settings, Some("orders:read"), query).await;
+    assert!(!names(&body["data"]["t"]["f"], "n").contains(&"echo".to_string()));
+
+    let query = "{ __schema { types { n: name f: fields { n: name } } } }";
+    let body = execute(&schema, &settings, None, query).await;
+    let types = body["data"]["__schema"]["types"].as_array().unwrap();
+    let query_root = types.iter().find(|ty| ty["n"] == "QueryRoot").unwrap();
+    assert!(!names(&query_root["f"], "n").contains(&"order".to_string()));
+    let status = types.iter().find(|ty| ty["n"] == "SystemStatus").unwrap();
+    assert!(names(&status["f"], "n").is_empty());
+}
+
+#[tokio::test]
+async fn test_introspection_unfiltered_by_default() {
+    let (schema, _, settings) = schema(false);
+    let query = r#"{ __type(name: "QueryRoot") { fields { name } } }"#;
+
+    let body = execute(&schema, &settings, None, query).await;
+    let fields = field_names(&body["data"]["__type"]);
+    assert!(fields.contains(&"order") && fields.contains(&"echo"));
+}
+
+#[tokio::test]
+async fn test_denied_subscription_never_polls() {
+    let (schema, hits, settings) = schema(false);
+    let subscribe = |scope| {
+        let request = Request::new(r#"subscription { orderWatch(id: "1") }"#);
+        schema.execute_stream(request.data(context(&settings, scope)))
+    };
+
+    let response = subscribe(Some("profile")).next().await.unwrap();
+    let body = serde_json::to_value(&response).unwrap();
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+    let response = subscribe(Some("orders:read")).next().await.unwrap();
+    let body = serde_json::to_value(&response).unwrap();
+    assert_eq!(body["data"]["orderWatch"], json!({ "id": "1" }));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+}
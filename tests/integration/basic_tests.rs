@@ -24,7 +24,7 @@ async fn test_graphql_schema_creation() {
     use std::sync::Arc;
     
     let settings = Arc::new(Settings::default());
-    let schema = create_schema(settings);
+    let schema = create_schema(settings).unwrap();
     
     // Test basic schema introspection
     let query = "query { __schema { types { name } } }";
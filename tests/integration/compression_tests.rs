@@ -0,0 +1,96 @@
+use rustql::config::settings::CompressionConfig;
+use rustql::server::compression::{CompressionPolicy, Encoding, with_compression};
+use rustql::server::listener::{self, ConnectionLimiter};
+use std::io::Read;
+use std::sync::Arc;
+use warp::Filter;
+use warp::hyper::Body;
+use warp::reply::Response;
+
+#[test]
+fn test_negotiate_encoding() {
+    let policy = CompressionPolicy::from_config(&CompressionConfig::default());
+
+    assert_eq!(policy.negotiate("gzip, deflate, br"), Some(Encoding::Brotli));
+    assert_eq!(policy.negotiate("gzip;q=1.0, br;q=0.5"), Some(Encoding::Gzip));
+    assert_eq!(policy.negotiate("zstd, gzip"), Some(Encoding::Zstd));
+    assert_eq!(policy.negotiate("br;q=0, *;q=0.1"), Some(Encoding::Zstd));
+    assert_eq!(policy.negotiate("identity"), None);
+
+    let disabled = CompressionPolicy::from_config(&CompressionConfig {
+        enabled: false,
+        ..CompressionConfig::default()
+    });
+    assert_eq!(disabled.negotiate("gzip"), None);
+}
+
+#[test]
+fn test_compress_round_trip() {
+    let payload = r#"{"data":{"users":[]}}"#.repeat(100);
+
+    let gzip = Encoding::Gzip.compress(payload.as_bytes()).unwrap();
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&gzip[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, payload);
+
+    let zstd = Encoding::Zstd.compress(payload.as_bytes()).unwrap();
+    assert_eq!(zstd::decode_all(&zstd[..]).unwrap(), payload.as_bytes());
+
+    let br = Encoding::Brotli.compress(payload.as_bytes()).unwrap();
+    assert!(br.len() < payload.len());
+}
+
+#[tokio::test]
+async fn test_h2c_prior_knowledge() {
+    let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = tcp.local_addr().unwrap();
+    let routes = warp::any().map(|| "hello over h2c");
+    tokio::spawn(listener::serve(
+        tcp,
+        routes,
+        ConnectionLimiter::new(None),
+        None,
+        true,
+    ));
+
+    let client = warp::hyper::Client::builder()
+        .http2_only(true)
+        .build_http::<warp::hyper::Body>();
+    let response = client
+        .get(format!("http://{}/", addr).parse().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.version(), warp::http::Version::HTTP_2);
+    let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert_eq!(&body[..], b"hello over h2c");
+}
+
+#[tokio::test]
+async fn test_unreadable_body_is_a_server_error() {
+    let routes = warp::any().map(|| {
+        let chunks: Vec<Result<&str, std::io::Error>> = vec![
+            Ok(r#"{"data":"#),
+            Err(std::io::Error::other("upstream reset")),
+        ];
+        let mut response = Response::new(Body::wrap_stream(futures_util::stream::iter(chunks)));
+        response
+            .headers_mut()
+            .insert("content-type", "application/json".parse().unwrap());
+        response
+    });
+    let policy = CompressionPolicy::from_config(&CompressionConfig {
+        min_size: 0,
+        ..CompressionConfig::default()
+    });
+    let filter = with_compression(Arc::new(policy), routes);
+
+    let response = warp::test::request()
+        .header("accept-encoding", "gzip")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 500);
+    assert!(response.headers().get("content-encoding").is_none());
+}
@@ -0,0 +1,311 @@
+use crate::fixtures::{self, api, fields, query, serve};
+use rustql::Settings;
+use rustql::config::settings::{
+    FieldType, PaginationConfig, PaginationStyle, QueryConfig, ResultMappingConfig, TypeConfig,
+};
+use rustql::graphql::RustQLSchema;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use warp::Filter;
+
+const CONNECTION: &str = "edges { cursor node } pageInfo { hasNextPage hasPreviousPage startCursor endCursor } totalCount";
+
+/// Items 0..5 as `{ "id": n }`.
+fn items(range: std::ops::Range<usize>) -> Vec<Value> {
+    range.map(|id| json!({ "id": id })).collect()
+}
+
+fn param(query: &HashMap<String, String>, name: &str, default: usize) -> usize {
+    query
+        .get(name)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn schema(addr: SocketAddr, path: &str, pagination: PaginationConfig) -> RustQLSchema {
+    let mut settings = Settings::default();
+    settings.apis.rest.push(api("items-api", addr));
+    settings.queries.push(QueryConfig {
+        result: Some(ResultMappingConfig {
+            defaults: [("kind".to_string(), json!("item"))].into(),
+            ..Default::default()
+        }),
+        pagination: Some(pagination),
+        ..query("items", "items-api", path, &[], None)
+    });
+    fixtures::schema(settings)
+}
+
+async fn page(schema: &RustQLSchema, args: &str) -> Value {
+    let query = format!("{{ items({}) {{ {} }} }}", args, CONNECTION);
+    let response = serde_json::to_value(schema.execute(query).await).unwrap();
+    assert!(response.get("errors").is_none(), "{}", response);
+    response["data"]["items"].clone()
+}
+
+fn ids(connection: &Value) -> Vec<u64> {
+    connection["edges"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|edge| edge["node"]["id"].as_u64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_offset_pagination() {
+    let upstream = warp::path!("items")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let offset = param(&query, "offset", 0).min(5);
+            let limit = param(&query, "limit", 20);
+            warp::reply::json(&json!({
+                "data": items(offset..(offset + limit).min(5)),
+                "meta": { "total": 5 },
+            }))
+        });
+    let schema = schema(
+        serve(upstream),
+        "/items",
+        PaginationConfig {
+            items: Some("data".to_string()),
+            total_count: Some("meta.total".to_string()),
+            ..Default::default()
+        },
+    );
+
+    let first = page(&schema, "first: 2").await;
+    assert_eq!(ids(&first), vec![0, 1]);
+    assert_eq!(first["totalCount"], 5);
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+    assert_eq!(first["pageInfo"]["hasPreviousPage"], false);
+    assert_eq!(first["edges"][0]["node"]["kind"], "item");
+
+    let after = first["pageInfo"]["endCursor"].as_str().unwrap();
+    let second = page(&schema, &format!("first: 10, after: \"{}\"", after)).await;
+    assert_eq!(ids(&second), vec![2, 3, 4]);
+    assert_eq!(second["pageInfo"]["hasNextPage"], false);
+    assert_eq!(second["pageInfo"]["hasPreviousPage"], true);
+
+    let before = second["edges"][2]["cursor"].as_str().unwrap();
+    let back = page(&schema, &format!("last: 2, before: \"{}\"", before)).await;
+    assert_eq!(ids(&back), vec![2, 3]);
+    assert_eq!(back["pageInfo"]["hasNextPage"], true);
+    assert_eq!(back["pageInfo"]["hasPreviousPage"], true);
+
+    // Without `before`, `last` counts back from the total count
+    let tail = page(&schema, "last: 2").await;
+    assert_eq!(ids(&tail), vec![3, 4]);
+    assert_eq!(tail["pageInfo"]["hasNextPage"], false);
+    assert_eq!(tail["pageInfo"]["hasPreviousPage"], true);
+}
+
+#[tokio::test]
+async fn test_page_pagination() {
+    let upstream = warp::path!("items")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let size = param(&query, "per_page", 20);
+            let start = ((param(&query, "page", 1) - 1) * size).min(5);
+            warp::reply::json(&items(start..(start + size).min(5)))
+        });
+    let schema = schema(
+        serve(upstream),
+        "/items",
+        PaginationConfig {
+            style: PaginationStyle::Page,
+            limit_param: "per_page".to_string(),
+            page_size: 2,
+            ..Default::default()
+        },
+    );
+
+    let first = page(&schema, "first: 2").await;
+    assert_eq!(ids(&first), vec![0, 1]);
+    assert_eq!(first["totalCount"], Value::Null);
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+
+    let after = first["pageInfo"]["endCursor"].as_str().unwrap();
+    let second = page(&schema, &format!("first: 2, after: \"{}\"", after)).await;
+    assert_eq!(ids(&second), vec![2, 3]);
+
+    // Starting mid-page skips the items before the cursor
+    let after = first["edges"][0]["cursor"].as_str().unwrap();
+    let shifted = page(&schema, &format!("first: 1, after: \"{}\"", after)).await;
+    assert_eq!(ids(&shifted), vec![1]);
+
+    // Slices spanning several upstream pages fetch each of them
+    let spanning = page(&schema, &format!("first: 4, after: \"{}\"", after)).await;
+    assert_eq!(ids(&spanning), vec![1, 2, 3, 4]);
+    assert_eq!(spanning["pageInfo"]["hasNextPage"], false);
+    let all = page(&schema, "first: 10").await;
+    assert_eq!(ids(&all), vec![0, 1, 2, 3, 4]);
+
+    // Without a total count, `last` needs `before`
+    let response = serde_json::to_value(
+        schema
+            .execute(format!("{{ items(last: 2) {{ {} }} }}", CONNECTION))
+            .await,
+    )
+    .unwrap();
+    assert_eq!(
+        response["errors"][0]["extensions"]["code"],
+        "VALIDATION_ERROR"
+    );
+}
+
+#[tokio::test]
+async fn test_cursor_pagination() {
+    let upstream = warp::path!("feed")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let start = param(&query, "cursor", 0).min(5);
+            let end = (start + param(&query, "limit", 20)).min(5);
+            let next = (end < 5).then(|| end.to_string());
+            warp::reply::json(&json!({ "items": items(start..end), "next": next }))
+        });
+    let schema = schema(
+        serve(upstream),
+        "/feed",
+        PaginationConfig {
+            style: PaginationStyle::Cursor,
+            items: Some("items".to_string()),
+            next_cursor: Some("next".to_string()),
+            ..Default::default()
+        },
+    );
+
+    let first = page(&schema, "first: 3").await;
+    assert_eq!(ids(&first), vec![0, 1, 2]);
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+
+    let after = first["pageInfo"]["endCursor"].as_str().unwrap();
+    let second = page(&schema, &format!("first: 3, after: \"{}\"", after)).await;
+    assert_eq!(ids(&second), vec![3, 4]);
+    assert_eq!(second["pageInfo"]["hasNextPage"], false);
+    assert_eq!(second["pageInfo"]["hasPreviousPage"], true);
+
+    let response = serde_json::to_value(
+        schema
+            .execute(format!("{{ items(after: \"nope\") {{ {} }} }}", CONNECTION))
+            .await,
+    )
+    .unwrap();
+    assert_eq!(
+        response["errors"][0]["extensions"]["code"],
+        "VALIDATION_ERROR"
+    );
+}
+
+#[tokio::test]
+async fn test_link_header_pagination() {
+    // Pages of two items, linked with relative `Link` targets
+    let upstream = warp::path!("events")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let page = param(&query, "page", 1);
+            let start = ((page - 1) * 2).min(5);
+            let end = (start + 2).min(5);
+            let mut links = vec![];
+            if end < 5 {
+                links.push(format!("</events?page={}>; rel=\"next\"", page + 1));
+            }
+            if page > 1 {
+                links.push(format!("</events?page={}>; rel=\"prev\"", page - 1));
+            }
+            warp::reply::with_header(
+                warp::reply::json(&items(start..end)),
+                "link",
+                links.join(", "),
+            )
+        });
+    let schema = schema(
+        serve(upstream),
+        "/events",
+        PaginationConfig {
+            style: PaginationStyle::Link,
+            ..Default::default()
+        },
+    );
+
+    let first = page(&schema, "first: 3").await;
+    assert_eq!(ids(&first), vec![0, 1]);
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+
+    let after = first["pageInfo"]["endCursor"].as_str().unwrap();
+    let second = page(&schema, &format!("first: 3, after: \"{}\"", after)).await;
+    assert_eq!(ids(&second), vec![2, 3]);
+    assert_eq!(second["pageInfo"]["hasPreviousPage"], true);
+
+    let before = second["pageInfo"]["startCursor"].as_str().unwrap();
+    let back = page(&schema, &format!("last: 1, before: \"{}\"", before)).await;
+    assert_eq!(ids(&back), vec![1]);
+    assert_eq!(back["pageInfo"]["hasPreviousPage"], true);
+}
+
+#[tokio::test]
+async fn test_typed_connection_nodes() {
+    let upstream = warp::path!("items").map(|| warp::reply::json(&items(0..3)));
+    let mut settings = Settings::default();
+    settings.apis.rest.push(api("items-api", serve(upstream)));
+    settings.types.push(TypeConfig {
+        name: "Item".to_string(),
+        description: None,
+        resource: None,
+        fields: fields(&[("id", FieldType::Id)]),
+        relationships: vec![],
+    });
+    settings.queries.push(QueryConfig {
+        pagination: Some(PaginationConfig::default()),
+        ..query("items", "items-api", "/items", &[], Some("Item"))
+    });
+    settings.queries.push(QueryConfig {
+        pagination: Some(PaginationConfig::default()),
+        ..query("rawItems", "items-api", "/items", &[], None)
+    });
+
+    let schema = fixtures::schema(settings.clone());
+    let response = serde_json::to_value(
+        schema
+            .execute(
+                "{ items(first: 2) { __typename edges { __typename node { __typename id } } } \
+                 rawItems(first: 1) { __typename edges { node } } }",
+            )
+            .await,
+    )
+    .unwrap();
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["items"],
+        json!({
+            "__typename": "ItemConnection",
+            "edges": [
+                { "__typename": "ItemEdge", "node": { "__typename": "Item", "id": "0" } },
+                { "__typename": "ItemEdge", "node": { "__typename": "Item", "id": "1" } },
+            ],
+        })
+    );
+    assert_eq!(
+        response["data"]["rawItems"],
+        json!({ "__typename": "JSONConnection", "edges": [{ "node": { "id": 0 } }] })
+    );
+
+    // Nodes are single items, and the generated types must be free
+    let mut invalid = settings.clone();
+    invalid.queries[0].returns = Some("[Item]".to_string());
+    assert!(invalid.validate().is_err());
+    // A placeholder without an argument would leave its segment empty
+    let mut invalid = settings.clone();
+    invalid.queries[1].path = "/items/{category}".to_string();
+    assert!(invalid.validate().is_err());
+    let mut invalid = settings;
+    invalid.types.push(TypeConfig {
+        name: "ItemEdge".to_string(),
+        description: None,
+        resource: None,
+        fields: fields(&[("id", FieldType::Id)]),
+        relationships: vec![],
+    });
+    assert!(invalid.validate().is_err());
+}
@@ -0,0 +1,46 @@
+use rustql::Settings;
+use rustql::server::cors::CorsPolicy;
+
+fn settings_with_origins(origins: &[&str]) -> Settings {
+    let mut settings = Settings::default();
+    settings.server.cors_origins = origins.iter().map(|o| o.to_string()).collect();
+    settings
+}
+
+#[test]
+fn test_exact_and_wildcard_origins() {
+    let settings = settings_with_origins(&["https://app.example.com", "https://*.example.org"]);
+    let policy = CorsPolicy::from_config(&settings.server);
+
+    assert!(policy.is_origin_allowed("https://app.example.com"));
+    assert!(policy.is_origin_allowed("https://api.example.org"));
+    assert!(policy.is_origin_allowed("https://a.b.example.org"));
+    assert!(!policy.is_origin_allowed("https://example.org"));
+    assert!(!policy.is_origin_allowed("http://api.example.org"));
+    assert!(!policy.is_origin_allowed("https://evil.com"));
+    assert!(!policy.is_origin_allowed("https://app.example.com.evil.com"));
+}
+
+#[test]
+fn test_preflight_methods_and_headers() {
+    let settings = settings_with_origins(&["*"]);
+    let policy = CorsPolicy::from_config(&settings.server);
+
+    assert!(policy.is_method_allowed("post"));
+    assert!(!policy.is_method_allowed("DELETE"));
+    assert!(policy.are_headers_allowed("Content-Type, Authorization"));
+    assert!(!policy.are_headers_allowed("x-custom"));
+}
+
+#[test]
+fn test_validate_rejects_wildcard_with_credentials() {
+    let mut settings = settings_with_origins(&["*"]);
+    settings.server.cors.allow_credentials = true;
+    assert!(settings.validate().is_err());
+
+    settings.server.cors_origins = vec!["https://*.example.com".to_string()];
+    assert!(settings.validate().is_ok());
+
+    settings.server.cors_origins = vec!["https://api.*.example.com".to_string()];
+    assert!(settings.validate().is_err());
+}
@@ -0,0 +1,136 @@
+use async_graphql::{ErrorExtensions, Request};
+use rustql::config::settings::{CircuitBreakerConfig, QueryConfig, RestApiConfig};
+use rustql::graphql::build_schema;
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::rest::RestClient;
+use rustql::utils::RustQLError;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use warp::Filter;
+use warp::http::StatusCode;
+
+/// Settings for an `order` query against an upstream that answers every
+/// request with `status`.
+fn settings(status: u16) -> Arc<Settings> {
+    let upstream = warp::path!("orders" / String).map(move |_: String| {
+        warp::reply::with_status(
+            warp::reply::json(&json!({ "message": "upstream says no" })),
+            StatusCode::from_u16(status).unwrap(),
+        )
+    });
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: format!("http://{}", addr),
+        circuit_breaker: CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 1,
+            reset_timeout: 60,
+        },
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "order".to_string(),
+        api: "orders-api".to_string(),
+        path: "/orders/{id}".to_string(),
+        arguments: vec!["id".to_string()],
+        ..Default::default()
+    });
+    Arc::new(settings)
+}
+
+async fn query_order(settings: &Arc<Settings>) -> Value {
+    let services = Services::from_settings(settings).unwrap();
+    let schema = build_schema(settings.clone(), services).unwrap();
+    let context = ResolverContext::new(settings.clone(), "req-42".to_string());
+    let request = Request::new(r#"{ order(id: "7") }"#).data(context);
+    serde_json::to_value(schema.execute(request).await).unwrap()
+}
+
+#[tokio::test]
+async fn test_upstream_client_error_extensions() {
+    let body = query_order(&settings(404)).await;
+
+    assert_eq!(body["data"]["order"], Value::Null);
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "REST_API_ERROR");
+    assert_eq!(extensions["upstreamStatus"], 404);
+    assert_eq!(extensions["api"], "orders-api");
+    assert_eq!(extensions["retryable"], false);
+    assert_eq!(extensions["requestId"], "req-42");
+
+    // Clients learn the API and status, not the upstream address or body
+    let message = body["errors"][0]["message"].as_str().unwrap();
+    assert!(message.contains("orders-api") && message.contains("404"), "{}", message);
+    assert!(!message.contains("127.0.0.1") && !message.contains("upstream says no"));
+}
+
+#[tokio::test]
+async fn test_unavailable_upstream_is_retryable() {
+    let settings = settings(503);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services).unwrap();
+    let execute = || {
+        let context = ResolverContext::new(settings.clone(), "req-1".to_string());
+        schema.execute(Request::new(r#"{ order(id: "7") }"#).data(context))
+    };
+
+    let body = serde_json::to_value(execute().await).unwrap();
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "REST_API_ERROR");
+    assert_eq!(extensions["upstreamStatus"], 503);
+    assert_eq!(extensions["retryable"], true);
+
+    // The failure opened the circuit
+    let body = serde_json::to_value(execute().await).unwrap();
+    let extensions = &body["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "CIRCUIT_OPEN");
+    assert_eq!(extensions["api"], "orders-api");
+    assert_eq!(extensions["retryable"], true);
+}
+
+#[tokio::test]
+async fn test_upstream_errors_name_their_api() {
+    let settings = settings(404);
+    let client = RestClient::from_config(&settings.apis.rest[0]).unwrap();
+
+    // Errors name the API without a resolver adding it
+    let error = client.get("/orders/7").await.unwrap_err();
+    let extensions = serde_json::to_value(error.extend().extensions.unwrap()).unwrap();
+    assert_eq!(extensions["api"], "orders-api");
+    assert_eq!(extensions["upstreamStatus"], 404);
+
+    let error = RustQLError::UnexpectedResponse {
+        api: "orders-api".to_string(),
+        message: "expected a list of items".to_string(),
+    };
+    let extensions = serde_json::to_value(error.extend().extensions.unwrap()).unwrap();
+    assert_eq!(extensions["api"], "orders-api");
+    assert_eq!(extensions["code"], "UNEXPECTED_RESPONSE");
+}
+
+#[test]
+fn test_internal_messages_masked_when_configured() {
+    let io_error = || RustQLError::Io(std::io::Error::other("/var/lib/rustql/secret missing"));
+
+    let mut settings = Settings::default();
+    let context = ResolverContext::new(Arc::new(settings.clone()), "req-7".to_string());
+    let error = context.graphql_error(&io_error());
+    assert!(error.message.contains("/var/lib/rustql/secret missing"));
+
+    settings.errors.mask_internal = true;
+    let context = ResolverContext::new(Arc::new(settings), "req-7".to_string());
+    let error = context.graphql_error(&io_error());
+    assert_eq!(error.message, "Internal server error");
+    let extensions = serde_json::to_value(error.extensions.unwrap()).unwrap();
+    assert_eq!(extensions["code"], "IO_ERROR");
+    assert_eq!(extensions["requestId"], "req-7");
+
+    // Client-facing errors keep their message
+    let error = context.graphql_error(&RustQLError::Validation("id must be numeric".to_string()));
+    assert_eq!(error.message, "Validation error: id must be numeric");
+}
@@ -0,0 +1,174 @@
+use async_graphql::dynamic::{Field, FieldFuture, FieldValue, Object, Schema, TypeRef};
+use rustql::graphql::build_schema;
+use rustql::graphql::partial::PartialResultsExtension;
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::{Services, Settings};
+use rustql::server::build_routes;
+use rustql::server::handlers::handle_graphql;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use warp::http::HeaderMap;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+fn routes(
+    settings: Settings,
+) -> impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone {
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    build_routes(settings, schema, services)
+}
+
+fn body_json(body: &[u8]) -> Value {
+    serde_json::from_slice(body).unwrap()
+}
+
+#[tokio::test]
+async fn test_get_executes_query() {
+    let response = warp::test::request()
+        .method("GET")
+        .path("/graphql?query=query%20E(%24m%3A%20String!)%20%7B%20echo(message%3A%20%24m)%20%7D&variables=%7B%22m%22%3A%22hi%22%7D&operationName=E")
+        .reply(&routes(Settings::default()))
+        .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(body_json(response.body())["data"]["echo"], "Echo: hi");
+}
+
+#[tokio::test]
+async fn test_get_rejects_mutations() {
+    let response = warp::test::request()
+        .method("GET")
+        .path("/graphql?query=mutation%20%7B%20testMutation(input%3A%20%22x%22)%20%7D")
+        .reply(&routes(Settings::default()))
+        .await;
+
+    assert_eq!(response.status(), 405);
+    assert_eq!(response.headers()["allow"], "POST");
+    assert_eq!(
+        body_json(response.body())["errors"][0]["extensions"]["code"],
+        "METHOD_NOT_ALLOWED"
+    );
+}
+
+#[tokio::test]
+async fn test_get_requires_query() {
+    let response = warp::test::request()
+        .method("GET")
+        .path("/graphql?operationName=E")
+        .reply(&routes(Settings::default()))
+        .await;
+
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_graphql_response_json_negotiation() {
+    let routes = routes(Settings::default());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .header("accept", "application/graphql-response+json")
+        .json(&json!({ "query": "{ health" }))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 400);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/graphql-response+json; charset=utf-8"
+    );
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .header("accept", "application/json")
+        .json(&json!({ "query": "{ health" }))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+}
+
+#[tokio::test]
+async fn test_graphql_response_json_executed_null_data_is_ok() {
+    // A failing non-null root field nulls `data` after execution started
+    let query = Object::new("Query").field(Field::new(
+        "required",
+        TypeRef::named_nn(TypeRef::STRING),
+        |_| FieldFuture::new(async { Err::<Option<FieldValue>, _>("upstream failed".into()) }),
+    ));
+    let schema = Schema::build("Query", None, None)
+        .register(query)
+        .extension(PartialResultsExtension)
+        .finish()
+        .unwrap();
+    let settings = Arc::new(Settings::default());
+    let services = Services::from_settings(&settings).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("accept", "application/graphql-response+json".parse().unwrap());
+
+    let reply = handle_graphql(
+        ResolverContext::new(settings.clone(), "req-1".to_string()).with_headers(headers),
+        settings,
+        schema,
+        services,
+        json!({ "query": "{ required }" }),
+    )
+    .await
+    .unwrap()
+    .into_response();
+
+    let status = reply.status();
+    let body = warp::hyper::body::to_bytes(reply.into_body()).await.unwrap();
+    let body = body_json(&body);
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"], Value::Null);
+    assert_eq!(body["errors"][0]["path"], json!(["required"]));
+}
+
+#[tokio::test]
+async fn test_batched_operations() {
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .json(&json!([
+            { "query": "{ health }" },
+            { "query": "mutation { testMutation(input: \"x\") }" },
+            { "query": 1 }
+        ]))
+        .reply(&routes(Settings::default()))
+        .await;
+
+    assert_eq!(response.status(), 200);
+    let body = body_json(response.body());
+    assert_eq!(body[0]["data"]["health"], "OK");
+    assert_eq!(body[1]["data"]["testMutation"], "Processed: x");
+    assert_eq!(body[2]["errors"][0]["extensions"]["code"], "BAD_REQUEST");
+}
+
+#[tokio::test]
+async fn test_batch_limits() {
+    let mut settings = Settings::default();
+    settings.server.batching.max_batch_size = 1;
+    let batch = json!([{ "query": "{ health }" }, { "query": "{ health }" }]);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .json(&batch)
+        .reply(&routes(settings.clone()))
+        .await;
+    assert_eq!(response.status(), 400);
+
+    settings.server.batching.enabled = false;
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .json(&json!([{ "query": "{ health }" }]))
+        .reply(&routes(settings))
+        .await;
+    assert_eq!(response.status(), 400);
+}
@@ -0,0 +1,181 @@
+use async_graphql::Request;
+use rustql::auth::Claims;
+use rustql::config::settings::{HeaderPropagation, QueryConfig, RestApiConfig};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::graphql::{RustQLSchema, build_schema};
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use warp::Filter;
+use warp::http::HeaderMap;
+
+fn rule(from: &str, to: Option<&str>) -> HeaderPropagation {
+    HeaderPropagation {
+        from: from.to_string(),
+        to: to.map(str::to_string),
+        default: None,
+    }
+}
+
+/// Upstream echoing the headers it received at `/whoami`.
+fn spawn_upstream(hits: Arc<AtomicUsize>) -> std::net::SocketAddr {
+    let routes = warp::path!("whoami")
+        .and(warp::header::headers_cloned())
+        .map(move |headers: HeaderMap| {
+            hits.fetch_add(1, Ordering::SeqCst);
+            let headers: serde_json::Map<String, Value> = headers
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        Value::from(value.to_str().unwrap_or_default()),
+                    )
+                })
+                .collect();
+            warp::reply::json(&headers)
+        });
+    let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+fn settings(addr: std::net::SocketAddr, propagate: Vec<HeaderPropagation>) -> Arc<Settings> {
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "users-api".to_string(),
+        base_url: format!("http://{}", addr),
+        headers: Some([("x-static".to_string(), "yes".to_string())].into()),
+        propagate,
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "whoami".to_string(),
+        api: "users-api".to_string(),
+        path: "/whoami".to_string(),
+        ..Default::default()
+    });
+    Arc::new(settings)
+}
+
+fn context(settings: &Arc<Settings>, headers: &[(&str, &str)]) -> ResolverContext {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        map.append(
+            warp::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    let claims: Claims =
+        serde_json::from_value(json!({ "sub": "alice", "groups": ["a", "b"] })).unwrap();
+    ResolverContext::new(settings.clone(), "req-42".to_string())
+        .with_headers(map)
+        .with_claims(Some(claims))
+}
+
+async fn whoami(schema: &RustQLSchema, context: ResolverContext) -> Value {
+    let response = schema
+        .execute(Request::new("{ whoami }").data(context))
+        .await;
+    let body = serde_json::to_value(&response).unwrap();
+    assert!(body.get("errors").is_none(), "{}", body);
+    body["data"]["whoami"].clone()
+}
+
+#[tokio::test]
+async fn test_headers_forwarded_renamed_and_injected() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let addr = spawn_upstream(hits);
+    let settings = settings(
+        addr,
+        vec![
+            rule("header:authorization", None),
+            rule("header:Accept-Language", None),
+            rule("header:x-tenant", Some("x-tenant-id")),
+            rule("header:x-trace-*", None),
+            rule("claim:sub", Some("x-user-id")),
+            rule("claim:groups", Some("x-user-groups")),
+            rule("request_id", Some("x-request-id")),
+            HeaderPropagation {
+                default: Some("standard".to_string()),
+                ..rule("header:x-plan", None)
+            },
+        ],
+    );
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services).unwrap();
+
+    let headers = whoami(
+        &schema,
+        context(
+            &settings,
+            &[
+                ("authorization", "Bearer token"),
+                ("accept-language", "de-CH"),
+                ("x-tenant", "acme"),
+                ("x-trace-id", "abc"),
+                ("x-trace-hop", "drop-me"),
+                ("connection", "x-trace-hop"),
+                ("x-unlisted", "nope"),
+            ],
+        ),
+    )
+    .await;
+
+    assert_eq!(headers["authorization"], "Bearer token");
+    assert_eq!(headers["accept-language"], "de-CH");
+    assert_eq!(headers["x-tenant-id"], "acme");
+    assert_eq!(headers["x-trace-id"], "abc");
+    assert_eq!(headers["x-user-id"], "alice");
+    assert_eq!(headers["x-user-groups"], "a,b");
+    assert_eq!(headers["x-request-id"], "req-42");
+    assert_eq!(headers["x-plan"], "standard");
+    assert_eq!(headers["x-static"], "yes");
+    assert!(headers.get("x-tenant").is_none());
+    assert!(headers.get("x-trace-hop").is_none());
+    assert!(headers.get("x-unlisted").is_none());
+}
+
+#[tokio::test]
+async fn test_cache_entries_separated_by_propagated_values() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let addr = spawn_upstream(hits.clone());
+    let settings = settings(
+        addr,
+        vec![
+            rule("header:x-tenant", Some("x-tenant-id")),
+            rule("request_id", Some("x-request-id")),
+        ],
+    );
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+
+    let acme = context(&settings, &[("x-tenant", "acme")]);
+    let globex = context(&settings, &[("x-tenant", "globex")]);
+
+    assert_eq!(whoami(&schema, acme.clone()).await["x-tenant-id"], "acme");
+    assert_eq!(
+        whoami(&schema, globex.clone()).await["x-tenant-id"],
+        "globex"
+    );
+    // The request id does not split the cache
+    let mut again = acme.clone();
+    again.request_id = "req-43".to_string();
+    assert_eq!(whoami(&schema, again).await["x-tenant-id"], "acme");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    assert_ne!(services.cache_scope(&acme), services.cache_scope(&globex));
+}
+
+#[tokio::test]
+async fn test_invalid_propagation_rules_rejected() {
+    let addr = ([127, 0, 0, 1], 1).into();
+    for rules in [
+        vec![rule("claim:sub", None)],
+        vec![rule("cookie:session", Some("x-session"))],
+        vec![rule("header:x-tenant-*", Some("x-tenant"))],
+        vec![rule("header:x-forwarded", Some("transfer-encoding"))],
+    ] {
+        assert!(Services::from_settings(&settings(addr, rules)).is_err());
+    }
+}
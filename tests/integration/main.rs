@@ -0,0 +1,30 @@
+#[allow(dead_code)]
+#[path = "../fixtures/mod.rs"]
+mod fixtures;
+
+mod basic_tests;
+mod cors_tests;
+mod server_tests;
+mod playground_tests;
+mod tls_tests;
+mod compression_tests;
+mod subscription_tests;
+mod transport_tests;
+mod graphql_http_tests;
+mod persisted_query_tests;
+mod response_cache_tests;
+mod stale_cache_tests;
+mod upstream_cache_tests;
+mod auth_tests;
+mod api_key_tests;
+mod authorization_tests;
+mod header_propagation_tests;
+mod upstream_auth_tests;
+mod error_extension_tests;
+mod partial_result_tests;
+mod result_mapping_tests;
+mod connection_tests;
+mod relationship_tests;
+mod query_plan_tests;
+mod sparse_fields_tests;
+mod mutation_tests;
@@ -0,0 +1,348 @@
+use crate::fixtures::{api, execute, serve};
+use rustql::Settings;
+use rustql::config::settings::{
+    BodyEncoding, FieldType, InputTypeConfig, MutationConfig, ResultMappingConfig, TypeConfig,
+    WriteMethod,
+};
+use rustql::rest::write::RequestBody;
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+use warp::http::{HeaderMap, Method, Response, StatusCode};
+
+/// Method, path, headers and body of a request the upstream received.
+type Received = Arc<Mutex<Vec<(Method, String, HeaderMap, String)>>>;
+
+/// An orders API accepting writes: creating an order with a negative total
+/// fails validation, `/orders/locked` is forbidden and `/orders/taken`
+/// conflicts.
+fn spawn_upstream(received: Received) -> std::net::SocketAddr {
+    let upstream = warp::method()
+        .and(warp::path::full())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .map(
+            move |method: Method,
+                  path: warp::path::FullPath,
+                  headers: HeaderMap,
+                  body: warp::hyper::body::Bytes| {
+                let body = String::from_utf8_lossy(&body).to_string();
+                received.lock().unwrap().push((
+                    method.clone(),
+                    path.as_str().to_string(),
+                    headers,
+                    body.clone(),
+                ));
+
+                let (status, reply) = match (method.as_str(), path.as_str()) {
+                    ("POST", "/orders") => {
+                        let order: Value = serde_json::from_str(&body).unwrap();
+                        if order["total"].as_i64().unwrap_or(0) < 0 {
+                            let errors = json!({ "errors": [
+                                { "field": "total", "message": "must be positive", "code": "min" },
+                                "order is invalid",
+                            ]});
+                            (StatusCode::UNPROCESSABLE_ENTITY, errors.to_string())
+                        } else {
+                            let created = json!({
+                                "id": "o9",
+                                "user_id": order["userId"],
+                                "total": order["total"],
+                            });
+                            (StatusCode::CREATED, created.to_string())
+                        }
+                    }
+                    ("DELETE", "/orders/locked") => (StatusCode::FORBIDDEN, String::new()),
+                    ("DELETE", _) => (StatusCode::NO_CONTENT, String::new()),
+                    (_, "/orders/taken") => {
+                        let errors = json!({ "errors": [{
+                            "detail": "already exists",
+                            "source": { "pointer": "/data/attributes/user/email" },
+                        }]});
+                        (StatusCode::CONFLICT, errors.to_string())
+                    }
+                    (_, "/orders/rails") => {
+                        let errors = json!({ "errors": { "note": ["is too long", "is rude"] } });
+                        (StatusCode::BAD_REQUEST, errors.to_string())
+                    }
+                    _ => (StatusCode::OK, json!({ "received": body }).to_string()),
+                };
+                Response::builder().status(status).body(reply).unwrap()
+            },
+        );
+    serve(upstream)
+}
+
+fn mutation(field: &str, method: WriteMethod, path: &str) -> MutationConfig {
+    MutationConfig {
+        field: field.to_string(),
+        api: "orders-api".to_string(),
+        method,
+        path: path.to_string(),
+        ..Default::default()
+    }
+}
+
+fn settings(received: Received) -> Settings {
+    let addr = spawn_upstream(received);
+    let mut settings = Settings::default();
+    settings.apis.rest.push(api("orders-api", addr));
+    settings.types.push(TypeConfig {
+        name: "Order".to_string(),
+        description: None,
+        resource: None,
+        fields: [
+            ("id".to_string(), FieldType::Id),
+            ("userId".to_string(), FieldType::Id),
+            ("total".to_string(), FieldType::Int),
+        ]
+        .into(),
+        relationships: vec![],
+    });
+    settings.inputs.push(InputTypeConfig {
+        name: "CreateOrderInput".to_string(),
+        description: None,
+        fields: [
+            ("userId".to_string(), FieldType::Id),
+            ("total".to_string(), FieldType::Int),
+        ]
+        .into(),
+        required: vec!["userId".to_string()],
+    });
+    settings.inputs.push(InputTypeConfig {
+        name: "OrderNoteInput".to_string(),
+        description: None,
+        fields: [
+            ("id".to_string(), FieldType::Id),
+            ("note".to_string(), FieldType::String),
+            ("metadata".to_string(), FieldType::Json),
+        ]
+        .into(),
+        required: vec!["id".to_string()],
+    });
+    settings.mutations.push(MutationConfig {
+        input: Some("CreateOrderInput".to_string()),
+        idempotency_header: Some("Idempotency-Key".to_string()),
+        result: Some(ResultMappingConfig {
+            camel_case: true,
+            ..Default::default()
+        }),
+        returns: Some("Order".to_string()),
+        ..mutation("createOrder", WriteMethod::Post, "/orders")
+    });
+    settings.mutations.push(MutationConfig {
+        input: Some("OrderNoteInput".to_string()),
+        body: BodyEncoding::Form,
+        ..mutation("annotateOrder", WriteMethod::Patch, "/orders/{input.id}")
+    });
+    settings.mutations.push(MutationConfig {
+        input: Some("OrderNoteInput".to_string()),
+        body: BodyEncoding::Multipart,
+        ..mutation("attachNote", WriteMethod::Put, "/orders/{input.id}/note")
+    });
+    settings.mutations.push(MutationConfig {
+        arguments: vec!["id".to_string()],
+        ..mutation("deleteOrder", WriteMethod::Delete, "/orders/{id}")
+    });
+    settings
+}
+
+#[tokio::test]
+async fn test_create_maps_result_and_forwards_idempotency_key() {
+    let received = Received::default();
+    let response = execute(
+        settings(received.clone()),
+        r#"mutation {
+            createOrder(input: { userId: "1", total: 12 }, idempotencyKey: "key-1") {
+                result { id userId total }
+                userErrors { message }
+            }
+            deleteOrder(id: "o9") { result userErrors { message } }
+        }"#,
+    )
+    .await;
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"],
+        json!({
+            "createOrder": {
+                "result": { "id": "o9", "userId": "1", "total": 12 },
+                "userErrors": [],
+            },
+            "deleteOrder": { "result": null, "userErrors": [] },
+        })
+    );
+
+    let received = received.lock().unwrap();
+    let (method, path, headers, body) = &received[0];
+    assert_eq!((method, path.as_str()), (&Method::POST, "/orders"));
+    assert_eq!(headers["idempotency-key"], "key-1");
+    assert_eq!(headers["content-type"], "application/json");
+    assert_eq!(
+        serde_json::from_str::<Value>(body).unwrap(),
+        json!({ "userId": "1", "total": 12 })
+    );
+    assert_eq!(received[1].0, Method::DELETE);
+    assert_eq!(received[1].1, "/orders/o9");
+    assert!(received[1].2.get("content-type").is_none());
+}
+
+#[tokio::test]
+async fn test_rejected_writes_become_user_errors() {
+    let response = execute(
+        settings(Received::default()),
+        r#"mutation {
+            invalid: createOrder(input: { userId: "1", total: -5 }) {
+                result { id }
+                userErrors { field message code }
+            }
+            conflict: annotateOrder(input: { id: "taken" }) {
+                userErrors { field message }
+            }
+            rails: annotateOrder(input: { id: "rails", note: "..." }) {
+                userErrors { field message }
+            }
+        }"#,
+    )
+    .await;
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["invalid"],
+        json!({
+            "result": null,
+            "userErrors": [
+                { "field": ["total"], "message": "must be positive", "code": "min" },
+                { "field": null, "message": "order is invalid", "code": null },
+            ],
+        })
+    );
+    assert_eq!(
+        response["data"]["conflict"]["userErrors"],
+        json!([{ "field": ["user", "email"], "message": "already exists" }])
+    );
+    assert_eq!(
+        response["data"]["rails"]["userErrors"],
+        json!([
+            { "field": ["note"], "message": "is too long" },
+            { "field": ["note"], "message": "is rude" },
+        ])
+    );
+
+    // Other rejections are errors at the field's path
+    let response = execute(
+        settings(Received::default()),
+        r#"mutation { deleteOrder(id: "locked") { userErrors { message } } }"#,
+    )
+    .await;
+    assert_eq!(response["data"], Value::Null);
+    assert_eq!(response["errors"][0]["path"], json!(["deleteOrder"]));
+    assert_eq!(response["errors"][0]["extensions"]["upstreamStatus"], 403);
+}
+
+#[tokio::test]
+async fn test_form_and_multipart_bodies() {
+    let received = Received::default();
+    let response = execute(
+        settings(received.clone()),
+        r#"mutation {
+            annotateOrder(input: { id: "o1", note: "ring twice", metadata: { tags: ["a", "b"] } }) {
+                result
+            }
+            attachNote(input: { id: "o1", note: "fragile" }) { result }
+        }"#,
+    )
+    .await;
+    assert!(response.get("errors").is_none(), "{}", response);
+
+    let received = received.lock().unwrap();
+    let (method, path, headers, body) = &received[0];
+    assert_eq!((method, path.as_str()), (&Method::PATCH, "/orders/o1"));
+    assert_eq!(headers["content-type"], "application/x-www-form-urlencoded");
+    let mut pairs: Vec<&str> = body.split('&').collect();
+    pairs.sort_unstable();
+    assert_eq!(
+        pairs,
+        vec![
+            "id=o1",
+            "metadata%5Btags%5D%5B%5D=a",
+            "metadata%5Btags%5D%5B%5D=b",
+            "note=ring%20twice",
+        ]
+    );
+
+    let (method, path, headers, body) = &received[1];
+    assert_eq!((method, path.as_str()), (&Method::PUT, "/orders/o1/note"));
+    let content_type = headers["content-type"].to_str().unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/form-data; boundary=")
+        .unwrap();
+    assert!(body.contains("Content-Disposition: form-data; name=\"note\"\r\n\r\nfragile\r\n"));
+    assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+}
+
+#[test]
+fn test_multipart_part_names_escaped() {
+    let body = RequestBody::encode(
+        BodyEncoding::Multipart,
+        &json!({ "note\"\r\nX-Injected: 1": "fragile" }),
+    );
+    let body = String::from_utf8(body.bytes).unwrap();
+    assert!(body.contains(
+        "Content-Disposition: form-data; name=\"note%22%0D%0AX-Injected: 1\"\r\n\r\nfragile\r\n"
+    ));
+    assert!(!body.contains("\r\nX-Injected"));
+}
+
+#[tokio::test]
+async fn test_invalid_mutations_rejected() {
+    let valid = settings(Received::default());
+
+    let mut invalid = valid.clone();
+    invalid.mutations[0].input = Some("MissingInput".to_string());
+    assert!(invalid.validate().is_err());
+
+    // The payload type would clash with a declared type
+    let mut invalid = valid.clone();
+    invalid.types[0].name = "CreateOrderPayload".to_string();
+    invalid.mutations[0].returns = None;
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = valid.clone();
+    invalid.mutations[0].user_error_statuses = vec![500];
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = valid.clone();
+    invalid.inputs[0].required = vec!["email".to_string()];
+    assert!(invalid.validate().is_err());
+
+    // Placeholders need an argument or a required input field
+    let mut invalid = valid.clone();
+    invalid.mutations[1].path = "/orders/{input.note}".to_string();
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = valid;
+    invalid.mutations[3].path = "/orders/{orderId}".to_string();
+    assert!(invalid.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_placeholder_without_value_not_sent() {
+    let received = Received::default();
+    let mut settings = settings(received.clone());
+    settings.inputs[1].required.push("metadata".to_string());
+    settings.mutations[1].path = "/orders/{input.metadata}".to_string();
+
+    // A JSON object has no path segment form
+    let response = execute(
+        settings,
+        r#"mutation { annotateOrder(input: { id: "o1", metadata: { a: 1 } }) { userErrors { message } } }"#,
+    )
+    .await;
+    assert_eq!(response["data"], Value::Null);
+    assert_eq!(
+        response["errors"][0]["extensions"]["code"],
+        "VALIDATION_ERROR"
+    );
+    assert_eq!(response["errors"][0]["extensions"]["requestId"], "req-1");
+    assert!(received.lock().unwrap().is_empty());
+}
@@ -0,0 +1,117 @@
+use rustql::config::settings::{QueryConfig, RestApiConfig};
+use rustql::graphql::build_schema;
+use rustql::server::build_routes;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use warp::Filter;
+use warp::http::StatusCode;
+use warp::reply::Response;
+
+/// Upstream serving `/orders/ok`, failing `/orders/broken` with a 500 and
+/// answering 404 for anything else.
+fn spawn_upstream() -> std::net::SocketAddr {
+    let upstream = warp::path!("orders" / String).map(|id: String| {
+        let status = match id.as_str() {
+            "ok" => StatusCode::OK,
+            "broken" => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::NOT_FOUND,
+        };
+        warp::reply::with_status(warp::reply::json(&json!({ "id": id })), status)
+    });
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+fn query(field: &str, api: &str) -> QueryConfig {
+    QueryConfig {
+        field: field.to_string(),
+        api: api.to_string(),
+        path: "/orders/{id}".to_string(),
+        arguments: vec!["id".to_string()],
+        ..Default::default()
+    }
+}
+
+/// Routes with `order` on an API reporting 404s as errors and `maybeOrder`
+/// on the same upstream resolving them to null.
+fn routes() -> impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone {
+    let addr = spawn_upstream();
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.apis.rest.push(RestApiConfig {
+        name: "lenient-orders-api".to_string(),
+        base_url: format!("http://{}", addr),
+        not_found_as_null: true,
+        ..Default::default()
+    });
+    settings.queries.push(query("order", "orders-api"));
+    settings
+        .queries
+        .push(query("maybeOrder", "lenient-orders-api"));
+
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    build_routes(settings, schema, services)
+}
+
+async fn post(
+    routes: &(impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone + 'static),
+    query: &str,
+) -> (u16, Value) {
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .header("accept", "application/graphql-response+json")
+        .json(&json!({ "query": query }))
+        .reply(routes)
+        .await;
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(response.body()).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_failed_field_nulled_with_located_error() {
+    let routes = routes();
+    let (status, body) = post(
+        &routes,
+        "{\n  good: order(id: \"ok\")\n  bad: order(id: \"broken\")\n  health\n}",
+    )
+    .await;
+
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["good"], json!({ "id": "ok" }));
+    assert_eq!(body["data"].get("bad"), Some(&Value::Null));
+    assert_eq!(body["data"]["health"], "OK");
+
+    let errors = body["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0]["path"], json!(["bad"]));
+    assert_eq!(errors[0]["locations"], json!([{ "line": 3, "column": 3 }]));
+    assert_eq!(errors[0]["extensions"]["code"], "REST_API_ERROR");
+    assert_eq!(errors[0]["extensions"]["upstreamStatus"], 500);
+}
+
+#[tokio::test]
+async fn test_not_found_as_null_is_per_api() {
+    let routes = routes();
+
+    let (status, body) = post(&routes, r#"{ maybeOrder(id: "missing") }"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["maybeOrder"], Value::Null);
+    assert!(body.get("errors").is_none());
+
+    let (status, body) = post(&routes, r#"{ order(id: "missing") }"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"], json!({ "order": null }));
+    assert_eq!(body["errors"][0]["extensions"]["upstreamStatus"], 404);
+    assert_eq!(body["errors"][0]["path"], json!(["order"]));
+}
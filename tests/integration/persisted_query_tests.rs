@@ -0,0 +1,230 @@
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use rustql::cache::CacheManager;
+use rustql::graphql::build_schema;
+use rustql::graphql::persisted::parse_manifest;
+use rustql::server::build_routes;
+use rustql::utils::{parse_size, sha256_hex};
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use warp::Filter;
+use warp::reply::Response;
+
+fn routes(
+    settings: Settings,
+) -> impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone {
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    build_routes(settings, schema, services)
+}
+
+fn persisted(hash: &str) -> Value {
+    json!({ "persistedQuery": { "version": 1, "sha256Hash": hash } })
+}
+
+async fn post(
+    routes: &(impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone + 'static),
+    body: Value,
+) -> (u16, Value) {
+    let response = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .json(&body)
+        .reply(routes)
+        .await;
+    (
+        response.status().as_u16(),
+        serde_json::from_slice(response.body()).unwrap(),
+    )
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("512"), Some(512));
+    assert_eq!(parse_size("64KB"), Some(64 << 10));
+    assert_eq!(parse_size("100mb"), Some(100 << 20));
+    assert_eq!(parse_size("1GB"), Some(1 << 30));
+    assert_eq!(parse_size("lots"), None);
+}
+
+#[tokio::test]
+async fn test_cache_manager_expiry_and_eviction() {
+    let mut settings = Settings::default();
+    settings.cache.max_size = "16B".to_string();
+    let cache = CacheManager::from_config(&settings.cache).unwrap();
+
+    cache.set("a", "12345678", 60).await.unwrap();
+    assert_eq!(cache.get("a").await.as_deref(), Some("12345678"));
+
+    // Exceeds the 16 byte budget, so the oldest entry is evicted
+    cache.set("b", "12345678", 60).await.unwrap();
+    assert_eq!(cache.get("a").await, None);
+    assert_eq!(cache.get("b").await.as_deref(), Some("12345678"));
+
+    cache.set("c", "x", 0).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    assert_eq!(cache.get("c").await, None);
+
+    cache.delete("b").await.unwrap();
+    assert_eq!(cache.get("b").await, None);
+}
+
+#[test]
+fn test_parse_manifest_formats() {
+    let apollo = parse_manifest(
+        &json!({
+            "format": "apollo-persisted-query-manifest",
+            "version": 1,
+            "operations": [{ "id": "abc", "name": "Health", "type": "query", "body": "{ health }" }]
+        })
+        .to_string(),
+    )
+    .unwrap();
+    assert_eq!(apollo["abc"], "{ health }");
+
+    let map = parse_manifest(r#"{ "abc": "{ health }" }"#).unwrap();
+    assert_eq!(map["abc"], "{ health }");
+
+    assert!(parse_manifest("[]").is_err());
+}
+
+#[tokio::test]
+async fn test_automatic_persisted_query_registration() {
+    let routes = routes(Settings::default());
+    let query = "{ health }";
+    let hash = sha256_hex(query);
+
+    let (status, body) = post(&routes, json!({ "extensions": persisted(&hash) })).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["errors"][0]["message"], "PersistedQueryNotFound");
+    assert_eq!(body["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+
+    let (status, body) =
+        post(&routes, json!({ "query": query, "extensions": persisted(&hash) })).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["health"], "OK");
+
+    let (_, body) = post(&routes, json!({ "extensions": persisted(&hash) })).await;
+    assert_eq!(body["data"]["health"], "OK");
+
+    let response = warp::test::request()
+        .method("GET")
+        .path(&format!(
+            "/graphql?extensions=%7B%22persistedQuery%22%3A%7B%22version%22%3A1%2C%22sha256Hash%22%3A%22{}%22%7D%7D",
+            hash
+        ))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(body["data"]["health"], "OK");
+}
+
+#[tokio::test]
+async fn test_get_mutation_is_not_registered() {
+    let routes = routes(Settings::default());
+    let query = r#"mutation { testMutation(input: "x") }"#;
+    let hash = sha256_hex(query);
+
+    let response = warp::test::request()
+        .method("GET")
+        .path(&format!(
+            "/graphql?query={}&extensions={}",
+            utf8_percent_encode(query, NON_ALPHANUMERIC),
+            utf8_percent_encode(&persisted(&hash).to_string(), NON_ALPHANUMERIC)
+        ))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 405);
+
+    let (_, body) = post(&routes, json!({ "extensions": persisted(&hash) })).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn test_persisted_query_hash_mismatch() {
+    let (status, body) = post(
+        &routes(Settings::default()),
+        json!({ "query": "{ health }", "extensions": persisted(&sha256_hex("{ other }")) }),
+    )
+    .await;
+    assert_eq!(status, 400);
+    assert_eq!(body["errors"][0]["message"], "provided sha does not match query");
+}
+
+#[tokio::test]
+async fn test_persisted_queries_disabled() {
+    let mut settings = Settings::default();
+    settings.persisted_queries.apq = false;
+
+    let (status, body) = post(
+        &routes(settings),
+        json!({ "extensions": persisted(&sha256_hex("{ health }")) }),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_SUPPORTED");
+}
+
+#[tokio::test]
+async fn test_allowlist_only_executes_manifest_operations() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = dir.path().join("manifest.json");
+    let query = "{ health }";
+    let hash = sha256_hex(query);
+    std::fs::write(&manifest, json!({ &hash: query }).to_string()).unwrap();
+
+    let mut settings = Settings::default();
+    settings.persisted_queries.manifest_path = Some(manifest.to_string_lossy().into_owned());
+    settings.persisted_queries.allowlist_only = true;
+    assert!(settings.validate().is_ok());
+    let routes = routes(settings);
+
+    let (_, body) = post(&routes, json!({ "extensions": persisted(&hash) })).await;
+    assert_eq!(body["data"]["health"], "OK");
+
+    let (_, body) = post(&routes, json!({ "query": query })).await;
+    assert_eq!(body["data"]["health"], "OK");
+
+    let (status, body) = post(&routes, json!({ "query": "{ apiInfo { name } }" })).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_ALLOWED");
+
+    // A full query with its hash must not be registered in allowlist mode
+    let other = "{ systemStatus { status } }";
+    let (status, _) = post(
+        &routes,
+        json!({ "query": other, "extensions": persisted(&sha256_hex(other)) }),
+    )
+    .await;
+    assert_eq!(status, 403);
+    let (_, body) = post(&routes, json!({ "extensions": persisted(&sha256_hex(other)) })).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "PERSISTED_QUERY_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn test_schema_extension_resolves_persisted_queries() {
+    let settings = Arc::new(Settings::default());
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services).unwrap();
+    let query = "{ health }";
+    let hash = sha256_hex(query);
+
+    let request = |query: &str| {
+        let mut request = async_graphql::Request::new(query);
+        request.extensions.insert(
+            "persistedQuery".to_string(),
+            async_graphql::Value::from_json(persisted(&hash)["persistedQuery"].clone()).unwrap(),
+        );
+        request
+    };
+
+    let response = schema.execute(request("")).await;
+    assert_eq!(response.errors[0].message, "PersistedQueryNotFound");
+
+    assert!(schema.execute(request(query)).await.errors.is_empty());
+    let response = schema.execute(request("")).await;
+    assert_eq!(response.data.into_json().unwrap()["health"], "OK");
+}
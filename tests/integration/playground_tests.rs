@@ -0,0 +1,47 @@
+use rustql::config::settings::{PlaygroundConfig, PlaygroundUi};
+use rustql::server::playground::{content_type, embedded_asset, render_html};
+
+#[test]
+fn test_render_playground_with_endpoint_and_headers() {
+    let mut config = PlaygroundConfig {
+        endpoint: "/api/graphql".to_string(),
+        ..PlaygroundConfig::default()
+    };
+    config
+        .headers
+        .insert("x-tenant".to_string(), "</script><script>".to_string());
+
+    let html = render_html(&config);
+    assert!(html.contains(r#"src="/playground/assets/playground.js""#));
+    assert!(html.contains(r#""endpoint":"/api/graphql""#));
+    assert!(html.contains(r#"<\/script><script>"#));
+    assert!(!html.contains("\"</script>"));
+    assert!(html.contains(r#"<body class="light">"#));
+}
+
+#[test]
+fn test_render_playground_ui() {
+    let config = PlaygroundConfig {
+        ui: PlaygroundUi::Playground,
+        ..PlaygroundConfig::default()
+    };
+
+    let html = render_html(&config);
+    assert!(html.contains(r#"<body class="dark">"#));
+    assert!(!html.contains("https://"));
+}
+
+#[test]
+fn test_assets_are_embedded() {
+    let script = embedded_asset("playground.js").unwrap();
+    assert!(std::str::from_utf8(script).unwrap().contains("playground-options"));
+    assert!(embedded_asset("playground.css").is_some());
+    assert!(embedded_asset("graphiql.min.js").is_none());
+}
+
+#[test]
+fn test_asset_content_types() {
+    assert_eq!(content_type("playground.js"), "application/javascript; charset=utf-8");
+    assert_eq!(content_type("playground.css"), "text/css; charset=utf-8");
+    assert_eq!(content_type("unknown"), "application/octet-stream");
+}
@@ -0,0 +1,358 @@
+use crate::fixtures::{api, execute_with_headers, fields, query, relationship, serve};
+use rustql::Settings;
+use rustql::config::settings::{
+    AuthRule, BatchConfig, FieldType, PaginationConfig, QueryConfig, RelationshipConfig, TypeConfig,
+};
+use rustql::rest::RestClient;
+use rustql::rest::batch::BatchLoader;
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use warp::Filter;
+use warp::http::{HeaderMap, HeaderValue};
+
+const DEBUG_HEADER: &str = "x-query-plan";
+
+/// What the shop API saw: the most requests in flight at once and the path
+/// of every request.
+#[derive(Clone, Default)]
+struct Seen {
+    max_in_flight: Arc<AtomicUsize>,
+    paths: Arc<Mutex<Vec<String>>>,
+}
+
+impl Seen {
+    fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    fn paths(&self) -> Vec<String> {
+        let mut paths = self.paths.lock().unwrap().clone();
+        paths.sort();
+        paths
+    }
+}
+
+/// A shop API whose every response takes `delay`.
+fn settings(delay: Duration, seen: Seen) -> Settings {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let upstream = warp::path::full().and_then(move |path: warp::path::FullPath| {
+        let in_flight = in_flight.clone();
+        let seen = seen.clone();
+        async move {
+            seen.paths.lock().unwrap().push(path.as_str().to_string());
+            let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            seen.max_in_flight.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(delay).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let body = match path.as_str() {
+                "/users" => json!([{ "id": 1, "name": "Alice" }, { "id": 2, "name": "Bob" }]),
+                "/orders" => json!([{ "id": "o1", "userId": 1 }, { "id": "o2", "userId": 2 }]),
+                "/stores" => json!([{ "id": "s1", "userId": 1 }]),
+                path => json!({ "id": path.rsplit('/').next(), "userId": 1 }),
+            };
+            Ok::<_, warp::Rejection>(warp::reply::json(&body))
+        }
+    });
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(api("shop-api", serve(upstream)));
+    settings
+        .queries
+        .push(query("users", "shop-api", "/users", &[], Some("[User]")));
+    settings.queries.push(query(
+        "order",
+        "shop-api",
+        "/orders/{id}",
+        &["id"],
+        Some("Order"),
+    ));
+    settings.queries.push(query(
+        "product",
+        "shop-api",
+        "/products/{id}",
+        &["id"],
+        None,
+    ));
+    settings.queries.push(query(
+        "store",
+        "shop-api",
+        "/stores/{id}",
+        &["id"],
+        Some("Store"),
+    ));
+    settings.queries.push(QueryConfig {
+        pagination: Some(PaginationConfig::default()),
+        ..query("stores", "shop-api", "/stores", &[], Some("Store"))
+    });
+    settings.types.push(TypeConfig {
+        name: "User".to_string(),
+        description: None,
+        resource: None,
+        fields: [("name".to_string(), FieldType::String)].into(),
+        relationships: vec![RelationshipConfig {
+            batch: Some(BatchConfig {
+                key: "userId".to_string(),
+                ..Default::default()
+            }),
+            ..relationship(
+                "orders",
+                "shop-api",
+                "/orders?userId={parent.id}",
+                "[Order]",
+            )
+        }],
+    });
+    settings.types.push(TypeConfig {
+        name: "Order".to_string(),
+        description: None,
+        resource: None,
+        fields: [("id".to_string(), FieldType::Id)].into(),
+        relationships: vec![RelationshipConfig {
+            batch: Some(BatchConfig {
+                key: "id".to_string(),
+                ..Default::default()
+            }),
+            ..relationship("user", "shop-api", "/users?id={parent.userId}", "User")
+        }],
+    });
+    // Opening hours need only an argument, the manager the store itself
+    settings.types.push(TypeConfig {
+        name: "Store".to_string(),
+        description: None,
+        resource: None,
+        fields: fields(&[("id", FieldType::Id)]),
+        relationships: vec![
+            RelationshipConfig {
+                arguments: vec!["day".to_string()],
+                returns: None,
+                ..relationship("hours", "shop-api", "/hours/{day}", "")
+            },
+            relationship("manager", "shop-api", "/users/{parent.userId}", "User"),
+        ],
+    });
+    settings.planner.debug_header = Some(DEBUG_HEADER.to_string());
+    settings
+}
+
+async fn execute(settings: Settings, query: &str, debug: bool) -> Value {
+    let mut headers = HeaderMap::new();
+    if debug {
+        headers.insert(DEBUG_HEADER, HeaderValue::from_static("1"));
+    }
+    execute_with_headers(settings, query, headers).await
+}
+
+/// Field, path, dependency and whether it was prefetched, per plan step.
+fn summary(plan: &Value) -> Vec<Value> {
+    plan["steps"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|step| {
+            json!([
+                step["field"],
+                step["path"],
+                step["dependsOn"],
+                step["prefetched"]
+            ])
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_plan_reported_with_debug_header() {
+    let query = r#"{
+        users { name orders { id } }
+        latest: order(id: "o2") { id user { name } }
+    }"#;
+    let settings = || settings(Duration::ZERO, Seen::default());
+
+    let body = execute(settings(), query, false).await;
+    assert!(body.get("errors").is_none(), "{}", body);
+    assert!(body["extensions"].get("queryPlan").is_none());
+
+    let body = execute(settings(), query, true).await;
+    assert!(body.get("errors").is_none(), "{}", body);
+    let plan = &body["extensions"]["queryPlan"];
+    assert_eq!(plan["maxConcurrency"], 16);
+    assert_eq!(plan["stages"], json!([[0, 2], [1, 3]]));
+
+    let steps = plan["steps"].as_array().unwrap();
+    let summary: Vec<Value> = steps
+        .iter()
+        .map(|step| {
+            json!([
+                step["field"],
+                step["path"],
+                step["dependsOn"],
+                step["batched"],
+                step["calls"].as_array().unwrap().len()
+            ])
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            json!(["QueryRoot.users", "users", null, false, 1]),
+            json!(["User.orders", "users.orders", 0, true, 2]),
+            json!(["QueryRoot.order", "latest", null, false, 1]),
+            json!(["Order.user", "latest.user", 2, true, 1]),
+        ]
+    );
+    assert_eq!(steps[2]["calls"][0]["path"], "/orders/o2");
+    assert_eq!(steps[2]["prefetched"], true);
+    assert_eq!(steps[3]["upstream"], "/users?id={parent.userId}");
+    assert_eq!(steps[3]["prefetched"], false);
+    let start = steps[3]["startMs"].as_f64().unwrap();
+    assert!(
+        start >= steps[2]["startMs"].as_f64().unwrap() + steps[2]["durationMs"].as_f64().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_independent_calls_prefetched() {
+    let query = r#"{ store(id: "s1") { id hours(day: "mon") manager { name } } }"#;
+    let seen = Seen::default();
+    let body = execute(
+        settings(Duration::from_millis(100), seen.clone()),
+        query,
+        true,
+    )
+    .await;
+    assert!(body.get("errors").is_none(), "{}", body);
+
+    // The hours need nothing from the store, so both are fetched at once
+    // and their resolvers await the prefetched calls
+    let plan = &body["extensions"]["queryPlan"];
+    assert_eq!(plan["stages"], json!([[0, 1], [2]]));
+    assert_eq!(
+        summary(plan),
+        vec![
+            json!(["QueryRoot.store", "store", null, true]),
+            json!(["Store.hours", "store.hours", null, true]),
+            json!(["Store.manager", "store.manager", 0, false]),
+        ]
+    );
+    assert_eq!(seen.max_in_flight(), 2);
+    assert_eq!(seen.paths(), vec!["/hours/mon", "/stores/s1", "/users/1"]);
+}
+
+#[tokio::test]
+async fn test_unresolvable_calls_not_prefetched() {
+    let seen = Seen::default();
+    let mut settings = settings(Duration::ZERO, seen.clone());
+    settings.auth.rules.insert(
+        "Store.hours".to_string(),
+        AuthRule {
+            requires: vec!["admin".to_string()],
+        },
+    );
+
+    // Denied, skipped and mutation fields wait for the executor, which
+    // never calls them
+    let query = r#"{
+        store(id: "s1") {
+            id
+            hours(day: "mon")
+            closed: hours(day: "sun") @skip(if: true)
+        }
+    }"#;
+    let body = execute(settings, query, true).await;
+    assert_eq!(body["data"]["store"]["hours"], Value::Null);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    assert_eq!(
+        summary(&body["extensions"]["queryPlan"]),
+        vec![
+            json!(["QueryRoot.store", "store", null, true]),
+            json!(["Store.hours", "store.hours", 0, false]),
+            json!(["Store.hours", "store.closed", 0, false]),
+        ]
+    );
+    assert_eq!(seen.paths(), vec!["/stores/s1"]);
+}
+
+#[tokio::test]
+async fn test_plan_descends_into_connection_nodes() {
+    let query = r#"{ stores(first: 1) { edges { node { id manager { name } } } } }"#;
+    let body = execute(settings(Duration::ZERO, Seen::default()), query, true).await;
+    assert!(body.get("errors").is_none(), "{}", body);
+    assert_eq!(
+        summary(&body["extensions"]["queryPlan"]),
+        vec![
+            json!(["QueryRoot.stores", "stores", null, false]),
+            json!(["Store.manager", "stores.edges.node.manager", 0, false]),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_concurrent_calls_bounded() {
+    let query = r#"{
+        a: product(id: "1") b: product(id: "2") c: product(id: "3")
+        d: product(id: "4") e: product(id: "5") f: product(id: "6")
+    }"#;
+
+    let seen = Seen::default();
+    let mut bounded = settings(Duration::from_millis(50), seen.clone());
+    bounded.planner.max_concurrency = 2;
+    let body = execute(bounded, query, false).await;
+    assert!(body.get("errors").is_none(), "{}", body);
+    assert_eq!(seen.max_in_flight(), 2);
+    assert_eq!(seen.paths().len(), 6);
+
+    // Independent calls otherwise all run at once
+    let seen = Seen::default();
+    let body = execute(
+        settings(Duration::from_millis(50), seen.clone()),
+        query,
+        false,
+    )
+    .await;
+    assert!(body.get("errors").is_none(), "{}", body);
+    assert_eq!(seen.max_in_flight(), 6);
+}
+
+#[tokio::test]
+async fn test_batch_waits_for_every_operation_limit() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counted = requests.clone();
+    let upstream = warp::path!("users").map(move || {
+        counted.fetch_add(1, Ordering::SeqCst);
+        warp::reply::json(&json!([{ "id": 1 }, { "id": 2 }]))
+    });
+    let client = RestClient::from_config(&api("shop-api", serve(upstream))).unwrap();
+    let config = BatchConfig {
+        key: "id".to_string(),
+        ..Default::default()
+    };
+    let loader = Arc::new(BatchLoader::from_config(&config, None).unwrap());
+
+    // Two operations share a batch while the second has no call to spare
+    let first = Arc::new(Semaphore::new(1));
+    let second = Arc::new(Semaphore::new(1));
+    let busy = second.clone().acquire_owned().await.unwrap();
+    let lookup = |permits: &Arc<Semaphore>, value: &str| {
+        let (loader, client) = (loader.clone(), client.clone());
+        let (permits, value) = (permits.clone(), value.to_string());
+        tokio::spawn(async move {
+            let prefix = "/users?id=".to_string();
+            loader
+                .load(client, Some(permits), prefix, String::new(), value)
+                .await
+        })
+    };
+    let lookups = [lookup(&first, "1"), lookup(&second, "2")];
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+    drop(busy);
+    for lookup in lookups {
+        assert_eq!(lookup.await.unwrap().unwrap().len(), 1);
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
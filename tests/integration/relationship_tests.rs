@@ -0,0 +1,242 @@
+use crate::fixtures::{api, fields, query, relationship, schema, serve};
+use rustql::Settings;
+use rustql::config::settings::{
+    BatchConfig, FieldType, RelationshipConfig, ResultMappingConfig, TypeConfig,
+};
+use rustql::rest::RestClient;
+use rustql::rest::batch::BatchLoader;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use warp::Filter;
+
+/// Users and orders served by two upstreams; every request to the orders
+/// API is recorded.
+fn settings(orders_requests: Arc<AtomicUsize>) -> Settings {
+    let users = warp::path!("users")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            let users = [
+                json!({ "id": 1, "name": "Alice" }),
+                json!({ "id": 2, "name": "Bob" }),
+                json!({ "id": 3, "name": "Carol" }),
+            ];
+            let matching: Vec<_> = match query.get("id") {
+                Some(ids) => {
+                    let ids: Vec<&str> = ids.split(',').collect();
+                    users
+                        .into_iter()
+                        .filter(|user| ids.contains(&user["id"].to_string().as_str()))
+                        .collect()
+                }
+                None => users.to_vec(),
+            };
+            warp::reply::json(&matching)
+        });
+    let users_addr = serve(users);
+
+    let orders = warp::path!("orders")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(move |query: HashMap<String, String>| {
+            orders_requests.fetch_add(1, Ordering::SeqCst);
+            let ids: Vec<&str> = query["userId"].split(',').collect();
+            let orders = [
+                json!({ "id": "o1", "userId": 1, "total": 10.5 }),
+                json!({ "id": "o2", "userId": 1, "total": 3 }),
+                json!({ "id": "o3", "userId": 2, "total": 7 }),
+            ];
+            let matching: Vec<_> = orders
+                .into_iter()
+                .filter(|order| ids.contains(&order["userId"].to_string().as_str()))
+                .collect();
+            warp::reply::json(&json!({ "data": matching }))
+        });
+    let order = warp::path!("orders" / String).map(|id: String| {
+        let user = if id == "o9" { json!(null) } else { json!(2) };
+        warp::reply::json(&json!({ "id": id, "userId": user, "total": 7 }))
+    });
+    let orders_addr = serve(orders.or(order));
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(api("users-api", users_addr));
+    settings.apis.rest.push(api("orders-api", orders_addr));
+    settings
+        .queries
+        .push(query("users", "users-api", "/users", &[], Some("[User]")));
+    settings.queries.push(query(
+        "order",
+        "orders-api",
+        "/orders/{id}",
+        &["id"],
+        Some("Order"),
+    ));
+    settings.types.push(TypeConfig {
+        name: "User".to_string(),
+        description: Some("A customer".to_string()),
+        resource: None,
+        fields: fields(&[("id", FieldType::Id), ("name", FieldType::String)]),
+        relationships: vec![RelationshipConfig {
+            result: Some(ResultMappingConfig {
+                select: Some("data".to_string()),
+                ..Default::default()
+            }),
+            batch: Some(BatchConfig {
+                key: "userId".to_string(),
+                ..Default::default()
+            }),
+            ..relationship(
+                "orders",
+                "orders-api",
+                "/orders?userId={parent.id}",
+                "[Order]",
+            )
+        }],
+    });
+    settings.types.push(TypeConfig {
+        name: "Order".to_string(),
+        description: None,
+        resource: None,
+        fields: fields(&[("id", FieldType::Id), ("total", FieldType::Float)]),
+        relationships: vec![RelationshipConfig {
+            batch: Some(BatchConfig {
+                key: "id".to_string(),
+                ..Default::default()
+            }),
+            ..relationship("user", "users-api", "/users?id={parent.userId}", "User")
+        }],
+    });
+    settings
+}
+
+#[tokio::test]
+async fn test_batched_relationship_across_apis() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let schema = schema(settings(requests.clone()));
+
+    let response = serde_json::to_value(
+        schema
+            .execute("{ users { id name orders { id total } } }")
+            .await,
+    )
+    .unwrap();
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["users"],
+        json!([
+            {
+                "id": "1",
+                "name": "Alice",
+                "orders": [{ "id": "o1", "total": 10.5 }, { "id": "o2", "total": 3 }],
+            },
+            { "id": "2", "name": "Bob", "orders": [{ "id": "o3", "total": 7 }] },
+            { "id": "3", "name": "Carol", "orders": [] },
+        ])
+    );
+    // One request for the orders of all three users
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_relationship_reads_parent_fields() {
+    let schema = schema(settings(Arc::new(AtomicUsize::new(0))));
+
+    let response = serde_json::to_value(
+        schema
+            .execute(r#"{ order(id: "o3") { id user { id name orders { id } } } }"#)
+            .await,
+    )
+    .unwrap();
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["order"],
+        json!({
+            "id": "o3",
+            "user": { "id": "2", "name": "Bob", "orders": [{ "id": "o3" }] },
+        })
+    );
+
+    // A parent without the value its path needs resolves to null
+    let response = serde_json::to_value(
+        schema
+            .execute(r#"{ order(id: "o9") { id user { id } } }"#)
+            .await,
+    )
+    .unwrap();
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(response["data"]["order"]["user"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_invalid_relationships_rejected() {
+    let valid = settings(Arc::new(AtomicUsize::new(0)));
+
+    let mut invalid = valid.clone();
+    invalid.types[1].relationships[0].returns = Some("[Customer]".to_string());
+    assert!(invalid.validate().is_err());
+
+    // Batching needs the parent placeholder to fill
+    let mut invalid = valid.clone();
+    invalid.types[0].relationships[0].path = "/orders".to_string();
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = valid.clone();
+    invalid.types[0].name = "JSON".to_string();
+    assert!(invalid.validate().is_err());
+
+    // Only `{parent.*}` placeholders are filled without an argument
+    let mut invalid = valid.clone();
+    invalid.types[0].relationships[0].path =
+        "/orders?userId={parent.id}&status={status}".to_string();
+    assert!(invalid.validate().is_err());
+
+    // Orders are listed under users, so an unbatched lookup of their user
+    // would call the API once per order
+    let mut invalid = valid;
+    invalid.types[1].relationships[0].batch = None;
+    assert!(invalid.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_abandoned_batch_not_fetched() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counted = requests.clone();
+    let orders = warp::path!("orders").map(move || {
+        counted.fetch_add(1, Ordering::SeqCst);
+        warp::reply::json(&json!([]))
+    });
+    let client = RestClient::from_config(&api("orders-api", serve(orders))).unwrap();
+    let loader = Arc::new(
+        BatchLoader::from_config(
+            &BatchConfig {
+                key: "userId".to_string(),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap(),
+    );
+
+    // The batch waits for the operation's only call permit until its
+    // request gives up
+    let permits = Arc::new(Semaphore::new(1));
+    let held = permits.clone().acquire_owned().await.unwrap();
+    let lookup = loader.load(
+        client,
+        Some(permits),
+        "/orders?userId=".to_string(),
+        String::new(),
+        "1".to_string(),
+    );
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), lookup)
+            .await
+            .is_err()
+    );
+
+    drop(held);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(requests.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,153 @@
+use rustql::graphql::build_schema;
+use rustql::graphql::document::normalize_operation;
+use rustql::server::build_routes;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use warp::Filter;
+use warp::reply::Response;
+
+fn cached_routes() -> impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone {
+    let mut settings = Settings::default();
+    settings.cache.response.enabled = true;
+    settings
+        .cache
+        .response
+        .hints
+        .insert("QueryRoot.apiInfo".to_string(), 60);
+    settings
+        .cache
+        .response
+        .hints
+        .insert("QueryRoot.echo".to_string(), 300);
+
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    build_routes(settings, schema, services)
+}
+
+async fn post(
+    routes: &(impl Filter<Extract = (Response,), Error = std::convert::Infallible> + Clone + 'static),
+    authorization: Option<&str>,
+    body: Value,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    let mut request = warp::test::request()
+        .method("POST")
+        .path("/graphql")
+        .json(&body);
+    if let Some(authorization) = authorization {
+        request = request.header("authorization", authorization);
+    }
+    request.reply(routes).await
+}
+
+fn uptime(response: &warp::http::Response<warp::hyper::body::Bytes>) -> String {
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    body["data"]["apiInfo"]["uptime"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_normalize_operation_ignores_formatting() {
+    let a = normalize_operation(
+        "query Q($b: Int, $a: String) {\n  # comment\n  echo(message: $a) ...F }\nfragment F on QueryRoot { health }",
+        None,
+    )
+    .unwrap();
+    let b = normalize_operation(
+        "query Q($a: String, $b: Int) { echo(message: $a) ... on QueryRoot { health } }",
+        None,
+    )
+    .unwrap();
+    assert_eq!(a, b);
+
+    let c = normalize_operation(r#"{ f(b: 1, a: { y: 2, x: 1 }) }"#, None).unwrap();
+    let d = normalize_operation(r#"{ f(a: { x: 1, y: 2 }, b: 1) }"#, None).unwrap();
+    assert_eq!(c, d);
+
+    let e = normalize_operation("{ health echo(message: \"x\") }", None).unwrap();
+    let f = normalize_operation("{ echo(message: \"x\") health }", None).unwrap();
+    assert_ne!(e, f);
+}
+
+#[tokio::test]
+async fn test_response_cached_for_minimum_max_age() {
+    let routes = cached_routes();
+
+    let first = post(
+        &routes,
+        None,
+        json!({ "query": "{ apiInfo { uptime } echo(message: \"a\") }" }),
+    )
+    .await;
+    assert_eq!(first.headers()["cache-control"], "max-age=60");
+    assert_eq!(first.headers()["age"], "0");
+
+    let second = post(
+        &routes,
+        None,
+        json!({ "query": "# same operation\n{\n  apiInfo { uptime }\n  echo(message: \"a\")\n}" }),
+    )
+    .await;
+    assert_eq!(second.headers()["cache-control"], "max-age=60");
+    assert_eq!(uptime(&first), uptime(&second));
+}
+
+#[tokio::test]
+async fn test_response_cache_keyed_by_variables_and_auth_scope() {
+    let routes = cached_routes();
+    let query = "query($m: String!) { apiInfo { uptime } echo(message: $m) }";
+
+    let anonymous = post(
+        &routes,
+        None,
+        json!({ "query": query, "variables": { "m": "a" } }),
+    )
+    .await;
+    let other_variables = post(
+        &routes,
+        None,
+        json!({ "query": query, "variables": { "m": "b" } }),
+    )
+    .await;
+    assert_ne!(uptime(&anonymous), uptime(&other_variables));
+
+    let alice = post(
+        &routes,
+        Some("Bearer alice"),
+        json!({ "query": query, "variables": { "m": "a" } }),
+    )
+    .await;
+    assert_eq!(alice.headers()["cache-control"], "max-age=60, private");
+    assert_ne!(uptime(&anonymous), uptime(&alice));
+
+    let alice_again = post(
+        &routes,
+        Some("Bearer alice"),
+        json!({ "query": query, "variables": { "m": "a" } }),
+    )
+    .await;
+    assert_eq!(uptime(&alice), uptime(&alice_again));
+}
+
+#[tokio::test]
+async fn test_unhinted_root_fields_are_not_cached() {
+    let routes = cached_routes();
+    let body = json!({ "query": "{ apiInfo { uptime } health }" });
+
+    let first = post(&routes, None, body.clone()).await;
+    assert!(!first.headers().contains_key("cache-control"));
+    let second = post(&routes, None, body).await;
+    assert_ne!(uptime(&first), uptime(&second));
+
+    let mutation = post(
+        &routes,
+        None,
+        json!({ "query": "mutation { testMutation(input: \"x\") }" }),
+    )
+    .await;
+    assert!(!mutation.headers().contains_key("cache-control"));
+}
@@ -0,0 +1,205 @@
+use rustql::config::settings::{Coercion, QueryConfig, RestApiConfig, ResultMappingConfig};
+use rustql::graphql::build_schema;
+use rustql::rest::adapter::ResultMapping;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use warp::Filter;
+
+fn mapping(config: ResultMappingConfig) -> ResultMapping {
+    ResultMapping::from_config(&config).unwrap()
+}
+
+#[test]
+fn test_select_extracts_wrapped_payloads() {
+    let response = json!({
+        "data": { "items": [{ "node": { "id": 1 } }, { "node": { "id": 2 } }] },
+        "next": "abc"
+    });
+
+    let items = mapping(ResultMappingConfig {
+        select: Some("data.items".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(
+        items
+            .apply(response.clone())
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let nodes = mapping(ResultMappingConfig {
+        select: Some("$.data.items.node".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(
+        nodes.apply(response.clone()).unwrap(),
+        json!([{ "id": 1 }, { "id": 2 }])
+    );
+
+    let second = mapping(ResultMappingConfig {
+        select: Some("data.items[1].node.id".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(second.apply(response.clone()).unwrap(), json!(2));
+
+    let missing = mapping(ResultMappingConfig {
+        select: Some("data.missing[3]".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(missing.apply(response).unwrap(), Value::Null);
+}
+
+#[test]
+fn test_renaming_coercion_and_defaults() {
+    let mapping = mapping(ResultMappingConfig {
+        camel_case: true,
+        aliases: [("author.user_name".to_string(), "login".to_string())].into(),
+        coerce: [
+            ("orderCount".to_string(), Coercion::Int),
+            ("createdAt".to_string(), Coercion::DateTime),
+            ("updatedAt".to_string(), Coercion::DateTime),
+            ("lineItems.unitPrice".to_string(), Coercion::Float),
+            ("isActive".to_string(), Coercion::Boolean),
+        ]
+        .into(),
+        defaults: [
+            ("status".to_string(), json!("pending")),
+            ("lineItems.quantity".to_string(), json!(1)),
+        ]
+        .into(),
+        ..Default::default()
+    });
+
+    let value = mapping
+        .apply(json!({
+            "order_count": "42",
+            "created_at": "2024-03-01 12:30:00",
+            "updated_at": 1709296200,
+            "is_active": "1",
+            "status": null,
+            "author": { "user_name": "alice", "_id": 7 },
+            "line_items": [{ "unit_price": "9.5", "quantity": 3 }, { "unit_price": 2 }],
+        }))
+        .unwrap();
+
+    assert_eq!(
+        value,
+        json!({
+            "orderCount": 42,
+            "createdAt": "2024-03-01T12:30:00Z",
+            "updatedAt": "2024-03-01T12:30:00Z",
+            "isActive": true,
+            "status": "pending",
+            "author": { "login": "alice", "_id": 7 },
+            "lineItems": [
+                { "unitPrice": 9.5, "quantity": 3 },
+                { "unitPrice": 2.0, "quantity": 1 },
+            ],
+        })
+    );
+
+    let error = mapping.apply(json!({ "order_count": "many" })).unwrap_err();
+    assert!(error.contains("orderCount"), "{}", error);
+}
+
+#[test]
+fn test_invalid_paths_rejected() {
+    for select in ["data..items", "items[x]", "items[0"] {
+        let config = ResultMappingConfig {
+            select: Some(select.to_string()),
+            ..Default::default()
+        };
+        assert!(ResultMapping::from_config(&config).is_err(), "{}", select);
+    }
+
+    let config = ResultMappingConfig {
+        coerce: [("items[0]".to_string(), Coercion::Int)].into(),
+        ..Default::default()
+    };
+    assert!(ResultMapping::from_config(&config).is_err());
+}
+
+#[tokio::test]
+async fn test_query_field_applies_mapping() {
+    let upstream = warp::path!("users").map(|| {
+        warp::reply::json(&json!({
+            "data": [
+                { "user_id": "1", "display_name": "Alice" },
+                { "user_id": "2", "display_name": "Bob", "role": "admin" },
+            ],
+        }))
+    });
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "users-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "users".to_string(),
+        api: "users-api".to_string(),
+        path: "/users".to_string(),
+        result: Some(ResultMappingConfig {
+            select: Some("data".to_string()),
+            camel_case: true,
+            aliases: [("display_name".to_string(), "name".to_string())].into(),
+            coerce: [("userId".to_string(), Coercion::Int)].into(),
+            defaults: [("role".to_string(), json!("member"))].into(),
+        }),
+        ..Default::default()
+    });
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services).unwrap();
+
+    let response = serde_json::to_value(schema.execute("{ users }").await).unwrap();
+    assert_eq!(
+        response["data"]["users"],
+        json!([
+            { "userId": 1, "name": "Alice", "role": "member" },
+            { "userId": 2, "name": "Bob", "role": "admin" },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_unconvertible_payload_is_not_an_upstream_failure() {
+    let upstream = warp::path!("users").map(|| warp::reply::json(&json!([{ "age": "old" }])));
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "users-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "users".to_string(),
+        api: "users-api".to_string(),
+        path: "/users".to_string(),
+        result: Some(ResultMappingConfig {
+            coerce: [("age".to_string(), Coercion::Int)].into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services).unwrap();
+
+    let response = serde_json::to_value(schema.execute("{ users }").await).unwrap();
+    assert_eq!(response["data"]["users"], Value::Null);
+    let extensions = &response["errors"][0]["extensions"];
+    assert_eq!(extensions["code"], "UNEXPECTED_RESPONSE");
+    assert_eq!(extensions["api"], "users-api");
+    assert_eq!(extensions["retryable"], false);
+    assert!(extensions.get("upstreamStatus").is_none());
+}
@@ -0,0 +1,116 @@
+use rustql::config::settings::{QueryConfig, RestApiConfig};
+use rustql::graphql::build_schema;
+use rustql::{Services, Settings};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::server::handlers::handle_graphql;
+use rustql::server::listener::ConnectionLimiter;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use warp::{Filter, Reply};
+
+async fn response_json(reply: impl Reply) -> (u16, Value) {
+    let response = reply.into_response();
+    let status = response.status().as_u16();
+    let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_connection_limiter_caps_permits() {
+    let limiter = ConnectionLimiter::new(Some(2));
+
+    let first = limiter.acquire().await;
+    let _second = limiter.acquire().await;
+    assert_eq!(limiter.available(), Some(0));
+
+    drop(first);
+    assert_eq!(limiter.available(), Some(1));
+
+    assert!(ConnectionLimiter::new(None).acquire().await.is_none());
+}
+
+#[tokio::test]
+async fn test_handle_graphql_executes_schema() {
+    let settings = Arc::new(Settings::default());
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+
+    let reply = handle_graphql(
+        ResolverContext::new(settings.clone(), "req-1".to_string()),
+        settings,
+        schema,
+        services,
+        json!({ "query": "{ health }" }),
+    )
+    .await
+    .unwrap();
+
+    let (status, body) = response_json(reply).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["health"], "OK");
+}
+
+#[tokio::test]
+async fn test_handle_graphql_rejects_malformed_request() {
+    let settings = Arc::new(Settings::default());
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+
+    let reply = handle_graphql(
+        ResolverContext::new(settings.clone(), "req-2".to_string()),
+        settings,
+        schema,
+        services,
+        json!({ "query": 42 }),
+    )
+    .await
+    .unwrap();
+
+    let (status, body) = response_json(reply).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "BAD_REQUEST");
+}
+
+#[tokio::test]
+async fn test_handle_graphql_times_out_slow_upstreams() {
+    let slow = warp::path!("slow").and_then(|| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok::<_, warp::Rejection>(warp::reply::json(&json!({ "ok": true })))
+    });
+    let (addr, server) = warp::serve(slow).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.server.request_timeout = Some(1);
+    settings.apis.rest.push(RestApiConfig {
+        name: "slow-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "slow".to_string(),
+        api: "slow-api".to_string(),
+        path: "/slow".to_string(),
+        ..Default::default()
+    });
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+
+    let started = Instant::now();
+    let reply = handle_graphql(
+        ResolverContext::new(settings.clone(), "req-3".to_string()),
+        settings,
+        schema,
+        services,
+        json!({ "query": "{ slow }" }),
+    )
+    .await
+    .unwrap();
+
+    let (status, body) = response_json(reply).await;
+    assert_eq!(status, 504);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "TIMEOUT");
+    assert!(started.elapsed() < Duration::from_secs(4));
+}
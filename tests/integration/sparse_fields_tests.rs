@@ -0,0 +1,216 @@
+use crate::fixtures::{api, execute, query, relationship, serve};
+use rustql::Settings;
+use rustql::config::settings::{
+    BatchConfig, FieldType, QueryConfig, RelationshipConfig, RestApiConfig, ResultMappingConfig,
+    SparseFieldsConfig, TypeConfig,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+
+type Requests = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
+
+/// Users and orders APIs recording the path and query of every request.
+/// Users take `?fields=`, orders JSON:API `fields[orders]=`.
+fn settings(requests: Requests) -> Settings {
+    let upstream = warp::path::full()
+        .and(warp::query::<HashMap<String, String>>())
+        .map(
+            move |path: warp::path::FullPath, query: HashMap<String, String>| {
+                requests
+                    .lock()
+                    .unwrap()
+                    .push((path.as_str().to_string(), query.clone()));
+                let body = match path.as_str() {
+                    "/users" => json!([
+                        { "id": 1, "name": "Alice", "email": "alice@example.com" },
+                        { "id": 2, "name": "Bob", "email": "bob@example.com" },
+                    ]),
+                    "/orders" => json!([
+                        { "id": "o1", "user_id": 1, "total": 10 },
+                        { "id": "o2", "user_id": 2, "total": 7 },
+                    ]),
+                    _ => json!({ "id": 1, "user_name": "alice", "email": "alice@example.com" }),
+                };
+                warp::reply::json(&body)
+            },
+        );
+    let addr = serve(upstream);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        sparse_fields: Some(SparseFieldsConfig::default()),
+        ..api("users-api", addr)
+    });
+    settings.apis.rest.push(RestApiConfig {
+        sparse_fields: Some(SparseFieldsConfig {
+            param: "fields[{type}]".to_string(),
+            ..Default::default()
+        }),
+        ..api("orders-api", addr)
+    });
+    settings
+        .queries
+        .push(query("users", "users-api", "/users", &[], Some("[User]")));
+    settings.queries.push(QueryConfig {
+        result: Some(ResultMappingConfig {
+            camel_case: true,
+            aliases: [("email".to_string(), "contact".to_string())].into(),
+            ..Default::default()
+        }),
+        ..query(
+            "profile",
+            "users-api",
+            "/profiles/{id}",
+            &["id"],
+            Some("Profile"),
+        )
+    });
+    settings.queries.push(query(
+        "rawProfile",
+        "users-api",
+        "/profiles/{id}",
+        &["id"],
+        None,
+    ));
+    settings.types.push(TypeConfig {
+        name: "User".to_string(),
+        description: None,
+        resource: None,
+        fields: [
+            ("id".to_string(), FieldType::Id),
+            ("name".to_string(), FieldType::String),
+            ("email".to_string(), FieldType::String),
+        ]
+        .into(),
+        relationships: vec![RelationshipConfig {
+            result: Some(ResultMappingConfig {
+                camel_case: true,
+                aliases: [("user_id".to_string(), "userId".to_string())].into(),
+                ..Default::default()
+            }),
+            batch: Some(BatchConfig {
+                key: "userId".to_string(),
+                ..Default::default()
+            }),
+            ..relationship(
+                "orders",
+                "orders-api",
+                "/orders?filter[user]={parent.id}",
+                "[Order]",
+            )
+        }],
+    });
+    settings.types.push(TypeConfig {
+        name: "Order".to_string(),
+        description: None,
+        resource: Some("orders".to_string()),
+        fields: [
+            ("id".to_string(), FieldType::Id),
+            ("total".to_string(), FieldType::Int),
+        ]
+        .into(),
+        relationships: vec![],
+    });
+    settings.types.push(TypeConfig {
+        name: "Profile".to_string(),
+        description: None,
+        resource: None,
+        fields: [
+            ("id".to_string(), FieldType::Id),
+            ("userName".to_string(), FieldType::String),
+            ("contact".to_string(), FieldType::String),
+        ]
+        .into(),
+        relationships: vec![],
+    });
+    settings
+}
+
+#[tokio::test]
+async fn test_selected_fields_pushed_down() {
+    let requests = Requests::default();
+    let response = execute(
+        settings(requests.clone()),
+        "{ users { __typename name orders { total } } }",
+    )
+    .await;
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["users"],
+        json!([
+            { "__typename": "User", "name": "Alice", "orders": [{ "total": 10 }] },
+            { "__typename": "User", "name": "Bob", "orders": [{ "total": 7 }] },
+        ])
+    );
+
+    let mut requests = requests.lock().unwrap().clone();
+    requests.sort_by(|a, b| a.0.cmp(&b.0));
+    // The parent key the relationship reads is requested alongside `name`,
+    // and the batch key alongside `total`
+    assert_eq!(requests[0].0, "/orders");
+    assert_eq!(requests[0].1["fields[orders]"], "total,user_id");
+    assert_eq!(requests[0].1["filter[user]"], "1,2");
+    assert_eq!(requests[1].0, "/users");
+    assert_eq!(requests[1].1["fields"], "id,name");
+    assert_eq!(requests.len(), 2);
+}
+
+#[tokio::test]
+async fn test_mapped_keys_pushed_down_by_upstream_name() {
+    let requests = Requests::default();
+    let response = execute(
+        settings(requests.clone()),
+        r#"{
+            profile(id: "1") { ... on Profile { id } contact }
+            rawProfile(id: "1")
+        }"#,
+    )
+    .await;
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["profile"],
+        json!({ "id": "1", "contact": "alice@example.com" })
+    );
+
+    let requests = requests.lock().unwrap().clone();
+    let fields: Vec<Option<&String>> = requests
+        .iter()
+        .map(|(_, query)| query.get("fields"))
+        .collect();
+    assert_eq!(requests.len(), 2);
+    // Untyped results are fetched whole
+    assert!(fields.contains(&Some(&"email,id".to_string())));
+    assert!(fields.contains(&None));
+}
+
+#[tokio::test]
+async fn test_ambiguous_keys_fetched_whole() {
+    // `userName` may come from `user_name` or `userName` upstream
+    let requests = Requests::default();
+    let response = execute(
+        settings(requests.clone()),
+        r#"{ profile(id: "1") { userName contact } }"#,
+    )
+    .await;
+    assert!(response.get("errors").is_none(), "{}", response);
+    assert_eq!(
+        response["data"]["profile"],
+        json!({ "userName": "alice", "contact": "alice@example.com" })
+    );
+
+    let requests = requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 1);
+    assert!(!requests[0].1.contains_key("fields"));
+}
+
+#[tokio::test]
+async fn test_sparse_fields_need_param() {
+    let mut settings = settings(Requests::default());
+    settings.apis.rest[0].sparse_fields = Some(SparseFieldsConfig {
+        param: String::new(),
+        ..Default::default()
+    });
+    assert!(settings.validate().is_err());
+}
@@ -0,0 +1,338 @@
+use futures_util::future::BoxFuture;
+use rustql::cache::http::Validators;
+use rustql::cache::{CacheManager, CachePolicy, Fetched};
+use rustql::config::settings::{CircuitBreakerConfig, QueryConfig, RestApiConfig};
+use rustql::graphql::RustQLSchema;
+use rustql::graphql::build_schema;
+use rustql::rest::RestClient;
+use rustql::rest::circuit::CircuitBreaker;
+use rustql::{Result, RustQLError, Services, Settings};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use warp::Filter;
+use warp::http::StatusCode;
+
+/// Upstream answering `/orders/{id}` with a request counter, or 503 while
+/// `failing` is set.
+fn spawn_upstream(hits: Arc<AtomicUsize>, failing: Arc<AtomicBool>) -> SocketAddr {
+    let routes = warp::path!("orders" / String).map(move |id: String| {
+        let hit = hits.fetch_add(1, Ordering::SeqCst) + 1;
+        if failing.load(Ordering::SeqCst) {
+            warp::reply::with_status(
+                warp::reply::json(&json!({})),
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+        } else {
+            warp::reply::with_status(
+                warp::reply::json(&json!({ "id": id, "hit": hit })),
+                StatusCode::OK,
+            )
+        }
+    });
+    let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+fn order_settings(addr: SocketAddr, failure_threshold: u32) -> Settings {
+    let mut settings = Settings::default();
+    settings.cache.default_ttl = 0;
+    settings.cache.stale_if_error = 60;
+    settings.apis.rest.push(RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: format!("http://{}", addr),
+        circuit_breaker: CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold,
+            reset_timeout: 60,
+        },
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "order".to_string(),
+        api: "orders-api".to_string(),
+        path: "/orders/{id}".to_string(),
+        arguments: vec!["id".to_string()],
+        ..Default::default()
+    });
+    settings
+}
+
+async fn query_order(schema: &RustQLSchema) -> Value {
+    let response = schema.execute(r#"{ order(id: "7") }"#).await;
+    serde_json::to_value(&response).unwrap()
+}
+
+/// A slow fetch returning how many times it has been called.
+fn counting_fetch(
+    calls: &Arc<AtomicUsize>,
+) -> impl FnOnce(Validators) -> BoxFuture<'static, Result<Fetched>> {
+    let calls = calls.clone();
+    move |_| {
+        Box::pin(async move {
+            let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(Fetched::value(call.to_string()))
+        })
+    }
+}
+
+#[test]
+fn test_circuit_breaker_opens_after_threshold() {
+    let breaker = CircuitBreaker::new(&CircuitBreakerConfig {
+        enabled: true,
+        failure_threshold: 2,
+        reset_timeout: 60,
+    });
+
+    breaker.record_failure("api");
+    assert!(breaker.allow());
+    breaker.record_failure("api");
+    assert!(breaker.is_open());
+    assert!(!breaker.allow());
+
+    breaker.record_success();
+    assert!(breaker.allow());
+
+    let half_open = CircuitBreaker::new(&CircuitBreakerConfig {
+        enabled: true,
+        failure_threshold: 1,
+        reset_timeout: 0,
+    });
+    half_open.record_failure("api");
+    assert!(half_open.allow());
+    assert!(!half_open.allow());
+
+    // A trial that never reports back reopens the circuit for another trial
+    half_open.abandon_trial();
+    assert!(half_open.is_open());
+    assert!(half_open.allow());
+}
+
+#[tokio::test]
+async fn test_cancelled_trial_call_does_not_wedge_circuit() {
+    // An upstream that accepts connections but never answers
+    let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = RestClient::from_config(&RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: format!("http://{}", silent.local_addr().unwrap()),
+        circuit_breaker: CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 1,
+            reset_timeout: 0,
+        },
+        ..Default::default()
+    })
+    .unwrap();
+    client.circuit_breaker().record_failure("orders-api");
+
+    // The trial call is dropped before the upstream answers
+    let trial = client.get("/orders/1");
+    assert!(tokio::time::timeout(Duration::from_millis(100), trial).await.is_err());
+
+    // The next call is let through as a new trial instead of being rejected
+    let next = client.get("/orders/1");
+    assert!(tokio::time::timeout(Duration::from_millis(100), next).await.is_err());
+}
+
+#[tokio::test]
+async fn test_panicking_refresh_can_be_retried() {
+    let cache = Arc::new(CacheManager::new());
+    let policy = CachePolicy {
+        ttl: Duration::ZERO,
+        stale_while_revalidate: Duration::from_secs(60),
+        stale_if_error: Duration::ZERO,
+    };
+    let calls = Arc::new(AtomicUsize::new(0));
+    cache
+        .get_or_refresh("panics", policy, counting_fetch(&calls))
+        .await
+        .unwrap();
+
+    let stale = cache
+        .get_or_refresh("panics", policy, |_| async { panic!("refresh bug") })
+        .await
+        .unwrap();
+    assert!(stale.stale_for.is_some());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    cache
+        .get_or_refresh("panics", policy, counting_fetch(&calls))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_stale_while_revalidate_refreshes_once_in_background() {
+    let cache = Arc::new(CacheManager::new());
+    let policy = CachePolicy {
+        ttl: Duration::ZERO,
+        stale_while_revalidate: Duration::from_secs(60),
+        stale_if_error: Duration::ZERO,
+    };
+    let calls = Arc::new(AtomicUsize::new(0));
+
+    let first = cache
+        .get_or_refresh("swr", policy, counting_fetch(&calls))
+        .await
+        .unwrap();
+    assert_eq!(first.value, "1");
+    assert_eq!(first.stale_for, None);
+
+    for _ in 0..5 {
+        let stale = cache
+            .get_or_refresh("swr", policy, counting_fetch(&calls))
+            .await
+            .unwrap();
+        assert_eq!(stale.value, "1");
+        assert!(stale.stale_for.is_some());
+    }
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let refreshed = cache
+        .get_or_refresh("swr", policy, counting_fetch(&calls))
+        .await
+        .unwrap();
+    assert_eq!(refreshed.value, "2");
+}
+
+#[tokio::test]
+async fn test_slow_refresh_abandoned_after_timeout() {
+    let cache = Arc::new(CacheManager::new().with_refresh_timeout(Some(Duration::from_millis(10))));
+    let policy = CachePolicy {
+        ttl: Duration::ZERO,
+        stale_while_revalidate: Duration::from_secs(60),
+        stale_if_error: Duration::ZERO,
+    };
+    let calls = Arc::new(AtomicUsize::new(0));
+    cache
+        .get_or_refresh("slow", policy, counting_fetch(&calls))
+        .await
+        .unwrap();
+
+    // The refresh outlives its budget, so nothing replaces the stale value
+    for _ in 0..2 {
+        let stale = cache
+            .get_or_refresh("slow", policy, counting_fetch(&calls))
+            .await
+            .unwrap();
+        assert_eq!(stale.value, "1");
+        assert!(stale.stale_for.is_some());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // Each abandoned refresh lets the next stale read start another
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_stale_if_error_only_for_upstream_failures() {
+    let cache = Arc::new(CacheManager::new());
+    let policy = CachePolicy {
+        ttl: Duration::ZERO,
+        stale_while_revalidate: Duration::ZERO,
+        stale_if_error: Duration::from_secs(60),
+    };
+
+    cache
+        .get_or_refresh("sie", policy, |_| async {
+            Ok(Fetched::value("cached".to_string()))
+        })
+        .await
+        .unwrap();
+
+    let stale = cache
+        .get_or_refresh("sie", policy, |_| async {
+            Err(RustQLError::RestApi {
+                api: "orders-api".to_string(),
+                message: "unavailable".to_string(),
+                status: 503,
+            })
+        })
+        .await
+        .unwrap();
+    assert_eq!(stale.value, "cached");
+    assert!(stale.stale_for.is_some());
+
+    let not_found = cache
+        .get_or_refresh("sie", policy, |_| async {
+            Err(RustQLError::RestApi {
+                api: "orders-api".to_string(),
+                message: "missing".to_string(),
+                status: 404,
+            })
+        })
+        .await;
+    assert!(not_found.is_err());
+}
+
+#[tokio::test]
+async fn test_stale_response_marked_in_extensions() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let failing = Arc::new(AtomicBool::new(false));
+    let addr = spawn_upstream(hits.clone(), failing.clone());
+
+    let settings = Arc::new(order_settings(addr, 5));
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services).unwrap();
+
+    let fresh = query_order(&schema).await;
+    assert_eq!(fresh["data"]["order"], json!({ "id": "7", "hit": 1 }));
+    assert!(fresh.get("extensions").is_none());
+
+    failing.store(true, Ordering::SeqCst);
+    let stale = query_order(&schema).await;
+    assert_eq!(stale["data"]["order"], json!({ "id": "7", "hit": 1 }));
+    assert!(stale.get("errors").is_none());
+    assert_eq!(stale["extensions"]["stale"][0]["path"], "order");
+    assert_eq!(stale["extensions"]["stale"][0]["api"], "orders-api");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_open_circuit_serves_stale_without_calling_upstream() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let failing = Arc::new(AtomicBool::new(false));
+    let addr = spawn_upstream(hits.clone(), failing.clone());
+
+    let settings = Arc::new(order_settings(addr, 1));
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services.clone()).unwrap();
+
+    query_order(&schema).await;
+    failing.store(true, Ordering::SeqCst);
+    query_order(&schema).await;
+    assert!(
+        services
+            .upstream("orders-api")
+            .unwrap()
+            .circuit_breaker()
+            .is_open()
+    );
+
+    let stale = query_order(&schema).await;
+    assert_eq!(stale["data"]["order"]["hit"], 1);
+    assert!(stale["extensions"]["stale"].is_array());
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_upstream_failure_without_cached_value_is_an_error() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let failing = Arc::new(AtomicBool::new(true));
+    let addr = spawn_upstream(hits, failing);
+
+    let settings = Arc::new(order_settings(addr, 5));
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services).unwrap();
+
+    let response = query_order(&schema).await;
+    assert_eq!(response["data"]["order"], Value::Null);
+    assert!(response["errors"].is_array());
+}
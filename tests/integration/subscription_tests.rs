@@ -0,0 +1,253 @@
+use futures_util::StreamExt;
+use rustql::config::settings::{PollConfig, RestApiConfig, SubscriptionConfig, WebhookConfig};
+use rustql::graphql::subscriptions::json_diff;
+use rustql::graphql::build_schema;
+use rustql::rest::render_path;
+use rustql::server::build_routes;
+use rustql::server::handlers::connection_init_headers;
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use warp::Filter;
+
+fn webhook_settings(secret: Option<&str>) -> Settings {
+    let mut settings = Settings::default();
+    settings.subscriptions.push(SubscriptionConfig {
+        field: "orderUpdated".to_string(),
+        description: Some("Order status changes".to_string()),
+        arguments: vec!["orderId".to_string()],
+        poll: None,
+        webhook: Some(WebhookConfig {
+            topic: "orders".to_string(),
+            secret: secret.map(str::to_string),
+        }),
+    });
+    settings
+}
+
+#[test]
+fn test_json_diff_reports_changed_pointers() {
+    let old = json!({ "id": 1, "status": "new", "items": [1, 2], "meta": { "updatedAt": "a" } });
+    let new =
+        json!({ "id": 1, "status": "paid", "items": [1, 2, 3], "meta": { "updatedAt": "b" } });
+
+    assert_eq!(
+        json_diff(&old, &new, &[]),
+        vec!["/items/2", "/meta/updatedAt", "/status"]
+    );
+    assert_eq!(
+        json_diff(&old, &new, &["/meta".to_string(), "/status".to_string()]),
+        vec!["/items/2"]
+    );
+    assert!(json_diff(&old, &old, &[]).is_empty());
+}
+
+#[test]
+fn test_render_path_encodes_arguments() {
+    let path = render_path("/orders/{id}/items", |name| {
+        (name == "id").then(|| "a b/c".to_string())
+    });
+    assert_eq!(path.unwrap(), "/orders/a%20b%2Fc/items");
+    assert_eq!(render_path("/orders/{id}/items", |_| None), Err("id".to_string()));
+}
+
+#[test]
+fn test_connection_init_headers() {
+    let headers = connection_init_headers(&json!({
+        "Authorization": "Bearer top",
+        "retries": 3,
+        "headers": { "authorization": "Bearer nested", "x-api-key": "k" }
+    }));
+
+    assert_eq!(headers["authorization"], "Bearer nested");
+    assert_eq!(headers["x-api-key"], "k");
+    assert!(!headers.contains_key("retries"));
+}
+
+#[test]
+fn test_subscription_validation() {
+    let mut settings = webhook_settings(None);
+    assert!(settings.validate().is_ok());
+
+    settings.subscriptions[0].poll = Some(PollConfig {
+        api: "orders-api".to_string(),
+        path: "/orders".to_string(),
+        interval: 5,
+        ignore_paths: vec![],
+    });
+    assert!(settings.validate().is_err());
+
+    settings.subscriptions[0].webhook = None;
+    assert!(settings.validate().is_err());
+
+    settings.apis.rest.push(RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: "http://localhost:9000".to_string(),
+        ..Default::default()
+    });
+    assert!(settings.validate().is_ok());
+
+    settings.subscriptions[0].poll.as_mut().unwrap().path = "/orders/{id}".to_string();
+    assert!(settings.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_webhook_subscription_filters_by_argument() {
+    let settings = Arc::new(webhook_settings(None));
+    let services = Services::from_settings(&settings).unwrap();
+    let broker = services.broker.clone();
+    let schema = build_schema(settings, services).unwrap();
+
+    let mut stream = schema.execute_stream(r#"subscription { orderUpdated(orderId: "2") }"#);
+    let next = tokio::spawn(async move { stream.next().await });
+
+    // Give the subscription time to attach to the topic
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    broker.publish("orders", json!({ "orderId": "1", "status": "paid" }));
+    broker.publish("orders", json!({ "orderId": 2, "status": "shipped" }));
+
+    let response = tokio::time::timeout(Duration::from_secs(2), next)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(response.errors.is_empty());
+    let data = response.data.into_json().unwrap();
+    assert_eq!(data["orderUpdated"]["status"], "shipped");
+}
+
+#[tokio::test]
+async fn test_webhook_route_checks_token() {
+    let settings = Arc::new(webhook_settings(Some("s3cret")));
+    let services = Services::from_settings(&settings).unwrap();
+    let broker = services.broker.clone();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    let routes = build_routes(settings, schema, services);
+
+    let mut receiver = broker.subscribe("orders");
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/webhooks/orders")
+        .header("x-webhook-token", "wrong")
+        .json(&json!({ "orderId": "1" }))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 401);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/webhooks/orders")
+        .header("x-webhook-token", "s3cret")
+        .json(&json!({ "orderId": "1" }))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 202);
+    assert_eq!(receiver.recv().await.unwrap()["orderId"], "1");
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/webhooks/unknown")
+        .json(&json!({}))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_graphql_transport_ws_subscription() {
+    let settings = Arc::new(webhook_settings(None));
+    let services = Services::from_settings(&settings).unwrap();
+    let broker = services.broker.clone();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    let routes = build_routes(settings, schema, services);
+
+    let mut client = warp::test::ws()
+        .path("/graphql")
+        .header("sec-websocket-protocol", "graphql-transport-ws")
+        .handshake(routes)
+        .await
+        .unwrap();
+
+    client
+        .send_text(json!({ "type": "connection_init", "payload": {} }).to_string())
+        .await;
+    let ack: Value = serde_json::from_str(client.recv().await.unwrap().to_str().unwrap()).unwrap();
+    assert_eq!(ack["type"], "connection_ack");
+
+    client
+        .send_text(
+            json!({
+                "id": "1",
+                "type": "subscribe",
+                "payload": { "query": "subscription { orderUpdated }" }
+            })
+            .to_string(),
+        )
+        .await;
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    broker.publish("orders", json!({ "orderId": "9" }));
+
+    let message: Value =
+        serde_json::from_str(client.recv().await.unwrap().to_str().unwrap()).unwrap();
+    assert_eq!(message["type"], "next");
+    assert_eq!(message["payload"]["data"]["orderUpdated"]["orderId"], "9");
+}
+
+#[tokio::test]
+async fn test_poll_subscription_yields_changes() {
+    let polls = Arc::new(AtomicUsize::new(0));
+    let counter = polls.clone();
+    let upstream = warp::path!("orders" / String).map(move |id: String| {
+        // The second poll only touches an ignored field
+        let (status, updated_at) = match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => ("new", 1),
+            1 => ("new", 2),
+            _ => ("paid", 3),
+        };
+        warp::reply::json(&json!({ "id": id, "status": status, "updatedAt": updated_at }))
+    });
+    let (addr, server) = warp::serve(upstream).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "orders-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.subscriptions.push(SubscriptionConfig {
+        field: "orderStatus".to_string(),
+        description: None,
+        arguments: vec!["orderId".to_string()],
+        poll: Some(PollConfig {
+            api: "orders-api".to_string(),
+            path: "/orders/{orderId}".to_string(),
+            interval: 1,
+            ignore_paths: vec!["/updatedAt".to_string()],
+        }),
+        webhook: None,
+    });
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings, services).unwrap();
+
+    let stream = schema.execute_stream(r#"subscription { orderStatus(orderId: "7") }"#);
+    let responses: Vec<_> = tokio::time::timeout(Duration::from_secs(5), stream.take(2).collect())
+        .await
+        .unwrap();
+
+    let statuses: Vec<Value> = responses
+        .into_iter()
+        .map(|response| {
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            response.data.into_json().unwrap()["orderStatus"].clone()
+        })
+        .collect();
+    assert_eq!(statuses[0], json!({ "id": "7", "status": "new", "updatedAt": 1 }));
+    assert_eq!(statuses[1], json!({ "id": "7", "status": "paid", "updatedAt": 3 }));
+    assert_eq!(polls.load(Ordering::SeqCst), 3);
+}
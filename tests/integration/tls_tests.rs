@@ -0,0 +1,199 @@
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair,
+};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::RootCertStore;
+use rustql::config::settings::{ClientAuthMode, TlsConfig};
+use rustql::server::listener::{self, ConnectionInfo, ConnectionLimiter};
+use rustql::server::tls::{TlsReloader, certificate_subject, load_server_config};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::TlsConnector;
+use warp::Filter;
+
+struct Pki {
+    ca: Certificate,
+    ca_key: KeyPair,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.distinguished_name.push(DnType::CommonName, "RustQL Test CA");
+        let ca = params.self_signed(&ca_key).unwrap();
+        Self { ca, ca_key }
+    }
+
+    fn issue(&self, common_name: &str, usage: ExtendedKeyUsagePurpose) -> (String, String) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, common_name);
+        params.extended_key_usages = vec![usage];
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+        (cert.pem(), key.serialize_pem())
+    }
+}
+
+fn write_tls_files(dir: &Path, pki: &Pki, mtls: bool) -> TlsConfig {
+    let (cert, key) = pki.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+    std::fs::write(dir.join("server.pem"), cert).unwrap();
+    std::fs::write(dir.join("server.key"), key).unwrap();
+    std::fs::write(dir.join("ca.pem"), pki.ca.pem()).unwrap();
+
+    TlsConfig {
+        cert_path: dir.join("server.pem").display().to_string(),
+        key_path: dir.join("server.key").display().to_string(),
+        client_ca_path: mtls.then(|| dir.join("ca.pem").display().to_string()),
+        client_auth: ClientAuthMode::Required,
+        reload_interval: None,
+    }
+}
+
+fn client_connector(pki: &Pki, client_cert: Option<(String, String)>) -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.add(pki.ca.der().clone()).unwrap();
+
+    let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots);
+
+    let config = match client_cert {
+        Some((cert, key)) => builder
+            .with_client_auth_cert(
+                vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+
+    TlsConnector::from(Arc::new(config))
+}
+
+#[test]
+fn test_load_server_config_rejects_missing_files() {
+    let config = TlsConfig {
+        cert_path: "/nonexistent/cert.pem".to_string(),
+        key_path: "/nonexistent/key.pem".to_string(),
+        client_ca_path: None,
+        client_auth: ClientAuthMode::Required,
+        reload_interval: None,
+    };
+    assert!(load_server_config(&config, true).is_err());
+}
+
+#[tokio::test]
+async fn test_mtls_exposes_client_subject() {
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki::new();
+    let config = write_tls_files(dir.path(), &pki, true);
+    let reloader = TlsReloader::new(config, true).unwrap();
+
+    let routes = warp::ext::optional::<ConnectionInfo>()
+        .map(|info: Option<ConnectionInfo>| {
+            info.and_then(|i| i.client_subject).unwrap_or_default()
+        });
+
+    let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = tcp.local_addr().unwrap();
+    tokio::spawn(listener::serve(
+        tcp,
+        routes,
+        ConnectionLimiter::new(None),
+        Some(reloader.clone()),
+        false,
+    ));
+
+    let client_cert = pki.issue("partner-service", ExtendedKeyUsagePurpose::ClientAuth);
+    let connector = client_connector(&pki, Some(client_cert));
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut tls = connector
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+
+    tls.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    tls.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("CN=partner-service"));
+
+    // A client without a certificate is refused when client auth is required
+    let connector = client_connector(&pki, None);
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let result = async {
+        let mut tls = connector
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        tls.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n").await?;
+        let mut buf = Vec::new();
+        tls.read_to_end(&mut buf).await?;
+        Ok::<_, std::io::Error>(buf)
+    }
+    .await;
+    assert!(result.is_err() || result.unwrap().is_empty());
+
+    // Rotating the server certificate takes effect without restarting
+    write_tls_files(dir.path(), &pki, true);
+    reloader.reload().unwrap();
+}
+
+/// Subject of the certificate the server presents in a fresh handshake.
+async fn served_subject(connector: &TlsConnector, addr: std::net::SocketAddr) -> String {
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let tls = connector
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    let certs = tls.get_ref().1.peer_certificates().unwrap();
+    certificate_subject(&certs[0]).unwrap()
+}
+
+#[tokio::test]
+async fn test_reload_task_serves_rotated_certificates() {
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki::new();
+    let mut config = write_tls_files(dir.path(), &pki, false);
+    config.reload_interval = Some(1);
+    let reloader = TlsReloader::new(config, false).unwrap();
+    reloader.spawn_reload_task();
+
+    let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = tcp.local_addr().unwrap();
+    tokio::spawn(listener::serve(
+        tcp,
+        warp::any().map(|| "ok"),
+        ConnectionLimiter::new(None),
+        Some(reloader),
+        false,
+    ));
+
+    let connector = client_connector(&pki, None);
+    assert_eq!(served_subject(&connector, addr).await, "CN=localhost");
+
+    // Swap the files on disk; the polling task picks them up without a restart
+    let (cert, key) = pki.issue("rotated", ExtendedKeyUsagePurpose::ServerAuth);
+    std::fs::write(dir.path().join("server.pem"), cert).unwrap();
+    std::fs::write(dir.path().join("server.key"), key).unwrap();
+
+    let mut subject = String::new();
+    for _ in 0..50 {
+        subject = served_subject(&connector, addr).await;
+        if subject == "CN=rotated" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(subject, "CN=rotated");
+}
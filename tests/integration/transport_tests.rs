@@ -0,0 +1,229 @@
+use async_graphql::Value as GraphQLValue;
+use async_graphql::dynamic::{Field, FieldFuture, Object, Schema, TypeRef};
+use futures_util::StreamExt;
+use rustql::config::settings::{
+    FieldType, QueryConfig, RelationshipConfig, RestApiConfig, TypeConfig,
+};
+use rustql::graphql::build_schema;
+use rustql::{Services, Settings};
+use rustql::graphql::incremental::{IncrementalPlan, strip_incremental_directives};
+use rustql::graphql::resolvers::ResolverContext;
+use rustql::server::handlers::handle_graphql;
+use rustql::server::transport::ResponseFormat;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use warp::Filter;
+use warp::http::HeaderMap;
+
+async fn graphql_body(accept: &str, body: Value) -> (String, String) {
+    let settings = Arc::new(Settings::default());
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services.clone()).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("accept", accept.parse().unwrap());
+
+    let response = handle_graphql(
+        ResolverContext::new(settings.clone(), "req-1".to_string()).with_headers(headers),
+        settings,
+        schema,
+        services,
+        body,
+    )
+    .await
+    .unwrap();
+    let content_type = response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let bytes = warp::hyper::body::to_bytes(response.into_body())
+        .await
+        .unwrap();
+    (content_type, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+fn multipart_payloads(body: &str) -> Vec<Value> {
+    assert!(body.ends_with("\r\n-----\r\n"));
+    body.split("\r\n---\r\n")
+        .skip(1)
+        .map(|part| {
+            let json = part.split("\r\n\r\n").nth(1).unwrap();
+            serde_json::from_str(json.trim_end_matches("\r\n-----\r\n")).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_response_format_negotiation() {
+    assert_eq!(ResponseFormat::negotiate(None), ResponseFormat::Json);
+    assert_eq!(
+        ResponseFormat::negotiate(Some("application/json")),
+        ResponseFormat::Json
+    );
+    assert_eq!(
+        ResponseFormat::negotiate(Some("text/event-stream")),
+        ResponseFormat::EventStream
+    );
+    assert_eq!(
+        ResponseFormat::negotiate(Some("multipart/mixed;deferSpec=20220824, application/json")),
+        ResponseFormat::Multipart
+    );
+    assert_eq!(
+        ResponseFormat::negotiate(Some("multipart/mixed;q=0.5, application/json")),
+        ResponseFormat::Json
+    );
+}
+
+#[tokio::test]
+async fn test_event_stream_response() {
+    let (content_type, body) =
+        graphql_body("text/event-stream", json!({ "query": "{ health }" })).await;
+
+    assert!(content_type.starts_with("text/event-stream"));
+    assert!(body.contains("event:next\ndata:{\"data\":{\"health\":\"OK\"}}"));
+    assert!(body.contains("event:complete"));
+}
+
+#[tokio::test]
+async fn test_multipart_defer() {
+    let query = r#"query Info { health ... @defer(label: "info") { apiInfo { name } } }"#;
+    let (content_type, body) = graphql_body(
+        "multipart/mixed;deferSpec=20220824, application/json",
+        json!({ "query": query }),
+    )
+    .await;
+
+    assert!(content_type.starts_with("multipart/mixed"));
+    let payloads = multipart_payloads(&body);
+    assert_eq!(payloads.len(), 3);
+    assert_eq!(payloads[0]["data"], json!({ "health": "OK" }));
+    assert_eq!(payloads[0]["hasNext"], true);
+    assert_eq!(
+        payloads[1]["incremental"][0]["data"]["apiInfo"]["name"],
+        "RustQL"
+    );
+    assert_eq!(payloads[1]["incremental"][0]["path"], json!([]));
+    assert_eq!(payloads[1]["incremental"][0]["label"], "info");
+    assert_eq!(payloads[2], json!({ "hasNext": false }));
+}
+
+#[tokio::test]
+async fn test_json_response_ignores_defer() {
+    let query = "{ health ...Info @defer } fragment Info on QueryRoot { apiInfo { name } }";
+    let (_, body) = graphql_body("application/json", json!({ "query": query })).await;
+    let body: Value = serde_json::from_str(&body).unwrap();
+
+    assert!(body.get("errors").is_none(), "{}", body);
+    assert_eq!(body["data"]["apiInfo"]["name"], "RustQL");
+}
+
+#[test]
+fn test_strip_incremental_directives_keeps_used_variables() {
+    let mut request = async_graphql::Request::new(
+        "query Q($m: String!, $d: Boolean) { echo(message: $m) ... @defer(if: $d) { health } }",
+    );
+    strip_incremental_directives(&mut request);
+    assert_eq!(
+        request.query,
+        "query Q($m: String!) { echo(message: $m) ... { health } }"
+    );
+}
+
+#[tokio::test]
+async fn test_stream_sends_remaining_items() {
+    let query = Object::new("Query").field(Field::new(
+        "numbers",
+        TypeRef::named_nn_list_nn(TypeRef::INT),
+        |_| {
+            FieldFuture::new(async {
+                Ok(Some(GraphQLValue::List(
+                    (1..=3).map(GraphQLValue::from).collect(),
+                )))
+            })
+        },
+    ));
+    let schema = Schema::build("Query", None, None)
+        .register(query)
+        .finish()
+        .unwrap();
+
+    let request = async_graphql::Request::new("{ numbers @stream(initialCount: 1) }");
+    let plan = IncrementalPlan::new(&request).unwrap();
+    let context = ResolverContext::new(Arc::new(Settings::default()), "req-1".to_string());
+    let payloads: Vec<Value> = plan
+        .execute(schema, &request, context, None)
+        .collect()
+        .await;
+
+    assert_eq!(payloads[0]["data"], json!({ "numbers": [1] }));
+    assert_eq!(
+        payloads[1]["incremental"][0],
+        json!({ "items": [2], "path": ["numbers", 1] })
+    );
+    assert_eq!(
+        payloads[2]["incremental"][0],
+        json!({ "items": [3], "path": ["numbers", 2] })
+    );
+    assert_eq!(payloads[3], json!({ "hasNext": false }));
+}
+
+#[tokio::test]
+async fn test_deferred_fragment_reuses_upstream_results() {
+    let user_requests = Arc::new(AtomicUsize::new(0));
+    let counter = user_requests.clone();
+    let user = warp::path!("users" / String).map(move |id: String| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let body = warp::reply::json(&json!({ "id": id, "name": "Alice" }));
+        warp::reply::with_header(body, "cache-control", "no-store")
+    });
+    let orders = warp::path!("orders").map(|| warp::reply::json(&json!([{ "id": "o1" }])));
+    let (addr, server) = warp::serve(user.or(orders)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut settings = Settings::default();
+    settings.apis.rest.push(RestApiConfig {
+        name: "shop-api".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "user".to_string(),
+        api: "shop-api".to_string(),
+        path: "/users/{id}".to_string(),
+        arguments: vec!["id".to_string()],
+        returns: Some("User".to_string()),
+        ..Default::default()
+    });
+    settings.types.push(TypeConfig {
+        name: "User".to_string(),
+        description: None,
+        resource: None,
+        fields: [("name".to_string(), FieldType::String)].into(),
+        relationships: vec![RelationshipConfig {
+            field: "orders".to_string(),
+            api: "shop-api".to_string(),
+            path: "/orders?userId={parent.id}".to_string(),
+            ..Default::default()
+        }],
+    });
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    let schema = build_schema(settings.clone(), services).unwrap();
+
+    let request =
+        async_graphql::Request::new(r#"{ user(id: "1") { name ... @defer { orders } } }"#);
+    let plan = IncrementalPlan::new(&request).unwrap();
+    let context = ResolverContext::new(settings, "req-1".to_string());
+    let payloads: Vec<Value> = plan
+        .execute(schema, &request, context, None)
+        .collect()
+        .await;
+
+    assert_eq!(payloads[0]["data"], json!({ "user": { "name": "Alice" } }));
+    assert_eq!(
+        payloads[1]["incremental"][0],
+        json!({ "data": { "orders": [{ "id": "o1" }] }, "path": ["user"] })
+    );
+    // The deferred fragment re-selects `user` without fetching it again
+    assert_eq!(user_requests.load(Ordering::SeqCst), 1);
+}
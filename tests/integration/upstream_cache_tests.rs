@@ -0,0 +1,182 @@
+use reqwest::header::{CACHE_CONTROL, HeaderMap, HeaderValue};
+use rustql::cache::http::{CacheDirectives, Validators};
+use rustql::config::settings::{QueryConfig, RestApiConfig};
+use rustql::graphql::{RustQLSchema, build_schema};
+use rustql::{Services, Settings};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use warp::Filter;
+
+const ETAG: &str = "\"v1\"";
+const LAST_MODIFIED: &str = "Wed, 21 Oct 2026 07:28:00 GMT";
+
+#[derive(Default)]
+struct Hits {
+    total: AtomicUsize,
+    conditional: AtomicUsize,
+}
+
+/// Cache-Control sent by the upstream for each resource.
+fn cache_control(resource: &str) -> &'static str {
+    match resource {
+        "shared" => "max-age=60",
+        "no-store" => "no-store",
+        "private" => "private, max-age=60",
+        _ => "no-cache",
+    }
+}
+
+/// Upstream answering `/{resource}` with validators, and with 304 to
+/// matching conditional requests.
+fn spawn_upstream(hits: Arc<Hits>) -> SocketAddr {
+    let routes = warp::path::param::<String>()
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(warp::header::optional::<String>("if-modified-since"))
+        .map(
+            move |resource: String, etag: Option<String>, modified: Option<String>| {
+                let hit = hits.total.fetch_add(1, Ordering::SeqCst) + 1;
+                let builder = warp::http::Response::builder()
+                    .header("cache-control", cache_control(&resource))
+                    .header("etag", ETAG)
+                    .header("last-modified", LAST_MODIFIED);
+
+                // Revalidating `flaky` finds the upstream down
+                if resource == "flaky" && etag.is_some() {
+                    return builder.status(503).body(String::new()).unwrap();
+                }
+                if etag.as_deref() == Some(ETAG) && modified.as_deref() == Some(LAST_MODIFIED) {
+                    hits.conditional.fetch_add(1, Ordering::SeqCst);
+                    return builder.status(304).body(String::new()).unwrap();
+                }
+                builder
+                    .header("content-type", "application/json")
+                    .body(json!({ "hit": hit }).to_string())
+                    .unwrap()
+            },
+        );
+    let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    addr
+}
+
+fn schema(addr: SocketAddr, default_ttl: u64, stale: u64) -> RustQLSchema {
+    let mut settings = Settings::default();
+    settings.cache.default_ttl = default_ttl;
+    settings.cache.stale_while_revalidate = stale;
+    settings.cache.stale_if_error = stale;
+    settings.apis.rest.push(RestApiConfig {
+        name: "upstream".to_string(),
+        base_url: format!("http://{}", addr),
+        ..Default::default()
+    });
+    settings.queries.push(QueryConfig {
+        field: "resource".to_string(),
+        api: "upstream".to_string(),
+        path: "/{id}".to_string(),
+        arguments: vec!["id".to_string()],
+        ..Default::default()
+    });
+
+    let settings = Arc::new(settings);
+    let services = Services::from_settings(&settings).unwrap();
+    build_schema(settings, services).unwrap()
+}
+
+async fn query(schema: &RustQLSchema, resource: &str) -> Value {
+    let query = format!(r#"{{ resource(id: "{}") }}"#, resource);
+    let response = schema.execute(query).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    serde_json::to_value(&response.data).unwrap()
+}
+
+fn directives(value: &str) -> CacheDirectives {
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_str(value).unwrap());
+    CacheDirectives::from_headers(&headers)
+}
+
+#[test]
+fn test_cache_control_directives() {
+    let shared = directives("public, max-age=60, s-maxage=\"120\"");
+    assert!(shared.storable());
+    assert_eq!(shared.ttl().unwrap().as_secs(), 120);
+
+    assert_eq!(directives("max-age=30").ttl().unwrap().as_secs(), 30);
+    assert_eq!(
+        directives("no-cache, max-age=30").ttl().unwrap().as_secs(),
+        0
+    );
+    assert_eq!(directives("").ttl(), None);
+    assert!(!directives("no-store").storable());
+    assert!(!directives("Private, max-age=60").storable());
+}
+
+#[test]
+fn test_validators_become_conditional_headers() {
+    let validators = Validators {
+        etag: Some(ETAG.to_string()),
+        last_modified: Some(LAST_MODIFIED.to_string()),
+    };
+    let headers = validators.conditional_headers();
+    assert_eq!(headers["if-none-match"], ETAG);
+    assert_eq!(headers["if-modified-since"], LAST_MODIFIED);
+    assert!(Validators::default().conditional_headers().is_empty());
+}
+
+#[tokio::test]
+async fn test_upstream_max_age_overrides_default_ttl() {
+    let hits = Arc::new(Hits::default());
+    let schema = schema(spawn_upstream(hits.clone()), 0, 0);
+
+    let first = query(&schema, "shared").await;
+    let second = query(&schema, "shared").await;
+    assert_eq!(first, second);
+    assert_eq!(hits.total.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_no_store_and_private_responses_are_not_cached() {
+    let hits = Arc::new(Hits::default());
+    let schema = schema(spawn_upstream(hits.clone()), 300, 0);
+
+    query(&schema, "no-store").await;
+    query(&schema, "no-store").await;
+    query(&schema, "private").await;
+    query(&schema, "private").await;
+    assert_eq!(hits.total.load(Ordering::SeqCst), 4);
+    assert_eq!(hits.conditional.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_expired_entries_revalidated_with_304() {
+    let hits = Arc::new(Hits::default());
+    let schema = schema(spawn_upstream(hits.clone()), 300, 0);
+
+    let first = query(&schema, "no-cache").await;
+    let second = query(&schema, "no-cache").await;
+    let third = query(&schema, "no-cache").await;
+
+    assert_eq!(first, json!({ "resource": { "hit": 1 } }));
+    assert_eq!(second, first);
+    assert_eq!(third, first);
+    assert_eq!(hits.total.load(Ordering::SeqCst), 3);
+    assert_eq!(hits.conditional.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_no_cache_responses_are_never_served_stale() {
+    let hits = Arc::new(Hits::default());
+    let schema = schema(spawn_upstream(hits.clone()), 300, 60);
+
+    // Revalidated inline rather than served stale while refreshing
+    query(&schema, "no-cache").await;
+    query(&schema, "no-cache").await;
+    assert_eq!(hits.conditional.load(Ordering::SeqCst), 1);
+
+    // Nor served stale when revalidation fails
+    query(&schema, "flaky").await;
+    let response = schema.execute(r#"{ resource(id: "flaky") }"#).await;
+    assert!(!response.errors.is_empty());
+}